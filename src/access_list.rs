@@ -0,0 +1,157 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+/// 名单文件名，保存在应用数据目录
+pub const FILE_NAME: &str = "access_lists.json";
+
+/// 房主的 Steam ID 允许列表和阻止列表
+///
+/// 阻止列表优先；允许列表为空时不限制，非空时只接受列表中的玩家
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessLists {
+    pub allowlist: BTreeSet<u64>,
+    pub blocklist: BTreeSet<u64>,
+}
+
+/// 要修改的名单
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListKind {
+    Allow,
+    Block,
+}
+
+impl AccessLists {
+    /// 是否允许该玩家连接
+    pub fn permits(&self, steam_id: u64) -> bool {
+        !self.blocklist.contains(&steam_id)
+            && (self.allowlist.is_empty() || self.allowlist.contains(&steam_id))
+    }
+
+    /// 该玩家是否明确在允许列表中 (且不在阻止列表中)
+    ///
+    /// 与 `permits` 不同，允许列表为空时不放行任何人，用于 RCON 等管理权限
+    pub fn explicitly_allows(&self, steam_id: u64) -> bool {
+        self.allowlist.contains(&steam_id) && !self.blocklist.contains(&steam_id)
+    }
+
+    fn list_mut(&mut self, kind: ListKind) -> &mut BTreeSet<u64> {
+        match kind {
+            ListKind::Allow => &mut self.allowlist,
+            ListKind::Block => &mut self.blocklist,
+        }
+    }
+}
+
+/// 当前名单，以及保存名单的文件路径 (未加载时为 None，修改只在内存中生效)
+static LISTS: LazyLock<Mutex<(AccessLists, Option<PathBuf>)>> =
+    LazyLock::new(|| Mutex::new((AccessLists::default(), None)));
+
+/// 从文件加载名单，之后的修改会写回该文件；文件不存在时从空名单开始
+pub fn load(path: &Path) {
+    let lists = match fs::read_to_string(path) {
+        Ok(contents) => match serde_json::from_str(&contents) {
+            Ok(lists) => lists,
+            Err(e) => {
+                warn!("⚠️ 名单文件 {} 格式无效，已忽略: {}", path.display(), e);
+                AccessLists::default()
+            }
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => AccessLists::default(),
+        Err(e) => {
+            warn!("⚠️ 无法读取名单文件 {}: {}", path.display(), e);
+            AccessLists::default()
+        }
+    };
+    info!(
+        "📋 已加载名单: 允许 {} 人, 阻止 {} 人",
+        lists.allowlist.len(),
+        lists.blocklist.len()
+    );
+    *LISTS.lock().unwrap() = (lists, Some(path.to_path_buf()));
+}
+
+/// 当前名单的副本
+pub fn get() -> AccessLists {
+    LISTS.lock().unwrap().0.clone()
+}
+
+/// 是否允许该玩家连接
+pub fn permits(steam_id: u64) -> bool {
+    LISTS.lock().unwrap().0.permits(steam_id)
+}
+
+/// 该玩家是否明确在允许列表中
+pub fn explicitly_allows(steam_id: u64) -> bool {
+    LISTS.lock().unwrap().0.explicitly_allows(steam_id)
+}
+
+/// 添加或移除名单条目并保存，返回修改后的名单
+pub fn update(kind: ListKind, steam_id: u64, present: bool) -> Result<AccessLists, String> {
+    let mut guard = LISTS.lock().unwrap();
+    let (lists, path) = &mut *guard;
+    let list = lists.list_mut(kind);
+    let changed = if present {
+        list.insert(steam_id)
+    } else {
+        list.remove(&steam_id)
+    };
+
+    if changed {
+        info!(
+            "📋 {}{}列表: {}",
+            if present { "加入" } else { "移出" },
+            match kind {
+                ListKind::Allow => "允许",
+                ListKind::Block => "阻止",
+            },
+            steam_id
+        );
+        if let Some(path) = path {
+            save(path, lists)?;
+        }
+    }
+    Ok(lists.clone())
+}
+
+fn save(path: &Path, lists: &AccessLists) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let contents = serde_json::to_string_pretty(lists).map_err(|e| e.to_string())?;
+    fs::write(path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocklist_wins_and_allowlist_restricts() {
+        let mut lists = AccessLists::default();
+        assert!(lists.permits(1));
+
+        lists.blocklist.insert(1);
+        assert!(!lists.permits(1));
+        assert!(lists.permits(2));
+
+        // 允许列表非空时只接受列表中的玩家，阻止列表仍然优先
+        assert!(!lists.explicitly_allows(2));
+        lists.allowlist.extend([1, 3]);
+        assert!(!lists.permits(1));
+        assert!(!lists.permits(2));
+        assert!(lists.permits(3));
+        assert!(!lists.explicitly_allows(1));
+        assert!(lists.explicitly_allows(3));
+
+        let json = serde_json::to_string(&lists).unwrap();
+        assert_eq!(serde_json::from_str::<AccessLists>(&json).unwrap(), lists);
+        assert_eq!(serde_json::from_str::<AccessLists>("{}").unwrap(), AccessLists::default());
+    }
+}
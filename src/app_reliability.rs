@@ -0,0 +1,509 @@
+use crate::config::{
+    APP_RELIABILITY_INITIAL_RTO_MS, APP_RELIABILITY_MAX_BACKLOG, APP_RELIABILITY_MAX_RETRANSMITS,
+    APP_RELIABILITY_MAX_RTO_MS, APP_RELIABILITY_MIN_RTO_MS, APP_RELIABILITY_WINDOW, MAX_UNRELIABLE_MESSAGE_SIZE,
+};
+use crate::transport::{Transport, TransportError};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 房主接受应用层可靠传输时在大厅元数据中公布的键，值为 "1"
+pub const LOBBY_KEY_APP_RELIABILITY: &str = "app_reliability";
+
+/// 数据包类型，与帧协议版本号 (首字节) 不会重合，未启用时的普通消息原样传递
+const PACKET_DATA: u8 = 0xB0;
+const PACKET_ACK: u8 = 0xB1;
+
+/// 数据包头: 类型(1) + 序号(4, 大端) + 是否为消息的最后一段(1)
+const DATA_HEADER_LEN: usize = 6;
+
+/// 确认包: 类型(1) + 下一个期望的序号(4, 大端) + 其后 SACK_BITS 个序号的接收位图(8, 大端)
+const ACK_LEN: usize = 13;
+const SACK_BITS: usize = 64;
+
+/// 每段负载上限，整个数据包不超过一个 UDP 包，丢包时只重传这一段
+const SEGMENT_PAYLOAD: usize = MAX_UNRELIABLE_MESSAGE_SIZE - DATA_HEADER_LEN;
+
+/// 链路上是否使用应用层可靠传输
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// 不使用，所有消息原样交给底层传输
+    Off,
+    /// 房主: 对端发来第一个应用层数据包或确认后开始使用
+    Accept,
+    /// 客户端: 从一开始就使用，首次接收时发出一个确认告知对端
+    Active,
+}
+
+/// 在不可靠消息之上实现的可靠、有序传输 (序号 + 选择确认 + 超时重传)
+///
+/// Steam 的可靠消息在大量传输 (如下载世界) 时可能受重传窗口限制。启用后
+/// `send` 把消息切分为不超过一个 UDP 包的段，以不可靠方式发送并保留到被确认；
+/// 接收方按序号重组后才交付，因此两端 MC 看到的仍是完整、有序的 TCP 字节流。
+/// 重传和确认在 `receive` 中进行，主循环每轮都会调用它。
+///
+/// 不做拥塞控制，只用窗口限制在途段数；Steam 无法立即发出的不可靠消息会被丢弃，
+/// 由超时重传补上。窗口外排队的段不超过 APP_RELIABILITY_MAX_BACKLOG，排满时 `send`
+/// 返回错误，和 Steam 发送缓冲区已满时一样由调用方稍后重试。
+/// `send_unreliable` 仍直接发送，不经过此层
+pub struct ReliableLink<T> {
+    inner: T,
+    state: RefCell<LinkState>,
+}
+
+struct LinkState {
+    mode: Mode,
+    sender: SendState,
+    receiver: ReceiveState,
+}
+
+/// 已发出、等待确认的段
+struct Segment {
+    packet: Vec<u8>,
+    sent_at: Instant,
+    transmissions: u32,
+}
+
+struct SendState {
+    /// unacked[0] 的序号
+    base: u32,
+    /// 在途的段，已被选择确认的为 None
+    unacked: VecDeque<Option<Segment>>,
+    /// 窗口已满时等待发送的段: (负载, 是否为消息的最后一段)
+    backlog: VecDeque<(Vec<u8>, bool)>,
+    /// 平滑往返时间，只用未重传过的段采样
+    srtt: Option<Duration>,
+}
+
+struct ReceiveState {
+    next_expected: u32,
+    /// 序号从 next_expected 开始的乱序到达的段
+    pending: VecDeque<Option<(Vec<u8>, bool)>>,
+    /// 正在重组的消息
+    partial: Vec<u8>,
+    ack_due: bool,
+}
+
+/// a 是否在 b 之前，序号回绕后仍然成立
+fn seq_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+impl<T: Transport> ReliableLink<T> {
+    pub fn new(inner: T, mode: Mode) -> Self {
+        Self {
+            inner,
+            state: RefCell::new(LinkState {
+                mode,
+                sender: SendState {
+                    base: 0,
+                    unacked: VecDeque::new(),
+                    backlog: VecDeque::new(),
+                    srtt: None,
+                },
+                receiver: ReceiveState {
+                    next_expected: 0,
+                    pending: VecDeque::new(),
+                    partial: Vec::new(),
+                    ack_due: mode == Mode::Active,
+                },
+            }),
+        }
+    }
+
+    /// 取回底层连接，如关闭 Steam 连接时
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// 是否已开始使用应用层可靠传输
+    pub fn is_active(&self) -> bool {
+        self.state.borrow().mode == Mode::Active
+    }
+
+    /// 是否还有未被对端确认的数据
+    pub fn has_unacked(&self) -> bool {
+        let state = self.state.borrow();
+        !state.sender.unacked.is_empty() || !state.sender.backlog.is_empty()
+    }
+
+    /// 关闭前继续处理确认和重传，直到数据全部被确认或到达 deadline；期间收到的数据丢弃
+    pub fn settle(&mut self, deadline: Instant) -> bool {
+        while self.has_unacked() {
+            if Instant::now() >= deadline || self.receive(64).is_err() {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+}
+
+impl<T> Deref for ReliableLink<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: Transport> Transport for ReliableLink<T> {
+    fn send(&self, data: &[u8]) -> Result<(), TransportError> {
+        let mut state = self.state.borrow_mut();
+        if state.mode != Mode::Active {
+            return self.inner.send(data);
+        }
+        let sender = &mut state.sender;
+        let segments = data.len().div_ceil(SEGMENT_PAYLOAD).max(1);
+        // 整条消息要么全部排队，要么不排队，不会只发出一部分
+        if sender.backlog.len() + segments > APP_RELIABILITY_MAX_BACKLOG {
+            return Err(TransportError::Failed(String::from("应用层发送队列已满")));
+        }
+        if data.is_empty() {
+            sender.backlog.push_back((Vec::new(), true));
+        }
+        for (index, chunk) in data.chunks(SEGMENT_PAYLOAD).enumerate() {
+            sender.backlog.push_back((chunk.to_vec(), index + 1 == segments));
+        }
+        sender.fill_window(&self.inner, Instant::now())
+    }
+
+    fn send_unreliable(&self, data: &[u8]) -> Result<(), TransportError> {
+        self.inner.send_unreliable(data)
+    }
+
+    fn receive(&mut self, max: usize) -> Result<Vec<Vec<u8>>, TransportError> {
+        let messages = self.inner.receive(max)?;
+        let state = self.state.get_mut();
+        let mut delivered = Vec::new();
+        for message in messages {
+            let layered = match message.first() {
+                Some(&PACKET_DATA) => message.len() >= DATA_HEADER_LEN,
+                Some(&PACKET_ACK) => message.len() == ACK_LEN,
+                _ => false,
+            };
+            if !layered || state.mode == Mode::Off {
+                delivered.push(message);
+                continue;
+            }
+            state.mode = Mode::Active;
+            let seq = u32::from_be_bytes([message[1], message[2], message[3], message[4]]);
+            if message[0] == PACKET_DATA {
+                let last = message[5] != 0;
+                state.receiver.on_data(seq, message[DATA_HEADER_LEN..].to_vec(), last, &mut delivered);
+            } else {
+                let bitmap = u64::from_be_bytes(message[5..ACK_LEN].try_into().unwrap_or_default());
+                state.sender.on_ack(seq, bitmap, Instant::now());
+            }
+        }
+
+        if state.mode == Mode::Active {
+            let now = Instant::now();
+            state.sender.retransmit(&self.inner, now)?;
+            state.sender.fill_window(&self.inner, now)?;
+            if state.receiver.ack_due {
+                state.receiver.ack_due = false;
+                send_packet(&self.inner, &state.receiver.ack())?;
+            }
+        }
+        Ok(delivered)
+    }
+}
+
+/// 以不可靠方式发送；暂时无法发送的包等待重传，只有连接关闭时报错
+fn send_packet(inner: &impl Transport, packet: &[u8]) -> Result<(), TransportError> {
+    match inner.send_unreliable(packet) {
+        Err(TransportError::Closed) => Err(TransportError::Closed),
+        _ => Ok(()),
+    }
+}
+
+impl SendState {
+    fn rto(&self) -> Duration {
+        let rto = self
+            .srtt
+            .map_or(Duration::from_millis(APP_RELIABILITY_INITIAL_RTO_MS), |srtt| srtt * 2);
+        rto.clamp(
+            Duration::from_millis(APP_RELIABILITY_MIN_RTO_MS),
+            Duration::from_millis(APP_RELIABILITY_MAX_RTO_MS),
+        )
+    }
+
+    /// 窗口有空位时为等待中的段分配序号并发出
+    fn fill_window(&mut self, inner: &impl Transport, now: Instant) -> Result<(), TransportError> {
+        while self.unacked.len() < APP_RELIABILITY_WINDOW {
+            let Some((payload, last)) = self.backlog.pop_front() else {
+                break;
+            };
+            let seq = self.base.wrapping_add(self.unacked.len() as u32);
+            let mut packet = Vec::with_capacity(DATA_HEADER_LEN + payload.len());
+            packet.push(PACKET_DATA);
+            packet.extend_from_slice(&seq.to_be_bytes());
+            packet.push(u8::from(last));
+            packet.extend_from_slice(&payload);
+            send_packet(inner, &packet)?;
+            self.unacked.push_back(Some(Segment {
+                packet,
+                sent_at: now,
+                transmissions: 1,
+            }));
+        }
+        Ok(())
+    }
+
+    /// 重传超时未确认的段，每次重传后超时时间翻倍；重传次数用尽视为连接失效
+    fn retransmit(&mut self, inner: &impl Transport, now: Instant) -> Result<(), TransportError> {
+        let rto = self.rto();
+        for segment in self.unacked.iter_mut().flatten() {
+            let timeout = rto * (1 << (segment.transmissions - 1).min(5));
+            if now.duration_since(segment.sent_at) < timeout {
+                continue;
+            }
+            if segment.transmissions > APP_RELIABILITY_MAX_RETRANSMITS {
+                return Err(TransportError::Failed(format!(
+                    "应用层重传 {} 次后仍未确认",
+                    APP_RELIABILITY_MAX_RETRANSMITS
+                )));
+            }
+            send_packet(inner, &segment.packet)?;
+            segment.sent_at = now;
+            segment.transmissions += 1;
+        }
+        Ok(())
+    }
+
+    fn on_ack(&mut self, next_expected: u32, bitmap: u64, now: Instant) {
+        // 累计确认: next_expected 之前的段都已收到
+        while !self.unacked.is_empty() && seq_before(self.base, next_expected) {
+            if let Some(segment) = self.unacked.pop_front().flatten() {
+                self.sample_rtt(&segment, now);
+            }
+            self.base = self.base.wrapping_add(1);
+        }
+        // 选择确认: 第 i 位表示 next_expected + 1 + i 已收到
+        for bit in 0..SACK_BITS {
+            if bitmap & (1 << bit) == 0 {
+                continue;
+            }
+            let seq = next_expected.wrapping_add(1 + bit as u32);
+            let offset = seq.wrapping_sub(self.base) as usize;
+            if seq_before(seq, self.base) || offset >= self.unacked.len() {
+                continue;
+            }
+            if let Some(segment) = self.unacked[offset].take() {
+                self.sample_rtt(&segment, now);
+            }
+        }
+        while let Some(None) = self.unacked.front() {
+            self.unacked.pop_front();
+            self.base = self.base.wrapping_add(1);
+        }
+    }
+
+    fn sample_rtt(&mut self, segment: &Segment, now: Instant) {
+        // 重传过的段无法确定确认对应哪一次发送
+        if segment.transmissions != 1 {
+            return;
+        }
+        let sample = now.duration_since(segment.sent_at);
+        self.srtt = Some(match self.srtt {
+            Some(srtt) => (srtt * 7 + sample) / 8,
+            None => sample,
+        });
+    }
+}
+
+impl ReceiveState {
+    fn on_data(&mut self, seq: u32, payload: Vec<u8>, last: bool, delivered: &mut Vec<Vec<u8>>) {
+        self.ack_due = true;
+        // 重复的段 (确认丢失后的重传) 只需再次确认
+        if seq_before(seq, self.next_expected) {
+            return;
+        }
+        let offset = seq.wrapping_sub(self.next_expected) as usize;
+        // 超出窗口的段说明对端状态异常，丢弃后等待重传
+        if offset >= APP_RELIABILITY_WINDOW {
+            return;
+        }
+        if self.pending.len() <= offset {
+            self.pending.resize_with(offset + 1, || None);
+        }
+        self.pending[offset] = Some((payload, last));
+
+        while let Some(Some(_)) = self.pending.front() {
+            let Some(Some((payload, last))) = self.pending.pop_front() else {
+                break;
+            };
+            self.next_expected = self.next_expected.wrapping_add(1);
+            self.partial.extend_from_slice(&payload);
+            if last {
+                delivered.push(std::mem::take(&mut self.partial));
+            }
+        }
+    }
+
+    fn ack(&self) -> Vec<u8> {
+        // pending[0] 总是缺失的段，位图从 pending[1] 开始
+        let bitmap = self
+            .pending
+            .iter()
+            .skip(1)
+            .take(SACK_BITS)
+            .enumerate()
+            .filter(|(_, segment)| segment.is_some())
+            .fold(0u64, |bitmap, (bit, _)| bitmap | (1 << bit));
+        let mut packet = Vec::with_capacity(ACK_LEN);
+        packet.push(PACKET_ACK);
+        packet.extend_from_slice(&self.next_expected.to_be_bytes());
+        packet.extend_from_slice(&bitmap.to_be_bytes());
+        packet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MemoryTransport;
+
+    /// 不可靠发送按固定模式丢包并两两交换顺序的内存传输
+    struct LossyTransport {
+        inner: MemoryTransport,
+        sent: RefCell<u32>,
+        held: RefCell<Option<Vec<u8>>>,
+    }
+
+    impl LossyTransport {
+        fn pair() -> (Self, Self) {
+            let (a, b) = MemoryTransport::pair();
+            let wrap = |inner| Self {
+                inner,
+                sent: RefCell::new(0),
+                held: RefCell::new(None),
+            };
+            (wrap(a), wrap(b))
+        }
+    }
+
+    impl Transport for LossyTransport {
+        fn send(&self, data: &[u8]) -> Result<(), TransportError> {
+            self.inner.send(data)
+        }
+
+        fn send_unreliable(&self, data: &[u8]) -> Result<(), TransportError> {
+            let mut sent = self.sent.borrow_mut();
+            *sent += 1;
+            // 每 3 个包丢 1 个
+            if *sent % 3 == 0 {
+                return Ok(());
+            }
+            // 其余的包两两交换顺序
+            let earlier = self.held.borrow_mut().take();
+            match earlier {
+                Some(earlier) => {
+                    self.inner.send(data)?;
+                    self.inner.send(&earlier)
+                }
+                None => {
+                    *self.held.borrow_mut() = Some(data.to_vec());
+                    Ok(())
+                }
+            }
+        }
+
+        fn receive(&mut self, max: usize) -> Result<Vec<Vec<u8>>, TransportError> {
+            // 长时间没有后续包时放出暂存的包，避免最后一个包永远不到
+            if let Some(held) = self.held.borrow_mut().take() {
+                self.inner.send(&held)?;
+            }
+            self.inner.receive(max)
+        }
+    }
+
+    #[test]
+    fn test_delivers_in_order_despite_loss_and_reordering() {
+        let (a, b) = LossyTransport::pair();
+        let mut sender = ReliableLink::new(a, Mode::Active);
+        let mut receiver = ReliableLink::new(b, Mode::Accept);
+
+        let messages: Vec<Vec<u8>> = (0..40u32)
+            .map(|i| (0..(i as usize * 257) % 5000).map(|j| (i as usize + j) as u8).collect())
+            .collect();
+        for message in &messages {
+            sender.send(message).unwrap();
+        }
+
+        let mut received = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while received.len() < messages.len() {
+            assert!(Instant::now() < deadline, "only {} messages arrived", received.len());
+            received.extend(receiver.receive(64).unwrap());
+            sender.receive(64).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(received, messages);
+        assert!(receiver.is_active());
+
+        // 最后的确认也可能丢失，重传后全部确认
+        while sender.has_unacked() {
+            assert!(Instant::now() < deadline, "segments left unacknowledged");
+            assert!(receiver.receive(64).unwrap().is_empty());
+            sender.receive(64).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_backlog_is_bounded() {
+        let (a, _b) = MemoryTransport::pair();
+        let sender = ReliableLink::new(a, Mode::Active);
+        let segment = vec![0u8; SEGMENT_PAYLOAD];
+
+        // 对端不确认: 窗口和排队都满后发送失败，而不是继续占用内存
+        for _ in 0..APP_RELIABILITY_WINDOW + APP_RELIABILITY_MAX_BACKLOG {
+            sender.send(&segment).unwrap();
+        }
+        assert!(matches!(sender.send(&segment), Err(TransportError::Failed(_))));
+        assert!(sender.send(&[]).is_err());
+        let state = sender.state.borrow();
+        assert_eq!(state.sender.unacked.len(), APP_RELIABILITY_WINDOW);
+        assert_eq!(state.sender.backlog.len(), APP_RELIABILITY_MAX_BACKLOG);
+    }
+
+    #[test]
+    fn test_plain_messages_pass_through() {
+        let (a, b) = MemoryTransport::pair();
+        let off = ReliableLink::new(a, Mode::Off);
+        let mut accept = ReliableLink::new(b, Mode::Accept);
+
+        off.send(b"frame").unwrap();
+        assert_eq!(accept.receive(64).unwrap(), vec![b"frame".to_vec()]);
+        assert!(!accept.is_active());
+        assert!(!off.has_unacked());
+    }
+
+    #[test]
+    fn test_sack_and_duplicates() {
+        let mut receiver = ReceiveState {
+            next_expected: u32::MAX,
+            pending: VecDeque::new(),
+            partial: Vec::new(),
+            ack_due: false,
+        };
+        let mut delivered = Vec::new();
+        // 序号回绕: u32::MAX 之后是 0
+        receiver.on_data(1, b"c".to_vec(), true, &mut delivered);
+        receiver.on_data(0, b"b".to_vec(), false, &mut delivered);
+        assert!(delivered.is_empty());
+        let ack = receiver.ack();
+        assert_eq!(&ack[1..5], &u32::MAX.to_be_bytes());
+        assert_eq!(u64::from_be_bytes(ack[5..].try_into().unwrap()), 0b11);
+
+        receiver.on_data(u32::MAX, b"a".to_vec(), false, &mut delivered);
+        assert_eq!(delivered, vec![b"abc".to_vec()]);
+        receiver.on_data(0, b"b".to_vec(), false, &mut delivered);
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(receiver.next_expected, 2);
+    }
+}
@@ -0,0 +1,43 @@
+/// 本程序的版本号，房主在大厅元数据中公布
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// 两个程序版本能否互联
+///
+/// 主版本号相同即兼容；0.x 阶段帧协议、保活格式等仍可能在次版本间变化，
+/// 此时还要求次版本号相同。无法解析的版本视为不兼容
+pub fn is_compatible(a: &str, b: &str) -> bool {
+    match (major_minor(a), major_minor(b)) {
+        (Some((0, minor_a)), Some((0, minor_b))) => minor_a == minor_b,
+        (Some((major_a, _)), Some((major_b, _))) => major_a == major_b,
+        _ => false,
+    }
+}
+
+/// 解析 "1.2.3" / "v1.2.3-beta" 形式版本号的主、次版本号
+fn major_minor(version: &str) -> Option<(u32, u32)> {
+    let version = version.trim();
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let mut parts = version.split(['.', '-', '+']);
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().map_or(Some(0), |minor| minor.parse().ok())?;
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_compatible() {
+        assert!(is_compatible("1.2.0", "1.5.3"));
+        assert!(is_compatible("v2.0.0-beta", "2.1"));
+        assert!(!is_compatible("1.2.0", "2.0.0"));
+
+        // 0.x 阶段次版本号也必须相同
+        assert!(is_compatible("0.3.1", "0.3.4"));
+        assert!(!is_compatible("0.1.0", "0.2.0"));
+
+        assert!(!is_compatible("", "0.1.0"));
+        assert!(!is_compatible("dev", "dev"));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::config::AUTO_STOP_WARNING_SECS;
+use log::warn;
+use serde::Serialize;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+/// 会话即将自动结束时发给前端的事件，负载为 `AutoStopWarning`
+pub const AUTO_STOP_WARNING_EVENT: &str = "session-auto-stop-warning";
+
+/// AUTO_STOP_WARNING_EVENT 的负载
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoStopWarning {
+    /// "host" 或 "client"
+    pub role: &'static str,
+    pub remaining_secs: u64,
+}
+
+/// 会话的最长运行时间，到时后主循环按正常流程关闭 (离开房间、关闭连接、停止广播)
+///
+/// 结束前 AUTO_STOP_WARNING_SECS 秒经 `warning_tx` 预警一次
+#[derive(Debug, Clone)]
+pub struct AutoStop {
+    role: &'static str,
+    deadline: Instant,
+    warned: bool,
+    warning_tx: Sender<AutoStopWarning>,
+}
+
+impl AutoStop {
+    pub fn new(role: &'static str, max_duration: Duration, warning_tx: Sender<AutoStopWarning>) -> Self {
+        Self {
+            role,
+            deadline: Instant::now() + max_duration,
+            warned: false,
+            warning_tx,
+        }
+    }
+
+    /// 是否已到最长运行时间；进入预警时间时发出预警
+    pub fn expired(&mut self) -> bool {
+        self.poll_at(Instant::now())
+    }
+
+    fn poll_at(&mut self, now: Instant) -> bool {
+        let remaining = self.deadline.saturating_duration_since(now);
+        if remaining.is_zero() {
+            return true;
+        }
+        if !self.warned && remaining <= Duration::from_secs(AUTO_STOP_WARNING_SECS) {
+            self.warned = true;
+            let remaining_secs = remaining.as_secs_f64().ceil() as u64;
+            warn!("⏰ 已接近最长运行时间，会话将在 {} 秒后自动结束", remaining_secs);
+            let _ = self.warning_tx.send(AutoStopWarning {
+                role: self.role,
+                remaining_secs,
+            });
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_warns_once_before_expiring() {
+        let (tx, rx) = mpsc::channel();
+        let mut auto_stop = AutoStop::new("host", Duration::from_secs(AUTO_STOP_WARNING_SECS + 30), tx);
+        let start = Instant::now();
+
+        assert!(!auto_stop.poll_at(start));
+        assert!(rx.try_recv().is_err());
+
+        let warn_at = start + Duration::from_secs(31);
+        assert!(!auto_stop.poll_at(warn_at));
+        assert!(!auto_stop.poll_at(warn_at + Duration::from_secs(1)));
+        let warning = rx.try_recv().unwrap();
+        assert_eq!(warning.role, "host");
+        assert!(warning.remaining_secs <= AUTO_STOP_WARNING_SECS);
+        assert!(rx.try_recv().is_err());
+
+        assert!(auto_stop.poll_at(start + Duration::from_secs(AUTO_STOP_WARNING_SECS + 30)));
+    }
+}
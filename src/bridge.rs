@@ -0,0 +1,718 @@
+use crate::config::{
+    BRIDGE_IDLE_WAIT_MAX_MICROS, BRIDGE_IDLE_WAIT_MIN_MICROS, BUFFER_SIZE, MC_CONNECT_ATTEMPTS,
+    MC_RECONNECT_INTERVAL_MS,
+};
+use crate::correlation;
+use crate::framing::{Channel, Frame, FrameKind};
+use crate::host::McEvent;
+use crate::mc_socket::configure_mc_stream;
+use crate::metrics;
+use crate::panic_guard;
+use crate::send_queue::{QueueFullPolicy, SendQueue};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+use steamworks::SteamId;
+
+/// 连接本地 MC 服务器的超时
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 房主到本地 MC 服务器的桥接线程池
+///
+/// 每条 MC 连接不再独占一对阻塞线程，而是分配给固定数量的工作线程，
+/// 由工作线程以非阻塞 I/O 轮流处理，线程数不随玩家数增长
+pub struct BridgePool {
+    workers: Vec<Sender<WorkerCommand>>,
+    next: AtomicUsize,
+    port: u16,
+    /// 同一台机器上可供选择的其他 MC 服务器端口，序号从 1 开始
+    extra_ports: Vec<u16>,
+    from_mc_tx: SendQueue<McEvent>,
+    retry: RetryPolicy,
+    dry_run: Option<DryRunMode>,
+    channel: Channel,
+}
+
+/// 试运行: 建立房间和 P2P 连接但不连接 MC 服务器，用于单独排查 Steam 传输问题
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DryRunMode {
+    /// 把收到的数据原样发回客户端
+    Echo,
+    /// 只记录收到的数据量，直接丢弃
+    Discard,
+}
+
+/// 连接 MC 服务器的重试策略
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    /// 每次 (重新) 连接最多尝试的次数
+    attempts: u32,
+    interval: Duration,
+}
+
+impl BridgePool {
+    /// 启动 `threads` 个工作线程，MC 服务器返回的数据写入 `from_mc_tx`
+    pub fn new(threads: usize, port: u16, from_mc_tx: SendQueue<McEvent>) -> Self {
+        let workers = (0..threads.max(1))
+            .map(|index| {
+                let (tx, rx) = mpsc::channel();
+                correlation::spawn(move || {
+                    panic_guard::run(format!("桥接线程 {}", index), || run_worker(index, rx))
+                });
+                tx
+            })
+            .collect();
+        Self {
+            workers,
+            next: AtomicUsize::new(0),
+            port,
+            extra_ports: Vec::new(),
+            from_mc_tx,
+            retry: RetryPolicy {
+                attempts: MC_CONNECT_ATTEMPTS.max(1),
+                interval: Duration::from_millis(MC_RECONNECT_INTERVAL_MS),
+            },
+            dry_run: None,
+            channel: Channel::Minecraft,
+        }
+    }
+
+    /// 桥接到 MC 服务器的其他端口 (如 RCON)，产生的帧标记为 `channel`
+    pub fn with_channel(mut self, channel: Channel) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// 客户端还可以选择连接的其他 MC 服务器，第 n 个端口对应服务器序号 n
+    pub fn with_extra_ports(mut self, ports: Vec<u16>) -> Self {
+        self.extra_ports = ports;
+        self
+    }
+
+    /// 服务器序号是否有效，0 为主服务器
+    pub fn has_server(&self, server: u8) -> bool {
+        usize::from(server) <= self.extra_ports.len()
+    }
+
+    /// 试运行模式: 之后打开的流不连接 MC 服务器，按 `mode` 回显或丢弃数据
+    pub fn with_dry_run(mut self, mode: Option<DryRunMode>) -> Self {
+        self.dry_run = mode;
+        self
+    }
+
+    pub fn dry_run(&self) -> Option<DryRunMode> {
+        self.dry_run
+    }
+
+    #[cfg(test)]
+    fn with_retry(mut self, attempts: u32, interval: Duration) -> Self {
+        self.retry = RetryPolicy { attempts, interval };
+        self
+    }
+
+    /// 当前新连接使用的 MC 服务器端口
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// 更换 MC 服务器端口
+    ///
+    /// 之后打开的连接使用新端口；已有连接保持原端口 (包括断线重连)，
+    /// 除非 `reconnect` 为 true，此时它们断开后立即重新连接到新端口。
+    /// 经 `open_server` 连接到其他服务器的连接不受影响
+    pub fn set_port(&mut self, port: u16, reconnect: bool) {
+        self.port = port;
+        for worker in &self.workers {
+            let _ = worker.send(WorkerCommand::Retarget { port, reconnect });
+        }
+    }
+
+    /// 为客户端的一条流打开到 MC 服务器的连接，返回向其写入数据的通道
+    ///
+    /// 通道断开（流或玩家已关闭）后，桥接写完剩余数据并关闭 TCP 连接。
+    /// MC 服务器不可达或断开时按重试策略重新连接，服务器短暂重启不会断开玩家；
+    /// 重试用尽后向主循环发送该流的关闭帧
+    pub fn open(&self, steam_id: SteamId, stream_id: u32) -> Sender<Vec<u8>> {
+        self.spawn_bridge(steam_id, stream_id, self.port, false)
+    }
+
+    /// 与 `open` 相同，但连接到序号为 `server` 的 MC 服务器，序号无效时返回 None
+    ///
+    /// 连接到其他服务器的连接固定使用其端口，不随 `set_port` 更换
+    pub fn open_server(&self, steam_id: SteamId, stream_id: u32, server: u8) -> Option<Sender<Vec<u8>>> {
+        match server {
+            0 => Some(self.open(steam_id, stream_id)),
+            n => {
+                let port = *self.extra_ports.get(usize::from(n) - 1)?;
+                Some(self.spawn_bridge(steam_id, stream_id, port, true))
+            }
+        }
+    }
+
+    fn spawn_bridge(&self, steam_id: SteamId, stream_id: u32, port: u16, pinned: bool) -> Sender<Vec<u8>> {
+        let (to_mc_tx, to_mc_rx) = mpsc::channel();
+        let bridge = Bridge {
+            steam_id,
+            stream_id,
+            port,
+            pinned,
+            to_mc_rx,
+            from_mc_tx: self.from_mc_tx.clone(),
+            retry: self.retry,
+            dry_run: self.dry_run,
+            channel: self.channel,
+        };
+
+        // 轮流分配；工作线程已退出时桥接随之丢弃，主循环写入失败后会关闭该流
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+        if self.workers[index].send(WorkerCommand::Open(bridge)).is_err() {
+            warn!("⚠️ 桥接线程 {} 已退出，无法为 {:?} (流 {}) 建立连接", index, steam_id, stream_id);
+        }
+        to_mc_tx
+    }
+}
+
+/// 线程池发给工作线程的命令
+enum WorkerCommand {
+    /// 接管一条新桥接
+    Open(Bridge),
+    /// MC 服务器端口已更换
+    Retarget { port: u16, reconnect: bool },
+}
+
+/// 等待建立的桥接
+struct Bridge {
+    steam_id: SteamId,
+    stream_id: u32,
+    port: u16,
+    /// 连接到其他服务器，不随 set_port 更换端口
+    pinned: bool,
+    to_mc_rx: Receiver<Vec<u8>>,
+    from_mc_tx: SendQueue<McEvent>,
+    retry: RetryPolicy,
+    dry_run: Option<DryRunMode>,
+    channel: Channel,
+}
+
+/// 工作线程持有的一条 MC 连接
+struct Connection {
+    steam_id: SteamId,
+    stream_id: u32,
+    addr: SocketAddr,
+    /// 连接到其他服务器，不随 set_port 更换端口
+    pinned: bool,
+    retry: RetryPolicy,
+    /// 连接 MC 服务器已失败的次数，在交换数据前就被断开也算一次
+    failures: u32,
+    /// 已与 MC 服务器交换过数据；此后断开不再重连，客户端的字节流无法接续到新连接上
+    exchanged: bool,
+    /// 未连接 (等待重连或已放弃) 时为 None
+    stream: Option<TcpStream>,
+    /// 正在 (重新) 连接 MC 服务器；stream 和 reconnect 都为 None 时送出关闭帧后移除
+    reconnect: Option<Reconnect>,
+    to_mc_rx: Receiver<Vec<u8>>,
+    from_mc_tx: SendQueue<McEvent>,
+    /// 已从 Steam 收到、尚未写入 MC 的数据
+    write_buf: Vec<u8>,
+    /// 主循环队列已满时暂存的帧，送出前不再读取该连接
+    held: VecDeque<Frame>,
+    /// 试运行时不连接 MC 服务器
+    dry_run: Option<DryRunMode>,
+    /// 产生的帧所属的通道
+    channel: Channel,
+}
+
+/// 连接 MC 服务器的进度
+struct Reconnect {
+    next_attempt: Instant,
+    /// 旧连接断开后重连: 期间收到的 Steam 数据属于旧连接，丢弃而不是缓冲
+    discard_input: bool,
+}
+
+/// 单次轮询的结果
+enum Progress {
+    Idle,
+    Busy,
+    Done,
+}
+
+/// 工作线程: 没有连接时阻塞等待新桥接，否则轮流处理各连接
+fn run_worker(index: usize, rx: Receiver<WorkerCommand>) {
+    let mut connections: Vec<Connection> = Vec::new();
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    // 连续没有任何数据可处理的轮数
+    let mut idle_rounds: u32 = 0;
+
+    let handle = |connections: &mut Vec<Connection>, command| match command {
+        WorkerCommand::Open(bridge) => connections.push(Connection::new(bridge)),
+        WorkerCommand::Retarget { port, reconnect: true } => {
+            for connection in connections.iter_mut().filter(|connection| !connection.pinned) {
+                connection.retarget(port);
+            }
+        }
+        WorkerCommand::Retarget { reconnect: false, .. } => {}
+    };
+
+    loop {
+        if connections.is_empty() {
+            match rx.recv() {
+                Ok(command) => handle(&mut connections, command),
+                Err(_) => break, // 线程池已释放且没有剩余连接
+            }
+        }
+        while let Ok(command) = rx.try_recv() {
+            handle(&mut connections, command);
+        }
+
+        let mut busy = false;
+        connections.retain_mut(|connection| match connection.poll(&mut buffer) {
+            Progress::Idle => true,
+            Progress::Busy => {
+                busy = true;
+                true
+            }
+            Progress::Done => false,
+        });
+
+        if busy {
+            idle_rounds = 0;
+        } else {
+            thread::sleep(idle_wait(idle_rounds));
+            idle_rounds = idle_rounds.saturating_add(1);
+        }
+    }
+    info!("桥接线程 {} 已退出", index);
+}
+
+/// 连续空闲 `idle_rounds` 轮后的等待时间
+///
+/// 多条连接共用一个线程，无法对单个 socket 阻塞读取；改为空闲越久等待越长，
+/// 避免长时间空闲时以固定的短间隔空转，有数据时立即回到最短等待
+fn idle_wait(idle_rounds: u32) -> Duration {
+    let micros = BRIDGE_IDLE_WAIT_MIN_MICROS
+        .max(1)
+        .saturating_mul(1 << idle_rounds.min(16))
+        .min(BRIDGE_IDLE_WAIT_MAX_MICROS.max(BRIDGE_IDLE_WAIT_MIN_MICROS));
+    Duration::from_micros(micros)
+}
+
+fn open_mc_stream(addr: &SocketAddr) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(addr, CONNECT_TIMEOUT)?;
+    configure_mc_stream(&stream)?;
+    stream.set_nonblocking(true)?;
+    Ok(stream)
+}
+
+impl Connection {
+    /// 新建的连接在第一次轮询时连接 MC 服务器 (试运行时不连接)
+    fn new(bridge: Bridge) -> Self {
+        Self {
+            steam_id: bridge.steam_id,
+            stream_id: bridge.stream_id,
+            addr: SocketAddr::from((Ipv4Addr::LOCALHOST, bridge.port)),
+            pinned: bridge.pinned,
+            retry: bridge.retry,
+            failures: 0,
+            exchanged: false,
+            stream: None,
+            reconnect: bridge.dry_run.is_none().then(|| Reconnect {
+                next_attempt: Instant::now(),
+                discard_input: false,
+            }),
+            to_mc_rx: bridge.to_mc_rx,
+            from_mc_tx: bridge.from_mc_tx,
+            write_buf: Vec::new(),
+            held: VecDeque::new(),
+            dry_run: bridge.dry_run,
+            channel: bridge.channel,
+        }
+    }
+
+    /// 处理一轮: 送出暂存帧、Steam -> MC 写入、MC -> Steam 读取
+    fn poll(&mut self, buffer: &mut [u8]) -> Progress {
+        let held_before = self.held.len();
+        if !self.deliver() {
+            return Progress::Done; // 主循环已退出
+        }
+        let mut busy = self.held.len() < held_before;
+
+        if let Some(mode) = self.dry_run {
+            return self.poll_dry_run(mode, busy);
+        }
+        if self.reconnect.is_some() {
+            return self.poll_reconnect();
+        }
+        let Some(stream) = self.stream.as_mut() else {
+            // 已放弃连接，关闭帧送出后移除
+            return if self.held.is_empty() { Progress::Done } else { Progress::Idle };
+        };
+
+        // Steam -> MC
+        let mut steam_closed = false;
+        loop {
+            match self.to_mc_rx.try_recv() {
+                Ok(data) => {
+                    self.write_buf.extend_from_slice(&data);
+                    busy = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    steam_closed = true;
+                    break;
+                }
+            }
+        }
+        while !self.write_buf.is_empty() {
+            match stream.write(&self.write_buf) {
+                Ok(0) => return self.close_mc(),
+                Ok(n) => {
+                    self.write_buf.drain(..n);
+                    self.exchanged = true;
+                    busy = true;
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return self.close_mc(), // MC server connection closed
+            }
+        }
+        if steam_closed && self.write_buf.is_empty() {
+            // 流或玩家已关闭且数据已写完，关闭 TCP 连接
+            let _ = stream.shutdown(Shutdown::Both);
+            return Progress::Done;
+        }
+
+        // MC -> Steam；有暂存帧时停止读取，由 TCP 流控把压力传回 MC 服务器
+        if self.held.is_empty() {
+            match stream.read(buffer) {
+                Ok(0) => {
+                    info!("MC 服务器关闭连接 ({:?}, 流 {})", self.steam_id, self.stream_id);
+                    return self.close_mc();
+                }
+                Ok(n) => {
+                    busy = true;
+                    self.exchanged = true;
+                    self.held.push_back(Frame::data(self.stream_id, buffer[..n].to_vec()).on(self.channel));
+                    if !self.deliver() {
+                        return Progress::Done;
+                    }
+                    if !self.held.is_empty() {
+                        metrics::record_backpressure();
+                    }
+                }
+                Err(ref e)
+                    if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    error!("✗ 读取 MC 服务器失败: {:?}", e);
+                    return self.close_mc();
+                }
+            }
+        }
+
+        if busy {
+            Progress::Busy
+        } else {
+            Progress::Idle
+        }
+    }
+
+    /// 试运行: 回显或丢弃收到的数据；有暂存帧时停止接收，和读取 MC 时一样施加背压
+    fn poll_dry_run(&mut self, mode: DryRunMode, mut busy: bool) -> Progress {
+        while self.held.is_empty() {
+            match self.to_mc_rx.try_recv() {
+                Ok(data) => {
+                    busy = true;
+                    match mode {
+                        DryRunMode::Echo => {
+                            self.held.push_back(Frame::data(self.stream_id, data).on(self.channel));
+                            if !self.deliver() {
+                                return Progress::Done;
+                            }
+                        }
+                        DryRunMode::Discard => debug!(
+                            "试运行: 丢弃 {:?} (流 {}) 的 {} 字节",
+                            self.steam_id,
+                            self.stream_id,
+                            data.len()
+                        ),
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                // 流或玩家已关闭
+                Err(TryRecvError::Disconnected) => return Progress::Done,
+            }
+        }
+        if busy {
+            Progress::Busy
+        } else {
+            Progress::Idle
+        }
+    }
+
+    /// 按顺序送出暂存的帧，返回 false 表示主循环已退出
+    ///
+    /// 队列满时按队列策略暂停读取或丢弃数据；关闭帧始终保留，保证连接最终能关闭
+    fn deliver(&mut self) -> bool {
+        while let Some(frame) = self.held.pop_front() {
+            match self.from_mc_tx.try_send((self.steam_id, frame)) {
+                Ok(()) => {}
+                Err(TrySendError::Full((_, frame)))
+                    if frame.kind == FrameKind::Data
+                        && self.from_mc_tx.policy() == QueueFullPolicy::Drop =>
+                {
+                    metrics::record_packet_dropped();
+                }
+                Err(TrySendError::Full((_, frame))) => {
+                    self.held.push_front(frame);
+                    break;
+                }
+                Err(TrySendError::Disconnected(_)) => return false,
+            }
+        }
+        true
+    }
+
+    /// 改用新端口: 已连接的断开后立即重连，正在连接的下一次尝试直接使用新端口
+    ///
+    /// 已交换过数据的连接无法接续到新服务器上，通知客户端关闭
+    fn retarget(&mut self, port: u16) {
+        self.addr.set_port(port);
+        self.failures = 0;
+        match (self.stream.take(), self.reconnect.as_mut()) {
+            (Some(stream), _) => {
+                let _ = stream.shutdown(Shutdown::Both);
+                // 未写出的数据属于旧连接
+                self.write_buf.clear();
+                if self.exchanged {
+                    info!("🔒 服务器端口已更改，关闭已建立的连接 ({:?}, 流 {})", self.steam_id, self.stream_id);
+                    self.give_up();
+                    return;
+                }
+                info!(
+                    "🔁 重新连接到 MC 服务器 {} ({:?}, 流 {})",
+                    self.addr, self.steam_id, self.stream_id
+                );
+                self.reconnect = Some(Reconnect {
+                    next_attempt: Instant::now(),
+                    discard_input: true,
+                });
+            }
+            (None, Some(reconnect)) => {
+                reconnect.next_attempt = Instant::now();
+            }
+            // 已放弃连接，等待关闭帧送出
+            (None, None) => {}
+        }
+    }
+
+    /// MC 侧已断开: 释放 TCP 连接
+    ///
+    /// 已交换过数据的流通知客户端关闭，新连接无法接续客户端的字节流；
+    /// 尚未交换数据时 (如服务器正在重启) 客户端的数据仍在 write_buf 中，稍后重新连接
+    fn close_mc(&mut self) -> Progress {
+        self.stream = None;
+        self.failures += 1;
+        if self.exchanged || self.failures >= self.retry.attempts {
+            self.write_buf.clear();
+            return self.give_up();
+        }
+        info!(
+            "🔁 {} ms 后重新连接 MC 服务器 ({:?}, 流 {})",
+            self.retry.interval.as_millis(),
+            self.steam_id,
+            self.stream_id
+        );
+        self.reconnect = Some(Reconnect {
+            next_attempt: Instant::now() + self.retry.interval,
+            discard_input: false,
+        });
+        Progress::Busy
+    }
+
+    /// 不再连接，送出关闭帧后移除
+    fn give_up(&mut self) -> Progress {
+        self.reconnect = None;
+        self.held.push_back(Frame::close(self.stream_id).on(self.channel));
+        if !self.deliver() {
+            return Progress::Done;
+        }
+        Progress::Busy
+    }
+
+    /// 到时间后尝试连接；重试用尽时通知主循环该流已结束
+    fn poll_reconnect(&mut self) -> Progress {
+        let Some(reconnect) = self.reconnect.as_mut() else {
+            return Progress::Idle;
+        };
+        loop {
+            match self.to_mc_rx.try_recv() {
+                Ok(data) if !reconnect.discard_input => self.write_buf.extend_from_slice(&data),
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => break,
+                // 客户端已关闭该流，无需再连接
+                Err(TryRecvError::Disconnected) => return Progress::Done,
+            }
+        }
+
+        if Instant::now() < reconnect.next_attempt {
+            return Progress::Idle;
+        }
+
+        info!(
+            "🔗 为 {:?} (流 {}) 连接 MC 服务器 {} ({}/{})...",
+            self.steam_id,
+            self.stream_id,
+            self.addr,
+            self.failures + 1,
+            self.retry.attempts
+        );
+        match open_mc_stream(&self.addr) {
+            Ok(stream) => {
+                info!("✅ {:?} 已连接到 MC 服务器", self.steam_id);
+                self.stream = Some(stream);
+                self.reconnect = None;
+            }
+            Err(e) => {
+                self.failures += 1;
+                if self.failures < self.retry.attempts {
+                    warn!("⚠️ 连接 MC 服务器失败 ({:?}, 流 {}): {}", self.steam_id, self.stream_id, e);
+                    reconnect.next_attempt = Instant::now() + self.retry.interval;
+                } else {
+                    warn!(
+                        "⚠️ MC 服务器连接断开 ({:?}, 流 {}): {}，已重试 {} 次，关闭该连接",
+                        self.steam_id, self.stream_id, e, self.failures
+                    );
+                    return self.give_up();
+                }
+            }
+        }
+        Progress::Busy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::send_queue;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_idle_wait_backs_off_to_limit() {
+        assert_eq!(idle_wait(0), Duration::from_micros(BRIDGE_IDLE_WAIT_MIN_MICROS));
+        assert!(idle_wait(1) > idle_wait(0));
+        assert_eq!(idle_wait(u32::MAX), Duration::from_micros(BRIDGE_IDLE_WAIT_MAX_MICROS));
+    }
+
+    #[test]
+    fn test_unreachable_server_reports_close_after_retries() {
+        // 绑定后立即释放，得到一个没有服务监听的端口
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let (from_mc_tx, from_mc_rx) = send_queue::bounded(4);
+        let pool = BridgePool::new(1, port, from_mc_tx).with_retry(3, Duration::from_millis(10));
+        let steam_id = SteamId::from_raw(76561198000000004);
+
+        let _to_mc = pool.open(steam_id, 7);
+        let (id, frame) = from_mc_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(id, steam_id);
+        assert_eq!(frame, Frame::close(7));
+    }
+
+    #[test]
+    fn test_server_closing_established_stream_closes_it() {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (from_mc_tx, from_mc_rx) = send_queue::bounded(4);
+        let pool = BridgePool::new(1, server.local_addr().unwrap().port(), from_mc_tx)
+            .with_retry(3, Duration::from_millis(10));
+        let steam_id = SteamId::from_raw(76561198000000007);
+
+        let to_mc = pool.open(steam_id, 9);
+        to_mc.send(b"handshake".to_vec()).unwrap();
+        let (mut stream, _) = server.accept().unwrap();
+        stream.read_exact(&mut [0u8; 9]).unwrap();
+        stream.write_all(b"status").unwrap();
+        let (_, frame) = from_mc_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(frame, Frame::data(9, b"status".to_vec()));
+
+        // 服务器关闭已交换过数据的连接: 通知客户端关闭，而不是把剩余数据送到新连接
+        drop(stream);
+        let (id, frame) = from_mc_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(id, steam_id);
+        assert_eq!(frame, Frame::close(9));
+        server.set_nonblocking(true).unwrap();
+        // 连接可能已被移除，发送失败也说明不会重连
+        let _ = to_mc.send(b"more".to_vec());
+        thread::sleep(Duration::from_millis(100));
+        assert!(server.accept().is_err());
+    }
+
+    #[test]
+    fn test_dry_run_echoes_without_connecting() {
+        // 端口上没有服务，试运行不应尝试连接
+        let (from_mc_tx, from_mc_rx) = send_queue::bounded(4);
+        let pool = BridgePool::new(1, 1, from_mc_tx).with_dry_run(Some(DryRunMode::Echo));
+        let steam_id = SteamId::from_raw(76561198000000006);
+
+        let to_mc = pool.open(steam_id, 3);
+        to_mc.send(b"ping".to_vec()).unwrap();
+        let (id, frame) = from_mc_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(id, steam_id);
+        assert_eq!(frame, Frame::data(3, b"ping".to_vec()));
+
+        // 关闭流不会产生关闭帧，也不会有其他数据
+        drop(to_mc);
+        assert!(from_mc_rx.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    #[test]
+    fn test_retarget_moves_existing_connections_only_on_request() {
+        let old_server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let new_server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (from_mc_tx, from_mc_rx) = send_queue::bounded(4);
+        let extra_server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut pool = BridgePool::new(1, old_server.local_addr().unwrap().port(), from_mc_tx)
+            .with_extra_ports(vec![extra_server.local_addr().unwrap().port()]);
+        let steam_id = SteamId::from_raw(76561198000000005);
+
+        let _to_mc = pool.open(steam_id, 1);
+        let (mut old_stream, _) = old_server.accept().unwrap();
+        // 这条连接已交换过数据
+        old_stream.write_all(b"status").unwrap();
+        let (_, frame) = from_mc_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(frame, Frame::data(1, b"status".to_vec()));
+
+        let new_port = new_server.local_addr().unwrap().port();
+        pool.set_port(new_port, false);
+        assert_eq!(pool.port(), new_port);
+        let _to_mc_new = pool.open(steam_id, 2);
+        new_server.accept().unwrap();
+
+        // 其他服务器上的连接固定端口
+        assert!(pool.open_server(steam_id, 4, 2).is_none());
+        let _to_extra = pool.open_server(steam_id, 3, 1).unwrap();
+        let (mut extra_stream, _) = extra_server.accept().unwrap();
+
+        // 要求重连后旧连接被关闭: 交换过数据的通知客户端关闭，其余的连接到新端口
+        pool.set_port(new_port, true);
+        new_server.accept().unwrap();
+        old_stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(old_stream.read(&mut [0u8; 16]).unwrap(), 0);
+        let (id, frame) = from_mc_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(id, steam_id);
+        assert_eq!(frame, Frame::close(1));
+        new_server.set_nonblocking(true).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert!(new_server.accept().is_err());
+        extra_stream.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+        assert!(extra_stream.read(&mut [0u8; 16]).is_err());
+    }
+}
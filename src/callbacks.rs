@@ -1,10 +1,128 @@
+use crate::commands;
+use crate::config::{ALLOW_MIXED_MODE, AUTO_JOIN_ON_INVITE};
+use crate::join_target;
+use crate::session::{self, SessionRole, SessionState};
 use log::{info, warn};
+use serde::Serialize;
 use std::sync::{Arc, Mutex};
 use steamworks::networking_types::NetConnectionStatusChanged;
 use steamworks::{
-    CallbackHandle, Client, GameLobbyJoinRequested, LobbyId, P2PSessionConnectFail,
-    P2PSessionRequest,
+    CallbackHandle, Client, GameLobbyJoinRequested, GameRichPresenceJoinRequested, LobbyId,
+    P2PSessionConnectFail, P2PSessionRequest,
 };
+use tauri::{AppHandle, Emitter};
+
+/// 前端监听的加入请求事件
+pub const JOIN_REQUESTED_EVENT: &str = "lobby-join-requested";
+
+/// 加入请求事件的负载，房间号以字符串传递
+#[derive(Debug, Clone, Serialize)]
+pub struct JoinRequest {
+    pub lobby_id: String,
+    /// 与当前会话的冲突，不为 None 时不会自动加入，由前端决定如何处理
+    pub conflict: JoinConflict,
+    /// 已加入的房间号，仅在已连接时提供
+    pub current_lobby_id: Option<String>,
+}
+
+/// 收到加入请求时与当前会话的关系
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JoinConflict {
+    /// 没有会话在运行，可以直接加入
+    None,
+    /// 已在请求的房间中
+    AlreadyJoined,
+    /// 已在 (或正在加入) 另一个房间，前端提示用户是否切换 (`switch_lobby`)
+    InOtherLobby,
+    /// 正在作为房主运行，不能同时加入
+    Hosting,
+}
+
+/// GUI 的加入请求回调句柄，丢弃句柄会注销回调，因此在进程生命周期内保留
+static JOIN_HANDLES: Mutex<Vec<CallbackHandle>> = Mutex::new(Vec::new());
+
+/// 注册 Steam 覆盖层"加入游戏"相关回调，收到请求后通知前端
+///
+/// 好友通过大厅邀请加入时触发 `GameLobbyJoinRequested`；通过富状态 connect
+/// 字符串或 `invite_user_to_game` 邀请加入时触发 `GameRichPresenceJoinRequested`
+pub fn register_join_requests(client: &Client, app: AppHandle) {
+    let lobby_app = app.clone();
+    let lobby_handle = client.register_callback(move |val: GameLobbyJoinRequested| {
+        info!("📨 收到 Steam 加入请求 (好友 {:?})", val.friend_steam_id);
+        handle_join_request(&lobby_app, val.lobby_steam_id.raw());
+    });
+
+    let presence_handle = client.register_callback(move |val: GameRichPresenceJoinRequested| {
+        info!("📨 收到 Steam 加入请求 (好友 {:?}): {}", val.friend_steam_id, val.connect);
+        match join_target::parse_connect_lobby(&val.connect) {
+            Some(lobby_id) => handle_join_request(&app, lobby_id),
+            None => warn!("⚠️ 无法识别的 connect 字符串: {}", val.connect),
+        }
+    });
+
+    JOIN_HANDLES
+        .lock()
+        .unwrap()
+        .extend([lobby_handle, presence_handle]);
+}
+
+/// 通知前端，没有会话冲突时按配置自动加入房间
+///
+/// 已有会话时不会自动加入，避免打断正在进行的游戏；切换由用户在前端确认
+fn handle_join_request(app: &AppHandle, lobby_id: u64) {
+    let current_lobby_id = match session::state() {
+        SessionState::Connected { lobby_id, .. }
+        | SessionState::SteamOffline {
+            lobby_id,
+            hosting: false,
+        } => Some(lobby_id),
+        _ => None,
+    };
+    let request = JoinRequest {
+        lobby_id: lobby_id.to_string(),
+        conflict: join_conflict(lobby_id, current_lobby_id.as_deref()),
+        current_lobby_id,
+    };
+    match request.conflict {
+        JoinConflict::None => {}
+        JoinConflict::AlreadyJoined => info!("已在房间 {} 中，忽略加入请求", lobby_id),
+        JoinConflict::InOtherLobby => info!("已在其他房间中，等待用户确认是否切换到 {}", lobby_id),
+        JoinConflict::Hosting => warn!("⚠️ 正在作为房主运行，无法加入房间 {}", lobby_id),
+    }
+    if let Err(e) = app.emit(JOIN_REQUESTED_EVENT, request.clone()) {
+        warn!("⚠️ 无法通知前端加入请求: {}", e);
+    }
+
+    if AUTO_JOIN_ON_INVITE && request.conflict == JoinConflict::None {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) =
+                commands::join_lobby(request.lobby_id, None, None, None, None, None, None, None, app).await
+            {
+                warn!("⚠️ 自动加入房间失败: {}", e);
+            }
+        });
+    }
+}
+
+/// 判断加入请求与当前会话的关系，`current_lobby_id` 为客户端已连接的房间
+fn join_conflict(lobby_id: u64, current_lobby_id: Option<&str>) -> JoinConflict {
+    if session::is_active(SessionRole::Client) {
+        // 仍在加入中的房间也算已加入，不提示切换到同一房间
+        return if current_lobby_id == Some(lobby_id.to_string().as_str())
+            || session::client_target() == Some(lobby_id)
+        {
+            JoinConflict::AlreadyJoined
+        } else {
+            JoinConflict::InOtherLobby
+        };
+    }
+    if !ALLOW_MIXED_MODE && session::is_active(SessionRole::Host) {
+        return JoinConflict::Hosting;
+    }
+    JoinConflict::None
+}
 
 #[allow(dead_code)]
 pub struct CallbackRegistry {
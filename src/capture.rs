@@ -0,0 +1,254 @@
+use crate::config::{CAPTURE_MAX_FILES, CAPTURE_MAX_FILE_BYTES, CAPTURE_QUEUE_CAPACITY};
+use crate::framing::Frame;
+use log::{info, warn};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender, TryRecvError, TrySendError};
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 抓包文件开头的标识和格式版本
+///
+/// 之后每条记录 (小端): 时间戳 μs (u64) | 对端 Steam ID (u64) | 方向 (u8) |
+/// 帧类型 (u8) | 通道 (u8) | 流 ID (u32) | 负载长度 (u32) | 负载
+const FILE_MAGIC: &[u8; 6] = b"MCCAP\x02";
+
+/// 单条记录头部长度
+const RECORD_HEADER_LEN: u64 = 8 + 8 + 1 + 1 + 1 + 4 + 4;
+
+/// 帧相对本机的方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// 从对端收到
+    Received = 0,
+    /// 发送给对端
+    Sent = 1,
+}
+
+struct CaptureRecord {
+    timestamp_us: u64,
+    peer: u64,
+    direction: Direction,
+    frame: Frame,
+}
+
+/// 是否正在抓包，转发热路径上先检查它，未启用时不复制数据
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// 写入线程的发送端，None 表示未启用
+static SINK: LazyLock<Mutex<Option<SyncSender<CaptureRecord>>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+/// 写入线程跟不上而丢弃的记录数
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// 开始把转发的帧写入指定文件，若已在抓包则切换到新文件
+///
+/// 抓包内容包含完整的游戏数据，只应在排查协议问题时临时开启。
+/// 文件超过 CAPTURE_MAX_FILE_BYTES 时轮转，最多保留 CAPTURE_MAX_FILES 个旧文件
+pub fn start(path: &Path) -> io::Result<()> {
+    let mut writer = RotatingWriter::create(path, CAPTURE_MAX_FILE_BYTES, CAPTURE_MAX_FILES)?;
+
+    // 有界队列: 磁盘跟不上时丢弃记录，而不是阻塞转发
+    let (tx, rx) = mpsc::sync_channel::<CaptureRecord>(CAPTURE_QUEUE_CAPACITY);
+    let display_path = path.display().to_string();
+    thread::spawn(move || {
+        let mut next = rx.recv().ok();
+        while let Some(record) = next {
+            if let Err(e) = writer.write(&record) {
+                warn!("✗ 写入抓包文件失败: {}", e);
+                ACTIVE.store(false, Ordering::Relaxed);
+                break;
+            }
+            // 没有积压时刷新，避免崩溃丢失数据
+            next = match rx.try_recv() {
+                Ok(record) => Some(record),
+                Err(TryRecvError::Empty) => {
+                    let _ = writer.flush();
+                    rx.recv().ok()
+                }
+                Err(TryRecvError::Disconnected) => None,
+            };
+        }
+        let _ = writer.flush();
+        info!(
+            "📦 抓包已停止: {} (丢弃 {} 条记录)",
+            display_path,
+            DROPPED.swap(0, Ordering::Relaxed)
+        );
+    });
+
+    *SINK.lock().unwrap() = Some(tx);
+    ACTIVE.store(true, Ordering::Relaxed);
+    warn!("📦 抓包已启用: {} (文件包含完整游戏数据)", path.display());
+    Ok(())
+}
+
+/// 停止抓包，写入线程会刷新缓冲区后退出
+pub fn stop() {
+    ACTIVE.store(false, Ordering::Relaxed);
+    SINK.lock().unwrap().take();
+}
+
+/// 记录一个转发的帧，未启用时什么也不做
+pub fn record(peer: u64, direction: Direction, frame: &Frame) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    let sink = SINK.lock().unwrap();
+    let Some(tx) = sink.as_ref() else {
+        return;
+    };
+
+    let record = CaptureRecord {
+        timestamp_us: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0),
+        peer,
+        direction,
+        frame: frame.clone(),
+    };
+    match tx.try_send(record) {
+        Ok(()) | Err(TrySendError::Disconnected(_)) => {}
+        Err(TrySendError::Full(_)) => {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// 默认的抓包文件: <应用数据目录>/capture/capture-<开始时间>.bin
+pub fn default_path(data_dir: &Path) -> PathBuf {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    data_dir
+        .join("capture")
+        .join(format!("capture-{}.bin", started))
+}
+
+/// 超过大小上限时轮转的抓包文件: path -> path.1 -> path.2 ...
+struct RotatingWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: BufWriter<File>,
+    written: u64,
+}
+
+impl RotatingWriter {
+    fn create(path: &Path, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            max_files,
+            file: BufWriter::new(File::create(path)?),
+            written: 0,
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        self.file.write_all(FILE_MAGIC)?;
+        self.written = FILE_MAGIC.len() as u64;
+        Ok(())
+    }
+
+    fn write(&mut self, record: &CaptureRecord) -> io::Result<()> {
+        let frame = &record.frame;
+        let len = RECORD_HEADER_LEN + frame.payload.len() as u64;
+        // 单条记录超过上限时仍然写入，避免空文件无限轮转
+        if self.written > FILE_MAGIC.len() as u64 && self.written + len > self.max_bytes {
+            self.rotate()?;
+        }
+
+        self.file.write_all(&record.timestamp_us.to_le_bytes())?;
+        self.file.write_all(&record.peer.to_le_bytes())?;
+        self.file
+            .write_all(&[record.direction as u8, frame.kind as u8, frame.channel as u8])?;
+        self.file.write_all(&frame.stream_id.to_le_bytes())?;
+        self.file.write_all(&(frame.payload.len() as u32).to_le_bytes())?;
+        self.file.write_all(&frame.payload)?;
+        self.written += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let _ = fs::remove_file(self.rotated_path(self.max_files));
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        if self.max_files > 0 {
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = BufWriter::new(File::create(&self.path)?);
+        self.write_header()
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::Channel;
+
+    #[test]
+    fn test_rotates_when_file_is_full() {
+        let dir = std::env::temp_dir().join(format!("mcconnect-capture-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("capture.bin");
+
+        let record = |stream_id| CaptureRecord {
+            timestamp_us: 1,
+            peer: 76561198000000002,
+            direction: Direction::Received,
+            frame: Frame::data(stream_id, vec![0xAB; 10]),
+        };
+        let record_len = RECORD_HEADER_LEN + 10;
+        // 每个文件只容得下两条记录，最多保留 1 个旧文件
+        let max_bytes = FILE_MAGIC.len() as u64 + 2 * record_len;
+        let mut writer = RotatingWriter::create(&path, max_bytes, 1).unwrap();
+        for stream_id in 0..5 {
+            writer.write(&record(stream_id)).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let current = fs::read(&path).unwrap();
+        assert_eq!(&current[..FILE_MAGIC.len()], FILE_MAGIC);
+        assert_eq!(current.len() as u64, FILE_MAGIC.len() as u64 + record_len);
+        // 最新文件只有第 5 条记录 (流 4)
+        let header = &current[FILE_MAGIC.len()..];
+        assert_eq!(u64::from_le_bytes(header[8..16].try_into().unwrap()), 76561198000000002);
+        assert_eq!(header[16], Direction::Received as u8);
+        assert_eq!(header[18], Channel::Minecraft as u8);
+        assert_eq!(u32::from_le_bytes(header[19..23].try_into().unwrap()), 4);
+        assert_eq!(u32::from_le_bytes(header[23..27].try_into().unwrap()), 10);
+
+        let rotated = fs::read(writer.rotated_path(1)).unwrap();
+        assert_eq!(rotated.len() as u64, max_bytes);
+        assert!(!writer.rotated_path(2).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
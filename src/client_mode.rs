@@ -1,27 +1,891 @@
-use crate::config::{BUFFER_SIZE, CLIENT_LISTEN_PORT};
-use crate::lan_discovery::LanBroadcaster;
-use crate::metrics;
+use crate::app_reliability::{self, ReliableLink};
+use crate::app_version::{self, APP_VERSION};
+use crate::auto_stop::AutoStop;
+use crate::capture::{self, Direction};
+use crate::config::{
+    APP_RELIABILITY, APP_UPDATE_URL, BUFFER_SIZE, CLIENT_LAN_BROADCAST, CLIENT_LISTEN_PORT, CLIENT_RCON_LISTEN_PORT,
+    CLIENT_LISTEN_PORT_FALLBACKS, LAN_DIRECT_CONNECT, LATENCY_POLL_INTERVAL_MS, LOBBY_DATA_SYNC_ATTEMPTS,
+    LOBBY_DATA_SYNC_INTERVAL_MS, MAX_CONSECUTIVE_RECEIVE_ERRORS, MC_READ_QUEUE_CAPACITY,
+    MC_READ_TIMEOUT_MS, P2P_VIRTUAL_PORT, REQUIRE_APP_VERSION_MATCH, SHUTDOWN_GRACE_PERIOD_MS,
+};
+use crate::correlation;
+use crate::panic_guard::{self, ThreadPanic};
+use crate::encryption;
+use crate::framing::{self, Channel, Frame, FrameKind};
+use crate::host::{
+    END_REASON_NOT_ALLOWED, END_REASON_UNENCRYPTED, LOBBY_KEY_APP_VERSION, LOBBY_KEY_HOST,
+    LOBBY_KEY_MC_SERVERS, LOBBY_KEY_MC_VERSION, LOBBY_KEY_MODPACK, LOBBY_KEY_PING_LOCATION, LOBBY_KEY_RCON,
+    LOBBY_KEY_VIRTUAL_PORT,
+};
+use crate::i18n::{Language, Message};
+use crate::lan_direct::{self, DirectBridges};
+use crate::lan_discovery::{BroadcastScope, LanBroadcaster};
+use crate::log_throttle::{self, LogThrottle};
+use crate::loop_timing::{self, TimerResolution};
+use crate::mc_socket::{bind_in_range, configure_mc_stream};
+use crate::metrics::{self, RouteKind};
+use crate::metrics_log;
+use crate::presence;
+use crate::route_preference;
+use crate::send_queue::{self, SendQueue};
+use crate::session::{self, SessionState};
+use crate::shutdown;
+use crate::steam::{self, OfflineWatch, OnlineChange};
+use crate::structured_log;
+use crate::transport::{Transport, TransportError};
 use log::{error, info, warn};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
 use std::io::{ErrorKind, Read, Write};
-use std::net::{TcpListener, TcpStream};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
 use std::sync::mpsc::{self, Receiver, Sender};
-use std::thread;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
-use steamworks::networking_types::{NetworkingConnectionState, NetworkingIdentity, SendFlags};
-use steamworks::{Client, LobbyId};
+use steamworks::networking_sockets::NetConnection;
+use steamworks::networking_types::{NetConnectionEnd, NetworkingConnectionState, NetworkingIdentity};
+use steamworks::{Client, LobbyId, SteamId};
+
+/// 当前客户端会话的控制通道，会话未运行时为 None
+static CLIENT_CONTROL: LazyLock<Mutex<Option<Sender<ClientCommand>>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+/// 发送给运行中客户端主循环的控制命令
+pub enum ClientCommand {
+    /// 离开房间，在宽限期内尽量送达待发送数据
+    Leave { grace_period: Duration },
+    /// 开启或关闭 RCON 转发，开启时经 reply 返回本地监听端口
+    SetRconForwarding {
+        enabled: bool,
+        reply: Sender<Result<Option<u16>, String>>,
+    },
+    /// 之后的新 MC 连接转发到房主的第 server 个 MC 服务器，已有连接不受影响
+    SetMcServer {
+        server: u8,
+        reply: Sender<Result<(), String>>,
+    },
+}
+
+/// 向运行中的客户端会话发送控制命令
+pub fn send_command(command: ClientCommand) -> Result<(), String> {
+    let control = CLIENT_CONTROL.lock().unwrap();
+    match control.as_ref() {
+        Some(tx) => tx
+            .send(command)
+            .map_err(|_| Message::ClientSessionEnded.text().to_string()),
+        None => Err(Message::NotInLobby.text().to_string()),
+    }
+}
+
+/// 客户端会话参数
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    pub password: Option<String>,
+    /// 本地 Minecraft 版本，用于与房主公布的版本比对
+    pub mc_version: Option<String>,
+    /// 本地整合包名称/版本
+    pub modpack: Option<String>,
+    /// LAN发现广播范围，默认仅本机
+    pub lan_scope: BroadcastScope,
+    /// 是否启动 LAN 发现广播，None 时使用 CLIENT_LAN_BROADCAST
+    ///
+    /// 关闭后 Minecraft 中不会出现 "LAN world"，需通过 直接连接 127.0.0.1:CLIENT_LISTEN_PORT 加入
+    pub lan_broadcast: Option<bool>,
+    /// 连接房主使用的 NetworkingSockets 虚拟端口，None 时使用 P2P_VIRTUAL_PORT
+    pub virtual_port: Option<i32>,
+    /// 最长运行时间，到时后自动离开房间
+    pub auto_stop: Option<AutoStop>,
+    /// 加入过程中等待房间元数据同步时的进度通知
+    pub progress_tx: Option<Sender<JoinProgress>>,
+    /// 会话中与房主的连接失效时的通知
+    pub connection_lost_tx: Option<Sender<ConnectionLost>>,
+    /// 工作线程 panic 时的通知
+    pub thread_panic_tx: Option<Sender<ThreadPanic>>,
+    /// 本地 MC 连接转发到房主的第几个 MC 服务器，0 为主服务器
+    pub mc_server: u8,
+}
+
+/// 会话中与房主的连接失效时发给前端的事件，负载为 `ConnectionLost`
+pub const CONNECTION_LOST_EVENT: &str = "client-connection-lost";
+
+/// CONNECTION_LOST_EVENT 的负载
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionLost {
+    pub message: String,
+}
+
+/// 加入过程中的进度通知，负载为 `JoinProgress`
+pub const JOIN_PROGRESS_EVENT: &str = "client-join-progress";
+
+/// JOIN_PROGRESS_EVENT 的负载
+#[derive(Debug, Clone, Serialize)]
+pub struct JoinProgress {
+    pub message: String,
+    /// 当前尝试次数，从 1 开始
+    pub attempt: u32,
+    pub max_attempts: u32,
+}
+
+/// 本地 MC 客户端连接，按帧协议中的流 ID 索引
+///
+/// MC 客户端会先建立一条连接查询服务器状态，再建立另一条连接进入游戏，
+/// 两者可能同时存在，每条连接在房主侧对应独立的服务器连接
+type McStreams = HashMap<u32, TcpStream>;
+
+/// 本地连接，MC 游戏连接和 RCON 连接各自使用独立的流 ID 空间
+#[derive(Default)]
+struct LocalStreams {
+    minecraft: McStreams,
+    rcon: McStreams,
+    /// 有数据未能发往房主的流，读取线程报告连接结束前其后的数据一律丢弃
+    broken: HashSet<(Channel, u32)>,
+}
+
+impl LocalStreams {
+    fn of(&mut self, channel: Channel) -> &mut McStreams {
+        match channel {
+            Channel::Minecraft => &mut self.minecraft,
+            Channel::Rcon => &mut self.rcon,
+        }
+    }
+
+    /// 关闭一条数据出现缺口的流，读取线程随后发出的关闭帧通知房主
+    fn break_stream(&mut self, channel: Channel, stream_id: u32) {
+        if !self.broken.insert((channel, stream_id)) {
+            return;
+        }
+        info!("⚠️ 流 {} ({:?}) 的数据未能发往房主，关闭该连接", stream_id, channel);
+        if let Some(stream) = self.of(channel).remove(&stream_id) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// MC -> Steam 读取线程，会话结束时统一取消并等待退出
+struct McReaders {
+    cancel: Arc<AtomicBool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl McReaders {
+    fn new() -> Self {
+        Self {
+            cancel: Arc::new(AtomicBool::new(false)),
+            handles: Vec::new(),
+        }
+    }
+
+    /// 为一条本地连接启动读取线程，读到的数据和连接结束通知作为 `channel` 上的帧经 `tx` 送往主循环
+    ///
+    /// 设置读超时，线程阻塞在 read 上时也能定期醒来检查取消标志
+    fn spawn(
+        &mut self,
+        channel: Channel,
+        stream_id: u32,
+        stream: &TcpStream,
+        tx: SendQueue<Frame>,
+    ) -> std::io::Result<()> {
+        let mut read_stream = stream.try_clone()?;
+        read_stream.set_read_timeout(Some(Duration::from_millis(MC_READ_TIMEOUT_MS)))?;
+        let cancel = Arc::clone(&self.cancel);
+
+        // 已结束的线程不再需要等待
+        self.handles.retain(|handle| !handle.is_finished());
+        self.handles.push(correlation::spawn(move || {
+            // 返回 false 表示主循环已不再接收
+            let receiving = panic_guard::run(format!("MC 读取线程 (流 {})", stream_id), || {
+                let mut buffer = [0u8; BUFFER_SIZE];
+                while !cancel.load(Ordering::Relaxed) {
+                    match read_stream.read(&mut buffer) {
+                        Ok(0) => {
+                            info!("[读取线程] MC 客户端断开连接 (流 {})", stream_id);
+                            break;
+                        }
+                        Ok(n) => {
+                            let frame = Frame::data(stream_id, buffer[..n].to_vec()).on(channel);
+                            if tx.send(frame).is_err() {
+                                return false;
+                            }
+                        }
+                        // 读超时，回到循环开头检查是否已取消
+                        Err(ref e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                        Err(e) => {
+                            error!("✗ 读取 MC 失败: {:?}", e);
+                            break;
+                        }
+                    }
+                }
+                true
+            });
+            // 通知主循环该连接已结束 (读取线程 panic 时同样关闭，不留下无人读取的连接)；
+            // 会话已结束时无人接收，不再通知
+            if receiving != Some(false) && !cancel.load(Ordering::Relaxed) {
+                let _ = tx.send_blocking(Frame::close(stream_id).on(channel));
+            }
+        }));
+        Ok(())
+    }
+
+    /// 取消所有读取线程并等待其退出
+    ///
+    /// 调用前须丢弃主循环的接收端，否则阻塞在满队列上的线程无法退出
+    fn stop(self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        for handle in self.handles {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// 加入成功后返回给前端的结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JoinReport {
+    /// 非致命警告（如版本不匹配），用户可以选择继续
+    pub warnings: Vec<String>,
+    /// 本地实际监听的端口，CLIENT_LISTEN_PORT 被占用时为回退端口
+    pub listen_port: u16,
+    /// 加入的房间 ID (字符串)，通过链接或好友代码加入时前端据此得知房间号
+    pub lobby_id: String,
+    /// 与房主在同一局域网时直接连接的 MC 服务器地址，None 时经 Steam 转发
+    pub lan_direct: Option<String>,
+    /// 房主开放的主服务器以外的 MC 服务器名称，第 n 项的序号为 n + 1
+    pub mc_servers: Vec<String>,
+    /// 本地 MC 连接转发到的服务器序号，0 为主服务器
+    pub mc_server: u8,
+}
+
+/// 加入房间失败的原因，序列化后返回给前端
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JoinError {
+    /// 房主与本机的程序版本不兼容，前端据此引导用户更新
+    VersionMismatch {
+        message: String,
+        /// 房主未公布版本 (更早的版本) 时为空
+        host_version: Option<String>,
+        local_version: String,
+        update_url: String,
+    },
+    /// 输入的链接或好友代码无法解析为房间
+    UnresolvedTarget {
+        message: String,
+        input: String,
+        /// 输入为好友时是其 Steam ID (字符串)，该好友当前不在房间中
+        friend_steam_id: Option<String>,
+    },
+    /// 其他失败，message 为给用户的提示
+    Failed { message: String },
+}
+
+impl JoinError {
+    fn version_mismatch(host_version: Option<String>) -> Self {
+        let message = Message::AppVersionMismatch
+            .format(&[&host_version.as_deref().unwrap_or("?"), &APP_VERSION]);
+        JoinError::VersionMismatch {
+            message,
+            host_version,
+            local_version: APP_VERSION.to_string(),
+            update_url: APP_UPDATE_URL.to_string(),
+        }
+    }
+
+    pub fn unresolved_target(input: &str, friend_steam_id: Option<u64>) -> Self {
+        let message = match friend_steam_id {
+            Some(_) => Message::FriendNotInLobby.text(),
+            None => Message::InvalidJoinTarget.text(),
+        };
+        JoinError::UnresolvedTarget {
+            message: message.to_string(),
+            input: input.trim().to_string(),
+            friend_steam_id: friend_steam_id.map(|id| id.to_string()),
+        }
+    }
+
+    /// 在提示末尾附加本次尝试的关联 ID
+    pub fn tagged(mut self, id: &str) -> Self {
+        let message = self.message_mut();
+        *message = correlation::tag_error(&message, id);
+        self
+    }
+
+    fn message_mut(&mut self) -> &mut String {
+        match self {
+            JoinError::VersionMismatch { message, .. }
+            | JoinError::UnresolvedTarget { message, .. }
+            | JoinError::Failed { message } => message,
+        }
+    }
+}
+
+impl std::fmt::Display for JoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinError::VersionMismatch { message, .. }
+            | JoinError::UnresolvedTarget { message, .. }
+            | JoinError::Failed { message } => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for JoinError {
+    fn from(message: String) -> Self {
+        JoinError::Failed { message }
+    }
+}
 
 pub fn run_client(
-    client: Client, 
-    lobby_id: LobbyId, 
-    password: Option<String>,
-    ready_tx: Sender<Result<(), String>>,
+    client: Client,
+    lobby_id: LobbyId,
+    options: ClientOptions,
+    ready_tx: Sender<Result<JoinReport, JoinError>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    session::set_state(SessionState::Connecting);
     info!("═══════════════════════════════════════════════════════");
     info!("开始加入房间流程");
     info!("目标房间 ID: {}", lobby_id.raw());
     info!("本机 Steam ID: {:?}", client.user().steam_id());
     info!("═══════════════════════════════════════════════════════");
 
+    let Established {
+        host_id,
+        connection,
+        framing_enabled,
+        mut report,
+    } = match establish(&client, lobby_id, &options) {
+        Ok(established) => established,
+        Err((_, err)) => {
+            client.matchmaking().leave_lobby(lobby_id);
+            let err_msg = err.to_string();
+            let _ = ready_tx.send(Err(err));
+            return Err(err_msg.into());
+        }
+    };
+    let sockets = client.networking_sockets();
+
+    // 房主公布接受应用层可靠传输时，大量数据改经不可靠消息 + 应用层重传发送
+    let reliability = if APP_RELIABILITY
+        && framing_enabled
+        && client.matchmaking().lobby_data(lobby_id, app_reliability::LOBBY_KEY_APP_RELIABILITY).as_deref()
+            == Some("1")
+    {
+        info!("📦 应用层可靠传输: 已启用");
+        app_reliability::Mode::Active
+    } else {
+        app_reliability::Mode::Off
+    };
+    let mut connection = ReliableLink::new(connection, reliability);
+
+    // 启动本地监听，首选端口被占用时（如上次会话未完全退出，或同一台机器上运行了多个客户端）
+    // 依次尝试后续端口；之后的连接提示和 LAN 广播都使用实际绑定的端口
+    let listener = match bind_in_range(Ipv4Addr::UNSPECIFIED, CLIENT_LISTEN_PORT, CLIENT_LISTEN_PORT_FALLBACKS) {
+        Ok(l) => l,
+        Err(e) => {
+            let range = format!(
+                "{}-{}",
+                CLIENT_LISTEN_PORT,
+                CLIENT_LISTEN_PORT.saturating_add(CLIENT_LISTEN_PORT_FALLBACKS)
+            );
+            let err_msg = Message::BindFailed.format(&[&range, &e]);
+            let _ = ready_tx.send(Err(err_msg.clone().into()));
+            return Err(err_msg.into());
+        }
+    };
+    listener.set_nonblocking(true)?;
+    let listen_port = listener.local_addr()?.port();
+    if listen_port != CLIENT_LISTEN_PORT {
+        let warning =
+            Message::ListenPortFallback.format(&[&CLIENT_LISTEN_PORT, &listen_port, &listen_port]);
+        warn!("⚠️ {}", warning);
+        report.warnings.push(warning);
+    }
+    report.listen_port = listen_port;
+
+    // 与房主在同一局域网时 MC 连接直接连到房主，Steam 连接仍保持用于房间状态
+    // 房主只公布主服务器的局域网端口，选择其他服务器时经 Steam 转发
+    let mut mc_server = options.mc_server;
+    let offered_servers = report.mc_servers.len();
+    let mut lan_direct = if LAN_DIRECT_CONNECT && mc_server == 0 {
+        find_lan_server(&client, lobby_id)
+    } else {
+        None
+    };
+    if let Some(addr) = lan_direct {
+        info!("🏠 房主在同一局域网，MC 连接将直接连到 {}", addr);
+        report.lan_direct = Some(addr.to_string());
+    }
+    let mut direct_bridges = DirectBridges::new();
+    info!(">>> 请在 Minecraft 中连接: 127.0.0.1:{}", listen_port);
+
+    // 启动LAN发现广播，关闭时只能通过直接连接加入
+    let _broadcast_handle = if options.lan_broadcast.unwrap_or(CLIENT_LAN_BROADCAST) {
+        let world_name = lan_world_name(listen_port);
+        let broadcaster = LanBroadcaster::new(Some(world_name.clone()), listen_port, options.lan_scope)?;
+        info!("✓ Minecraft LAN发现广播已启动 (服务器名称: {}, 端口 {})", world_name, listen_port);
+        Some(broadcaster.start())
+    } else {
+        info!("LAN发现广播已关闭，请在 Minecraft 中使用直接连接");
+        None
+    };
+
+    info!("");
+    info!("┌─────────────────────────────────────────────────────────┐");
+    info!("│  ✅ 已连接到房主!                                       │");
+    info!("├─────────────────────────────────────────────────────────┤");
+    info!("│  🎮 Minecraft 连接方式:                                 │");
+    info!(
+        "│     多人游戏 -> 添加服务器 -> 输入: 127.0.0.1:{}    │",
+        listen_port
+    );
+    info!("└─────────────────────────────────────────────────────────┘");
+    info!("");
+
+    // 通知前端连接已就绪
+    session::set_state(SessionState::Connected {
+        lobby_id: lobby_id.raw().to_string(),
+        ping_ms: None,
+    });
+    let _ = ready_tx.send(Ok(report));
+    let mut published_ping: Option<u32> = None;
+    let mut route = update_route(&client, &connection, host_id, None);
+
+    // 好友列表中显示正在游玩的世界，并允许好友通过覆盖层加入同一房间
+    let host_name = client.friends().get_friend(host_id).name();
+    presence::set_connected(&client, &host_name);
+    presence::set_connect(&client, lobby_id);
+
+    // Channel: MC读取线程 -> 主循环 (发送到Steam)
+    // 有界队列: Steam 发送跟不上时读取线程停止读取 TCP，而不是无限缓冲
+    let (from_mc_tx, from_mc_rx): (SendQueue<Frame>, Receiver<Frame>) =
+        send_queue::bounded(MC_READ_QUEUE_CAPACITY);
+
+    let mut local_streams = LocalStreams::default();
+    let mut mc_readers = McReaders::new();
+    let mut next_stream_id: u32 = 0;
+    // 管理员按需开启，只监听本机
+    let mut rcon_listener: Option<TcpListener> = None;
+    let mut next_rcon_stream_id: u32 = 0;
+
+    // 性能统计会话
+    let session_metrics = metrics::SessionMetrics::new();
+    let mut last_report_time = Instant::now();
+    let mut last_latency_poll: Option<Instant> = None;
+    let mut steam_watch = OfflineWatch::new("client");
+    let mut auto_stop = options.auto_stop;
+    // 连接失效时的原因；此时没有可送达的数据，跳过宽限期直接清理
+    let mut lost: Option<String> = None;
+    let mut receive_errors: u32 = 0;
+    // 会话结束时恢复，主循环的短休眠在此期间更准确
+    let _timer_resolution = TimerResolution::raise();
+
+    // 控制通道: 前端通过它请求离开房间
+    let (control_tx, control_rx) = mpsc::channel();
+    *CLIENT_CONTROL.lock().unwrap() = Some(control_tx);
+    // 之前的会话遗留的线程 panic 与本会话无关
+    panic_guard::take();
+
+    let shutdown_grace = loop {
+        // 处理前端控制命令
+        match control_rx.try_recv() {
+            Ok(ClientCommand::Leave { grace_period }) => break grace_period,
+            Ok(ClientCommand::SetRconForwarding { enabled, reply }) => {
+                let result = if enabled {
+                    start_rcon_listener(&client, lobby_id, framing_enabled, &mut rcon_listener)
+                } else {
+                    stop_rcon_forwarding(&mut rcon_listener, &mut local_streams.rcon);
+                    Ok(None)
+                };
+                let _ = reply.send(result);
+            }
+            Ok(ClientCommand::SetMcServer { server, reply }) => {
+                let result = if usize::from(server) <= offered_servers {
+                    info!("🎯 之后的 MC 连接将转发到房主的服务器 {}", server);
+                    mc_server = server;
+                    if server != 0 {
+                        lan_direct = None;
+                    }
+                    Ok(())
+                } else {
+                    Err(Message::McServerUnavailable.format(&[&server]))
+                };
+                let _ = reply.send(result);
+            }
+            Err(_) => {}
+        }
+        if auto_stop.as_mut().is_some_and(AutoStop::expired) {
+            info!("⏰ 已达到最长运行时间，自动离开房间");
+            structured_log::emit("client_auto_stop", &[]);
+            break Duration::from_millis(SHUTDOWN_GRACE_PERIOD_MS);
+        }
+
+        // Steam 离线时暂停转发，此时的收发失败不代表与房主的连接出了问题
+        match steam_watch.poll() {
+            Some(OnlineChange::WentOffline) => {
+                session::set_state(SessionState::SteamOffline {
+                    lobby_id: lobby_id.raw().to_string(),
+                    hosting: false,
+                });
+            }
+            Some(OnlineChange::CameBack) => {
+                session::set_state(SessionState::Connected {
+                    lobby_id: lobby_id.raw().to_string(),
+                    ping_ms: published_ping,
+                });
+            }
+            None => {}
+        }
+        if steam_watch.paused() {
+            thread::sleep(Duration::from_millis(100));
+            continue;
+        }
+
+        // 读取线程 panic 时已关闭自己的连接并通知房主，会话本身不受影响，只需告知用户
+        if panic_guard::pending() {
+            for report in panic_guard::take() {
+                structured_log::emit(
+                    "thread_panicked",
+                    &[("thread", json!(report.thread)), ("message", json!(report.message))],
+                );
+                if let Some(tx) = &options.thread_panic_tx {
+                    let _ = tx.send(report);
+                }
+            }
+        }
+
+        // 定期打印性能报告
+        if last_report_time.elapsed() > Duration::from_secs(5) {
+            session_metrics.print_report();
+            session_metrics.sample_throughput();
+            // 客户端没有玩家连接表，以房间成员代替
+            session_metrics.record_players(
+                client
+                    .matchmaking()
+                    .lobby_members(lobby_id)
+                    .into_iter()
+                    .map(|member| member.raw()),
+            );
+            let (stats, duration) = session_metrics.get_session_stats();
+            structured_log::emit_metrics("client", &stats, duration);
+            metrics_log::record("client", &stats, duration.as_millis() as u64);
+            // ICE 打洞可能在连接建立后才成功，路由随时会从中继切换为直连
+            route = update_route(&client, &connection, host_id, route);
+            last_report_time = Instant::now();
+        }
+
+        // 检查是否有新的 MC 客户端连接
+        // 原始转发模式无法区分连接，同一时间只能转发一条
+        if framing_enabled || local_streams.minecraft.is_empty() {
+            match listener.accept() {
+                Ok((stream, addr)) => {
+                    info!("┌─────────────────────────────────────");
+                    info!("│ [连接] MC 客户端已连接: {}", addr);
+                    info!("└─────────────────────────────────────");
+
+                    configure_mc_stream(&stream)?;
+
+                    // 局域网直连失败时本次及之后的连接都改经 Steam 转发
+                    let direct = lan_direct.is_some_and(|addr| match direct_bridges.connect(&stream, addr) {
+                        Ok(()) => {
+                            info!("🏠 已直连房主 MC 服务器 {}", addr);
+                            true
+                        }
+                        Err(e) => {
+                            warn!("⚠️ 局域网直连 {} 失败: {}，改经 Steam 转发", addr, e);
+                            false
+                        }
+                    });
+                    if !direct {
+                        lan_direct = None;
+
+                        // 每条 MC 连接使用独立的流 ID，房主为其建立独立的服务器连接
+                        let stream_id = next_stream_id;
+                        next_stream_id = next_stream_id.wrapping_add(1);
+
+                        // 先告诉房主这条流连接哪个服务器，读取线程的数据在其后发出
+                        let opened = mc_server == 0
+                            || send_to_host(&connection, host_id, framing_enabled, &Frame::open(stream_id, mc_server))
+                                .is_ok();
+
+                        // 启动 MC -> Steam 读取线程
+                        mc_readers.spawn(Channel::Minecraft, stream_id, &stream, from_mc_tx.clone())?;
+
+                        local_streams.minecraft.insert(stream_id, stream);
+                        if !opened {
+                            // 数据不能发往错误的服务器
+                            local_streams.break_stream(Channel::Minecraft, stream_id);
+                        }
+                    }
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => {
+                    error!("等待 MC 连接时发生错误: {:?}", e);
+                }
+            }
+        }
+
+        // 检查是否有新的 RCON 客户端连接
+        if let Some(rcon) = &rcon_listener {
+            match rcon.accept() {
+                Ok((stream, addr)) => {
+                    info!("🔧 RCON 客户端已连接: {}", addr);
+                    let stream_id = next_rcon_stream_id;
+                    next_rcon_stream_id = next_rcon_stream_id.wrapping_add(1);
+                    configure_mc_stream(&stream)?;
+                    mc_readers.spawn(Channel::Rcon, stream_id, &stream, from_mc_tx.clone())?;
+                    local_streams.rcon.insert(stream_id, stream);
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => error!("等待 RCON 连接时发生错误: {:?}", e),
+            }
+        }
+
+        // 更新延迟信息 (限频；连接的对端就是建立连接时确定的房主)
+        let latency_due = last_latency_poll
+            .is_none_or(|t| t.elapsed() >= Duration::from_millis(LATENCY_POLL_INTERVAL_MS));
+        if latency_due {
+            last_latency_poll = Some(Instant::now());
+            // 连接失效时接收不一定报错，只是再也收不到数据，定期检查连接状态
+            if let Some(reason) = connection_lost(&client, &connection) {
+                lost = Some(reason);
+                break Duration::ZERO;
+            }
+            if let Ok((status, _)) = sockets.get_realtime_connection_status(&connection, 0) {
+                let ping_ms = status.ping() as u32;
+                metrics::update_latency(host_id.raw(), ping_ms);
+                if published_ping != Some(ping_ms) {
+                    published_ping = Some(ping_ms);
+                    session::set_state(SessionState::Connected {
+                        lobby_id: lobby_id.raw().to_string(),
+                        ping_ms: published_ping,
+                    });
+                }
+            }
+        }
+
+        // 从 MC 读取数据 -> 发送到 Steam
+        while let Ok(frame) = from_mc_rx.try_recv() {
+            handle_mc_frame(&connection, host_id, framing_enabled, &mut local_streams, frame);
+        }
+
+        // 从 Steam 接收数据 -> 写入 MC
+        // 偶发的接收失败继续重试；连接已关闭或持续失败时结束会话
+        match receive_from_host(&mut connection, host_id, framing_enabled, &mut local_streams) {
+            Ok(()) => receive_errors = 0,
+            Err(err) => {
+                receive_errors += 1;
+                let reason = connection_lost(&client, &connection).or_else(|| {
+                    (matches!(err, TransportError::Closed | TransportError::Protocol(_))
+                        || receive_errors >= MAX_CONSECUTIVE_RECEIVE_ERRORS)
+                        .then(|| err.to_string())
+                });
+                if let Some(reason) = reason {
+                    lost = Some(reason);
+                    break Duration::ZERO;
+                }
+            }
+        }
+
+        loop_timing::pause();
+    };
+
+    *CLIENT_CONTROL.lock().unwrap() = None;
+
+    let lost = lost.map(|reason| {
+        let message = Message::HostConnectionLost.format(&[&reason]);
+        error!("✗ {}", message);
+        structured_log::emit("client_connection_lost", &[("reason", json!(reason))]);
+        if let Some(tx) = &options.connection_lost_tx {
+            let _ = tx.send(ConnectionLost {
+                message: message.clone(),
+            });
+        }
+        message
+    });
+
+    // 优雅关闭: 先送出已排队的数据，再等待 Steam 发送缓冲区清空
+    info!("🛑 正在离开房间 (宽限期 {} ms)...", shutdown_grace.as_millis());
+    while let Ok(frame) = from_mc_rx.try_recv() {
+        handle_mc_frame(&connection, host_id, framing_enabled, &mut local_streams, frame);
+    }
+    let _ = receive_from_host(&mut connection, host_id, framing_enabled, &mut local_streams);
+    // 经应用层可靠传输发出的数据要等对端确认，Steam 发送缓冲区不知道这些数据
+    let deadline = Instant::now() + shutdown_grace;
+    if connection.is_active() {
+        connection.settle(deadline);
+    }
+    let connection = connection.into_inner();
+    if shutdown::drain_send_buffers(&client, &[&connection], deadline.saturating_duration_since(Instant::now())) {
+        info!("✓ 所有待发送数据已送达");
+    }
+    connection.close(NetConnectionEnd::AppGeneric, Some("客户端已离开"), true);
+    for (_, stream) in local_streams.minecraft.drain().chain(local_streams.rcon.drain()) {
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+    drop(from_mc_rx);
+    mc_readers.stop();
+    direct_bridges.stop();
+    metrics::clear_peer(host_id.raw());
+    presence::clear_presence(&client);
+    client.matchmaking().leave_lobby(lobby_id);
+    info!("✓ 已离开房间");
+
+    // 由命令层把会话状态置为错误，前端据此提示重新加入
+    match lost {
+        Some(message) => Err(message.into()),
+        None => Ok(()),
+    }
+}
+
+/// 连通性探测结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProbeReport {
+    /// 是否成功与房主完成握手
+    pub reachable: bool,
+    /// 密码是否通过验证，未进行到该步骤时为空
+    pub password_ok: Option<bool>,
+    /// 到房主的延迟
+    pub ping_ms: Option<u32>,
+    /// 失败原因
+    pub error: Option<String>,
+    /// 非致命警告（如版本不匹配）
+    pub warnings: Vec<String>,
+}
+
+/// 不加入房间，仅通过 `request_lobby_data` 读取元数据判断房间是否设置了密码
+pub fn lobby_requires_password(client: &Client, lobby_id: LobbyId) -> Result<bool, String> {
+    fetch_lobby_data(client, lobby_id)?;
+    let required = client
+        .matchmaking()
+        .lobby_data(lobby_id, "password")
+        .is_some_and(|pwd| !pwd.is_empty());
+    info!("房间 {} {}", lobby_id.raw(), if required { "需要密码" } else { "无需密码" });
+    Ok(required)
+}
+
+/// 不加入房间，读取房主开放的主服务器以外的 MC 服务器名称，供加入前选择
+pub fn lobby_mc_servers(client: &Client, lobby_id: LobbyId) -> Result<Vec<String>, String> {
+    fetch_lobby_data(client, lobby_id)?;
+    Ok(offered_mc_servers(client, lobby_id))
+}
+
+/// 不加入房间，根据房主公布的中继网络位置估算到房主的延迟 (毫秒)
+///
+/// 房主未公布位置 (旧版本或位置尚未测量完成) 或无法估算时返回 None
+pub fn estimate_lobby_ping(client: &Client, lobby_id: LobbyId) -> Result<Option<u32>, String> {
+    fetch_lobby_data(client, lobby_id)?;
+    let estimate = client
+        .matchmaking()
+        .lobby_data(lobby_id, LOBBY_KEY_PING_LOCATION)
+        .and_then(|location| steam::estimate_ping(client, &location));
+    match estimate {
+        Some(ping_ms) => info!("房间 {} 的房主预计延迟约 {} ms", lobby_id.raw(), ping_ms),
+        None => info!("房间 {} 无法估算延迟", lobby_id.raw()),
+    }
+    Ok(estimate)
+}
+
+/// 请求房间元数据并等待同步完成
+///
+/// 元数据异步到达，以房主必定公布的 `LOBBY_KEY_HOST` 作为同步完成的标志，
+/// 与 `establish` 中读取密码一样有限次重试
+fn fetch_lobby_data(client: &Client, lobby_id: LobbyId) -> Result<(), String> {
+    let matchmaking = client.matchmaking();
+    if !matchmaking.request_lobby_data(lobby_id) {
+        return Err(Message::LobbyInfoUnavailable.text().to_string());
+    }
+
+    for i in 0..LOBBY_DATA_SYNC_ATTEMPTS {
+        if i > 0 {
+            thread::sleep(Duration::from_millis(LOBBY_DATA_SYNC_INTERVAL_MS));
+        }
+        if matchmaking.lobby_data(lobby_id, LOBBY_KEY_HOST).is_some() {
+            return Ok(());
+        }
+        info!("等待房间数据同步... (尝试 #{})", i + 1);
+    }
+    Err(Message::LobbyInfoUnavailable.text().to_string())
+}
+
+/// 探测房间是否可以连接: 加入、验证密码、与房主握手后立即离开
+///
+/// 不绑定本地端口，也不启动LAN广播
+pub fn probe_lobby(client: &Client, lobby_id: LobbyId, options: &ClientOptions) -> ProbeReport {
+    info!("🔎 探测房间 {} 的连通性...", lobby_id.raw());
+    let report = match establish(client, lobby_id, options) {
+        Ok(established) => {
+            // 刚建立的连接可能还没有延迟数据，短暂等待
+            let sockets = client.networking_sockets();
+            let deadline = Instant::now() + Duration::from_secs(1);
+            let ping_ms = loop {
+                let ping = sockets
+                    .get_realtime_connection_status(&established.connection, 0)
+                    .ok()
+                    .map(|(status, _)| status.ping())
+                    .filter(|&ping| ping > 0);
+                if ping.is_some() || Instant::now() >= deadline {
+                    break ping.map(|ping| ping as u32);
+                }
+                thread::sleep(Duration::from_millis(50));
+            };
+            established
+                .connection
+                .close(NetConnectionEnd::AppGeneric, Some("连通性探测完成"), false);
+
+            ProbeReport {
+                reachable: true,
+                password_ok: Some(true),
+                ping_ms,
+                error: None,
+                warnings: established.report.warnings,
+            }
+        }
+        Err((stage, err)) => ProbeReport {
+            reachable: false,
+            password_ok: match stage {
+                SetupStage::Join => None,
+                SetupStage::Password => Some(false),
+                SetupStage::Handshake => Some(true),
+            },
+            ping_ms: None,
+            error: Some(err.to_string()),
+            warnings: Vec::new(),
+        },
+    };
+    client.matchmaking().leave_lobby(lobby_id);
+    info!("🔎 探测结束: {:?}", report);
+    report
+}
+
+/// 建立会话的阶段，用于区分失败原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetupStage {
+    /// 加入 Steam 房间
+    Join,
+    /// 验证房间密码
+    Password,
+    /// 与房主建立 NetworkingSockets 连接
+    Handshake,
+}
+
+/// 已加入房间并与房主建立连接
+struct Established {
+    host_id: SteamId,
+    connection: NetConnection,
+    framing_enabled: bool,
+    report: JoinReport,
+}
+
+/// 加入房间、验证密码并与房主完成握手，不绑定本地端口也不启动广播
+///
+/// 完整加入和连通性探测共用此流程。失败时调用方负责离开房间。
+fn establish(
+    client: &Client,
+    lobby_id: LobbyId,
+    options: &ClientOptions,
+) -> Result<Established, (SetupStage, JoinError)> {
+    // 空密码等同于未填写
+    let password = options.password.clone().filter(|pwd| !pwd.is_empty());
     let (tx, rx) = mpsc::channel();
     info!("📡 正在向 Steam 发送加入房间请求...");
     client.matchmaking().join_lobby(lobby_id, move |result| {
@@ -31,7 +895,6 @@ pub fn run_client(
 
     let join_deadline = Instant::now() + Duration::from_secs(10);
     loop {
-        client.run_callbacks();
         if let Ok(result) = rx.try_recv() {
             match result {
                 Ok(_) => {
@@ -41,35 +904,40 @@ pub fn run_client(
                 Err(_) => {
                     // Steam 的 join_lobby 只返回 Err(())，无法获取具体错误原因
                     // 常见原因：房间不存在、已关闭、已满员、Steam服务不可用
-                    let err_msg = "加入房间失败 - 请检查: 1) 房间号是否正确 2) 房主是否仍在运行 3) Steam是否正常连接".to_string();
+                    let err_msg = Message::JoinFailed.text().to_string();
                     error!("{}", err_msg);
-                    let _ = ready_tx.send(Err(err_msg));
-                    return Ok(());
+                    return Err((SetupStage::Join, err_msg.into()));
                 }
             }
         }
         
         if Instant::now() > join_deadline {
-            let err_msg = "加入房间超时 - Steam服务可能暂时不可用，请稍后重试".to_string();
+            let err_msg = Message::JoinTimeout.text().to_string();
             error!("{}", err_msg);
-            let _ = ready_tx.send(Err(err_msg));
-            return Ok(());
+            return Err((SetupStage::Join, err_msg.into()));
         }
         thread::sleep(Duration::from_millis(50));
     }
 
     // 验证房间密码，增加重试逻辑应对Steam后端数据同步延迟
-    let lobby_password = (0..15)
+    let lobby_password = (0..LOBBY_DATA_SYNC_ATTEMPTS)
         .find_map(|i| {
-            client.run_callbacks();
             if i > 0 {
-                thread::sleep(Duration::from_millis(200));
+                thread::sleep(Duration::from_millis(LOBBY_DATA_SYNC_INTERVAL_MS));
             }
             let pw = client.matchmaking().lobby_data(lobby_id, "password");
 
             // 如果客户端提供了密码，我们必须等到从lobby元数据中读到密码
             if password.is_some() && pw.is_none() {
                 info!("等待房间密码数据同步... (尝试 #{})", i + 1);
+                // 同步较慢时让前端知道仍在进行，而不是卡住了
+                if let Some(tx) = &options.progress_tx {
+                    let _ = tx.send(JoinProgress {
+                        message: Message::SyncingLobbyInfo.text().to_string(),
+                        attempt: i + 1,
+                        max_attempts: LOBBY_DATA_SYNC_ATTEMPTS,
+                    });
+                }
                 None
             } else {
                 Some(pw)
@@ -77,40 +945,89 @@ pub fn run_client(
         })
         .flatten();
 
-    // 执行密码验证
-    match (password.as_deref(), lobby_password.as_deref()) {
-        // 客户端提供了密码
-        (Some(client_pwd), Some(lobby_pwd)) => {
-            if client_pwd != lobby_pwd {
-                let err_msg = "房间密码错误".to_string();
-                let _ = ready_tx.send(Err(err_msg.clone()));
-                return Err(err_msg.into());
-            }
-        }
-        (Some(_), None) => {
-            let err_msg = "验证密码超时，或房主未设置密码".to_string();
-            let _ = ready_tx.send(Err(err_msg.clone()));
-            return Err(err_msg.into());
-        }
-        // 客户端未提供密码，但房间有密码 (且不为空)
-        (None, Some(lobby_pwd)) if !lobby_pwd.is_empty() => {
-            let err_msg = "房间需要密码，但未提供密码".to_string();
-            let _ = ready_tx.send(Err(err_msg.clone()));
-            return Err(err_msg.into());
+    verify_password(password.as_deref(), lobby_password.as_deref())
+        .map_err(|err| (SetupStage::Password, err))?;
+    info!("✓ 密码验证成功");
+
+    // 不同版本间帧协议、保活格式可能不兼容，按兼容策略检查房主的程序版本
+    let host_version = client.matchmaking().lobby_data(lobby_id, LOBBY_KEY_APP_VERSION);
+    if REQUIRE_APP_VERSION_MATCH
+        && !host_version
+            .as_deref()
+            .is_some_and(|version| app_version::is_compatible(version, APP_VERSION))
+    {
+        let err = JoinError::version_mismatch(host_version);
+        error!("✗ {}", err);
+        return Err((SetupStage::Handshake, err));
+    }
+    info!(
+        "房主程序版本: {} (本机 {})",
+        host_version.as_deref().unwrap_or("未公布"),
+        APP_VERSION
+    );
+
+    // 根据房主公布的帧协议版本决定是否启用帧协议，未公布则按原始字节转发
+    let framing_enabled = match client
+        .matchmaking()
+        .lobby_data(lobby_id, framing::LOBBY_KEY_FRAMING)
+    {
+        None => false,
+        Some(version) if version == framing::FRAME_VERSION.to_string() => true,
+        Some(version) => {
+            let err_msg = Message::FramingMismatch.format(&[&version, &framing::FRAME_VERSION]);
+            error!("{}", err_msg);
+            return Err((SetupStage::Handshake, err_msg.into()));
         }
-        // 其他情况（都无密码，或房间密码为空）均视为通过
-        _ => {}
+    };
+    // 虚拟端口不一致时连接会一直停留在 Connecting，提前检查并给出明确错误
+    let virtual_port = options.virtual_port.unwrap_or(P2P_VIRTUAL_PORT);
+    let host_port = client
+        .matchmaking()
+        .lobby_data(lobby_id, LOBBY_KEY_VIRTUAL_PORT)
+        .map_or(Some(0), |port| port.parse::<i32>().ok());
+    if host_port != Some(virtual_port) {
+        let host_port = host_port.map_or_else(|| "?".to_string(), |port| port.to_string());
+        let err_msg = Message::VirtualPortMismatch.format(&[&host_port, &virtual_port]);
+        error!("{}", err_msg);
+        return Err((SetupStage::Handshake, err_msg.into()));
+    }
+
+    // 选择其他服务器需要打开帧，原始转发时只有主服务器
+    let mc_servers = if framing_enabled {
+        offered_mc_servers(client, lobby_id)
+    } else {
+        Vec::new()
+    };
+    if usize::from(options.mc_server) > mc_servers.len() {
+        let err_msg = Message::McServerUnavailable.format(&[&options.mc_server]);
+        error!("✗ {}", err_msg);
+        return Err((SetupStage::Handshake, err_msg.into()));
     }
-    info!("✓ 密码验证成功");
 
-    let host_id = client.matchmaking().lobby_owner(lobby_id);
+    let report = JoinReport {
+        warnings: check_lobby_metadata(client, lobby_id, options),
+        lobby_id: lobby_id.raw().to_string(),
+        mc_servers,
+        mc_server: options.mc_server,
+        ..Default::default()
+    };
+
+    info!(
+        "帧协议: {}",
+        if framing_enabled { "已启用" } else { "未启用 (原始转发)" }
+    );
+
+    let Some(host_id) = resolve_host_id(client, lobby_id) else {
+        let err_msg = Message::HostUnknown.text().to_string();
+        error!("✗ {}", err_msg);
+        return Err((SetupStage::Handshake, err_msg.into()));
+    };
     info!("房主 Steam ID: {:?}", host_id);
 
     if host_id == client.user().steam_id() {
-        let err_msg = "无法连接自己，请使用两个不同的账号测试".to_string();
+        let err_msg = Message::CannotConnectSelf.text().to_string();
         error!("!!! 错误: {} !!!", err_msg);
-        let _ = ready_tx.send(Err(err_msg.clone()));
-        return Err(err_msg.into());
+        return Err((SetupStage::Handshake, err_msg.into()));
     }
 
     // 使用新版 NetworkingSockets API 连接房主
@@ -118,12 +1035,13 @@ pub fn run_client(
     let sockets = client.networking_sockets();
     let host_identity = NetworkingIdentity::new_steam_id(host_id);
 
-    let mut connection = match sockets.connect_p2p(host_identity, 0, vec![]) {
+    let route = route_preference::get();
+    info!("路径偏好: {:?}", route);
+    let connection = match sockets.connect_p2p(host_identity, virtual_port, route.config_entries()) {
         Ok(conn) => conn,
         Err(_) => {
-            let err_msg = "无法向房主发起连接，Steam NetworkingSockets 初始化失败".to_string();
-            let _ = ready_tx.send(Err(err_msg.clone()));
-            return Err(err_msg.into());
+            let err_msg = Message::ConnectInitFailed.text().to_string();
+            return Err((SetupStage::Handshake, err_msg.into()));
         }
     };
 
@@ -131,7 +1049,6 @@ pub fn run_client(
     let connect_deadline = Instant::now() + Duration::from_secs(15);
     let mut last_state_log = Instant::now();
     loop {
-        client.run_callbacks();
         if let Ok(info) = sockets.get_connection_info(&connection) {
             if let Ok(state) = info.state() {
                 // 每秒打印一次连接状态
@@ -143,19 +1060,40 @@ pub fn run_client(
                 match state {
                     NetworkingConnectionState::Connected => {
                         info!("✅ NetworkingSockets 连接已建立");
+                        if !encryption::verify(client, &connection, host_id.raw()) {
+                            let err_msg = Message::ConnectionUnencrypted.text().to_string();
+                            connection.close(
+                                NetConnectionEnd::Other(END_REASON_UNENCRYPTED),
+                                Some("连接未加密"),
+                                false,
+                            );
+                            return Err((SetupStage::Handshake, err_msg.into()));
+                        }
+                        structured_log::emit(
+                            "connection_established",
+                            &[("host_steam_id", json!(host_id.raw())), ("lobby_id", json!(lobby_id.raw()))],
+                        );
                         break;
                     }
                     NetworkingConnectionState::ClosedByPeer => {
-                        let err_msg = "房主拒绝了连接 (ClosedByPeer) - 请确保房主程序正在运行且房间号正确".to_string();
+                        let err_msg = if info.end_reason()
+                            == Some(NetConnectionEnd::Other(END_REASON_NOT_ALLOWED))
+                        {
+                            Message::NotOnAllowList.text().to_string()
+                        } else if info.end_reason()
+                            == Some(NetConnectionEnd::Other(END_REASON_UNENCRYPTED))
+                        {
+                            Message::ConnectionUnencrypted.text().to_string()
+                        } else {
+                            Message::ClosedByPeer.text().to_string()
+                        };
                         error!("{}", err_msg);
-                        let _ = ready_tx.send(Err(err_msg.clone()));
-                        return Err(err_msg.into());
+                        return Err((SetupStage::Handshake, err_msg.into()));
                     }
                     NetworkingConnectionState::ProblemDetectedLocally => {
-                        let err_msg = "本地检测到连接问题 (ProblemDetectedLocally) - 可能是网络问题或Steam服务不可用".to_string();
+                        let err_msg = Message::ProblemDetectedLocally.text().to_string();
                         error!("{}", err_msg);
-                        let _ = ready_tx.send(Err(err_msg.clone()));
-                        return Err(err_msg.into());
+                        return Err((SetupStage::Handshake, err_msg.into()));
                     }
                     NetworkingConnectionState::None => {
                         info!("⏳ 连接状态: None (初始化中...)");
@@ -171,161 +1109,507 @@ pub fn run_client(
         }
 
         if Instant::now() > connect_deadline {
-            let err_msg = "连接房主超时 (15秒) - 房主可能不在线或网络问题".to_string();
+            let err_msg = Message::HostConnectTimeout.text().to_string();
             error!("{}", err_msg);
-            let _ = ready_tx.send(Err(err_msg.clone()));
-            return Err(err_msg.into());
+            return Err((SetupStage::Handshake, err_msg.into()));
         }
         thread::sleep(Duration::from_millis(50));
     }
 
-    // 启动本地监听
-    let listener = match TcpListener::bind(format!("0.0.0.0:{}", CLIENT_LISTEN_PORT)) {
-        Ok(l) => l,
-        Err(e) => {
-            let err_msg = format!("无法绑定端口 {}: {}", CLIENT_LISTEN_PORT, e);
-            let _ = ready_tx.send(Err(err_msg.clone()));
-            return Err(err_msg.into());
-        }
+    Ok(Established {
+        host_id,
+        connection,
+        framing_enabled,
+        report,
+    })
+}
+
+/// 查询连接当前走直连还是中继，发生变化时记录并告知用户
+fn update_route(
+    client: &Client,
+    connection: &NetConnection,
+    host_id: SteamId,
+    previous: Option<RouteKind>,
+) -> Option<RouteKind> {
+    let Ok(detail) = client
+        .networking_sockets()
+        .get_detailed_connection_status(connection)
+    else {
+        return previous;
     };
-    listener.set_nonblocking(true)?;
-    info!(
-        ">>> 请在 Minecraft 中连接: 127.0.0.1:{}",
-        CLIENT_LISTEN_PORT
-    );
+    let route = metrics::classify_route(&detail);
+    metrics::update_route(host_id.raw(), route);
 
-    // 启动LAN发现广播
-    let broadcaster = LanBroadcaster::new(Some("LAN world".to_string()), CLIENT_LISTEN_PORT)?;
-    let _broadcast_handle = broadcaster.start();
-    info!("✓ Minecraft LAN发现广播已启动 (服务器名称: LAN world)");
+    if previous != Some(route) {
+        match route {
+            RouteKind::Direct => info!("🛰️ 连接路由: 直连"),
+            RouteKind::Relayed => {
+                info!("🛰️ 连接路由: Steam 中继 (未能直连，延迟可能较高)")
+            }
+            RouteKind::Unknown => info!("🛰️ 连接路由: 未知"),
+        }
+        structured_log::emit(
+            "route_changed",
+            &[("host_steam_id", json!(host_id.raw())), ("route", json!(route))],
+        );
+    }
+    Some(route)
+}
 
-    info!("");
-    info!("┌─────────────────────────────────────────────────────────┐");
-    info!("│  ✅ 已连接到房主!                                       │");
-    info!("├─────────────────────────────────────────────────────────┤");
-    info!("│  🎮 Minecraft 连接方式:                                 │");
-    info!(
-        "│     多人游戏 -> 添加服务器 -> 输入: 127.0.0.1:{}    │",
-        CLIENT_LISTEN_PORT
-    );
-    info!("└─────────────────────────────────────────────────────────┘");
-    info!("");
+/// 处理读取线程产生的帧: 转发数据，连接结束时清理并通知房主
+///
+/// 数据未能发出时流已出现缺口，关闭该连接而不是继续转发其后的数据
+fn handle_mc_frame(
+    connection: &impl Transport,
+    host_id: SteamId,
+    framing_enabled: bool,
+    streams: &mut LocalStreams,
+    frame: Frame,
+) {
+    if frame.kind == FrameKind::Close {
+        streams.of(frame.channel).remove(&frame.stream_id);
+        streams.broken.remove(&(frame.channel, frame.stream_id));
+    } else if streams.broken.contains(&(frame.channel, frame.stream_id)) {
+        metrics::record_packet_dropped();
+        return;
+    }
+    if send_to_host(connection, host_id, framing_enabled, &frame).is_err() && frame.kind == FrameKind::Data {
+        streams.break_stream(frame.channel, frame.stream_id);
+    }
+}
 
-    // 通知前端连接已就绪
-    let _ = ready_tx.send(Ok(()));
+/// 编码（如启用帧协议）并发送帧到房主
+///
+/// 帧被丢弃时返回错误；分块发送时在第一个失败的块处停止
+fn send_to_host(
+    connection: &impl Transport,
+    host_id: SteamId,
+    framing_enabled: bool,
+    frame: &Frame,
+) -> Result<(), TransportError> {
+    // 超过单条消息上限的负载按顺序分块发送，而不是丢弃
+    if frame.payload.len() > framing::MAX_CHUNK_PAYLOAD {
+        for chunk in framing::split(frame, framing::MAX_CHUNK_PAYLOAD) {
+            send_to_host(connection, host_id, framing_enabled, &chunk)?;
+        }
+        return Ok(());
+    }
+    capture::record(host_id.raw(), Direction::Sent, frame);
 
-    // Channel: MC读取线程 -> 主循环 (发送到Steam)
-    let (from_mc_tx, from_mc_rx): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = mpsc::channel();
+    let message = if framing_enabled {
+        match framing::encode(frame) {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                error!("✗ 编码帧失败: {}", e);
+                metrics::record_packet_dropped();
+                return Err(TransportError::Failed(e.to_string()));
+            }
+        }
+    } else if frame.kind == FrameKind::Data {
+        frame.payload.clone()
+    } else {
+        // 原始转发模式无法传递连接关闭
+        return Ok(());
+    };
+    match connection.send(&message) {
+        Ok(_) => {
+            let payload = frame.payload_len();
+            metrics::record_packet_sent(payload as u64, (message.len() - payload) as u64);
+            Ok(())
+        }
+        Err(err) => {
+            static SEND_FAILED_LOG: LogThrottle = LogThrottle::new();
+            if let Some(suppressed) = SEND_FAILED_LOG.allow() {
+                error!("✗ 发送到房主失败: {}{}", err, log_throttle::suppressed_note(suppressed));
+            }
+            metrics::record_packet_dropped();
+            Err(err)
+        }
+    }
+}
 
-    let mut mc_stream: Option<TcpStream> = None;
-    let mut mc_read_thread_started = false;
+/// 从房主接收数据，按通道分发
+///
+/// 接收失败时返回错误，由调用方根据连接状态判断是偶发失败还是连接已失效
+fn receive_from_host(
+    connection: &mut impl Transport,
+    host_id: SteamId,
+    framing_enabled: bool,
+    streams: &mut LocalStreams,
+) -> Result<(), TransportError> {
+    match connection.receive(64) {
+        Ok(messages) => {
+            for data in messages {
+                if data.is_empty() {
+                    continue;
+                }
+                let wire_len = data.len();
 
-    // 性能统计会话
-    let session_metrics = metrics::SessionMetrics::new();
-    let mut last_report_time = Instant::now();
+                let frame = if framing_enabled {
+                    match framing::decode(&data) {
+                        Ok(frame) => frame,
+                        Err(e) => {
+                            // 跳过这一帧会让 MC 字节流缺一段，只能结束会话
+                            error!("✗ 收到来自房主的无效帧: {}", e);
+                            metrics::record_packet_received(0, wire_len as u64);
+                            metrics::record_packet_dropped();
+                            return Err(TransportError::Protocol(e.to_string()));
+                        }
+                    }
+                } else {
+                    // 原始转发模式只有当前这一条 MC 连接
+                    let stream_id = streams.minecraft.keys().next().copied().unwrap_or(0);
+                    Frame::data(stream_id, data)
+                };
+                let payload = frame.payload_len();
+                metrics::record_packet_received(payload as u64, (wire_len - payload) as u64);
+                capture::record(host_id.raw(), Direction::Received, &frame);
 
-    loop {
-        client.run_callbacks();
+                // MC 流量为通道 0，附加通道在此分发到各自的处理函数
+                match frame.channel {
+                    Channel::Minecraft => deliver_mc_frame(&mut streams.minecraft, frame),
+                    Channel::Rcon => deliver_mc_frame(&mut streams.rcon, frame),
+                }
+            }
+            Ok(())
+        }
+        Err(err) => {
+            static RECEIVE_FAILED_LOG: LogThrottle = LogThrottle::new();
+            if let Some(suppressed) = RECEIVE_FAILED_LOG.allow() {
+                warn!("⚠️ 从房主接收数据失败: {}{}", err, log_throttle::suppressed_note(suppressed));
+            }
+            Err(err)
+        }
+    }
+}
 
-        // 定期打印性能报告
-        if last_report_time.elapsed() > Duration::from_secs(5) {
-            session_metrics.print_report();
-            last_report_time = Instant::now();
+/// 与房主的连接是否已经结束，返回原因；仍在连接 (包括重新寻找路由) 或无法查询时为 None
+fn connection_lost(client: &Client, connection: &NetConnection) -> Option<String> {
+    let info = client.networking_sockets().get_connection_info(connection).ok()?;
+    match info.state().ok()? {
+        NetworkingConnectionState::Connected
+        | NetworkingConnectionState::Connecting
+        | NetworkingConnectionState::FindingRoute => None,
+        state => Some(match info.end_reason() {
+            Some(reason) => format!("{:?}: {:?}", state, reason),
+            None => format!("{:?}", state),
+        }),
+    }
+}
+
+/// 把帧写入所属通道上对应的本地连接
+fn deliver_mc_frame(mc_streams: &mut McStreams, frame: Frame) {
+    // 属于已关闭连接的数据直接丢弃
+    let Some(stream) = mc_streams.get_mut(&frame.stream_id) else {
+        return;
+    };
+
+    match frame.kind {
+        FrameKind::Data => {
+            if let Err(e) = stream.write_all(&frame.payload) {
+                error!("✗ 写入 MC 失败 (流 {}): {:?}", frame.stream_id, e);
+                // 关闭 socket 让读取线程退出并通知房主
+                let _ = stream.shutdown(Shutdown::Both);
+                mc_streams.remove(&frame.stream_id);
+            }
+        }
+        // 房主不会打开流
+        FrameKind::Open => {}
+        FrameKind::Close => {
+            info!("房主侧 MC 服务器关闭了连接 ({:?}, 流 {})", frame.channel, frame.stream_id);
+            let _ = stream.shutdown(Shutdown::Both);
+            mc_streams.remove(&frame.stream_id);
         }
+    }
+}
 
-        // 检查是否有新的 MC 客户端连接
-        if mc_stream.is_none() {
-            match listener.accept() {
-                Ok((stream, addr)) => {
-                    info!("┌─────────────────────────────────────");
-                    info!("│ [连接] MC 客户端已连接: {}", addr);
-                    info!("└─────────────────────────────────────");
+/// LAN 广播中显示的世界名称
+///
+/// 同一台机器上运行多个客户端时各自的广播带上端口，在 Minecraft 的列表中可以区分
+fn lan_world_name(listen_port: u16) -> String {
+    if listen_port == CLIENT_LISTEN_PORT {
+        "LAN world".to_string()
+    } else {
+        format!("LAN world ({})", listen_port)
+    }
+}
 
-                    stream.set_nodelay(true)?;
-
-                    // 启动 MC -> Steam 读取线程
-                    if !mc_read_thread_started {
-                        let mut read_stream = stream.try_clone()?;
-                        let from_mc_tx_clone = from_mc_tx.clone();
-                        thread::spawn(move || {
-                            let mut buffer = [0u8; BUFFER_SIZE];
-                            loop {
-                                match read_stream.read(&mut buffer) {
-                                    Ok(0) => {
-                                        info!("[读取线程] MC 客户端断开连接");
-                                        break;
-                                    }
-                                    Ok(n) => {
-                                        if from_mc_tx_clone.send(buffer[..n].to_vec()).is_err() {
-                                            break;
-                                        }
-                                    }
-                                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
-                                        thread::sleep(Duration::from_micros(100));
-                                    }
-                                    Err(e) => {
-                                        error!("✗ 读取 MC 失败: {:?}", e);
-                                        break;
-                                    }
-                                }
-                            }
-                        });
-                        mc_read_thread_started = true;
-                    }
+/// 开始在本机监听 RCON 连接，已在监听时返回当前端口
+///
+/// 只有房主公布了 `LOBBY_KEY_RCON` 才开启；房主仍会按允许列表决定是否接受每条连接
+fn start_rcon_listener(
+    client: &Client,
+    lobby_id: LobbyId,
+    framing_enabled: bool,
+    rcon_listener: &mut Option<TcpListener>,
+) -> Result<Option<u16>, String> {
+    if let Some(listener) = rcon_listener {
+        return Ok(listener.local_addr().ok().map(|addr| addr.port()));
+    }
+    let offered = client.matchmaking().lobby_data(lobby_id, LOBBY_KEY_RCON).as_deref() == Some("1");
+    if !framing_enabled || !offered {
+        return Err(Message::RconNotOffered.text().to_string());
+    }
 
-                    mc_stream = Some(stream);
-                }
-                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
-                Err(e) => {
-                    error!("等待 MC 连接时发生错误: {:?}", e);
-                }
+    // RCON 可以执行任意服务器命令，只监听本机，不暴露给局域网
+    let listener = bind_in_range(Ipv4Addr::LOCALHOST, CLIENT_RCON_LISTEN_PORT, CLIENT_LISTEN_PORT_FALLBACKS)
+        .map_err(|e| Message::BindFailed.format(&[&CLIENT_RCON_LISTEN_PORT, &e]))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    info!("🔧 RCON 转发已开启，请在 RCON 客户端中连接 127.0.0.1:{}", port);
+    structured_log::emit("rcon_forwarding", &[("enabled", json!(true)), ("port", json!(port))]);
+    *rcon_listener = Some(listener);
+    Ok(Some(port))
+}
+
+/// 停止监听并断开已有的 RCON 连接，读取线程随之通知房主关闭对应的流
+fn stop_rcon_forwarding(rcon_listener: &mut Option<TcpListener>, rcon_streams: &mut McStreams) {
+    if rcon_listener.take().is_some() {
+        info!("🔧 RCON 转发已关闭");
+        structured_log::emit("rcon_forwarding", &[("enabled", json!(false))]);
+    }
+    for (_, stream) in rcon_streams.drain() {
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// 比对客户端填写的密码与房间元数据中的密码
+///
+/// 空字符串等同于没有密码；密码按原样比较，不忽略空白和大小写。
+/// 客户端填写了密码而房间元数据中读不到时，视为元数据尚未同步；
+/// 房间密码为空 (已移除) 而客户端填写了密码时视为密码错误
+fn verify_password(client: Option<&str>, lobby: Option<&str>) -> Result<(), JoinError> {
+    let client = client.filter(|pwd| !pwd.is_empty());
+    let message = match (client, lobby) {
+        (Some(client), Some(lobby)) if client != lobby => Message::WrongPassword,
+        (Some(_), None) => Message::PasswordVerifyTimeout,
+        (None, Some(lobby)) if !lobby.is_empty() => Message::PasswordRequired,
+        // 一致，或双方都没有密码
+        _ => return Ok(()),
+    };
+    Err(message.text().to_string().into())
+}
+
+/// 比对房主公布的 MC 版本/整合包与本地信息，返回不匹配警告
+fn check_lobby_metadata(client: &Client, lobby_id: LobbyId, options: &ClientOptions) -> Vec<String> {
+    let checks = [
+        (Message::LabelMcVersion, LOBBY_KEY_MC_VERSION, options.mc_version.as_deref()),
+        (Message::LabelModpack, LOBBY_KEY_MODPACK, options.modpack.as_deref()),
+    ];
+
+    let mut warnings = Vec::new();
+    for (label, key, local) in checks {
+        let remote = client.matchmaking().lobby_data(lobby_id, key);
+        match (remote.as_deref(), local) {
+            (Some(remote), Some(local)) if !remote.trim().eq_ignore_ascii_case(local.trim()) => {
+                let warning = Message::MetadataMismatch.format(&[&label.text(), &remote, &local]);
+                warn!("⚠️ {}", warning);
+                warnings.push(warning);
             }
+            (Some(remote), _) => info!("房主{}: {}", label.text_in(Language::Zh), remote),
+            _ => {}
         }
+    }
+    warnings
+}
+
+/// 房主公布的主服务器以外的 MC 服务器名称，未公布或无法解析时为空
+fn offered_mc_servers(client: &Client, lobby_id: LobbyId) -> Vec<String> {
+    client
+        .matchmaking()
+        .lobby_data(lobby_id, LOBBY_KEY_MC_SERVERS)
+        .and_then(|servers| serde_json::from_str(&servers).ok())
+        .unwrap_or_default()
+}
+
+/// 房主与本机在同一局域网且其 MC 服务器可直接连通时，返回该服务器的地址
+fn find_lan_server(client: &Client, lobby_id: LobbyId) -> Option<SocketAddr> {
+    let host_addrs = lan_direct::parse(&client.matchmaking().lobby_data(lobby_id, lan_direct::LOBBY_KEY_LAN_ADDRS)?);
+    let port = client
+        .matchmaking()
+        .lobby_data(lobby_id, lan_direct::LOBBY_KEY_LAN_PORT)?
+        .parse()
+        .ok()?;
+    let (verify_port, nonce) =
+        lan_direct::parse_verify(&client.matchmaking().lobby_data(lobby_id, lan_direct::LOBBY_KEY_LAN_VERIFY)?)?;
+    let candidates = lan_direct::same_lan(&host_addrs, &lan_direct::local_addrs());
+    if candidates.is_empty() {
+        return None;
+    }
+    info!("房主可能在同一局域网，正在确认: {:?}", candidates);
+    lan_direct::probe(&candidates, port, verify_port, &nonce)
+}
 
-        // 更新延迟信息
-        if let Ok((status, _)) = sockets.get_realtime_connection_status(&connection, 0) {
-            let ping_ms = status.ping() as u32;
-            let host_id = client.matchmaking().lobby_owner(lobby_id);
-            metrics::update_latency(host_id.raw(), ping_ms);
+/// 确定实际运行桥接的房主 Steam ID
+///
+/// 优先使用房主公布的元数据（房间所有权可能已被 Steam 转移），未公布时回退到
+/// `lobby_owner`。刚加入房间时所有者信息可能尚未同步 (返回 0)，此时重新请求
+/// 大厅数据并等待回调线程处理更新，多次尝试后仍无法确定时返回 None。
+fn resolve_host_id(client: &Client, lobby_id: LobbyId) -> Option<SteamId> {
+    for i in 0..10 {
+        if i > 0 {
+            client.matchmaking().request_lobby_data(lobby_id);
+            thread::sleep(Duration::from_millis(200));
         }
 
-        // 从 MC 读取数据 -> 发送到 Steam
-        while let Ok(data) = from_mc_rx.try_recv() {
-            match connection.send_message(&data, SendFlags::RELIABLE_NO_NAGLE) {
-                Ok(_) => {
-                    metrics::record_packet_sent(data.len() as u64);
-                }
-                Err(err) => {
-                    error!("✗ 发送到房主失败: {:?}", err);
-                    metrics::record_packet_dropped();
+        let owner = client.matchmaking().lobby_owner(lobby_id);
+        let advertised = client
+            .matchmaking()
+            .lobby_data(lobby_id, LOBBY_KEY_HOST)
+            .and_then(|value| value.parse::<u64>().ok())
+            .filter(|&raw| raw != 0)
+            .map(SteamId::from_raw);
+
+        match advertised {
+            Some(host) => {
+                if owner.raw() != 0 && owner != host {
+                    warn!(
+                        "⚠️ 房间所有者 ({:?}) 与运行桥接的房主 ({:?}) 不一致，将连接后者",
+                        owner, host
+                    );
                 }
+                return Some(host);
             }
+            None if owner.raw() != 0 => return Some(owner),
+            None => info!("等待房主信息同步... (尝试 #{})", i + 1),
         }
+    }
 
-        // 从 Steam 接收数据 -> 写入 MC
-        match connection.receive_messages(64) {
-            Ok(messages) => {
-                for message in messages {
-                    let data = message.data();
-                    if data.is_empty() {
-                        continue;
-                    }
-                    metrics::record_packet_received(data.len() as u64);
+    None
+}
 
-                    // 直接写入 MC stream
-                    if let Some(ref mut stream) = mc_stream {
-                        if let Err(e) = stream.write_all(data) {
-                            error!("✗ 写入 MC 失败: {:?}", e);
-                            mc_stream = None;
-                        }
-                    }
-                }
-            }
-            Err(err) => {
-                warn!("⚠️ 从房主接收数据失败: {:?}", err);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MemoryTransport;
+    use steamworks::SteamId;
+
+    #[test]
+    fn test_verify_password() {
+        let rejected_with = |client, lobby, expected: Message| match verify_password(client, lobby) {
+            Err(JoinError::Failed { message }) => assert_eq!(message, expected.text(), "{:?} / {:?}", client, lobby),
+            other => panic!("{:?} / {:?}: {:?}", client, lobby, other),
+        };
+
+        // 双方都没有密码 (未设置或为空)
+        for client in [None, Some("")] {
+            for lobby in [None, Some("")] {
+                assert!(verify_password(client, lobby).is_ok(), "{:?} / {:?}", client, lobby);
             }
         }
 
-        thread::sleep(Duration::from_micros(100));
+        assert!(verify_password(Some("hunter2"), Some("hunter2")).is_ok());
+        rejected_with(Some("hunter3"), Some("hunter2"), Message::WrongPassword);
+        rejected_with(None, Some("hunter2"), Message::PasswordRequired);
+        rejected_with(Some(""), Some("hunter2"), Message::PasswordRequired);
+        rejected_with(Some("hunter2"), None, Message::PasswordVerifyTimeout);
+        rejected_with(Some("hunter2"), Some(""), Message::WrongPassword);
+
+        // 空白和大小写都是密码的一部分
+        rejected_with(Some(" hunter2"), Some("hunter2"), Message::WrongPassword);
+        rejected_with(Some("Hunter2"), Some("hunter2"), Message::WrongPassword);
+        assert!(verify_password(Some("  "), Some("  ")).is_ok());
+
+        let long = "密".repeat(4096);
+        assert!(verify_password(Some(&long), Some(&long)).is_ok());
+        rejected_with(Some(&long[..long.len() - 3]), Some(&long), Message::WrongPassword);
+    }
+
+    /// 接受一条本地连接，返回 (MC 客户端端, 桥接端)
+    fn local_pair(listener: &TcpListener) -> (TcpStream, TcpStream) {
+        let mc = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (bridged, _) = listener.accept().unwrap();
+        (mc, bridged)
+    }
+
+    #[test]
+    fn test_status_ping_and_play_connections_coexist() {
+        let host_id = SteamId::from_raw(76561198000000010);
+        let (client_end, mut host_end) = MemoryTransport::pair();
+        let (to_client, mut client_rx_end) = MemoryTransport::pair();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (tx, rx) = send_queue::bounded(16);
+        let mut readers = McReaders::new();
+        let mut streams = LocalStreams::default();
+
+        // 状态查询连接尚未关闭时，游戏连接已经建立
+        let (mut ping, ping_bridged) = local_pair(&listener);
+        let (mut play, play_bridged) = local_pair(&listener);
+        readers.spawn(Channel::Minecraft, 0, &ping_bridged, tx.clone()).unwrap();
+        readers.spawn(Channel::Minecraft, 1, &play_bridged, tx.clone()).unwrap();
+        streams.minecraft.insert(0, ping_bridged);
+        streams.minecraft.insert(1, play_bridged);
+
+        ping.write_all(b"status").unwrap();
+        play.write_all(b"login").unwrap();
+        let mut sent = Vec::new();
+        while sent.len() < 2 {
+            let frame = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+            sent.push((frame.stream_id, frame.payload.clone()));
+            handle_mc_frame(&client_end, host_id, true, &mut streams, frame);
+        }
+        sent.sort();
+        assert_eq!(sent, [(0, b"status".to_vec()), (1, b"login".to_vec())]);
+        let received: Vec<Frame> = host_end
+            .receive(64)
+            .unwrap()
+            .iter()
+            .map(|data| framing::decode(data).unwrap())
+            .collect();
+        assert_eq!(received.len(), 2);
+
+        // 房主的回复按流 ID 写回各自的连接
+        for frame in [Frame::data(1, b"welcome".to_vec()), Frame::data(0, b"motd".to_vec())] {
+            to_client.send(&framing::encode(&frame).unwrap()).unwrap();
+        }
+        receive_from_host(&mut client_rx_end, host_id, true, &mut streams).unwrap();
+        let mut buf = [0u8; 16];
+        let n = ping.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"motd");
+        let n = play.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"welcome");
+
+        // 状态查询结束后游戏连接不受影响
+        drop(ping);
+        let close = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!((close.kind, close.stream_id), (FrameKind::Close, 0));
+        handle_mc_frame(&client_end, host_id, true, &mut streams, close);
+        assert_eq!(streams.minecraft.keys().copied().collect::<Vec<_>>(), [1]);
+
+        to_client.send(&framing::encode(&Frame::data(1, b"chunk".to_vec())).unwrap()).unwrap();
+        receive_from_host(&mut client_rx_end, host_id, true, &mut streams).unwrap();
+        let n = play.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"chunk");
+
+        drop(rx);
+        readers.stop();
+    }
+    #[test]
+    fn test_data_that_cannot_be_sent_closes_its_stream() {
+        use crate::config::{APP_RELIABILITY_MAX_BACKLOG, APP_RELIABILITY_WINDOW};
+
+        let host_id = SteamId::from_raw(76561198000000011);
+        let (client_end, _host_end) = MemoryTransport::pair();
+        // 房主不确认，应用层发送队列被填满
+        let connection = ReliableLink::new(client_end, app_reliability::Mode::Active);
+        for _ in 0..APP_RELIABILITY_WINDOW + APP_RELIABILITY_MAX_BACKLOG {
+            connection.send(b"queued").unwrap();
+        }
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (mut mc, bridged) = local_pair(&listener);
+        let mut streams = LocalStreams::default();
+        streams.minecraft.insert(1, bridged);
+
+        // 发不出去的数据让流出现缺口，本地连接被关闭而不是继续转发后面的数据
+        handle_mc_frame(&connection, host_id, true, &mut streams, Frame::data(1, b"lost".to_vec()));
+        assert!(streams.minecraft.is_empty());
+        let mut buf = [0u8; 8];
+        assert_eq!(mc.read(&mut buf).unwrap(), 0);
+
+        // 读取线程报告连接结束前，该流剩下的数据都被丢弃
+        handle_mc_frame(&connection, host_id, true, &mut streams, Frame::data(1, b"after".to_vec()));
+        assert!(streams.broken.contains(&(Channel::Minecraft, 1)));
+        handle_mc_frame(&connection, host_id, true, &mut streams, Frame::close(1));
+        assert!(streams.broken.is_empty());
     }
 }
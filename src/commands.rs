@@ -1,18 +1,45 @@
-use crate::client_mode::run_client;
-use crate::host::run_host;
-use crate::metrics;
-use crate::minecraft_discovery;
-use lazy_static::lazy_static;
-use log::info;
+use crate::access_list::{self, AccessLists, ListKind};
+use crate::auto_stop::{AutoStop, AUTO_STOP_WARNING_EVENT};
+use crate::bridge::DryRunMode;
+use crate::capture;
+use crate::client_mode::{
+    self, run_client, ClientCommand, ClientOptions, ConnectionLost, JoinError, JoinProgress, JoinReport,
+    ProbeReport, CONNECTION_LOST_EVENT, JOIN_PROGRESS_EVENT,
+};
+use crate::config::{
+    CLIENT_LISTEN_PORT, DISCOVERY_TIMEOUT_SECS, LOBBY_LOCK, LOG_BUFFER_CAPACITY, MAX_DISCOVERY_TIMEOUT_SECS,
+    MAX_SHUTDOWN_GRACE_PERIOD_MS, MC_SERVER_PORT, MC_STATUS_TIMEOUT_MS, MIN_DISCOVERY_TIMEOUT_SECS, PAUSE_POLICY, SHUTDOWN_GRACE_PERIOD_MS, SWITCH_LEAVE_TIMEOUT_MS, VERIFY_MC_SERVER,
+};
+use crate::correlation;
+use crate::diagnostics;
+use crate::friends::{self, FriendInfo};
+use crate::host::{self, run_host, ForwardingState, HostCommand, HostOptions, LobbyLock, McServer, PausePolicy};
+use crate::i18n::{self, Language, Message};
+use crate::join_target::{self, JoinTarget};
+use crate::lan_discovery::BroadcastScope;
+use crate::log_buffer::{self, LogEntry};
+use crate::metrics::{self, MetricsSnapshot, RouteKind};
+use crate::mc_readiness::{self, McReadiness};
+use crate::mc_socket;
+use crate::mc_status::{self, ServerStatus};
+use crate::metrics_log;
+use crate::minecraft_discovery::{self, DiscoveryError, NetworkInterface};
+use crate::panic_guard;
+use crate::presence;
+use crate::process_stats::{self, ProcessStats};
+use crate::route_preference::{self, RoutePreference};
+use crate::server_properties::{self, ServerProperties};
+use crate::session::{self, SessionRole, SessionState};
+use crate::steam;
+use log::{error, info, warn, LevelFilter};
 use serde::Serialize;
-use std::sync::{mpsc, Mutex};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::thread;
-use steamworks::{Client, LobbyId};
-use tauri::command;
-
-lazy_static! {
-    static ref LOBBY_ID: Mutex<Option<u64>> = Mutex::new(None);
-}
+use std::time::Duration;
+use steamworks::{LobbyId, SteamId};
+use tauri::{command, AppHandle, Emitter, Manager};
 
 #[derive(Serialize)]
 pub struct PerformanceMetrics {
@@ -20,147 +47,1027 @@ pub struct PerformanceMetrics {
     packets_received: u64,
     bytes_sent: u64,
     bytes_received: u64,
+    payload_bytes_sent: u64,
+    payload_bytes_received: u64,
+    overhead_bytes_sent: u64,
+    overhead_bytes_received: u64,
     packets_dropped: u64,
+    /// 最近窗口内的丢包率 (百分比)
+    drop_rate: f32,
+    /// MC 读取队列已满、读取被暂停的累计次数
+    backpressure_events: u64,
     send_rate_mbps: f32,
     recv_rate_mbps: f32,
     send_rate_pps: f32,
     recv_rate_pps: f32,
+    /// 各连接的延迟，按名称排序
+    peers: Vec<PeerLatency>,
+    /// 平滑后的延迟；多个连接时取最差 (平滑延迟最高) 的一个，
+    /// 下面的瞬时延迟、抖动和路由也来自同一连接
     latency_ms: Option<u32>,
+    /// 最近一次的瞬时延迟
+    latency_raw_ms: Option<u32>,
+    jitter_ms: Option<f32>,
+    route: Option<RouteKind>,
+    /// 所有已确认的连接都加密时为 true，任一连接未加密时为 false，无法确认时为 None
+    is_connection_encrypted: Option<bool>,
+    session_started_at_ms: Option<u64>,
+    session_uptime_secs: Option<u64>,
+}
+
+/// 单个连接的延迟
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerLatency {
+    /// 以字符串返回，Steam ID 超出 JavaScript 安全整数范围
+    steam_id: String,
+    name: String,
+    latency_ms: u32,
+    latency_raw_ms: u32,
+    jitter_ms: f32,
+    route: Option<RouteKind>,
+    encrypted: Option<bool>,
 }
 
 #[command]
 pub fn get_steam_name() -> Result<String, String> {
-    match Client::init() {
-        Ok(client) => Ok(client.friends().name()),
-        Err(e) => Err(format!("Steam 未运行或初始化失败: {}", e)),
+    steam::client().map(|client| client.friends().name())
+}
+
+/// 本机 Steam ID 的几种常用形式
+///
+/// 64 位 ID 以字符串返回，避免超出 JavaScript 安全整数范围
+#[derive(Debug, Clone, Serialize)]
+pub struct SteamIdInfo {
+    /// 64 位 Steam ID (即 `SteamId::raw`)
+    steam_id64: String,
+    /// 32 位账户 ID，即 Steam "添加好友" 中使用的好友代码
+    friend_code: u32,
+    /// Steam3 格式，如 [U:1:12345678]
+    steam3: String,
+}
+
+impl From<SteamId> for SteamIdInfo {
+    fn from(steam_id: SteamId) -> Self {
+        let account_id = steam_id.account_id().raw();
+        Self {
+            steam_id64: steam_id.raw().to_string(),
+            friend_code: account_id,
+            steam3: format!("[U:1:{}]", account_id),
+        }
     }
 }
 
+/// 获取本机 Steam ID，Steam 不可用时返回原因
+#[command]
+pub fn get_steam_id() -> Result<SteamIdInfo, String> {
+    let client = steam::client()?;
+    Ok(client.user().steam_id().into())
+}
+
+/// Steam 是否可用，不可用时附带原因 (如缺少 App ID)
+#[derive(Debug, Clone, Serialize)]
+pub struct SteamStatus {
+    available: bool,
+    error: Option<String>,
+}
+
+#[command]
+pub fn is_steam_available() -> SteamStatus {
+    match steam::client() {
+        Ok(_) => SteamStatus {
+            available: true,
+            error: None,
+        },
+        Err(e) => SteamStatus {
+            available: false,
+            error: Some(e),
+        },
+    }
+}
+
+#[command]
+pub fn get_session_state() -> SessionState {
+    session::state()
+}
+
 #[command]
 pub fn get_lobby_id() -> Option<u64> {
-    *LOBBY_ID.lock().unwrap()
+    session::lobby_id()
 }
 
 #[command]
-pub async fn detect_minecraft_server() -> Option<minecraft_discovery::MinecraftServer> {
-    info!("Tauri: 收到自动检测 Minecraft 服务器请求");
+pub fn get_invite_link() -> Result<String, String> {
+    if !host::is_active() {
+        return Err(Message::NoHostSession.text().to_string());
+    }
+    let lobby_id = session::lobby_id().ok_or(Message::LobbyNotReady.text())?;
+    let client = steam::client()?;
+
+    Ok(host::invite_link(
+        client.utils().app_id().0,
+        lobby_id,
+        client.user().steam_id().raw(),
+    ))
+}
+
+#[command]
+pub fn find_friend(name: String) -> Result<Vec<FriendInfo>, String> {
+    let client = steam::client()?;
+    Ok(friends::find_friends(&client, &name))
+}
+
+/// 邀请好友加入当前所在的房间
+#[command]
+pub fn invite_friend(steam_id: String) -> Result<(), String> {
+    let lobby_id = session::lobby_id().ok_or(Message::NotInLobby.text())?;
+    let steam_id = steam_id
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| Message::InvalidSteamId.text())?;
+    let client = steam::client()?;
+
+    friends::invite_to_lobby(&client, SteamId::from_raw(steam_id), lobby_id);
+    info!("Tauri: 已邀请好友 {} 加入房间 {}", steam_id, lobby_id);
+    Ok(())
+}
+
+/// 前端使用的名单，Steam ID 以字符串返回
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessListsView {
+    allowlist: Vec<String>,
+    blocklist: Vec<String>,
+}
+
+impl From<AccessLists> for AccessListsView {
+    fn from(lists: AccessLists) -> Self {
+        Self {
+            allowlist: lists.allowlist.iter().map(u64::to_string).collect(),
+            blocklist: lists.blocklist.iter().map(u64::to_string).collect(),
+        }
+    }
+}
+
+#[command]
+pub fn get_access_lists() -> AccessListsView {
+    access_list::get().into()
+}
+
+/// 添加 (present 为 true) 或移除名单条目，修改会保存到磁盘并立即对运行中的房间生效
+#[command]
+pub fn set_access_list_entry(
+    list: ListKind,
+    steam_id: String,
+    present: bool,
+) -> Result<AccessListsView, String> {
+    let steam_id = steam_id
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| Message::InvalidSteamId.text())?;
+    let lists = access_list::update(list, steam_id, present)?;
+
+    // 没有运行中的房主会话时无需处理
+    let _ = host::send_command(HostCommand::EnforceAccessLists);
+    Ok(lists.into())
+}
+
+/// 检查本地端口是否空闲，默认检查客户端监听端口 (CLIENT_LISTEN_PORT)
+///
+/// 前端可在加入房间前提示端口冲突；run_client 在端口被占用时会自动改用后续端口
+#[command]
+pub fn check_port_available(port: Option<u16>) -> bool {
+    mc_socket::is_port_available(port.unwrap_or(CLIENT_LISTEN_PORT))
+}
+
+/// 从服务器目录的 server.properties 读取端口和 MOTD，用于预填开服参数
+#[command]
+pub fn read_server_properties(dir: String) -> Result<ServerProperties, String> {
+    server_properties::read(Path::new(&dir))
+}
+
+/// 列出可用于搜索服务器的网络接口，供前端选择
+#[command]
+pub fn list_network_interfaces() -> Result<Vec<NetworkInterface>, String> {
+    minecraft_discovery::list_interfaces().map_err(|e| e.to_string())
+}
+
+/// 搜索本地 Minecraft 服务器；超时未找到返回 `Ok(None)`，无法监听广播时返回具体原因
+///
+/// `interface` 为加入组播使用的接口 IPv4 地址，未指定时由系统选择；
+/// `verify` 为是否用服务器列表查询确认服务器，默认 VERIFY_MC_SERVER
+#[command]
+pub async fn detect_minecraft_server(
+    timeout_secs: Option<u64>,
+    interface: Option<String>,
+    verify: Option<bool>,
+) -> Result<Option<minecraft_discovery::MinecraftServer>, DiscoveryError> {
+    // 限制在合理范围内，前端可提供"搜索更久"选项
+    let timeout_secs = timeout_secs
+        .unwrap_or(DISCOVERY_TIMEOUT_SECS)
+        .clamp(MIN_DISCOVERY_TIMEOUT_SECS, MAX_DISCOVERY_TIMEOUT_SECS);
+    info!("Tauri: 收到自动检测 Minecraft 服务器请求 (超时 {} 秒)", timeout_secs);
+
+    let interface = match interface.as_deref().map(str::trim) {
+        Some(address) if !address.is_empty() => {
+            address
+                .parse::<Ipv4Addr>()
+                .map_err(|_| DiscoveryError::InvalidInterface {
+                    address: address.to_string(),
+                })?
+        }
+        _ => Ipv4Addr::UNSPECIFIED,
+    };
+    let verify = verify.unwrap_or(VERIFY_MC_SERVER);
 
     // 使用 spawn_blocking 在单独的线程中运行阻塞操作，避免阻塞 Tauri 主线程
-    let result = tauri::async_runtime::spawn_blocking(|| {
-        minecraft_discovery::discover_minecraft_server()
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        minecraft_discovery::discover_minecraft_server(Duration::from_secs(timeout_secs), interface, verify)
     })
     .await
-    .ok()
-    .flatten();
+    .map_err(|e| DiscoveryError::Socket {
+        reason: format!("{:?}", e),
+    })
+    .and_then(|result| result);
 
-    match result {
-        Some(server) => {
+    match &result {
+        Ok(Some(server)) => {
             info!(
                 "Tauri: 检测到服务器 - {} ({}:{}) at {:.2}ms",
                 server.motd, server.ip, server.port, server.latency_ms
             );
-            Some(server)
         }
-        None => {
-            info!("Tauri: 未检测到 Minecraft 服务器");
-            None
+        Ok(None) => info!("Tauri: 未检测到 Minecraft 服务器"),
+        Err(e) => warn!("Tauri: 无法搜索 Minecraft 服务器: {}", e),
+    }
+    result
+}
+
+/// get_metrics_delta 的结果
+#[derive(Serialize)]
+pub struct MetricsDelta {
+    /// 自基线以来的增量
+    delta: MetricsSnapshot,
+    /// 基线之后计数被重置过 (新会话开始)，增量从重置时算起
+    reset: bool,
+    /// 当前计数，下次调用时原样传回作为基线
+    baseline: MetricsSnapshot,
+}
+
+/// 返回自前端传回的基线以来的流量增量，未提供基线时从计数开始时算起
+///
+/// 计数被重置 (会话重启) 时不会得到负数或零，增量改为从重置时算起
+#[command]
+pub fn get_metrics_delta(baseline: Option<MetricsSnapshot>) -> MetricsDelta {
+    let current = metrics::get_snapshot();
+    let (delta, reset) = match &baseline {
+        Some(baseline) => current.since(baseline),
+        None => (current.clone(), false),
+    };
+    MetricsDelta {
+        delta,
+        reset,
+        baseline: current,
+    }
+}
+
+/// 返回当前或最近一个会话的累计流量、峰值吞吐量和玩家统计，从未开始过会话时返回 None
+///
+/// 会话结束后仍保留到下一个会话开始，可用于结束后的汇总界面
+#[command]
+pub fn get_session_summary() -> Option<metrics::SessionSummary> {
+    metrics::get_session_summary()
+}
+
+/// 房主会话的本地 MC 服务器是否已可连接，没有房主会话时返回 None
+///
+/// 状态变化同时通过 mc_readiness::MC_READINESS_EVENT 事件通知前端
+#[command]
+pub fn get_mc_readiness() -> Option<McReadiness> {
+    mc_readiness::current()
+}
+
+/// 用服务器列表查询读取本地 MC 服务器的在线人数、人数上限、版本和 MOTD
+///
+/// 默认查询运行中房主会话转发到的端口，没有房主会话时查询 MC_SERVER_PORT；
+/// 房主可在邀请好友前确认服务器运行正常
+#[command]
+pub async fn get_local_mc_status(port: Option<u16>) -> Result<ServerStatus, String> {
+    let port = port.or_else(host::mc_target_port).unwrap_or(MC_SERVER_PORT);
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    info!("Tauri: 收到本地 MC 服务器状态查询请求: {}", addr);
+
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        mc_status::query(addr, Duration::from_millis(MC_STATUS_TIMEOUT_MS))
+    })
+    .await
+    .map_err(|e| format!("{:?}", e))?;
+
+    match result {
+        Ok(status) => {
+            info!(
+                "Tauri: 本地 MC 服务器 {} 在线 {}/{} ({}, {:.2}ms)",
+                addr, status.players_online, status.players_max, status.version, status.latency_ms
+            );
+            Ok(status)
+        }
+        Err(e) => {
+            warn!("Tauri: 本地 MC 服务器 {} 状态查询失败: {}", addr, e);
+            Err(Message::LocalMcStatusFailed.format(&[&addr, &e]))
         }
     }
 }
 
+/// 返回本进程的 CPU 占用 (自上次查询以来) 和常驻内存，用于观察主循环的资源开销
+#[command]
+pub fn get_process_stats() -> ProcessStats {
+    process_stats::sample()
+}
+
 #[command]
 pub fn get_performance_metrics() -> PerformanceMetrics {
     let snapshot = metrics::get_snapshot();
     
-    // Return absolute values - frontend will calculate deltas if needed
-    let send_rate_mbps = (snapshot.bytes_sent as f32) / 1024.0 / 1024.0;
-    let recv_rate_mbps = (snapshot.bytes_received as f32) / 1024.0 / 1024.0;
+    // Return absolute values - use get_metrics_delta for deltas.
+    // Throughput counts Minecraft payload only, not framing or control overhead.
+    let send_rate_mbps = (snapshot.payload_bytes_sent as f32) / 1024.0 / 1024.0;
+    let recv_rate_mbps = (snapshot.payload_bytes_received as f32) / 1024.0 / 1024.0;
     let send_rate_pps = snapshot.packets_sent as f32;
     let recv_rate_pps = snapshot.packets_received as f32;
 
-    // 获取延迟信息（如果有多个连接，返回第一个）
-    let latency_ms = metrics::get_all_latencies()
-        .values()
-        .next()
-        .copied();
+    // 多个连接时汇总值取延迟最差的一个
+    let latencies = metrics::get_all_latencies();
+    let routes = metrics::get_all_routes();
+    let encryption = metrics::get_all_encryption();
+    let worst = metrics::worst_latency(&latencies);
+    let latency = worst.map(|(_, stats)| stats);
+    let route = worst.and_then(|(steam_id, _)| routes.get(&steam_id).copied());
+
+    let client = steam::client().ok();
+    let mut peers: Vec<PeerLatency> = latencies
+        .iter()
+        .map(|(&steam_id, stats)| PeerLatency {
+            steam_id: steam_id.to_string(),
+            name: match &client {
+                Some(client) => friends::persona_name(client, SteamId::from_raw(steam_id)),
+                None => steam_id.to_string(),
+            },
+            latency_ms: stats.smoothed_ms.round() as u32,
+            latency_raw_ms: stats.raw_ms,
+            jitter_ms: stats.jitter_ms,
+            route: routes.get(&steam_id).copied(),
+            encrypted: encryption.get(&steam_id).copied(),
+        })
+        .collect();
+    peers.sort_by(|a, b| {
+        a.name
+            .to_lowercase()
+            .cmp(&b.name.to_lowercase())
+            .then_with(|| a.steam_id.cmp(&b.steam_id))
+    });
+
+    // 会话开始时间与已运行时长（无活动会话时为空）
+    let session = metrics::get_session_uptime();
 
     PerformanceMetrics {
         packets_sent: snapshot.packets_sent,
         packets_received: snapshot.packets_received,
         bytes_sent: snapshot.bytes_sent,
         bytes_received: snapshot.bytes_received,
+        payload_bytes_sent: snapshot.payload_bytes_sent,
+        payload_bytes_received: snapshot.payload_bytes_received,
+        overhead_bytes_sent: snapshot.overhead_bytes_sent,
+        overhead_bytes_received: snapshot.overhead_bytes_received,
         packets_dropped: snapshot.packets_dropped,
+        drop_rate: metrics::drop_rate(),
+        backpressure_events: metrics::get_backpressure_events(),
         send_rate_mbps,
         recv_rate_mbps,
         send_rate_pps,
         recv_rate_pps,
-        latency_ms,
+        peers,
+        latency_ms: latency.map(|stats| stats.smoothed_ms.round() as u32),
+        latency_raw_ms: latency.map(|stats| stats.raw_ms),
+        jitter_ms: latency.map(|stats| stats.jitter_ms),
+        route,
+        is_connection_encrypted: (!encryption.is_empty())
+            .then(|| encryption.values().all(|&encrypted| encrypted)),
+        session_started_at_ms: session.map(|(started_at_ms, _)| started_at_ms),
+        session_uptime_secs: session.map(|(_, uptime)| uptime.as_secs()),
+    }
+}
+
+#[command]
+pub fn get_language() -> Language {
+    i18n::language()
+}
+
+#[command]
+pub fn set_language(language: Language) {
+    info!("Tauri: 切换界面语言为 {:?}", language);
+    i18n::set_language(language);
+}
+
+/// 开关指标文件记录，未指定路径时写入应用数据目录，返回实际使用的文件路径
+#[command]
+pub fn set_metrics_logging(
+    app: AppHandle,
+    enabled: bool,
+    path: Option<String>,
+) -> Result<Option<String>, String> {
+    if !enabled {
+        metrics_log::stop();
+        return Ok(None);
+    }
+
+    let path = match path {
+        Some(path) if !path.trim().is_empty() => PathBuf::from(path.trim()),
+        _ => {
+            let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+            metrics_log::default_path(&data_dir)
+        }
+    };
+    metrics_log::start(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(Some(path.display().to_string()))
+}
+
+/// 开关抓包：把转发的帧 (含方向和时间戳) 写入二进制文件，返回实际使用的文件路径
+///
+/// 文件包含完整的游戏数据，只在排查协议问题时临时开启；未指定路径时写入应用数据目录
+#[command]
+pub fn set_packet_capture(
+    app: AppHandle,
+    enabled: bool,
+    path: Option<String>,
+) -> Result<Option<String>, String> {
+    if !enabled {
+        capture::stop();
+        return Ok(None);
     }
+
+    let path = match path {
+        Some(path) if !path.trim().is_empty() => PathBuf::from(path.trim()),
+        _ => {
+            let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+            capture::default_path(&data_dir)
+        }
+    };
+    capture::start(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(Some(path.display().to_string()))
+}
+
+/// 导出诊断信息 (JSON)，未指定路径时写入应用数据目录，返回实际使用的文件路径
+///
+/// 包含最近日志、指标、会话与 Steam 状态、系统信息和配置，房间密码会被隐藏
+#[command]
+pub fn export_diagnostics(app: AppHandle, path: Option<String>) -> Result<String, String> {
+    let path = match path {
+        Some(path) if !path.trim().is_empty() => PathBuf::from(path.trim()),
+        _ => {
+            let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+            data_dir.join(diagnostics::default_file_name())
+        }
+    };
+    let lobby_id = session::lobby_id();
+    diagnostics::export(&path, lobby_id, get_performance_metrics())
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path.display().to_string())
+}
+
+/// 内存中最近的日志，供界面显示事件日志
+///
+/// `min_level` 为 "error" / "warn" / "info" / "debug" / "trace"，未指定时返回所有级别；
+/// `count` 未指定时返回缓冲区中的全部日志
+#[command]
+pub fn get_recent_logs(count: Option<usize>, min_level: Option<String>) -> Result<Vec<LogEntry>, String> {
+    let min_level = match min_level {
+        Some(level) => level
+            .trim()
+            .parse::<LevelFilter>()
+            .map_err(|_| Message::InvalidLogLevel.format(&[&level]))?,
+        None => LevelFilter::Trace,
+    };
+    Ok(log_buffer::recent(count.unwrap_or(LOG_BUFFER_CAPACITY), min_level))
+}
+
+#[command]
+pub fn get_rich_presence_enabled() -> bool {
+    presence::is_enabled()
 }
 
+/// 开关 Steam 富状态；关闭时立即清除，重新开启后在下一次状态变化时恢复
 #[command]
-pub async fn start_host(port: u16, password: Option<String>) -> Result<(), String> {
+pub fn set_rich_presence_enabled(enabled: bool) -> Result<(), String> {
+    let client = steam::client()?;
+    presence::set_enabled(&client, enabled);
+    info!("Tauri: 富状态已{}", if enabled { "开启" } else { "关闭" });
+    Ok(())
+}
+
+#[command]
+pub fn get_route_preference() -> RoutePreference {
+    route_preference::get()
+}
+
+/// 设置 P2P 路径偏好 (自动 / 只用中继 / 只用直连)，下次开服或加入房间时生效
+///
+/// 强制直连在对称 NAT 等网络下可能无法连接
+#[command]
+pub fn set_route_preference(preference: RoutePreference) {
+    info!("Tauri: 路径偏好设置为 {:?}", preference);
+    route_preference::set(preference);
+}
+
+// 每个参数对应前端传入的一个字段
+#[allow(clippy::too_many_arguments)]
+#[command]
+pub async fn start_host(
+    port: u16,
+    password: Option<String>,
+    mc_version: Option<String>,
+    modpack: Option<String>,
+    dry_run: Option<DryRunMode>,
+    max_duration_secs: Option<u64>,
+    rcon_port: Option<u16>,
+    lock_at_players: Option<usize>,
+    extra_servers: Option<Vec<McServer>>,
+    app: AppHandle,
+) -> Result<String, String> {
+    let extra_servers = host::normalize_servers(port, extra_servers.unwrap_or_default())?;
+    // Reject a second host before any Steam resources are created
+    let guard = session::try_begin(SessionRole::Host)?;
+
+    let options = HostOptions {
+        port,
+        extra_servers,
+        password,
+        mc_version,
+        modpack,
+        virtual_port: None,
+        dry_run,
+        auto_stop: auto_stop(&app, "host", max_duration_secs),
+        peer_removed_tx: Some(forward_events(&app, host::PEER_REMOVED_EVENT)),
+        rcon_port,
+        lock_at_players,
+        lobby_lock_tx: Some(forward_events(&app, host::LOBBY_LOCK_EVENT)),
+        readiness_tx: Some(forward_events(&app, mc_readiness::MC_READINESS_EVENT)),
+        thread_panic_tx: Some(forward_events(&app, panic_guard::THREAD_PANIC_EVENT)),
+    };
+
     // Create channel to receive lobby ID
     let (tx, rx) = mpsc::channel();
-    
-    // This runs in a separate thread to avoid blocking the UI
+
+    let attempt_id = correlation::new_id();
+    info!("Tauri: 开始创建房间 [#{}]", attempt_id);
+
+    // This runs in a separate thread to avoid blocking the UI.
+    // Every log line from the host session carries the attempt's correlation ID.
+    let thread_attempt_id = attempt_id.clone();
     thread::spawn(move || {
-        match Client::init() {
+        correlation::set(Some(thread_attempt_id.clone()));
+        // Session slot is held until the host loop exits
+        let _guard = guard;
+        match steam::client() {
             Ok(client) => {
-                if let Err(e) = run_host(client, port, password, tx) {
-                    eprintln!("Host error: {}", e);
+                if let Err(e) = run_host(client, options, tx) {
+                    error!("✗ 房主会话出错: {}", e);
+                    session::set_state(SessionState::Error {
+                        message: correlation::tag_error(&e, &thread_attempt_id),
+                    });
                 }
             }
             Err(e) => {
-                eprintln!("Steam 初始化失败: {}", e);
+                error!("✗ {}", e);
+                session::set_state(SessionState::Error {
+                    message: correlation::tag_error(&e, &thread_attempt_id),
+                });
+                let _ = tx.send(Err(e));
             }
         }
     });
 
-    // Wait for lobby ID and store it
-    if let Ok(lobby_id) = rx.recv() {
-        *LOBBY_ID.lock().unwrap() = Some(lobby_id);
+    // Wait for lobby creation so the frontend gets the ID without polling.
+    // The ID is returned as a string: Steam IDs exceed JavaScript's safe integer range.
+    let result = match rx.recv_timeout(Duration::from_secs(30)) {
+        Ok(Ok(lobby_id)) => {
+            session::set_lobby_id(SessionRole::Host, lobby_id);
+            Ok(lobby_id.to_string())
+        }
+        Ok(Err(e)) => Err(e),
+        Err(RecvTimeoutError::Timeout) => Err(Message::LobbyCreateTimeout.text().to_string()),
+        Err(RecvTimeoutError::Disconnected) => Err(Message::HostThreadExited.text().to_string()),
+    };
+    result.map_err(|e| correlation::tag_error(e, &attempt_id))
+}
+
+#[command]
+pub fn rotate_password(new_password: Option<String>, kick_peers: Option<bool>) -> Result<(), String> {
+    info!("Tauri: 收到更新房间密码请求");
+    host::send_command(HostCommand::RotatePassword {
+        password: new_password,
+        kick_peers: kick_peers.unwrap_or(false),
+    })
+}
+
+/// 运行中房主会话转发到的 MC 服务器端口
+#[command]
+pub fn get_mc_target_port() -> Result<u16, String> {
+    host::mc_target_port().ok_or_else(|| Message::NoHostSession.text().to_string())
+}
+
+/// 更换运行中房主会话转发到的 MC 服务器端口
+///
+/// 之后的新连接使用新端口；已建立的连接保持原端口 (断线重连也使用原端口)，
+/// `reconnect` 为 true 时它们会立即断开并重新连接到新端口
+#[command]
+pub fn set_mc_target_port(port: u16, reconnect: Option<bool>) -> Result<(), String> {
+    info!("Tauri: 收到更换 MC 服务器端口请求: {}", port);
+    if port == 0 {
+        return Err(Message::InvalidMcPort.format(&[&port]));
     }
-    
-    Ok(())
+    host::send_command(HostCommand::SetMcTargetPort {
+        port,
+        reconnect: reconnect.unwrap_or(false),
+    })
 }
 
+/// 断开某个玩家与 MC 服务器之间的连接，但不将其移出房间
+///
+/// 玩家的 Minecraft 会断线，可通过仍保持的 Steam 连接直接重新加入 (如修复不同步)
 #[command]
-pub async fn join_lobby(lobby_id_str: String, password: Option<String>) -> Result<(), String> {
-    let lobby_id_u64 = lobby_id_str
+pub fn reset_mc_bridge(steam_id: String) -> Result<(), String> {
+    let steam_id = steam_id
+        .trim()
         .parse::<u64>()
-        .map_err(|_| "Invalid Lobby ID")?;
+        .map_err(|_| Message::InvalidSteamId.text())?;
+    info!("Tauri: 收到重置 MC 连接请求: {}", steam_id);
+
+    let (reply, rx) = mpsc::channel();
+    host::send_command(HostCommand::ResetMcBridge {
+        steam_id: SteamId::from_raw(steam_id),
+        reply,
+    })?;
+    rx.recv_timeout(Duration::from_secs(5))
+        .map_err(|_| Message::HostSessionEnded.text().to_string())?
+}
+
+/// 暂停房主会话的转发 (如重启 MC 服务器期间)，玩家的 Steam 连接保持不变
+///
+/// `policy` 决定暂停期间的数据缓冲到恢复后送达还是直接丢弃，默认 PAUSE_POLICY；
+/// 状态变化通过 host::FORWARDING_EVENT 事件通知前端
+#[command]
+pub fn pause_forwarding(app: AppHandle, policy: Option<PausePolicy>) -> Result<(), String> {
+    let policy = policy.unwrap_or(PAUSE_POLICY);
+    info!("Tauri: 收到暂停转发请求 ({:?})", policy);
+    host::send_command(HostCommand::PauseForwarding { policy })?;
+    emit_forwarding_state(&app, Some(policy));
+    Ok(())
+}
+
+/// 恢复房主会话的转发
+#[command]
+pub fn resume_forwarding(app: AppHandle) -> Result<(), String> {
+    info!("Tauri: 收到恢复转发请求");
+    host::send_command(HostCommand::ResumeForwarding)?;
+    emit_forwarding_state(&app, None);
+    Ok(())
+}
+
+/// 锁定房间: 切换为仅好友可加入或私密 (默认 LOBBY_LOCK)，已连接的玩家不受影响
+///
+/// 状态变化通过 host::LOBBY_LOCK_EVENT 事件通知前端
+#[command]
+pub fn lock_lobby(lock: Option<LobbyLock>) -> Result<(), String> {
+    let lock = lock.unwrap_or(LOBBY_LOCK);
+    info!("Tauri: 收到锁定房间请求 ({:?})", lock);
+    set_lobby_lock(Some(lock))
+}
+
+/// 解锁房间，重新公开
+#[command]
+pub fn unlock_lobby() -> Result<(), String> {
+    info!("Tauri: 收到解锁房间请求");
+    set_lobby_lock(None)
+}
+
+fn set_lobby_lock(lock: Option<LobbyLock>) -> Result<(), String> {
+    let (reply, rx) = mpsc::channel();
+    host::send_command(HostCommand::SetLobbyLock { lock, reply })?;
+    rx.recv_timeout(Duration::from_secs(5))
+        .map_err(|_| Message::HostSessionEnded.text().to_string())?
+}
+
+fn emit_forwarding_state(app: &AppHandle, policy: Option<PausePolicy>) {
+    let state = ForwardingState {
+        paused: policy.is_some(),
+        policy,
+    };
+    if let Err(e) = app.emit(host::FORWARDING_EVENT, state) {
+        warn!("⚠️ 无法通知前端转发状态: {}", e);
+    }
+}
+
+/// 解析前端传入的关闭宽限期，限制在合理范围内
+fn grace_period(grace_period_ms: Option<u64>) -> Duration {
+    Duration::from_millis(
+        grace_period_ms
+            .unwrap_or(SHUTDOWN_GRACE_PERIOD_MS)
+            .min(MAX_SHUTDOWN_GRACE_PERIOD_MS),
+    )
+}
+
+/// 按前端传入的最长运行时间 (秒，0 或 None 表示不限) 创建自动结束计时器
+///
+/// 预警经转发线程发给前端，会话结束、计时器释放后线程随之退出
+fn auto_stop(app: &AppHandle, role: &'static str, max_duration_secs: Option<u64>) -> Option<AutoStop> {
+    let secs = max_duration_secs.filter(|&secs| secs > 0)?;
+    info!("⏰ {} 会话最长运行 {} 秒", role, secs);
+    let tx = forward_events(app, AUTO_STOP_WARNING_EVENT);
+    Some(AutoStop::new(role, Duration::from_secs(secs), tx))
+}
+
+/// 会话线程发出的通知经返回的通道转为前端事件
+///
+/// 转发线程在所有发送端释放 (会话结束) 后退出
+fn forward_events<T: Serialize + Clone + Send + 'static>(app: &AppHandle, event: &'static str) -> mpsc::Sender<T> {
+    let (tx, rx) = mpsc::channel::<T>();
+    let app = app.clone();
+    thread::spawn(move || {
+        for payload in rx {
+            if let Err(e) = app.emit(event, payload) {
+                warn!("⚠️ 无法发送前端事件 {}: {}", event, e);
+            }
+        }
+    });
+    tx
+}
+
+#[command]
+pub fn stop_host(grace_period_ms: Option<u64>) -> Result<(), String> {
+    info!("Tauri: 收到停止房主请求");
+    host::send_command(HostCommand::Shutdown {
+        grace_period: grace_period(grace_period_ms),
+    })?;
+    // The lobby is going away; don't offer it for invites during the grace period
+    session::clear_lobby_id(SessionRole::Host);
+    Ok(())
+}
+
+#[command]
+pub fn leave_lobby(grace_period_ms: Option<u64>) -> Result<(), String> {
+    info!("Tauri: 收到离开房间请求");
+    client_mode::send_command(ClientCommand::Leave {
+        grace_period: grace_period(grace_period_ms),
+    })?;
+    session::clear_lobby_id(SessionRole::Client);
+    Ok(())
+}
+
+/// 开启或关闭客户端的 RCON 转发，开启时返回本机监听端口
+///
+/// 房主须在开房时开放 RCON，且 (默认) 只接受允许列表中玩家的 RCON 连接
+#[command]
+pub fn set_rcon_forwarding(enabled: bool) -> Result<Option<u16>, String> {
+    info!("Tauri: 收到{} RCON 转发请求", if enabled { "开启" } else { "关闭" });
+    let (reply, rx) = mpsc::channel();
+    client_mode::send_command(ClientCommand::SetRconForwarding { enabled, reply })?;
+    rx.recv_timeout(Duration::from_secs(5))
+        .map_err(|_| Message::ClientSessionEnded.text().to_string())?
+}
+
+/// 之后的新 MC 连接转发到房主的第 server 个 MC 服务器，0 为主服务器
+///
+/// 已在游戏中的连接不受影响，在 Minecraft 中重新连接后生效
+#[command]
+pub fn set_mc_server(server: u8) -> Result<(), String> {
+    info!("Tauri: 收到切换 MC 服务器请求 ({})", server);
+    let (reply, rx) = mpsc::channel();
+    client_mode::send_command(ClientCommand::SetMcServer { server, reply })?;
+    rx.recv_timeout(Duration::from_secs(5))
+        .map_err(|_| Message::ClientSessionEnded.text().to_string())?
+}
+
+/// 离开当前房间后加入另一个房间，如游戏中接受了另一位好友的邀请
+///
+/// 等当前客户端会话完全结束 (离开房间、关闭连接、停止广播) 后才开始加入
+#[command]
+pub async fn switch_lobby(
+    lobby_id_str: String,
+    password: Option<String>,
+    grace_period_ms: Option<u64>,
+    app: AppHandle,
+) -> Result<JoinReport, JoinError> {
+    // 已在目标房间中 (如接受了同一房间的邀请) 时不必离开再加入
+    if join_target::parse_lobby_id(&lobby_id_str).is_some_and(|id| session::client_target() == Some(id)) {
+        return Err(Message::AlreadyInLobby.text().to_string().into());
+    }
+    if session::is_active(SessionRole::Client) {
+        info!("Tauri: 切换到房间 {}，先离开当前房间", lobby_id_str);
+        let grace_period = grace_period(grace_period_ms);
+        client_mode::send_command(ClientCommand::Leave { grace_period })?;
+        if !session::wait_until_ended(SessionRole::Client, grace_period + Duration::from_millis(SWITCH_LEAVE_TIMEOUT_MS)) {
+            return Err(Message::SwitchLeaveTimeout.text().to_string().into());
+        }
+    }
+    join_lobby(lobby_id_str, password, None, None, None, None, None, None, app).await
+}
+
+/// 加入前查询房间是否需要密码，不加入房间
+#[command]
+pub async fn lobby_requires_password(lobby_id_str: String) -> Result<bool, String> {
+    let lobby_id_u64 =
+        join_target::parse_lobby_id(&lobby_id_str).ok_or(Message::InvalidLobbyId.text())?;
+    let client = steam::client()?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        client_mode::lobby_requires_password(&client, LobbyId::from_raw(lobby_id_u64))
+    })
+    .await
+    .map_err(|_| Message::TaskFailed.text().to_string())?
+}
+
+/// 加入前查询房主开放的主服务器以外的 MC 服务器名称，不加入房间
+#[command]
+pub async fn get_lobby_mc_servers(lobby_id_str: String) -> Result<Vec<String>, String> {
+    let lobby_id_u64 =
+        join_target::parse_lobby_id(&lobby_id_str).ok_or(Message::InvalidLobbyId.text())?;
+    let client = steam::client()?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        client_mode::lobby_mc_servers(&client, LobbyId::from_raw(lobby_id_u64))
+    })
+    .await
+    .map_err(|_| Message::TaskFailed.text().to_string())?
+}
+
+/// 加入前根据房主公布的中继网络位置估算延迟 (毫秒)，无法估算时返回 None
+#[command]
+pub async fn estimate_lobby_ping(lobby_id_str: String) -> Result<Option<u32>, String> {
+    let lobby_id_u64 =
+        join_target::parse_lobby_id(&lobby_id_str).ok_or(Message::InvalidLobbyId.text())?;
+    let client = steam::client()?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        client_mode::estimate_lobby_ping(&client, LobbyId::from_raw(lobby_id_u64))
+    })
+    .await
+    .map_err(|_| Message::TaskFailed.text().to_string())?
+}
+
+/// 本机在 Steam 中继网络中的位置
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkLocation {
+    /// 位置字符串，可交给对方估算延迟；中继网络尚未就绪时为空
+    ping_location: Option<String>,
+}
+
+/// 查询本机在 Steam 中继网络中的位置，首次调用时初始化中继网络访问
+#[command]
+pub fn get_network_location() -> Result<NetworkLocation, String> {
+    let client = steam::client()?;
+    client.networking_utils().init_relay_network_access();
+    Ok(NetworkLocation {
+        ping_location: steam::local_ping_location(&client),
+    })
+}
+
+/// 测试房间是否可连接，不启动完整的桥接
+#[command]
+pub async fn probe_lobby(
+    lobby_id_str: String,
+    password: Option<String>,
+) -> Result<ProbeReport, String> {
+    let lobby_id_u64 =
+        join_target::parse_lobby_id(&lobby_id_str).ok_or(Message::InvalidLobbyId.text())?;
+    let guard = session::try_begin(SessionRole::Client)?;
+    let client = steam::client()?;
+    let options = ClientOptions {
+        password,
+        ..Default::default()
+    };
+
+    let attempt_id = correlation::new_id();
+    info!("Tauri: 开始探测房间 {} [#{}]", lobby_id_u64, attempt_id);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        correlation::set(Some(attempt_id.clone()));
+        let _guard = guard;
+        let mut report =
+            client_mode::probe_lobby(&client, LobbyId::from_raw(lobby_id_u64), &options);
+        report.error = report.error.map(|e| correlation::tag_error(e, &attempt_id));
+        // spawn_blocking 的线程会被复用，探测结束后清除关联 ID
+        correlation::set(None);
+        report
+    })
+    .await
+    .map_err(|_| Message::TaskFailed.text().to_string())
+}
+
+/// 通过房间号、`steam://joinlobby/...` 链接、connect 字符串或好友代码加入房间
+///
+/// 好友代码 (或好友的 Steam ID) 解析为该好友当前所在的房间，之后与 join_lobby 相同
+#[command]
+pub async fn join_by_connect_string(
+    connect: String,
+    password: Option<String>,
+    lan_broadcast: Option<bool>,
+    max_duration_secs: Option<u64>,
+    app: AppHandle,
+) -> Result<JoinReport, JoinError> {
+    let lobby_id = match join_target::parse(&connect) {
+        Some(JoinTarget::Lobby(lobby_id)) => lobby_id,
+        Some(JoinTarget::Friend(friend_id)) => {
+            let client = steam::client()?;
+            friends::friend_lobby(&client, SteamId::from_raw(friend_id))
+                .ok_or_else(|| JoinError::unresolved_target(&connect, Some(friend_id)))?
+                .raw()
+        }
+        None => return Err(JoinError::unresolved_target(&connect, None)),
+    };
+    info!("Tauri: {} 解析为房间 {}", connect.trim(), lobby_id);
+    join_lobby(lobby_id.to_string(), password, None, None, None, lan_broadcast, max_duration_secs, None, app).await
+}
+
+// 每个参数对应前端传入的一个字段
+#[allow(clippy::too_many_arguments)]
+#[command]
+pub async fn join_lobby(
+    lobby_id_str: String,
+    password: Option<String>,
+    mc_version: Option<String>,
+    modpack: Option<String>,
+    lan_scope: Option<BroadcastScope>,
+    lan_broadcast: Option<bool>,
+    max_duration_secs: Option<u64>,
+    mc_server: Option<u8>,
+    app: AppHandle,
+) -> Result<JoinReport, JoinError> {
+    let lobby_id_u64 = join_target::parse_lobby_id(&lobby_id_str)
+        .ok_or_else(|| Message::InvalidLobbyId.text().to_string())?;
     let lobby_id = LobbyId::from_raw(lobby_id_u64);
 
+    // 重复点击加入同一房间时保留现有会话，不再走一遍加入流程
+    let guard = session::try_begin_join(lobby_id_u64)?;
+    let auto_stop = auto_stop(&app, "client", max_duration_secs);
+    let progress_tx = forward_events::<JoinProgress>(&app, JOIN_PROGRESS_EVENT);
+    let connection_lost_tx = forward_events::<ConnectionLost>(&app, CONNECTION_LOST_EVENT);
+    let thread_panic_tx = forward_events(&app, panic_guard::THREAD_PANIC_EVENT);
+
     // Create channel to receive connection result
     let (tx, rx) = mpsc::channel();
 
+    let attempt_id = correlation::new_id();
+    info!("Tauri: 开始加入房间 {} [#{}]", lobby_id_u64, attempt_id);
+
+    // 客户端会话的每一行日志都带有本次尝试的关联 ID
+    let thread_attempt_id = attempt_id.clone();
     thread::spawn(move || {
-        match Client::init() {
+        correlation::set(Some(thread_attempt_id.clone()));
+        // 会话占用在客户端主循环退出前一直保持
+        let _guard = guard;
+        match steam::client() {
             Ok(client) => {
-                match run_client(client, lobby_id, password, tx.clone()) {
+                let options = ClientOptions {
+                    password,
+                    mc_version,
+                    modpack,
+                    lan_scope: lan_scope.unwrap_or_default(),
+                    lan_broadcast,
+                    virtual_port: None,
+                    auto_stop,
+                    progress_tx: Some(progress_tx),
+                    connection_lost_tx: Some(connection_lost_tx),
+                    thread_panic_tx: Some(thread_panic_tx),
+                    mc_server: mc_server.unwrap_or(0),
+                };
+                match run_client(client, lobby_id, options, tx.clone()) {
                     Ok(_) => {}
                     Err(e) => {
-                        eprintln!("Client error: {}", e);
-                        let _ = tx.send(Err(format!("客户端错误: {}", e)));
+                        error!("✗ 客户端会话出错: {}", e);
+                        session::set_state(SessionState::Error {
+                            message: correlation::tag_error(&e, &thread_attempt_id),
+                        });
+                        let _ = tx.send(Err(Message::ClientError.format(&[&e]).into()));
                     }
                 }
             }
             Err(e) => {
-                eprintln!("Steam 初始化失败: {}", e);
-                let _ = tx.send(Err(format!("Steam 未运行或初始化失败: {}", e)));
+                error!("✗ {}", e);
+                session::set_state(SessionState::Error {
+                    message: correlation::tag_error(&e, &thread_attempt_id),
+                });
+                let _ = tx.send(Err(e.into()));
             }
         }
     });
 
     // Wait for connection result (success or error)
-    match rx.recv_timeout(std::time::Duration::from_secs(30)) {
-        Ok(Ok(())) => {
-            // Store the lobby ID after successful connection
-            *LOBBY_ID.lock().unwrap() = Some(lobby_id_u64);
-            Ok(())
+    let result = match rx.recv_timeout(std::time::Duration::from_secs(30)) {
+        Ok(Ok(report)) => {
+            // Store the lobby ID after successful connection; cleared when the session ends
+            session::set_lobby_id(SessionRole::Client, lobby_id_u64);
+            Ok(report)
         }
         Ok(Err(e)) => Err(e),
-        Err(_) => Err("连接超时".to_string()),
-    }
+        Err(_) => Err(Message::ConnectTimeout.text().to_string().into()),
+    };
+    result.map_err(|e| e.tagged(&attempt_id))
 }
@@ -1,12 +1,139 @@
+use crate::host::{LobbyLock, PausePolicy};
+use crate::i18n::Language;
+use crate::route_preference::RoutePreference;
+use crate::send_queue::QueueFullPolicy;
+use crate::structured_log::LogFormat;
+use std::net::Ipv4Addr;
+
 // 网络端口配置
-#[allow(dead_code)]
-pub const MC_SERVER_PORT: u16 = 25565;
+pub const MC_SERVER_PORT: u16 = 25565; // server.properties 未设置端口时 Minecraft 使用的默认值
 pub const CLIENT_LISTEN_PORT: u16 = 55555;
+pub const CLIENT_LISTEN_PORT_FALLBACKS: u16 = 10; // CLIENT_LISTEN_PORT 被占用时依次尝试其后的端口数量
 
 // 性能优化配置
 pub const BUFFER_SIZE: usize = 65536; // 64KB 读取缓冲区
+pub const MC_READ_QUEUE_CAPACITY: usize = 64; // MC 读取线程到主循环的队列容量 (条)，满时停止读取 TCP
+pub const SEND_QUEUE_FULL_POLICY: QueueFullPolicy = QueueFullPolicy::Block; // 队列满时等待 (TCP 背压) 还是丢弃；丢弃会破坏 MC 的 TCP 字节流，见 send_queue::QueueFullPolicy
+pub const MC_READ_TIMEOUT_MS: u64 = 200; // 客户端 MC 读取线程阻塞读取的超时，超时后检查会话是否已结束；有数据时读取立即返回，不影响延迟
+pub const MC_BRIDGE_THREADS: usize = 2; // 房主桥接 MC 服务器的工作线程数，所有 MC 连接以非阻塞 I/O 复用这些线程
+pub const BRIDGE_IDLE_WAIT_MIN_MICROS: u64 = 200; // 桥接线程所有连接都空闲时的首次等待，有数据后恢复为此值，决定活跃连接的额外延迟
+pub const BRIDGE_IDLE_WAIT_MAX_MICROS: u64 = 2000; // 持续空闲时等待时间逐轮翻倍的上限，决定空闲后第一批数据的最大额外延迟
+pub const MC_HANDSHAKE_CHECK: bool = true; // 房主检查每条 MC 连接的首批数据是否为 Minecraft 握手，明显不是时断开该玩家；代理或模组发送特殊数据时关闭
+pub const MC_CONNECT_ATTEMPTS: u32 = 5; // 房主连接 MC 服务器的最多尝试次数，MC 服务器重启期间不会立即断开玩家
+pub const MC_RECONNECT_INTERVAL_MS: u64 = 2000; // 连接 MC 服务器失败或断开后的重试间隔
+pub const MC_READINESS_RETRY_MS: u64 = 2000; // 房间创建后本地 MC 服务器尚不可连接时，后台重试检查的间隔
+pub const MC_READINESS_TIMEOUT_MS: u64 = 1000; // 检查本地 MC 服务器是否可连接的超时
+pub const LATENCY_POLL_INTERVAL_MS: u64 = 1000; // 客户端查询到房主延迟的间隔，延迟变化不快，无需每轮循环查询
+pub const LATENCY_SMOOTHING_FACTOR: f32 = 0.2; // 延迟指数移动平均的权重 (0..=1)，越大越跟随最新值；按每秒一个样本约 5 秒内跟上变化
+pub const TCP_NODELAY: bool = true; // MC 连接禁用 Nagle 算法，降低延迟
+pub const LOOP_SLEEP_MICROS: u64 = 100; // 房主/客户端主循环每轮的休眠时间，越短延迟越低、CPU 占用越高；0 表示只让出时间片 (占满一个核心)
+pub const RAISE_TIMER_RESOLUTION: bool = true; // Windows 上会话期间把系统计时器精度提高到 1 ms (timeBeginPeriod)，否则短休眠会被拉长到约 15.6 ms
+pub const MAX_EXTRA_MC_SERVERS: usize = 7; // 房主在主服务器以外最多同时开放的 MC 服务器数，客户端加入时从中选择
+pub const MC_RCON_PORT: u16 = 25575; // server.properties 中 rcon.port 的默认值，房主开放 RCON 转发时未指定端口则使用
+pub const CLIENT_RCON_LISTEN_PORT: u16 = 55575; // 客户端转发 RCON 时在本机 (仅 127.0.0.1) 监听的端口，被占用时依次尝试其后 CLIENT_LISTEN_PORT_FALLBACKS 个端口
+pub const RCON_REQUIRE_ALLOWLIST: bool = true; // 只有明确在允许列表中的玩家才能使用 RCON 转发；关闭后所有能加入房间的玩家都可以
+pub const TCP_KEEPALIVE_IDLE_SECS: u64 = 30; // 连接空闲多久后开始发送 keepalive 探测
+pub const TCP_KEEPALIVE_INTERVAL_SECS: u64 = 10; // keepalive 探测间隔
+
+// 界面语言，None 表示跟随系统区域设置
+pub const DEFAULT_LANGUAGE: Option<Language> = None;
+
+// 日志配置
+pub const STRUCTURED_LOG_FORMAT: LogFormat = LogFormat::Off; // 排查问题时改为 KeyValue 或 Json
+pub const LOG_THROTTLE_INTERVAL_SECS: u64 = 5; // 主循环中反复出现的同一错误每隔多少秒最多记录一次，期间省略的次数附在下一条日志中
+
+// 抓包配置 (通过 set_packet_capture 命令临时开启，文件包含完整游戏数据)
+pub const CAPTURE_MAX_FILE_BYTES: u64 = 64 * 1024 * 1024; // 单个抓包文件上限 64MB，超过后轮转
+pub const CAPTURE_MAX_FILES: usize = 3; // 轮转后保留的旧抓包文件数
+pub const CAPTURE_QUEUE_CAPACITY: usize = 4096; // 待写入的抓包记录数上限，写入跟不上时丢弃记录而不阻塞转发
+
+// 诊断信息配置
+pub const DIAGNOSTICS_LOG_LINES: usize = 500; // 导出诊断信息时附带的最近日志行数
+pub const LOG_BUFFER_CAPACITY: usize = 1000; // 内存中保留的最近日志条数，供界面事件日志和诊断导出使用
+
+// Steam 回调配置
+pub const CALLBACK_PUMP_INTERVAL_MS: u64 = 5; // 回调线程调用 run_callbacks 的间隔
+
+// Steam 配置
+pub const STEAM_APP_ID: Option<u32> = None; // 指定 App ID 初始化，None 时使用 Steam 启动环境或 steam_appid.txt
+pub const FALLBACK_APP_ID: u32 = 480; // 缺少 App ID 时提示用户使用的 ID (Spacewar)
+
+// 会话配置
+pub const AUTO_JOIN_ON_INVITE: bool = false; // 收到 Steam "加入游戏" 请求时是否直接加入（否则只通知前端）
+pub const ALLOW_MIXED_MODE: bool = false; // 是否允许同时作为房主和客户端运行
+pub const MAX_CONCURRENT_PEERS: usize = 9; // 房主同时接受的 P2P 连接上限（大厅 10 人，除去房主）
+pub const LOBBY_DATA_SYNC_ATTEMPTS: u32 = 25; // 加入房间后等待密码等房间元数据从 Steam 同步的最多尝试次数
+pub const LOBBY_DATA_SYNC_INTERVAL_MS: u64 = 200; // 等待房间元数据同步时每次尝试的间隔，默认共等待约 5 秒
+pub const LOBBY_LOCK: LobbyLock = LobbyLock::FriendsOnly; // 锁定房间 (手动或达到自动锁定人数) 时默认切换到的大厅类型
+pub const HOST_IDLE_TIMEOUT_SECS: Option<u64> = None; // 房间无人连接超过该时长后自动关闭，None 表示不自动关闭
+
+// 发送重试配置
+pub const SEND_RETRY_ATTEMPTS: u32 = 3; // 单条消息发送失败后的重试次数
+pub const SEND_RETRY_BACKOFF_MS: u64 = 1; // 首次重试前的等待时间，之后每次翻倍；在之后的主循环中重试，不阻塞其他玩家的转发
+pub const SEND_RETRY_QUEUE_CAPACITY: usize = 256; // 每名玩家等待重试的消息数上限，排满后新消息计为发送失败并关闭其所属的流
+pub const MAX_CONSECUTIVE_SEND_FAILURES: u32 = 8; // 连续多少条消息发送失败后断开该玩家
+pub const MAX_CONSECUTIVE_RECEIVE_ERRORS: u32 = 50; // 客户端连续多少次接收失败后视为与房主的连接已失效 (连接状态已关闭时立即结束)
+
+// 会话关闭配置
+pub const SHUTDOWN_GRACE_PERIOD_MS: u64 = 3000; // 关闭时等待发送缓冲区清空的默认宽限期
+pub const MAX_SHUTDOWN_GRACE_PERIOD_MS: u64 = 30000;
+pub const SWITCH_LEAVE_TIMEOUT_MS: u64 = 10000; // 切换房间时，宽限期之外再等待当前会话退出的时间
+pub const AUTO_STOP_WARNING_SECS: u64 = 60; // 设置了最长运行时间时，提前多久通知前端会话即将自动结束
+
+// 暂停转发配置
+pub const PAUSE_POLICY: PausePolicy = PausePolicy::Buffer; // 暂停期间的默认处理方式：缓冲到恢复后送达，或直接丢弃
+pub const MAX_PAUSE_BUFFER_BYTES: usize = 16 * 1024 * 1024; // 暂停期间每名玩家发往 MC 服务器的数据最多缓冲量，超出时关闭数据被丢弃的 MC 连接
+
+// NetworkingSockets 配置
+pub const P2P_VIRTUAL_PORT: i32 = 0; // 房主监听和客户端连接使用的默认虚拟端口
+pub const ROUTE_PREFERENCE: RoutePreference = RoutePreference::Automatic; // 默认路径偏好，可通过 set_route_preference 修改；强制直连在部分网络下无法连接
+pub const RELAY_WARMUP_TIMEOUT_MS: u64 = 10000; // 创建监听端口前等待 Steam 中继网络就绪的最长时间
+pub const LISTEN_SOCKET_ATTEMPTS: u32 = 3; // 创建监听端口失败时的尝试次数
+pub const LISTEN_SOCKET_RETRY_DELAY_MS: u64 = 1000; // 创建监听端口重试前的等待时间
+pub const REQUIRE_ENCRYPTED_CONNECTIONS: bool = true; // 确认 Steam 连接未加密时立即断开，而不是以明文转发 MC 流量
+
+// 版本检查配置
+pub const REQUIRE_APP_VERSION_MATCH: bool = true; // 客户端加入前检查房主的程序版本，不兼容 (见 app_version::is_compatible) 时拒绝连接
+pub const APP_UPDATE_URL: &str = "https://github.com/ByteFlowing1337/MCconnectRust/releases"; // 版本不匹配时引导用户下载新版本的页面
+
+// 帧协议配置
+pub const ENABLE_FRAMING: bool = true; // 房主是否启用长度前缀帧协议
+pub const MAX_FRAME_PAYLOAD: usize = 256 * 1024; // 单帧最大负载 256KB
+pub const STEAM_MAX_MESSAGE_SIZE: usize = 512 * 1024; // Steam 单条消息发送上限 (k_cbMaxSteamNetworkingSocketsMessageSizeSend)
+pub const UNRELIABLE_STATUS_PINGS: bool = false; // 房主是否以不可靠方式回复服务器列表查询 (状态/Ping)；游戏流量始终可靠，无法识别的连接也按可靠发送
+pub const MAX_UNRELIABLE_MESSAGE_SIZE: usize = 1200; // 不可靠发送的单条消息上限，约为一个 UDP 包；更大的消息任一分片丢失即整条丢失，改为可靠发送
+pub const APP_RELIABILITY: bool = false; // 实验性: 帧改经不可靠消息发送，由程序自行编号、确认和重传 (见 app_reliability)，用于大量传输时绕开 Steam 可靠消息的重传窗口；房主和客户端都开启才生效
+pub const APP_RELIABILITY_WINDOW: usize = 1024; // 应用层可靠传输最多在途 (未确认) 的段数，每段约一个 UDP 包
+pub const APP_RELIABILITY_MAX_BACKLOG: usize = 4096; // 窗口已满时最多排队等待发送的段数，超出时发送失败，由调用方稍后重试
+pub const APP_RELIABILITY_INITIAL_RTO_MS: u64 = 200; // 尚未测得往返时间时的重传超时
+pub const APP_RELIABILITY_MIN_RTO_MS: u64 = 30; // 重传超时下限，实际超时为平滑往返时间的 2 倍
+pub const APP_RELIABILITY_MAX_RTO_MS: u64 = 1000; // 重传超时上限 (每段重传后另行翻倍)
+pub const APP_RELIABILITY_MAX_RETRANSMITS: u32 = 20; // 同一段重传多少次仍未确认后视为连接失效
 
 // LAN发现配置
 pub const LAN_DISCOVERY_PORT: u16 = 4445;
+pub const LAN_MULTICAST_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 2, 60);
 pub const LAN_BROADCAST_INTERVAL_MS: u64 = 1500;
 pub const LAN_SERVER_NAME: &str = "LAN world";
+pub const MAX_LAN_MOTD_LEN: usize = 256; // LAN广播中MOTD的最大字符数
+pub const CLIENT_LAN_BROADCAST: bool = true; // 客户端是否广播 "LAN world"；关闭后需直接连接 127.0.0.1:CLIENT_LISTEN_PORT
+pub const LAN_DIRECT_CONNECT: bool = false; // 与房主在同一局域网时直接连接其 MC 服务器而不经 Steam；房主据此在公开的大厅元数据中公布私有地址。直连不经过房主的访问列表、握手检查、人数上限、暂停和踢出
+pub const LAN_DIRECT_PROBE_TIMEOUT_MS: u64 = 500; // 局域网直连前查询房主 MC 服务器的超时
+
+// 服务器自动检测配置
+pub const DISCOVERY_TIMEOUT_SECS: u64 = 3; // 默认搜索时长
+pub const MIN_DISCOVERY_TIMEOUT_SECS: u64 = 1;
+pub const MAX_DISCOVERY_TIMEOUT_SECS: u64 = 30;
+pub const VERIFY_MC_SERVER: bool = true; // 发现服务器后是否用服务器列表查询 (Server List Ping) 确认它确实是 Minecraft
+pub const MC_STATUS_TIMEOUT_MS: u64 = 1500; // 服务器列表查询的连接和读写超时
+
+// 指标统计配置
+pub const DROP_RATE_WINDOW_SECS: u64 = 10; // 丢包率统计的滑动窗口
+
+// 指标端点配置（需启用 prometheus feature）
+#[allow(dead_code)]
+pub const ENABLE_METRICS_ENDPOINT: bool = false; // 默认关闭
+#[allow(dead_code)]
+pub const METRICS_ENDPOINT_ADDR: Ipv4Addr = Ipv4Addr::LOCALHOST; // 默认只监听本机
+#[allow(dead_code)]
+pub const METRICS_ENDPOINT_PORT: u16 = 9464;
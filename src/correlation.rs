@@ -0,0 +1,118 @@
+use crate::log_buffer;
+use log::{Log, Metadata, Record};
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+thread_local! {
+    /// 当前线程所属的连接尝试，由会话线程设置并传递给其派生的线程
+    static CURRENT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// 生成一个简短的关联 ID (6 位十六进制)
+///
+/// 只用于在日志中区分同一文件里的多次加入/开服尝试，不需要密码学强度
+pub fn new_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0),
+    );
+    format!("{:06x}", hasher.finish() & 0xFF_FFFF)
+}
+
+/// 设置当前线程的关联 ID
+pub fn set(id: Option<String>) {
+    CURRENT.with(|current| *current.borrow_mut() = id);
+}
+
+/// 当前线程的关联 ID
+pub fn current() -> Option<String> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// 给错误信息附加关联 ID，用户反馈问题时可据此在日志中定位
+pub fn tag_error(message: impl std::fmt::Display, id: &str) -> String {
+    format!("{} [#{}]", message, id)
+}
+
+/// 与 `thread::spawn` 相同，但新线程继承当前线程的关联 ID
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let id = current();
+    thread::spawn(move || {
+        set(id);
+        f()
+    })
+}
+
+/// 包装日志后端，为带有关联 ID 的线程输出的每一行加上 `[#id]` 前缀
+///
+/// 同时把日志记入内存中的环形缓冲区，供界面事件日志和导出诊断信息使用
+pub struct Logger {
+    inner: Box<dyn Log>,
+}
+
+impl Logger {
+    pub fn new(inner: Box<dyn Log>) -> Self {
+        Self { inner }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        let id = current();
+        if self.enabled(record.metadata()) {
+            log_buffer::push(record, id.as_deref());
+        }
+        match id {
+            Some(id) => self.inner.log(
+                &Record::builder()
+                    .args(format_args!("[#{}] {}", id, record.args()))
+                    .metadata(record.metadata().clone())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            ),
+            None => self.inner.log(record),
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawned_threads_inherit_id() {
+        let id = new_id();
+        assert_eq!(id.len(), 6);
+        assert_ne!(id, new_id());
+
+        set(Some(id.clone()));
+        let inherited = spawn(current).join().unwrap();
+        assert_eq!(inherited, Some(id));
+
+        set(None);
+        assert_eq!(spawn(current).join().unwrap(), None);
+    }
+}
@@ -0,0 +1,287 @@
+use crate::commands::PerformanceMetrics;
+use crate::config::{
+    CLIENT_LAN_BROADCAST, CLIENT_LISTEN_PORT, DIAGNOSTICS_LOG_LINES, ENABLE_FRAMING,
+    MAX_CONCURRENT_PEERS, MC_BRIDGE_THREADS, MC_HANDSHAKE_CHECK, MC_READ_QUEUE_CAPACITY, P2P_VIRTUAL_PORT,
+    RCON_REQUIRE_ALLOWLIST, REQUIRE_ENCRYPTED_CONNECTIONS, STEAM_APP_ID, UNRELIABLE_STATUS_PINGS,
+};
+use crate::host::{
+    LOBBY_KEY_APP_VERSION, LOBBY_KEY_HOST, LOBBY_KEY_MC_VERSION, LOBBY_KEY_MODPACK,
+    LOBBY_KEY_PING_LOCATION, LOBBY_KEY_RCON, LOBBY_KEY_VIRTUAL_PORT,
+};
+use crate::i18n::{self, Language};
+use crate::lan_direct::{LOBBY_KEY_LAN_ADDRS, LOBBY_KEY_LAN_PORT};
+use crate::log_buffer;
+use crate::metrics::{self, LatencyStats, PeerTraffic, RouteKind};
+use crate::presence;
+use crate::process_stats::{self, ProcessStats};
+use crate::route_preference;
+use crate::session::{self, SessionState};
+use crate::steam;
+use log::{info, LevelFilter};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use steamworks::LobbyId;
+
+/// 替换敏感信息使用的占位符
+const REDACTED: &str = "[REDACTED]";
+
+/// 诊断包中包含的大厅元数据键
+const LOBBY_KEYS: [&str; 10] = [
+    LOBBY_KEY_HOST,
+    LOBBY_KEY_MC_VERSION,
+    LOBBY_KEY_MODPACK,
+    LOBBY_KEY_VIRTUAL_PORT,
+    LOBBY_KEY_APP_VERSION,
+    LOBBY_KEY_PING_LOCATION,
+    LOBBY_KEY_LAN_ADDRS,
+    LOBBY_KEY_LAN_PORT,
+    LOBBY_KEY_RCON,
+    "password",
+];
+
+/// 大厅元数据中需要隐藏值的键
+const SECRET_LOBBY_KEYS: [&str; 1] = ["password"];
+
+/// 本进程中设置过的房间密码；房间关闭或密码更换后，日志中仍可能出现旧值
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// 记下一个需要从诊断日志中清除的值
+pub fn remember_secret(secret: &str) {
+    let mut secrets = SECRETS.lock().unwrap();
+    if !secret.is_empty() && !secrets.iter().any(|known| known == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// 诊断包内容
+#[derive(Serialize)]
+struct Bundle {
+    generated_at_ms: u64,
+    app_version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    language: Language,
+    rich_presence: bool,
+    session: SessionState,
+    steam: SteamDiagnostics,
+    lobby: Option<LobbyDiagnostics>,
+    metrics: PerformanceMetrics,
+    peers: PeerDiagnostics,
+    process: ProcessStats,
+    config: Value,
+    logs: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SteamDiagnostics {
+    available: bool,
+    online: bool,
+    error: Option<String>,
+    app_id: Option<u32>,
+    /// Steam Datagram Relay 网络状态
+    relay: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct LobbyDiagnostics {
+    lobby_id: String,
+    member_count: usize,
+    metadata: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct PeerDiagnostics {
+    latency: HashMap<u64, LatencyStats>,
+    routes: HashMap<u64, RouteKind>,
+    encryption: HashMap<u64, bool>,
+    traffic: HashMap<u64, PeerTraffic>,
+}
+
+/// 收集各子系统的状态，写入 `path` (JSON)
+///
+/// 房间密码不会写入文件：大厅元数据中的密码值和日志中出现的密码都会被替换，
+/// 没有活动大厅时也会替换本进程设置过的密码
+pub fn export(
+    path: &Path,
+    lobby_id: Option<u64>,
+    performance: PerformanceMetrics,
+) -> Result<(), String> {
+    let client = steam::client();
+    let mut secrets = SECRETS.lock().unwrap().clone();
+
+    let lobby = match (&client, lobby_id) {
+        (Ok(client), Some(lobby_id)) => {
+            let lobby = LobbyId::from_raw(lobby_id);
+            let matchmaking = client.matchmaking();
+            let mut metadata = BTreeMap::new();
+            for key in LOBBY_KEYS {
+                if let Some(value) = matchmaking.lobby_data(lobby, key) {
+                    metadata.insert(key.to_string(), value);
+                }
+            }
+            secrets.extend(redact_metadata(&mut metadata));
+            Some(LobbyDiagnostics {
+                lobby_id: lobby_id.to_string(),
+                member_count: matchmaking.lobby_member_count(lobby),
+                metadata,
+            })
+        }
+        _ => None,
+    };
+
+    let steam = match &client {
+        Ok(client) => {
+            let relay = client.networking_utils().relay_network_status();
+            SteamDiagnostics {
+                available: true,
+                online: steam::is_online(),
+                error: None,
+                app_id: Some(client.utils().app_id().0),
+                relay: Some(json!({
+                    "availability": format!("{:?}", relay.availability()),
+                    "network_config": format!("{:?}", relay.network_config_availability()),
+                    "any_relay": format!("{:?}", relay.any_relay_availability()),
+                    "ping_measurement_in_progress": relay.is_ping_measurement_in_progress(),
+                    "message": relay.debugging_message(),
+                })),
+            }
+        }
+        Err(e) => SteamDiagnostics {
+            available: false,
+            online: false,
+            error: Some(e.clone()),
+            app_id: None,
+            relay: None,
+        },
+    };
+
+    let logs = redacted_logs(&secrets);
+
+    let bundle = Bundle {
+        generated_at_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        app_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        language: i18n::language(),
+        rich_presence: presence::is_enabled(),
+        session: session::state(),
+        steam,
+        lobby,
+        metrics: performance,
+        peers: PeerDiagnostics {
+            latency: metrics::get_all_latencies(),
+            routes: metrics::get_all_routes(),
+            encryption: metrics::get_all_encryption(),
+            traffic: metrics::get_all_peer_traffic(),
+        },
+        process: process_stats::sample(),
+        config: json!({
+            "client_listen_port": CLIENT_LISTEN_PORT,
+            "client_lan_broadcast": CLIENT_LAN_BROADCAST,
+            "steam_app_id": STEAM_APP_ID,
+            "p2p_virtual_port": P2P_VIRTUAL_PORT,
+            "require_encrypted_connections": REQUIRE_ENCRYPTED_CONNECTIONS,
+            "enable_framing": ENABLE_FRAMING,
+            "max_concurrent_peers": MAX_CONCURRENT_PEERS,
+            "mc_read_queue_capacity": MC_READ_QUEUE_CAPACITY,
+            "mc_bridge_threads": MC_BRIDGE_THREADS,
+            "mc_handshake_check": MC_HANDSHAKE_CHECK,
+            "unreliable_status_pings": UNRELIABLE_STATUS_PINGS,
+            "rcon_require_allowlist": RCON_REQUIRE_ALLOWLIST,
+            "route_preference": route_preference::get(),
+        }),
+        logs,
+    };
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let contents = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+    fs::write(path, contents).map_err(|e| e.to_string())?;
+    info!("🩺 诊断信息已导出到 {}", path.display());
+    Ok(())
+}
+
+/// 默认的诊断文件名，带时间戳避免覆盖之前的导出
+pub fn default_file_name() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!("diagnostics-{}.json", secs)
+}
+
+/// 隐藏敏感的元数据值，返回被隐藏的原值，用于继续清理日志
+fn redact_metadata(metadata: &mut BTreeMap<String, String>) -> Vec<String> {
+    let mut secrets = Vec::new();
+    for key in SECRET_LOBBY_KEYS {
+        if let Some(value) = metadata.get_mut(key) {
+            if !value.is_empty() {
+                secrets.push(std::mem::replace(value, REDACTED.to_string()));
+            }
+        }
+    }
+    secrets
+}
+
+/// 最近的日志，其中出现的敏感值已被替换
+fn redacted_logs(secrets: &[String]) -> Vec<String> {
+    log_buffer::recent(DIAGNOSTICS_LOG_LINES, LevelFilter::Trace)
+        .iter()
+        .map(|entry| redact(&entry.line(), secrets))
+        .collect()
+}
+
+/// 把文本中出现的敏感值替换为占位符
+fn redact(text: &str, secrets: &[String]) -> String {
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(text.to_string(), |text, secret| text.replace(secret.as_str(), REDACTED))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_password() {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("password".to_string(), "hunter2".to_string());
+        metadata.insert(LOBBY_KEY_MC_VERSION.to_string(), "1.20.1".to_string());
+
+        let secrets = redact_metadata(&mut metadata);
+        assert_eq!(metadata["password"], REDACTED);
+        assert_eq!(metadata[LOBBY_KEY_MC_VERSION], "1.20.1");
+        assert_eq!(
+            redact("[INFO] 密码 hunter2 已设置", &secrets),
+            format!("[INFO] 密码 {} 已设置", REDACTED)
+        );
+        assert_eq!(redact("no secrets here", &[]), "no secrets here");
+    }
+
+    #[test]
+    fn test_redacts_remembered_password_without_lobby() {
+        remember_secret("correct-horse");
+        log_buffer::push(
+            &log::Record::builder()
+                .args(format_args!("密码 correct-horse 已更换"))
+                .level(log::Level::Info)
+                .build(),
+            None,
+        );
+
+        // 没有活动大厅时没有元数据可供清理，仍要替换设置过的密码
+        let logs = redacted_logs(&SECRETS.lock().unwrap().clone());
+        assert!(logs.iter().any(|line| line.contains(REDACTED)));
+        assert!(logs.iter().all(|line| !line.contains("correct-horse")));
+    }
+}
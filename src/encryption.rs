@@ -0,0 +1,81 @@
+use crate::config::REQUIRE_ENCRYPTED_CONNECTIONS;
+use crate::metrics;
+use crate::structured_log;
+use log::{error, info};
+use serde_json::json;
+use steamworks::networking_sockets::NetConnection;
+use steamworks::Client;
+
+/// 从 Steam 的详细连接状态文本判断连接是否加密，文本未提及加密时返回 None
+///
+/// 与路由一样，steamworks-rs 没有暴露连接信息中的 Unencrypted 标志，只能解析
+/// `get_detailed_connection_status` 的文本: 取提到加密 (crypt) 的行，
+/// 其中出现 unencrypted/none/plaintext 视为未加密，否则视为已加密
+pub fn classify(detail: &str) -> Option<bool> {
+    detail
+        .lines()
+        .map(str::to_lowercase)
+        .find(|line| line.contains("crypt"))
+        .map(|line| {
+            let words: Vec<&str> = line
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .collect();
+            !words
+                .iter()
+                .any(|w| matches!(*w, "unencrypted" | "none" | "plaintext" | "disabled"))
+        })
+}
+
+/// 在连接建立时确认它已加密，并记录到对端指标中
+///
+/// Steam NetworkingSockets 默认加密所有连接，这里只做校验而不实现任何加密。
+/// 确认未加密且 REQUIRE_ENCRYPTED_CONNECTIONS 开启时返回 false，调用方须关闭连接；
+/// 无法确认 (状态文本未提及加密) 时按 Steam 的默认行为放行
+pub fn verify(client: &Client, connection: &NetConnection, steam_id: u64) -> bool {
+    let encrypted = client
+        .networking_sockets()
+        .get_detailed_connection_status(connection)
+        .ok()
+        .and_then(|detail| classify(&detail));
+    if let Some(encrypted) = encrypted {
+        metrics::update_encryption(steam_id, encrypted);
+    }
+
+    match encrypted {
+        Some(true) => {
+            info!("🔒 与 {} 的连接已加密", steam_id);
+            true
+        }
+        Some(false) => {
+            error!("🔓 与 {} 的连接未加密，Minecraft 流量可能被窃听", steam_id);
+            structured_log::emit(
+                "connection_unencrypted",
+                &[
+                    ("steam_id", json!(steam_id)),
+                    ("closing", json!(REQUIRE_ENCRYPTED_CONNECTIONS)),
+                ],
+            );
+            !REQUIRE_ENCRYPTED_CONNECTIONS
+        }
+        None => {
+            info!("无法从连接状态确认与 {} 的连接是否加密，按 Steam 默认视为已加密", steam_id);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let encrypted = "Connection state: connected\nCrypto: AES-GCM-256\nTransport: SDR via sto2";
+        assert_eq!(classify(encrypted), Some(true));
+
+        assert_eq!(classify("Crypto: none\nTransport: ICE"), Some(false));
+        assert_eq!(classify("Connection is UNENCRYPTED"), Some(false));
+        assert_eq!(classify("Connection state: connected\nTransport: ICE (udp)"), None);
+    }
+}
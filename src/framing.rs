@@ -0,0 +1,498 @@
+use crate::config::{MAX_FRAME_PAYLOAD, STEAM_MAX_MESSAGE_SIZE};
+use std::fmt;
+
+/// 当前帧格式版本，格式变更时递增
+///
+/// v2: 增加帧类型和流 ID，支持同一对端的多条 MC 连接复用一条 Steam 连接
+/// v3: 增加通道 ID，MC 流量为通道 0，其他通道留给文件传输、聊天等附加功能
+pub const FRAME_VERSION: u8 = 3;
+
+/// 帧头长度: 版本(1) + 类型(1) + 通道(1) + 流 ID(4, 大端) + 负载长度(4, 大端)
+pub const FRAME_HEADER_LEN: usize = 11;
+
+/// 单条消息可携带的最大负载，同时受帧协议上限和 Steam 单条消息上限约束
+///
+/// NetworkingUtils 没有暴露查询消息上限的接口，这里使用 SDK 中的固定值
+pub const MAX_CHUNK_PAYLOAD: usize = if MAX_FRAME_PAYLOAD < STEAM_MAX_MESSAGE_SIZE - FRAME_HEADER_LEN {
+    MAX_FRAME_PAYLOAD
+} else {
+    STEAM_MAX_MESSAGE_SIZE - FRAME_HEADER_LEN
+};
+
+/// 房主在大厅元数据中公布帧协议版本使用的键
+pub const LOBBY_KEY_FRAMING: &str = "framing";
+
+/// 帧类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    /// 某条 MC 连接上的数据
+    Data = 0,
+    /// 某条 MC 连接已关闭
+    Close = 1,
+    /// 客户端在一条流的首个数据帧之前选择要连接的 MC 服务器，负载为服务器序号 (1 字节)
+    ///
+    /// 只在房主公布了多个 MC 服务器时发送，旧版房主不会收到，因此不需要升级帧版本
+    Open = 2,
+}
+
+impl FrameKind {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(FrameKind::Data),
+            1 => Some(FrameKind::Close),
+            2 => Some(FrameKind::Open),
+            _ => None,
+        }
+    }
+}
+
+/// 帧所属的通道，同一条 Steam 连接上的不同功能按通道分发
+///
+/// 新增通道时在此添加变体，并在房主和客户端的接收路径上为其增加处理分支
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Minecraft 游戏流量
+    Minecraft = 0,
+    /// 管理员转发到房主 MC 服务器 RCON 端口的流量，需房主开放
+    Rcon = 1,
+}
+
+impl Channel {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Channel::Minecraft),
+            1 => Some(Channel::Rcon),
+            _ => None,
+        }
+    }
+}
+
+/// 一个逻辑帧，stream_id 标识对端在该通道上的一条连接
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    pub channel: Channel,
+    pub stream_id: u32,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// MC 通道上的数据帧
+    pub fn data(stream_id: u32, payload: Vec<u8>) -> Self {
+        Self {
+            kind: FrameKind::Data,
+            channel: Channel::Minecraft,
+            stream_id,
+            payload,
+        }
+    }
+
+    /// MC 通道上的连接关闭帧
+    pub fn close(stream_id: u32) -> Self {
+        Self {
+            kind: FrameKind::Close,
+            channel: Channel::Minecraft,
+            stream_id,
+            payload: Vec::new(),
+        }
+    }
+
+    /// MC 通道上选择第 `server` 个 MC 服务器的打开帧
+    pub fn open(stream_id: u32, server: u8) -> Self {
+        Self {
+            kind: FrameKind::Open,
+            channel: Channel::Minecraft,
+            stream_id,
+            payload: vec![server],
+        }
+    }
+
+    /// 改为另一个通道上的同类帧
+    pub fn on(mut self, channel: Channel) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// 帧中转发给 MC 的字节数，控制帧为 0，用于区分游戏流量和协议开销
+    pub fn payload_len(&self) -> usize {
+        match self.kind {
+            FrameKind::Data => self.payload.len(),
+            FrameKind::Close | FrameKind::Open => 0,
+        }
+    }
+}
+
+/// 帧编解码错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    /// 数据不足一个完整帧
+    Truncated,
+    /// 对端使用了不支持的帧版本
+    UnsupportedVersion(u8),
+    /// 未知的帧类型
+    UnknownKind(u8),
+    /// 未知的通道
+    UnknownChannel(u8),
+    /// 负载超过允许的最大长度
+    TooLarge(usize),
+    /// 帧头声明的长度与实际消息长度不符
+    LengthMismatch { declared: usize, actual: usize },
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Truncated => write!(f, "帧数据不完整"),
+            FrameError::UnsupportedVersion(v) => write!(f, "不支持的帧版本: {}", v),
+            FrameError::UnknownKind(k) => write!(f, "未知的帧类型: {}", k),
+            FrameError::UnknownChannel(c) => write!(f, "未知的通道: {}", c),
+            FrameError::TooLarge(len) => {
+                write!(f, "帧负载过大: {} 字节 (上限 {})", len, MAX_FRAME_PAYLOAD)
+            }
+            FrameError::LengthMismatch { declared, actual } => {
+                write!(f, "帧长度不符: 声明 {} 字节, 实际 {} 字节", declared, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+/// 将一个帧编码为字节
+pub fn encode(frame: &Frame) -> Result<Vec<u8>, FrameError> {
+    if frame.payload.len() > MAX_FRAME_PAYLOAD {
+        return Err(FrameError::TooLarge(frame.payload.len()));
+    }
+
+    let mut buf = Vec::with_capacity(FRAME_HEADER_LEN + frame.payload.len());
+    buf.push(FRAME_VERSION);
+    buf.push(frame.kind as u8);
+    buf.push(frame.channel as u8);
+    buf.extend_from_slice(&frame.stream_id.to_be_bytes());
+    buf.extend_from_slice(&(frame.payload.len() as u32).to_be_bytes());
+    buf.extend_from_slice(&frame.payload);
+    Ok(buf)
+}
+
+/// 将负载过大的帧拆分为同一流上按顺序发送的多个帧
+///
+/// Steam 可靠消息保证按序到达，接收端把各块依次写入同一条 MC 连接即完成重组，
+/// 因此不需要额外的分块头。负载不超过上限的帧原样返回。
+///
+/// 透明代理的核心约束: 每条流上接收端写出的字节必须与发送端读到的字节完全一致、
+/// 顺序相同，无论数据如何切分、多条流如何交错。`test_ordering_*` 测试以随机输入
+/// 检查这一点，对帧层的任何优化 (合并、批量发送等) 都不能破坏它
+pub fn split(frame: &Frame, max_payload: usize) -> Vec<Frame> {
+    if frame.payload.len() <= max_payload || max_payload == 0 {
+        return vec![frame.clone()];
+    }
+    frame
+        .payload
+        .chunks(max_payload)
+        .map(|chunk| Frame {
+            kind: frame.kind,
+            channel: frame.channel,
+            stream_id: frame.stream_id,
+            payload: chunk.to_vec(),
+        })
+        .collect()
+}
+
+/// 解析后的帧头
+struct Header {
+    kind: FrameKind,
+    channel: Channel,
+    stream_id: u32,
+    len: usize,
+}
+
+/// 解析帧头
+fn parse_header(buf: &[u8]) -> Result<Header, FrameError> {
+    if buf.len() < FRAME_HEADER_LEN {
+        return Err(FrameError::Truncated);
+    }
+    if buf[0] != FRAME_VERSION {
+        return Err(FrameError::UnsupportedVersion(buf[0]));
+    }
+
+    let kind = FrameKind::from_u8(buf[1]).ok_or(FrameError::UnknownKind(buf[1]))?;
+    let channel = Channel::from_u8(buf[2]).ok_or(FrameError::UnknownChannel(buf[2]))?;
+    let stream_id = u32::from_be_bytes([buf[3], buf[4], buf[5], buf[6]]);
+    let len = u32::from_be_bytes([buf[7], buf[8], buf[9], buf[10]]) as usize;
+    if len > MAX_FRAME_PAYLOAD {
+        return Err(FrameError::TooLarge(len));
+    }
+    Ok(Header {
+        kind,
+        channel,
+        stream_id,
+        len,
+    })
+}
+
+/// 解码一条 Steam 消息，要求消息恰好包含一个完整帧
+pub fn decode(message: &[u8]) -> Result<Frame, FrameError> {
+    let header = parse_header(message)?;
+    let actual = message.len() - FRAME_HEADER_LEN;
+    if actual != header.len {
+        return Err(FrameError::LengthMismatch {
+            declared: header.len,
+            actual,
+        });
+    }
+    Ok(Frame {
+        kind: header.kind,
+        channel: header.channel,
+        stream_id: header.stream_id,
+        payload: message[FRAME_HEADER_LEN..].to_vec(),
+    })
+}
+
+/// 流式帧解码器，用于从任意切分的字节流中还原帧
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+#[allow(dead_code)]
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加收到的字节
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// 取出下一个完整帧，数据不足时返回 Ok(None)
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, FrameError> {
+        let header = match parse_header(&self.buffer) {
+            Ok(header) => header,
+            Err(FrameError::Truncated) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let total = FRAME_HEADER_LEN + header.len;
+        if self.buffer.len() < total {
+            return Ok(None);
+        }
+
+        let payload = self.buffer[FRAME_HEADER_LEN..total].to_vec();
+        self.buffer.drain(..total);
+        Ok(Some(Frame {
+            kind: header.kind,
+            channel: header.channel,
+            stream_id: header.stream_id,
+            payload,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+
+    #[test]
+    fn test_roundtrip() {
+        let frame = Frame::data(7, b"hello".to_vec());
+        let encoded = encode(&frame).unwrap();
+        assert_eq!(encoded.len(), FRAME_HEADER_LEN + 5);
+        assert_eq!(decode(&encoded).unwrap(), frame);
+
+        let close = Frame::close(7);
+        assert_eq!(decode(&encode(&close).unwrap()).unwrap(), close);
+
+        let open = Frame::open(7, 2);
+        assert_eq!(open.payload_len(), 0);
+        assert_eq!(decode(&encode(&open).unwrap()).unwrap(), open);
+
+        let rcon = Frame::data(7, b"list".to_vec()).on(Channel::Rcon);
+        assert_eq!(decode(&encode(&rcon).unwrap()).unwrap(), rcon);
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_input() {
+        assert_eq!(decode(&[FRAME_VERSION, 0, 0]), Err(FrameError::Truncated));
+        assert_eq!(
+            decode(&[0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Err(FrameError::UnsupportedVersion(0xFF))
+        );
+        assert_eq!(
+            decode(&[FRAME_VERSION, 9, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Err(FrameError::UnknownKind(9))
+        );
+        assert_eq!(
+            decode(&[FRAME_VERSION, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Err(FrameError::UnknownChannel(7))
+        );
+
+        let mut encoded = encode(&Frame::data(0, b"abc".to_vec())).unwrap();
+        encoded.push(b'd');
+        assert_eq!(
+            decode(&encoded),
+            Err(FrameError::LengthMismatch {
+                declared: 3,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_oversized_payload() {
+        let frame = Frame::data(0, vec![0u8; MAX_FRAME_PAYLOAD + 1]);
+        assert_eq!(encode(&frame), Err(FrameError::TooLarge(MAX_FRAME_PAYLOAD + 1)));
+    }
+
+    #[test]
+    fn test_split_oversized_payload_reassembles() {
+        let payload: Vec<u8> = (0..MAX_CHUNK_PAYLOAD * 3 + 17).map(|i| (i % 251) as u8).collect();
+        let frame = Frame::data(5, payload.clone());
+        assert_eq!(encode(&frame), Err(FrameError::TooLarge(payload.len())));
+
+        let chunks = split(&frame, MAX_CHUNK_PAYLOAD);
+        assert_eq!(chunks.len(), 4);
+
+        let mut reassembled = Vec::new();
+        for chunk in &chunks {
+            let encoded = encode(chunk).unwrap();
+            assert!(encoded.len() <= STEAM_MAX_MESSAGE_SIZE);
+            let decoded = decode(&encoded).unwrap();
+            assert_eq!(decoded.stream_id, 5);
+            reassembled.extend_from_slice(&decoded.payload);
+        }
+        assert_eq!(reassembled, payload);
+
+        assert_eq!(split(&Frame::close(5), MAX_CHUNK_PAYLOAD), vec![Frame::close(5)]);
+    }
+
+    #[test]
+    fn test_stream_decoder_concatenated_and_split() {
+        let first = Frame::data(1, b"first".to_vec());
+        let second = Frame::data(2, b"second".to_vec());
+        let mut stream = encode(&first).unwrap();
+        stream.extend(encode(&second).unwrap());
+
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&stream[..3]);
+        assert_eq!(decoder.next_frame().unwrap(), None);
+
+        decoder.push(&stream[3..]);
+        assert_eq!(decoder.next_frame().unwrap(), Some(first));
+        assert_eq!(decoder.next_frame().unwrap(), Some(second));
+        assert_eq!(decoder.next_frame().unwrap(), None);
+    }
+
+    /// 测试用的确定性伪随机数 (xorshift64*)，失败时可按种子复现
+    struct Rng(u64);
+
+    impl Rng {
+        fn new(seed: u64) -> Self {
+            Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+        }
+
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        /// [0, n) 内的随机数
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn bytes(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    /// 一次随机会话: 若干条流交错发送随机切分的数据，部分流中途关闭后由新流接替 (重连)
+    ///
+    /// 返回按发送顺序排列的帧，以及每条流应当收到的完整字节
+    fn random_session(rng: &mut Rng) -> (Vec<Frame>, HashMap<u32, Vec<u8>>) {
+        let mut frames = Vec::new();
+        let mut expected: HashMap<u32, Vec<u8>> = HashMap::new();
+        let mut open: Vec<u32> = (0..1 + rng.below(3) as u32).collect();
+        let mut next_stream = open.len() as u32;
+
+        for _ in 0..rng.below(60) {
+            let index = rng.below(open.len());
+            let stream_id = open[index];
+            if rng.below(10) == 0 {
+                frames.push(Frame::close(stream_id));
+                open[index] = next_stream;
+                next_stream += 1;
+                continue;
+            }
+            // 偶尔产生超过单块上限的写入，经 split 拆分
+            let len = if rng.below(20) == 0 { 1 + rng.below(4096) } else { rng.below(300) };
+            let data = rng.bytes(len);
+            expected.entry(stream_id).or_default().extend_from_slice(&data);
+            let max_chunk = 1 + rng.below(512);
+            frames.extend(split(&Frame::data(stream_id, data), max_chunk));
+        }
+        (frames, expected)
+    }
+
+    /// 按接收顺序把帧写回各条流，检查关闭后的流不再收到数据
+    fn reassemble(frames: impl IntoIterator<Item = Frame>) -> HashMap<u32, Vec<u8>> {
+        let mut streams: HashMap<u32, Vec<u8>> = HashMap::new();
+        let mut closed = HashSet::new();
+        for frame in frames {
+            assert!(!closed.contains(&frame.stream_id), "流 {} 关闭后仍收到帧", frame.stream_id);
+            match frame.kind {
+                FrameKind::Data => streams.entry(frame.stream_id).or_default().extend(frame.payload),
+                FrameKind::Open => {}
+                FrameKind::Close => {
+                    closed.insert(frame.stream_id);
+                }
+            }
+        }
+        streams.retain(|_, data| !data.is_empty());
+        streams
+    }
+
+    #[test]
+    fn test_ordering_preserved_per_message() {
+        for seed in 0..300 {
+            let mut rng = Rng::new(seed);
+            let (frames, mut expected) = random_session(&mut rng);
+            expected.retain(|_, data| !data.is_empty());
+
+            let received = frames
+                .iter()
+                .map(|frame| decode(&encode(frame).unwrap()).unwrap());
+            assert_eq!(reassemble(received), expected, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_ordering_preserved_across_arbitrary_boundaries() {
+        for seed in 0..300 {
+            let mut rng = Rng::new(seed);
+            let (frames, mut expected) = random_session(&mut rng);
+            expected.retain(|_, data| !data.is_empty());
+
+            // 所有帧首尾相连后在随机位置切开，模拟批量发送或任意分段
+            let wire: Vec<u8> = frames.iter().flat_map(|frame| encode(frame).unwrap()).collect();
+            let mut decoder = FrameDecoder::new();
+            let mut received = Vec::new();
+            let mut offset = 0;
+            while offset < wire.len() {
+                let end = (offset + 1 + rng.below(64)).min(wire.len());
+                decoder.push(&wire[offset..end]);
+                offset = end;
+                while let Some(frame) = decoder.next_frame().unwrap() {
+                    received.push(frame);
+                }
+            }
+
+            assert_eq!(received, frames, "seed {}", seed);
+            assert_eq!(reassemble(received), expected, "seed {}", seed);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use serde::Serialize;
+use steamworks::{Client, FriendFlags, FriendState, LobbyId, SteamId};
+
+/// 返回给前端的好友信息
+#[derive(Debug, Clone, Serialize)]
+pub struct FriendInfo {
+    /// 以字符串返回，Steam ID 超出 JavaScript 安全整数范围
+    pub steam_id: String,
+    pub name: String,
+    pub online: bool,
+    pub state: &'static str,
+}
+
+/// 按名称查找好友，忽略大小写并支持部分匹配，同时匹配好友昵称
+///
+/// 在线好友排在前面，其余按名称排序
+pub fn find_friends(client: &Client, query: &str) -> Vec<FriendInfo> {
+    let mut matches: Vec<FriendInfo> = client
+        .friends()
+        .get_friends(FriendFlags::IMMEDIATE)
+        .into_iter()
+        .filter(|friend| {
+            name_matches(&friend.name(), query)
+                || friend.nick_name().is_some_and(|nick| name_matches(&nick, query))
+        })
+        .map(|friend| {
+            let state = friend.state();
+            FriendInfo {
+                steam_id: friend.id().raw().to_string(),
+                name: friend.name(),
+                online: !matches!(state, FriendState::Offline),
+                state: state_label(state),
+            }
+        })
+        .collect();
+
+    matches.sort_by(|a, b| {
+        b.online
+            .cmp(&a.online)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    matches
+}
+
+/// 邀请好友加入房间，好友在 Steam 中接受后通过 connect 字符串加入
+pub fn invite_to_lobby(client: &Client, steam_id: SteamId, lobby_id: u64) {
+    client
+        .friends()
+        .get_friend(steam_id)
+        .invite_user_to_game(&format!("+connect_lobby {}", lobby_id));
+}
+
+/// 玩家的 Steam 名称，Steam 尚未获取到时以 Steam ID 代替
+pub fn persona_name(client: &Client, steam_id: SteamId) -> String {
+    let name = client.friends().get_friend(steam_id).name();
+    if name.is_empty() || name == "[unknown]" {
+        steam_id.raw().to_string()
+    } else {
+        name
+    }
+}
+
+/// 好友当前所在的本应用房间，好友不在游戏中或未加入房间时为 None
+pub fn friend_lobby(client: &Client, steam_id: SteamId) -> Option<LobbyId> {
+    let game = client.friends().get_friend(steam_id).game_played()?;
+    (game.game.app_id() == client.utils().app_id() && game.lobby.raw() != 0).then_some(game.lobby)
+}
+
+/// 忽略大小写的部分匹配，空查询不匹配任何好友
+fn name_matches(name: &str, query: &str) -> bool {
+    let query = query.trim();
+    !query.is_empty() && name.to_lowercase().contains(&query.to_lowercase())
+}
+
+fn state_label(state: FriendState) -> &'static str {
+    match state {
+        FriendState::Offline => "offline",
+        FriendState::Online => "online",
+        FriendState::Busy => "busy",
+        FriendState::Away => "away",
+        FriendState::Snooze => "snooze",
+        FriendState::LookingToTrade => "looking_to_trade",
+        FriendState::LookingToPlay => "looking_to_play",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_matches() {
+        assert!(name_matches("Alice", "ali"));
+        assert!(name_matches("Alice", "ALICE"));
+        assert!(name_matches("小明的世界", "小明"));
+        assert!(!name_matches("Alice", "bob"));
+        assert!(!name_matches("Alice", "  "));
+    }
+}
@@ -1,26 +1,467 @@
-use crate::config::BUFFER_SIZE;
+use crate::access_list;
+use crate::app_reliability::{self, ReliableLink};
+use crate::app_version::APP_VERSION;
+use crate::auto_stop::AutoStop;
+use crate::bridge::{BridgePool, DryRunMode};
+use crate::capture::{self, Direction};
+use crate::config::{
+    APP_RELIABILITY, ENABLE_FRAMING, HOST_IDLE_TIMEOUT_SECS, LAN_DIRECT_CONNECT, LOBBY_LOCK, MAX_CONCURRENT_PEERS,
+    MAX_CONSECUTIVE_SEND_FAILURES, LISTEN_SOCKET_ATTEMPTS, LISTEN_SOCKET_RETRY_DELAY_MS, MAX_PAUSE_BUFFER_BYTES, MC_BRIDGE_THREADS,
+    MAX_EXTRA_MC_SERVERS,
+    MAX_UNRELIABLE_MESSAGE_SIZE, MC_HANDSHAKE_CHECK, MC_READ_QUEUE_CAPACITY, P2P_VIRTUAL_PORT,
+    RCON_REQUIRE_ALLOWLIST, RELAY_WARMUP_TIMEOUT_MS, SEND_RETRY_ATTEMPTS, SEND_RETRY_BACKOFF_MS, SEND_RETRY_QUEUE_CAPACITY,
+    SHUTDOWN_GRACE_PERIOD_MS,
+    UNRELIABLE_STATUS_PINGS,
+};
+use crate::diagnostics;
+use crate::encryption;
+use crate::framing::{self, Channel, Frame, FrameKind};
+use crate::i18n::Message;
+use crate::lan_direct;
+use crate::log_throttle::{self, LogThrottle};
+use crate::loop_timing::{self, TimerResolution};
+use crate::mc_handshake::{self, Intent, Verdict};
+use crate::mc_readiness::{McReadiness, ReadinessWatch};
 use crate::metrics;
+use crate::metrics_log;
+use crate::panic_guard::{self, ThreadPanic};
+use crate::presence;
+use crate::route_preference;
+use crate::send_queue::{self, SendQueue};
+use crate::session::{self, SessionState};
+use crate::shutdown;
+use crate::steam::{self, OfflineWatch, OnlineChange};
+use crate::structured_log;
+use crate::transport::{Transport, TransportError};
 use log::{error, info, warn};
-use std::collections::HashMap;
-use std::io::{ErrorKind, Read, Write};
-use std::net::TcpStream;
-use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, LazyLock, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
-use steamworks::networking_sockets::NetConnection;
-use steamworks::networking_types::{ListenSocketEvent, SendFlags};
-use steamworks::{Client, LobbyType, SteamId};
+use steamworks::networking_sockets::{ListenSocket, NetConnection};
+use steamworks::networking_types::{ListenSocketEvent, NetConnectionEnd};
+use steamworks::{Client, LobbyChatUpdate, LobbyDataUpdate, LobbyId, LobbyType, SteamId};
 
 static RUNNING: AtomicBool = AtomicBool::new(true);
 
-struct PeerState {
-    connection: NetConnection,
-    // Channel to send data to the MC server bridge thread
-    to_mc_tx: Sender<Vec<u8>>,
+/// 运行中会话转发到的本地 MC 服务器端口
+static MC_TARGET_PORT: AtomicU16 = AtomicU16::new(0);
+
+/// 房主在大厅元数据中公布自身 Steam ID 使用的键
+///
+/// 房间所有权可能被 Steam 转移，客户端应连接到实际运行桥接的房主而不是当前所有者
+pub const LOBBY_KEY_HOST: &str = "host_steam_id";
+
+/// 房主公布 Minecraft 版本使用的键
+pub const LOBBY_KEY_MC_VERSION: &str = "mc_version";
+
+/// 房主公布整合包信息使用的键
+pub const LOBBY_KEY_MODPACK: &str = "modpack";
+
+/// 房主公布 NetworkingSockets 监听虚拟端口使用的键，未公布时为 0
+pub const LOBBY_KEY_VIRTUAL_PORT: &str = "virtual_port";
+
+/// 房主公布程序版本使用的键，客户端据此拒绝不兼容的版本
+pub const LOBBY_KEY_APP_VERSION: &str = "app_version";
+
+/// 房主公布自身在 Steam 中继网络中位置的键，客户端加入前据此估算延迟
+pub const LOBBY_KEY_PING_LOCATION: &str = "ping_location";
+
+/// 房主开放 RCON 转发时公布的键，值为 "1"；未公布时客户端不提供 RCON 转发
+pub const LOBBY_KEY_RCON: &str = "rcon";
+
+/// 房主开放多个 MC 服务器时公布的键，值为主服务器以外各服务器名称的 JSON 数组
+///
+/// 客户端选择的服务器序号为数组下标加 1，序号 0 为主服务器；未公布时只有主服务器
+pub const LOBBY_KEY_MC_SERVERS: &str = "mc_servers";
+
+/// 拒绝不在允许列表中 (或在阻止列表中) 的玩家时使用的关闭原因
+///
+/// 位于 Steam 为应用保留的关闭原因范围 (1000-1999)，客户端据此显示对应提示
+pub const END_REASON_NOT_ALLOWED: i32 = 1001;
+
+/// 连接未加密时使用的关闭原因，同样位于应用保留范围
+pub const END_REASON_UNENCRYPTED: i32 = 1002;
+
+/// 暂停/恢复转发时发给前端的事件，负载为 `ForwardingState`
+pub const FORWARDING_EVENT: &str = "host-forwarding-changed";
+
+/// 暂停转发期间如何处理 MC 与 Steam 之间的数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PausePolicy {
+    /// 缓冲数据，恢复后按原顺序送达；MC 服务器发出的数据留在有界队列中，
+    /// 队列满后桥接线程停止读取，由 TCP 流控限速
+    #[default]
+    Buffer,
+    /// 丢弃数据，只保留 Steam 连接；数据被丢弃的 MC 连接会被关闭，适合重启 MC 服务器
+    Discard,
+}
+
+/// 锁定/解锁房间时发给前端的事件，负载为 `LobbyLockState`
+pub const LOBBY_LOCK_EVENT: &str = "host-lobby-lock-changed";
+
+/// 锁定房间后的大厅类型；已连接的玩家不受影响，只阻止新的陌生人加入
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LobbyLock {
+    /// 只有 Steam 好友能看到和加入
+    #[default]
+    FriendsOnly,
+    /// 不出现在任何列表中，只能通过邀请加入
+    Private,
+}
+
+impl LobbyLock {
+    fn lobby_type(self) -> LobbyType {
+        match self {
+            LobbyLock::FriendsOnly => LobbyType::FriendsOnly,
+            LobbyLock::Private => LobbyType::Private,
+        }
+    }
+}
+
+/// LOBBY_LOCK_EVENT 的负载
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LobbyLockState {
+    /// 当前的锁定类型，None 表示公开
+    pub locked: Option<LobbyLock>,
+    /// 是否因达到自动锁定人数而锁定
+    pub automatic: bool,
 }
 
-pub fn run_host(client: Client, port: u16, password: Option<String>, lobby_id_tx: mpsc::Sender<u64>) -> Result<(), Box<dyn std::error::Error>> {
+/// 移除玩家时发给前端的事件，负载为 `PeerRemoved`
+pub const PEER_REMOVED_EVENT: &str = "host-peer-removed";
+
+/// 玩家被移除的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalReason {
+    /// 玩家主动断开，或 Steam 报告连接已关闭
+    Disconnected,
+    /// 接收消息失败，连接已不可用
+    ConnectionLost,
+    /// 连续多条消息发送失败
+    SendFailures,
+    /// 未启用帧协议时唯一的 MC 连接已关闭
+    McClosed,
+    /// 发来的数据不是 Minecraft 握手
+    InvalidHandshake,
+    /// 不在允许列表中或在阻止列表中
+    NotAllowed,
+    /// 房间密码已更换，要求重新验证
+    PasswordChanged,
+    /// 发来的帧无法解析，连接上的字节流已不可信
+    ProtocolError,
+}
+
+impl RemovalReason {
+    fn describe(self) -> &'static str {
+        match self {
+            RemovalReason::Disconnected => "玩家已断开",
+            RemovalReason::ConnectionLost => "连接丢失",
+            RemovalReason::SendFailures => "连续发送失败",
+            RemovalReason::McClosed => "MC 连接已关闭",
+            RemovalReason::InvalidHandshake => "不是 Minecraft 握手",
+            RemovalReason::NotAllowed => "不在允许列表中",
+            RemovalReason::PasswordChanged => "房间密码已更换",
+            RemovalReason::ProtocolError => "协议错误",
+        }
+    }
+
+    /// 主动关闭连接时告知客户端的原因；对方已断开时为 None
+    fn close_reason(self) -> Option<(NetConnectionEnd, &'static str)> {
+        match self {
+            RemovalReason::Disconnected | RemovalReason::ConnectionLost => None,
+            RemovalReason::NotAllowed => Some((NetConnectionEnd::Other(END_REASON_NOT_ALLOWED), "不在允许列表中")),
+            RemovalReason::SendFailures => Some((NetConnectionEnd::AppGeneric, "发送失败")),
+            RemovalReason::McClosed => Some((NetConnectionEnd::AppGeneric, "MC 连接已关闭")),
+            RemovalReason::InvalidHandshake => Some((NetConnectionEnd::AppGeneric, "不是 Minecraft 连接")),
+            RemovalReason::PasswordChanged => Some((NetConnectionEnd::AppGeneric, "房间密码已更换")),
+            RemovalReason::ProtocolError => Some((NetConnectionEnd::AppGeneric, "协议错误")),
+        }
+    }
+}
+
+/// PEER_REMOVED_EVENT 的负载
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerRemoved {
+    /// SteamID64 (字符串)
+    pub steam_id: String,
+    pub reason: RemovalReason,
+}
+
+/// FORWARDING_EVENT 的负载
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ForwardingState {
+    pub paused: bool,
+    /// 暂停时使用的策略，未暂停时为 None
+    pub policy: Option<PausePolicy>,
+}
+
+/// 当前房主会话的控制通道，会话未运行时为 None
+static HOST_CONTROL: LazyLock<Mutex<Option<Sender<HostCommand>>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+/// 发送给运行中房主主循环的控制命令
+pub enum HostCommand {
+    /// 更新房间密码 (None 表示移除密码)，可选踢出当前玩家要求其重新验证
+    RotatePassword {
+        password: Option<String>,
+        kick_peers: bool,
+    },
+    /// 名单已修改，断开不再被允许的玩家
+    EnforceAccessLists,
+    /// 更换本地 MC 服务器端口；已有连接保持原端口，除非要求重连
+    SetMcTargetPort { port: u16, reconnect: bool },
+    /// 断开某个玩家的 MC 连接但保留其 Steam 连接，结果通过 reply 返回
+    ResetMcBridge {
+        steam_id: SteamId,
+        reply: Sender<Result<(), String>>,
+    },
+    /// 暂停 MC 与 Steam 之间的转发，Steam 连接保持不变
+    PauseForwarding { policy: PausePolicy },
+    /// 恢复转发，缓冲的数据先于新数据送达
+    ResumeForwarding,
+    /// 锁定 (Some) 或解锁 (None) 房间，结果通过 reply 返回
+    SetLobbyLock {
+        lock: Option<LobbyLock>,
+        reply: Sender<Result<(), String>>,
+    },
+    /// 停止会话，在宽限期内尽量送达待发送数据
+    Shutdown { grace_period: Duration },
+}
+
+/// 当前是否有房主会话在运行
+pub fn is_active() -> bool {
+    HOST_CONTROL.lock().unwrap().is_some()
+}
+
+/// 运行中会话当前转发到的 MC 服务器端口，没有会话时为 None
+pub fn mc_target_port() -> Option<u16> {
+    is_active().then(|| MC_TARGET_PORT.load(Ordering::Relaxed))
+}
+
+/// 生成可分享的 Steam 加入链接，好友点击后由 Steam 客户端直接加入房间
+pub fn invite_link(app_id: u32, lobby_id: u64, owner_id: u64) -> String {
+    format!("steam://joinlobby/{}/{}/{}", app_id, lobby_id, owner_id)
+}
+
+/// 向运行中的房主会话发送控制命令
+pub fn send_command(command: HostCommand) -> Result<(), String> {
+    let control = HOST_CONTROL.lock().unwrap();
+    match control.as_ref() {
+        Some(tx) => tx
+            .send(command)
+            .map_err(|_| Message::HostSessionEnded.text().to_string()),
+        None => Err(Message::NoHostSession.text().to_string()),
+    }
+}
+
+struct PeerState<T = ReliableLink<NetConnection>> {
+    connection: T,
+    // Channels to the MC server bridges, keyed by the client's stream id.
+    // Each MC connection opened by the client maps to its own host-side TCP socket.
+    streams: HashMap<u32, Sender<Vec<u8>>>,
+    // Messages that failed to send in a row, even after retries. Once this
+    // reaches the limit the stream is no longer trustworthy and the peer is closed.
+    send_failures: u32,
+    // Reliable messages whose send failed, retried in order on later loop iterations
+    // so one peer's transient failure never stalls the others. Anything sent while
+    // this is non-empty queues behind it, keeping the byte stream in order.
+    retry: VecDeque<PendingSend>,
+    // Failed attempts for the message at the front of `retry`, and when to try it again
+    retry_attempts: u32,
+    retry_at: Instant,
+    // Streams whose first bytes have not been checked against the MC handshake yet
+    handshake_pending: HashSet<u32>,
+    // RCON streams opened by the client, each bridged to the MC server's RCON port
+    rcon_streams: HashMap<u32, Sender<Vec<u8>>>,
+    // Server list ping streams whose replies may go out unreliably. A stream that
+    // ever sends reliably stays reliable, so its replies cannot be reordered.
+    status_streams: HashSet<u32>,
+    // MC server chosen by an open frame, for streams that have not sent data yet.
+    // A rejected choice stays here so the stream's data is dropped until it closes.
+    stream_servers: HashMap<u32, u8>,
+    // Frames from the client held back while forwarding is paused, and their payload size
+    held: VecDeque<Frame>,
+    held_bytes: usize,
+    // Streams closed because data in either direction had to be dropped while paused.
+    // Their byte stream has a hole in it, so anything still in flight for them is
+    // discarded until the client closes them too.
+    broken_streams: HashSet<u32>,
+}
+
+impl<T> PeerState<T> {
+    fn new(connection: T) -> Self {
+        Self {
+            connection,
+            streams: HashMap::new(),
+            send_failures: 0,
+            retry: VecDeque::new(),
+            retry_attempts: 0,
+            retry_at: Instant::now(),
+            handshake_pending: HashSet::new(),
+            rcon_streams: HashMap::new(),
+            status_streams: HashSet::new(),
+            stream_servers: HashMap::new(),
+            held: VecDeque::new(),
+            held_bytes: 0,
+            broken_streams: HashSet::new(),
+        }
+    }
+
+    /// Track a newly opened stream, queueing its first data for the handshake check
+    fn add_stream(&mut self, stream_id: u32, tx: Sender<Vec<u8>>) {
+        self.streams.insert(stream_id, tx);
+        if MC_HANDSHAKE_CHECK {
+            self.handshake_pending.insert(stream_id);
+        }
+    }
+}
+
+/// An encoded message waiting to be sent again
+struct PendingSend {
+    message: Vec<u8>,
+    /// The frame's stream, closed if the message is dropped for good
+    stream_id: u32,
+    channel: Channel,
+    kind: FrameKind,
+    /// Minecraft payload bytes in the message, for the metrics
+    payload: usize,
+}
+
+/// Switch the lobby type, leaving connected players untouched.
+/// `None` makes the lobby public again.
+fn set_lobby_lock(
+    client: &Client,
+    lobby_id: LobbyId,
+    lock: Option<LobbyLock>,
+    automatic: bool,
+    lobby_lock_tx: Option<&Sender<LobbyLockState>>,
+) -> Result<(), String> {
+    let lobby_type = lock.map_or(LobbyType::Public, LobbyLock::lobby_type);
+    if !client.matchmaking().set_lobby_type(lobby_id, lobby_type) {
+        warn!("⚠️ 无法更改房间类型为 {:?}", lobby_type);
+        return Err(Message::LobbyLockFailed.text().to_string());
+    }
+    match lock {
+        Some(lock) => info!("🔒 房间已锁定 ({:?})，现有玩家不受影响", lock),
+        None => info!("🔓 房间已解锁，重新公开"),
+    }
+    structured_log::emit(
+        "lobby_lock_changed",
+        &[("locked", json!(lock)), ("automatic", json!(automatic))],
+    );
+    if let Some(tx) = lobby_lock_tx {
+        let _ = tx.send(LobbyLockState { locked: lock, automatic });
+    }
+    Ok(())
+}
+
+/// Frames produced by bridge threads, tagged with the peer they belong to
+pub(crate) type McEvent = (SteamId, Frame);
+
+/// 与主服务器一起开放、供客户端选择的另一个本地 MC 服务器
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct McServer {
+    /// 显示给客户端的名称，如 "生存服"
+    pub label: String,
+    pub port: u16,
+}
+
+/// 整理前端传入的其他 MC 服务器: 去掉名称首尾空白，拒绝无法在房间中区分的列表
+pub fn normalize_servers(port: u16, servers: Vec<McServer>) -> Result<Vec<McServer>, String> {
+    let mut ports = HashSet::from([port]);
+    let valid = servers.len() <= MAX_EXTRA_MC_SERVERS
+        && servers
+            .iter()
+            .all(|server| !server.label.trim().is_empty() && server.port != 0 && ports.insert(server.port));
+    if !valid {
+        return Err(Message::InvalidMcServers.format(&[&MAX_EXTRA_MC_SERVERS]));
+    }
+    Ok(servers
+        .into_iter()
+        .map(|server| McServer {
+            label: server.label.trim().to_string(),
+            port: server.port,
+        })
+        .collect())
+}
+
+/// 房主会话参数
+#[derive(Debug, Clone, Default)]
+pub struct HostOptions {
+    /// 本地 Minecraft 服务器端口
+    pub port: u16,
+    /// 主服务器以外同时开放的 MC 服务器，客户端按序号 1.. 选择
+    pub extra_servers: Vec<McServer>,
+    pub password: Option<String>,
+    /// 公布给客户端的 Minecraft 版本，用于加入前比对
+    pub mc_version: Option<String>,
+    /// 公布给客户端的整合包名称/版本
+    pub modpack: Option<String>,
+    /// NetworkingSockets 监听的虚拟端口，None 时使用 P2P_VIRTUAL_PORT
+    pub virtual_port: Option<i32>,
+    /// 试运行: 不连接 MC 服务器，只回显或丢弃收到的数据
+    pub dry_run: Option<DryRunMode>,
+    /// 最长运行时间，到时后自动关闭房间
+    pub auto_stop: Option<AutoStop>,
+    /// 移除玩家时的通知，由命令层转发给前端
+    pub peer_removed_tx: Option<Sender<PeerRemoved>>,
+    /// 开放 RCON 转发时 MC 服务器的 RCON 端口，None 表示不开放
+    pub rcon_port: Option<u16>,
+    /// 同时在线玩家达到此人数时自动锁定房间 (LOBBY_LOCK)，None 表示不自动锁定
+    pub lock_at_players: Option<usize>,
+    /// 房间锁定状态变化的通知，由命令层转发给前端
+    pub lobby_lock_tx: Option<Sender<LobbyLockState>>,
+    /// 本地 MC 服务器是否可连接的通知，由命令层转发给前端
+    pub readiness_tx: Option<Sender<McReadiness>>,
+    /// 桥接线程 panic 时的通知，由命令层转发给前端
+    pub thread_panic_tx: Option<Sender<ThreadPanic>>,
+}
+
+pub fn run_host(client: Client, options: HostOptions, lobby_id_tx: mpsc::Sender<Result<u64, String>>) -> Result<(), Box<dyn std::error::Error>> {
+    let HostOptions {
+        port,
+        mut extra_servers,
+        password,
+        mc_version,
+        modpack,
+        virtual_port,
+        dry_run,
+        mut auto_stop,
+        peer_removed_tx,
+        rcon_port,
+        lock_at_players,
+        lobby_lock_tx,
+        readiness_tx,
+        thread_panic_tx,
+    } = options;
+    // RCON streams ride on the frame protocol's channels; a dry run has no server to reach
+    let rcon_port = rcon_port.filter(|_| ENABLE_FRAMING && dry_run.is_none());
+    // Choosing a server takes an open frame, which raw mode cannot carry
+    if !ENABLE_FRAMING {
+        extra_servers.clear();
+    }
+    let virtual_port = virtual_port.unwrap_or(P2P_VIRTUAL_PORT);
+    RUNNING.store(true, Ordering::Relaxed);
+
+    // Listen before advertising a lobby, so a failure doesn't leave an unusable lobby behind
+    let listen_socket = match create_listen_socket(&client, virtual_port) {
+        Ok(socket) => socket,
+        Err(err_msg) => {
+            error!("✗ {}", err_msg);
+            let _ = lobby_id_tx.send(Err(err_msg.clone()));
+            return Err(err_msg.into());
+        }
+    };
+    info!("📡 NetworkingSockets 监听已启动 (虚拟端口 {})", virtual_port);
+
     info!("🏗 正在创建 Steam 大厅...");
 
     // Create channel to receive lobby creation result
@@ -31,9 +472,11 @@ pub fn run_host(client: Client, port: u16, password: Option<String>, lobby_id_tx
             let _ = tx.send(result);
         });
 
+    // Answers LAN direct-connect verification for as long as the session runs
+    let mut lan_responder: Option<lan_direct::NonceResponder> = None;
+
     // Wait for lobby creation result
-    let _lobby_id = loop {
-        client.run_callbacks();
+    let lobby_id = loop {
         if let Ok(result) = rx.try_recv() {
             match result {
                 Ok(id) => {
@@ -44,20 +487,101 @@ pub fn run_host(client: Client, port: u16, password: Option<String>, lobby_id_tx
                     // 设置房间密码（如果有）
                     if let Some(ref pwd) = password {
                         client.matchmaking().set_lobby_data(id, "password", pwd);
-                        info!("│ 房间密码: {}", pwd);
+                        diagnostics::remember_secret(pwd);
+                        info!("│ 房间密码: 已设置");
                     } else {
                         info!("│ 房间无密码");
                     }
+
+                    // 公布版本/整合包信息，客户端加入前据此提示不匹配
+                    if let Some(ref version) = mc_version {
+                        client.matchmaking().set_lobby_data(id, LOBBY_KEY_MC_VERSION, version);
+                        info!("│ MC 版本: {}", version);
+                    }
+                    if let Some(ref pack) = modpack {
+                        client.matchmaking().set_lobby_data(id, LOBBY_KEY_MODPACK, pack);
+                        info!("│ 整合包: {}", pack);
+                    }
+
+                    // 公布帧协议版本，客户端据此决定是否使用帧协议
+                    if ENABLE_FRAMING {
+                        client.matchmaking().set_lobby_data(
+                            id,
+                            framing::LOBBY_KEY_FRAMING,
+                            &framing::FRAME_VERSION.to_string(),
+                        );
+                        info!("│ 帧协议: v{}", framing::FRAME_VERSION);
+                    }
+                    client.matchmaking().set_lobby_data(
+                        id,
+                        LOBBY_KEY_VIRTUAL_PORT,
+                        &virtual_port.to_string(),
+                    );
+                    client
+                        .matchmaking()
+                        .set_lobby_data(id, LOBBY_KEY_APP_VERSION, APP_VERSION);
+                    info!("│ 程序版本: {}", APP_VERSION);
+                    // 中继网络已在创建监听端口前就绪，位置通常已测量完成
+                    if let Some(location) = steam::local_ping_location(&client) {
+                        client
+                            .matchmaking()
+                            .set_lobby_data(id, LOBBY_KEY_PING_LOCATION, &location);
+                    }
+                    // 同一局域网的客户端据此直接连接 MC 服务器；试运行时没有可连接的服务器
+                    // 直连绕过房主的访问控制，默认关闭 (见 LAN_DIRECT_CONNECT)
+                    let lan_addrs = if LAN_DIRECT_CONNECT && dry_run.is_none() {
+                        lan_direct::local_addrs()
+                    } else {
+                        Vec::new()
+                    };
+                    if !lan_addrs.is_empty() {
+                        let nonce = lan_direct::new_nonce();
+                        match lan_direct::NonceResponder::start(nonce.clone()) {
+                            Ok(responder) => {
+                                let matchmaking = client.matchmaking();
+                                matchmaking.set_lobby_data(id, lan_direct::LOBBY_KEY_LAN_ADDRS, &lan_direct::encode(&lan_addrs));
+                                matchmaking.set_lobby_data(id, lan_direct::LOBBY_KEY_LAN_PORT, &port.to_string());
+                                matchmaking.set_lobby_data(
+                                    id,
+                                    lan_direct::LOBBY_KEY_LAN_VERIFY,
+                                    &lan_direct::encode_verify(responder.port(), &nonce),
+                                );
+                                info!("│ 局域网地址: {}", lan_direct::encode(&lan_addrs));
+                                lan_responder = Some(responder);
+                            }
+                            Err(e) => warn!("│ ⚠️ 无法启动局域网验证端口，不公布局域网地址: {}", e),
+                        }
+                    }
+                    if APP_RELIABILITY && ENABLE_FRAMING {
+                        client
+                            .matchmaking()
+                            .set_lobby_data(id, app_reliability::LOBBY_KEY_APP_RELIABILITY, "1");
+                        info!("│ 应用层可靠传输: 已开启 (实验性)");
+                    }
+                    if let Some(rcon_port) = rcon_port {
+                        client.matchmaking().set_lobby_data(id, LOBBY_KEY_RCON, "1");
+                        info!("│ RCON 转发: 已开放 (端口 {})", rcon_port);
+                    }
+                    if !extra_servers.is_empty() {
+                        let labels: Vec<&str> = extra_servers.iter().map(|server| server.label.as_str()).collect();
+                        client
+                            .matchmaking()
+                            .set_lobby_data(id, LOBBY_KEY_MC_SERVERS, &json!(labels).to_string());
+                        for server in &extra_servers {
+                            info!("│ 其他 MC 服务器: {} (端口 {})", server.label, server.port);
+                        }
+                    }
                     info!("│ 好友可通过此 ID 加入游戏");
                     info!("└─────────────────────────────────────");
                     
                     // Send lobby ID back to commands layer
-                    let _ = lobby_id_tx.send(id.raw());
+                    let _ = lobby_id_tx.send(Ok(id.raw()));
                     
                     break id;
                 }
                 Err(e) => {
                     error!("✗ 房间创建失败: {:?}", e);
+                    let _ = lobby_id_tx.send(Err(Message::LobbyCreateFailed.format(&[&format!("{:?}", e)])));
                     return Err(format!("✗ 房间创建失败: {:?}", e).into());
                 }
             }
@@ -65,18 +589,72 @@ pub fn run_host(client: Client, port: u16, password: Option<String>, lobby_id_tx
         thread::sleep(Duration::from_millis(10));
     };
 
-    // Peer management: SteamId -> NetConnection
-    let listen_socket = client
-        .networking_sockets()
-        .create_listen_socket_p2p(0, vec![])
-        .map_err(|err| format!("无法创建 Steam NetworkingSockets 监听端口: {err:?}"))?;
-    info!("📡 NetworkingSockets 监听已启动 (虚拟端口 0)");
+    // Advertise who is actually running the bridge, independent of lobby ownership
+    let host_steam_id = client.user().steam_id();
+    client
+        .matchmaking()
+        .set_lobby_data(lobby_id, LOBBY_KEY_HOST, &host_steam_id.raw().to_string());
+
+    // Rich presence connect string enables "Join Game" from the Steam overlay
+    presence::set_connect(&client, lobby_id);
+    presence::set_hosting(&client, 0);
+    let mut presence_players = 0;
+    session::set_state(SessionState::Hosting {
+        lobby_id: lobby_id.raw().to_string(),
+        peers: 0,
+    });
 
+    // Watch for ownership changes: Steam may reassign the lobby during transient disconnects
+    let owner_dirty = Arc::new(AtomicBool::new(false));
+    let owner_dirty_chat = Arc::clone(&owner_dirty);
+    let _chat_update_handle = client.register_callback(move |update: LobbyChatUpdate| {
+        if update.lobby == lobby_id {
+            owner_dirty_chat.store(true, Ordering::Relaxed);
+        }
+    });
+    let owner_dirty_data = Arc::clone(&owner_dirty);
+    let _data_update_handle = client.register_callback(move |update: LobbyDataUpdate| {
+        if update.lobby == lobby_id {
+            owner_dirty_data.store(true, Ordering::Relaxed);
+        }
+    });
+    let mut owner_lost = false;
+
+    // Peer management: SteamId -> NetConnection
     let mut peers: HashMap<SteamId, PeerState> = HashMap::new();
+    let mut shutdown_grace = Duration::from_millis(SHUTDOWN_GRACE_PERIOD_MS);
+    // Set while forwarding is paused; Steam connections keep being serviced
+    let mut paused: Option<PausePolicy> = None;
+    // The automatic lock fires at most once, so a manual unlock afterwards sticks
+    let mut auto_lock_at = lock_at_players.filter(|&players| players > 0);
 
-    // Channel to receive data from MC server threads: (steam_id, data)
-    let (from_mc_tx, from_mc_rx): (Sender<(SteamId, Vec<u8>)>, Receiver<(SteamId, Vec<u8>)>) =
-        mpsc::channel();
+    // Control channel for commands issued while the host is running
+    let (control_tx, control_rx) = mpsc::channel();
+    *HOST_CONTROL.lock().unwrap() = Some(control_tx);
+    // Panics left over from an earlier session's threads say nothing about this one
+    panic_guard::take();
+    // Set when the session has to end because one of its threads died
+    let mut failure: Option<String> = None;
+
+    // Channel to receive frames from MC server threads
+    // Bounded so a slow Steam link stalls the MC reads instead of buffering without limit
+    let (from_mc_tx, from_mc_rx): (SendQueue<McEvent>, Receiver<McEvent>) =
+        send_queue::bounded(MC_READ_QUEUE_CAPACITY);
+    // MC connections are multiplexed over a fixed set of bridge threads
+    // RCON traffic is light, one worker thread is enough
+    let rcon_bridges = rcon_port
+        .map(|rcon_port| BridgePool::new(1, rcon_port, from_mc_tx.clone()).with_channel(Channel::Rcon));
+    let mut bridges = BridgePool::new(MC_BRIDGE_THREADS, port, from_mc_tx)
+        .with_extra_ports(extra_servers.iter().map(|server| server.port).collect())
+        .with_dry_run(dry_run);
+    MC_TARGET_PORT.store(port, Ordering::Relaxed);
+    // The lobby is already announced; players may join before the MC server is up,
+    // so the host is told when it becomes reachable instead of finding out from failed bridges
+    let start_readiness = |port: u16| match dry_run {
+        Some(_) => ReadinessWatch::skip(port, readiness_tx.clone()),
+        None => ReadinessWatch::start(port, readiness_tx.clone()),
+    };
+    let mut readiness = start_readiness(port);
 
     info!("");
     info!("┌─────────────────────────────────────────────────────────┐");
@@ -89,15 +667,218 @@ pub fn run_host(client: Client, port: u16, password: Option<String>, lobby_id_tx
     info!("│  确保你的 Minecraft 服务器正在运行!                     │");
     info!("└─────────────────────────────────────────────────────────┘");
     info!("");
+    match dry_run {
+        Some(DryRunMode::Echo) => warn!("🧪 试运行模式: 不连接 MC 服务器，收到的数据原样发回客户端"),
+        Some(DryRunMode::Discard) => warn!("🧪 试运行模式: 不连接 MC 服务器，收到的数据直接丢弃"),
+        None => {}
+    }
 
     // Performance metrics
-    let session_metrics = metrics::SessionMetrics::new();
+    let mut session_metrics = metrics::SessionMetrics::new();
     let mut last_report_time = Instant::now();
 
+    // When the lobby last became empty (or was created); None while peers are connected
+    let mut empty_since = Some(Instant::now());
+    let mut steam_watch = OfflineWatch::new("host");
+
     info!("🔄 开始主循环，监听 NetworkingSockets 事件...");
+    // Held until the session ends so the loop's short sleeps are honored
+    let _timer_resolution = TimerResolution::raise();
 
     while RUNNING.load(Ordering::Relaxed) {
-        client.run_callbacks();
+        // Check lobby ownership after membership/data changes
+        if owner_dirty.swap(false, Ordering::Relaxed) {
+            let owner = client.matchmaking().lobby_owner(lobby_id);
+            if owner != host_steam_id && !owner_lost {
+                warn!("┌─────────────────────────────────────");
+                warn!("│ ⚠️ 房间所有权已转移");
+                warn!("│ 新所有者: {:?}", owner);
+                warn!("│ 桥接仍由本机运行，新玩家将通过 {} 元数据连接本机", LOBBY_KEY_HOST);
+                warn!("└─────────────────────────────────────");
+                owner_lost = true;
+            } else if owner == host_steam_id && owner_lost {
+                info!("✓ 已重新获得房间所有权");
+                owner_lost = false;
+            }
+        }
+
+        // Handle control commands from the frontend
+        while let Ok(command) = control_rx.try_recv() {
+            match command {
+                HostCommand::RotatePassword {
+                    password,
+                    kick_peers,
+                } => {
+                    match password.as_deref().filter(|pwd| !pwd.is_empty()) {
+                        Some(pwd) => {
+                            client.matchmaking().set_lobby_data(lobby_id, "password", pwd);
+                            diagnostics::remember_secret(pwd);
+                            info!("🔑 房间密码已更新");
+                        }
+                        None => {
+                            client.matchmaking().delete_lobby_data(lobby_id, "password");
+                            info!("🔓 房间密码已移除");
+                        }
+                    }
+
+                    if kick_peers && !peers.is_empty() {
+                        info!("👢 断开 {} 名玩家，要求其使用新密码重新加入", peers.len());
+                        let kicked: Vec<SteamId> = peers.keys().copied().collect();
+                        for steam_id in kicked {
+                            remove_peer(&mut peers, steam_id, RemovalReason::PasswordChanged, peer_removed_tx.as_ref());
+                        }
+                    }
+                }
+                HostCommand::EnforceAccessLists => {
+                    let denied: Vec<SteamId> = peers
+                        .keys()
+                        .filter(|steam_id| !access_list::permits(steam_id.raw()))
+                        .copied()
+                        .collect();
+                    for steam_id in denied {
+                        remove_peer(&mut peers, steam_id, RemovalReason::NotAllowed, peer_removed_tx.as_ref());
+                    }
+                    // Players taken off the allowlist keep playing but lose RCON
+                    for (steam_id, peer) in peers.iter_mut() {
+                        if !peer.rcon_streams.is_empty() && !rcon_permitted(*steam_id) {
+                            close_rcon_streams(*steam_id, peer);
+                        }
+                    }
+                }
+                HostCommand::SetMcTargetPort { port, reconnect } => {
+                    info!(
+                        "🎯 MC 服务器端口: {} -> {}{}",
+                        bridges.port(),
+                        port,
+                        if reconnect { "，现有连接将重连" } else { "" }
+                    );
+                    bridges.set_port(port, reconnect);
+                    MC_TARGET_PORT.store(port, Ordering::Relaxed);
+                    // Stop the old check before the new one publishes its result
+                    drop(readiness);
+                    readiness = start_readiness(port);
+                    if client.matchmaking().lobby_data(lobby_id, lan_direct::LOBBY_KEY_LAN_PORT).is_some() {
+                        client.matchmaking().set_lobby_data(
+                            lobby_id,
+                            lan_direct::LOBBY_KEY_LAN_PORT,
+                            &port.to_string(),
+                        );
+                    }
+                }
+                HostCommand::ResetMcBridge { steam_id, reply } => {
+                    let result = match peers.get_mut(&steam_id) {
+                        Some(peer) => {
+                            reset_mc_bridge(steam_id, peer, &bridges);
+                            Ok(())
+                        }
+                        None => Err(Message::PeerNotConnected.format(&[&steam_id.raw()])),
+                    };
+                    let _ = reply.send(result);
+                }
+                HostCommand::PauseForwarding { policy } => {
+                    info!("⏸ 暂停转发 ({:?})，Steam 连接保持不变", policy);
+                    if policy == PausePolicy::Discard {
+                        // Switching from Buffer: held data is discarded too, closing its streams
+                        let mut broken = Vec::new();
+                        for (steam_id, peer) in peers.iter_mut() {
+                            let stream_ids: HashSet<u32> = peer
+                                .held
+                                .iter()
+                                .filter(|frame| frame.kind == FrameKind::Data)
+                                .map(|frame| frame.stream_id)
+                                .collect();
+                            for stream_id in stream_ids {
+                                metrics::record_packet_dropped();
+                                if let Err(reason) = break_stream(*steam_id, peer, stream_id) {
+                                    broken.push((*steam_id, reason));
+                                    break;
+                                }
+                            }
+                        }
+                        for (steam_id, reason) in broken {
+                            remove_peer(&mut peers, steam_id, reason, peer_removed_tx.as_ref());
+                        }
+                    }
+                    paused = Some(policy);
+                    structured_log::emit("forwarding_paused", &[("policy", json!(policy))]);
+                }
+                HostCommand::ResumeForwarding => {
+                    if paused.take().is_some() {
+                        let held: usize = peers.values().map(|peer| peer.held.len()).sum();
+                        info!("▶ 恢复转发，送达暂停期间缓冲的 {} 条消息", held);
+                        structured_log::emit("forwarding_resumed", &[("held", json!(held))]);
+                    }
+                }
+                HostCommand::SetLobbyLock { lock, reply } => {
+                    let result = set_lobby_lock(&client, lobby_id, lock, false, lobby_lock_tx.as_ref());
+                    let _ = reply.send(result);
+                }
+                HostCommand::Shutdown { grace_period } => {
+                    shutdown_grace = grace_period;
+                    RUNNING.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+
+        // A dead bridge worker takes an unknown set of connections with it, so end the
+        // session cleanly rather than keep a lobby whose players silently stop getting data
+        if panic_guard::pending() {
+            let panics = panic_guard::take();
+            for report in &panics {
+                structured_log::emit(
+                    "thread_panicked",
+                    &[("thread", json!(report.thread)), ("message", json!(report.message))],
+                );
+            }
+            if let Some(first) = panics.first() {
+                error!("💥 {} 已崩溃，关闭房间", first.thread);
+                failure = Some(Message::ThreadPanicked.format(&[&first.thread, &first.message]));
+            }
+            if let Some(tx) = &thread_panic_tx {
+                for report in panics {
+                    let _ = tx.send(report);
+                }
+            }
+            if failure.is_some() {
+                break;
+            }
+        }
+
+        // Scheduled stop; checked before the offline pause so it fires on time regardless.
+        // Like the idle timeout it leaves the loop, so whichever comes first ends the session
+        if auto_stop.as_mut().is_some_and(AutoStop::expired) {
+            info!("⏰ 已达到最长运行时间，自动关闭房间");
+            structured_log::emit("host_auto_stop", &[]);
+            break;
+        }
+
+        // Pause while Steam is offline: failures now say nothing about the peers themselves
+        match steam_watch.poll() {
+            Some(OnlineChange::WentOffline) => {
+                session::set_state(SessionState::SteamOffline {
+                    lobby_id: lobby_id.raw().to_string(),
+                    hosting: true,
+                });
+            }
+            Some(OnlineChange::CameBack) => {
+                for peer in peers.values_mut() {
+                    peer.send_failures = 0;
+                }
+                // Time spent offline does not count towards the idle timeout
+                if empty_since.is_some() {
+                    empty_since = Some(Instant::now());
+                }
+                session::set_state(SessionState::Hosting {
+                    lobby_id: lobby_id.raw().to_string(),
+                    peers: peers.len(),
+                });
+            }
+            None => {}
+        }
+        if steam_watch.paused() {
+            thread::sleep(Duration::from_millis(100));
+            continue;
+        }
 
         // Handle listen socket events first so connections are ready before data flows
         while let Some(event) = listen_socket.try_receive_event() {
@@ -110,6 +891,27 @@ pub fn run_host(client: Client, port: u16, password: Option<String>, lobby_id_tx
                         remote.debug_string(),
                         remote.steam_id()
                     );
+                    // 名单按 Steam ID 判断，无法确认身份的连接同样拒绝
+                    if !remote.steam_id().is_some_and(|id| access_list::permits(id.raw())) {
+                        warn!("🚫 拒绝连接 {}: 不在允许列表中", remote.debug_string());
+                        request.reject(
+                            NetConnectionEnd::Other(END_REASON_NOT_ALLOWED),
+                            Some("不在允许列表中"),
+                        );
+                        continue;
+                    }
+                    // 只统计已建立的连接；同一玩家重连会替换旧连接，不占新名额
+                    let reconnecting = remote.steam_id().is_some_and(|id| peers.contains_key(&id));
+                    if !reconnecting && peers.len() >= MAX_CONCURRENT_PEERS {
+                        warn!(
+                            "🚫 拒绝连接 {}: 已达到连接上限 ({}/{})",
+                            remote.debug_string(),
+                            peers.len(),
+                            MAX_CONCURRENT_PEERS
+                        );
+                        request.reject(NetConnectionEnd::AppGeneric, Some("房间已满"));
+                        continue;
+                    }
                     match request.accept() {
                         Ok(_) => {
                             info!("✓ 连接请求已接受，等待 Connected 事件...");
@@ -125,32 +927,56 @@ pub fn run_host(client: Client, port: u16, password: Option<String>, lobby_id_tx
                     if let Some(steam_id) = remote.steam_id() {
                         let connection = connected.take_connection();
 
-                        // Create channel for sending data to MC server
-                        let (to_mc_tx, to_mc_rx): (Sender<Vec<u8>>, Receiver<Vec<u8>>) =
-                            mpsc::channel();
-
-                        // Spawn thread to bridge this peer to MC server
-                        let from_mc_tx_clone = from_mc_tx.clone();
-                        let steam_id_clone = steam_id;
-                        thread::spawn(move || {
-                            if let Err(e) = bridge_to_mc_server(
-                                steam_id_clone,
-                                port,
-                                to_mc_rx,
-                                from_mc_tx_clone,
-                            ) {
-                                warn!("⚠️ MC 服务器连接断开 ({:?}): {}", steam_id_clone, e);
-                            }
-                        });
-
-                        peers.insert(
-                            steam_id,
-                            PeerState {
-                                connection,
-                                to_mc_tx,
-                            },
-                        );
+                        // 名单可能在请求被接受后修改，建立时再检查一次
+                        if !access_list::permits(steam_id.raw()) {
+                            warn!("🚫 关闭连接 {:?}: 不在允许列表中", steam_id);
+                            connection.close(
+                                NetConnectionEnd::Other(END_REASON_NOT_ALLOWED),
+                                Some("不在允许列表中"),
+                                false,
+                            );
+                            continue;
+                        }
+
+                        if !encryption::verify(&client, &connection, steam_id.raw()) {
+                            connection.close(
+                                NetConnectionEnd::Other(END_REASON_UNENCRYPTED),
+                                Some("连接未加密"),
+                                false,
+                            );
+                            continue;
+                        }
+
+                        // 多个请求可能在名额用尽前同时被接受，建立时再检查一次
+                        if !peers.contains_key(&steam_id) && peers.len() >= MAX_CONCURRENT_PEERS {
+                            warn!(
+                                "🚫 关闭连接 {:?}: 已达到连接上限 ({}/{})",
+                                steam_id,
+                                peers.len(),
+                                MAX_CONCURRENT_PEERS
+                            );
+                            connection.close(NetConnectionEnd::AppGeneric, Some("房间已满"), false);
+                            continue;
+                        }
 
+                        // With framing, bridges open lazily per client stream.
+                        // Without it the peer has a single implicit stream 0.
+                        // The client switches to app-level reliability only if the lobby offers it
+                        let reliability = if APP_RELIABILITY && ENABLE_FRAMING {
+                            app_reliability::Mode::Accept
+                        } else {
+                            app_reliability::Mode::Off
+                        };
+                        let mut peer = PeerState::new(ReliableLink::new(connection, reliability));
+                        if !ENABLE_FRAMING {
+                            peer.add_stream(0, bridges.open(steam_id, 0));
+                        }
+                        peers.insert(steam_id, peer);
+
+                        structured_log::emit(
+                            "peer_added",
+                            &[("steam_id", json!(steam_id.raw())), ("peers", json!(peers.len()))],
+                        );
                         info!("┌─────────────────────────────────────");
                         info!("│ [新玩家] Steam ID: {:?}", steam_id);
                         info!("│ 已建立连接并桥接到 MC 服务器");
@@ -164,129 +990,1126 @@ pub fn run_host(client: Client, port: u16, password: Option<String>, lobby_id_tx
                 }
                 ListenSocketEvent::Disconnected(disconnected) => {
                     if let Some(steam_id) = disconnected.remote().steam_id() {
-                        peers.remove(&steam_id);
-                        info!("👋 玩家离开: {:?}", steam_id);
+                        remove_peer(&mut peers, steam_id, RemovalReason::Disconnected, peer_removed_tx.as_ref());
                     }
                 }
             }
         }
 
         // Process data from MC server -> Send to peers via Steam
-        while let Ok((steam_id, data)) = from_mc_rx.try_recv() {
-            if let Some(peer) = peers.get(&steam_id) {
-                if let Err(err) = peer
-                    .connection
-                    .send_message(&data, SendFlags::RELIABLE_NO_NAGLE)
-                {
-                    error!("✗ 发送数据到客户端失败: {err:?}");
-                    metrics::record_packet_dropped();
-                } else {
-                    metrics::record_packet_sent(data.len() as u64);
+        // While buffering the frames stay queued, so a full queue stalls the MC reads.
+        // RCON replies share the queue and wait for the resume too, but are never discarded
+        if paused != Some(PausePolicy::Buffer) {
+            let mut broken = Vec::new();
+            while let Ok((steam_id, frame)) = from_mc_rx.try_recv() {
+                if let Some(peer) = peers.get_mut(&steam_id) {
+                    if let Err(reason) = forward_mc_frame(steam_id, peer, frame, paused) {
+                        if !broken.iter().any(|(id, _)| *id == steam_id) {
+                            broken.push((steam_id, reason));
+                        }
+                    }
                 }
             }
+            for (steam_id, reason) in broken {
+                remove_peer(&mut peers, steam_id, reason, peer_removed_tx.as_ref());
+            }
         }
 
         // Process Steam packets from peers -> Forward to MC server
         // Also update latency information
         let sockets = client.networking_sockets();
-        let peers_to_remove: Vec<SteamId> = peers
+        let peers_to_remove: Vec<(SteamId, RemovalReason)> = peers
             .iter_mut()
             .filter_map(|(steam_id, peer)| {
                 // 更新延迟信息
                 if let Ok((status, _)) = sockets.get_realtime_connection_status(&peer.connection, 0) {
                     metrics::update_latency(steam_id.raw(), status.ping() as u32);
                 }
-                
-                match peer.connection.receive_messages(64) {
-                    Ok(messages) => {
-                        for message in messages {
-                            let data = message.data();
-                            if data.is_empty() {
-                                continue;
-                            }
-                            metrics::record_packet_received(data.len() as u64);
-                            if peer.to_mc_tx.send(data.to_vec()).is_err() {
-                                // MC connection closed
-                                return Some(*steam_id);
-                            }
-                        }
-                    }
-                    Err(_) => {
-                        return Some(*steam_id);
-                    }
+
+                retry_sends(*steam_id, peer);
+                if peer.send_failures >= MAX_CONSECUTIVE_SEND_FAILURES {
+                    warn!("🚫 连续 {} 条消息发送失败: {:?}", peer.send_failures, steam_id);
+                    Some((*steam_id, RemovalReason::SendFailures))
+                } else {
+                    receive_from_peer(*steam_id, peer, &bridges, rcon_bridges.as_ref(), paused)
+                        .err()
+                        .map(|reason| (*steam_id, reason))
                 }
-                None
             })
             .collect();
 
-        for steam_id in peers_to_remove {
-            peers.remove(&steam_id);
-            metrics::clear_latency(steam_id.raw());
-            info!("🔌 移除断开的玩家: {:?}", steam_id);
+        for (steam_id, reason) in peers_to_remove {
+            remove_peer(&mut peers, steam_id, reason, peer_removed_tx.as_ref());
+        }
+
+        // Keep the friends-list status and session state in sync with the player count
+        if peers.len() != presence_players {
+            presence_players = peers.len();
+            session_metrics.record_players(peers.keys().map(|steam_id| steam_id.raw()));
+            empty_since = if peers.is_empty() { Some(Instant::now()) } else { None };
+            presence::set_hosting(&client, presence_players);
+            session::set_state(SessionState::Hosting {
+                lobby_id: lobby_id.raw().to_string(),
+                peers: presence_players,
+            });
+
+            // Keep the current group but stop new strangers from finding the lobby
+            if auto_lock_at.is_some_and(|players| presence_players >= players) {
+                auto_lock_at = None;
+                info!("🔒 已有 {} 名玩家，自动锁定房间", presence_players);
+                let _ = set_lobby_lock(&client, lobby_id, Some(LOBBY_LOCK), true, lobby_lock_tx.as_ref());
+            }
+        }
+
+        // Close a lobby nobody has used for a while instead of leaving it listed forever
+        if let (Some(idle_secs), Some(since)) = (HOST_IDLE_TIMEOUT_SECS, empty_since) {
+            if since.elapsed() >= Duration::from_secs(idle_secs) {
+                info!("💤 房间已空闲 {} 秒，自动关闭", idle_secs);
+                structured_log::emit("host_idle_timeout", &[("idle_secs", json!(idle_secs))]);
+                break;
+            }
         }
 
         // Periodic reporting
         if last_report_time.elapsed() > Duration::from_secs(5) {
             session_metrics.print_report();
+            session_metrics.print_peer_report();
+            session_metrics.sample_throughput();
+            let (stats, duration) = session_metrics.get_session_stats();
+            structured_log::emit_metrics("host", &stats, duration);
+            metrics_log::record("host", &stats, duration.as_millis() as u64);
             last_report_time = Instant::now();
         }
 
-        thread::sleep(Duration::from_micros(100)); // 100μs for higher throughput
+        loop_timing::pause();
     }
 
+    *HOST_CONTROL.lock().unwrap() = None;
+    // No new LAN clients once the lobby is gone
+    drop(lan_responder);
+    shutdown(
+        &client,
+        lobby_id,
+        peers,
+        &bridges,
+        rcon_bridges.as_ref(),
+        &from_mc_rx,
+        shutdown_grace,
+    );
+    match failure {
+        Some(message) => Err(message.into()),
+        None => Ok(()),
+    }
+}
+
+/// Create the P2P listen socket once the relay network is warmed up, retrying transient failures
+fn create_listen_socket(client: &Client, virtual_port: i32) -> Result<ListenSocket, String> {
+    info!("🌐 正在初始化 Steam 中继网络...");
+    let relay_status = match steam::wait_for_relay(client, Duration::from_millis(RELAY_WARMUP_TIMEOUT_MS)) {
+        Ok(()) => {
+            info!("✓ Steam 中继网络已就绪");
+            "Current".to_string()
+        }
+        Err(status) => {
+            // Direct connections may still work without relays, so try anyway
+            warn!("⚠️ Steam 中继网络未就绪 ({})，仍尝试创建监听端口", status);
+            status
+        }
+    };
+
+    let route = route_preference::get();
+    info!("🧭 路径偏好: {:?}", route);
+    let mut last_error = String::new();
+    for attempt in 1..=LISTEN_SOCKET_ATTEMPTS {
+        if attempt > 1 {
+            thread::sleep(Duration::from_millis(LISTEN_SOCKET_RETRY_DELAY_MS));
+        }
+        match client
+            .networking_sockets()
+            .create_listen_socket_p2p(virtual_port, route.config_entries())
+        {
+            Ok(socket) => return Ok(socket),
+            Err(err) => {
+                last_error = format!("{err:?}");
+                warn!(
+                    "⚠️ 创建监听端口失败 (第 {}/{} 次): {}",
+                    attempt, LISTEN_SOCKET_ATTEMPTS, last_error
+                );
+            }
+        }
+    }
+    Err(Message::ListenSocketFailed.format(&[&last_error, &relay_status]))
+}
+
+/// Route a frame from a bridge thread: forward data, or tear down a closed stream
+fn handle_mc_frame<T: Transport>(steam_id: SteamId, peer: &mut PeerState<T>, frame: Frame) {
+    match frame.kind {
+        // The client was already told a broken stream is closed
+        FrameKind::Data if frame.channel == Channel::Minecraft && peer.broken_streams.contains(&frame.stream_id) => {
+            metrics::record_packet_dropped();
+        }
+        FrameKind::Data => send_to_peer(steam_id, peer, &frame),
+        // Only clients open streams
+        FrameKind::Open => {}
+        FrameKind::Close if frame.channel == Channel::Rcon => {
+            if peer.rcon_streams.remove(&frame.stream_id).is_some() {
+                info!("RCON 连接已关闭 ({:?}, 流 {})", steam_id, frame.stream_id);
+                send_to_peer(steam_id, peer, &frame);
+            }
+        }
+        FrameKind::Close => {
+            // Only announce closes for streams we still track; a close for a stream
+            // the client already closed is just the bridge winding down.
+            if peer.streams.remove(&frame.stream_id).is_some() {
+                info!("MC 连接已关闭 ({:?}, 流 {})", steam_id, frame.stream_id);
+                send_to_peer(steam_id, peer, &frame);
+            }
+            peer.status_streams.remove(&frame.stream_id);
+        }
+    }
+}
+
+/// Forward a frame from an MC bridge to its peer
+///
+/// While discarding, game data is dropped and its stream closed on both ends, since
+/// the client's MC connection must not carry on with a hole in it. RCON is never paused.
+/// Returns the reason when the peer should be removed
+fn forward_mc_frame<T: Transport>(
+    steam_id: SteamId,
+    peer: &mut PeerState<T>,
+    frame: Frame,
+    paused: Option<PausePolicy>,
+) -> Result<(), RemovalReason> {
+    if paused == Some(PausePolicy::Discard) && frame.channel == Channel::Minecraft && frame.kind == FrameKind::Data {
+        metrics::record_packet_dropped();
+        return break_stream(steam_id, peer, frame.stream_id);
+    }
+    handle_mc_frame(steam_id, peer, frame);
     Ok(())
 }
 
-/// Bridge thread: connects to local MC server, forwards data bidirectionally
-fn bridge_to_mc_server(
+/// Remove a peer: close its connection if it is still open, tear down its MC bridges,
+/// clear its metrics and report why, in the log, the structured log and the frontend
+fn remove_peer(
+    peers: &mut HashMap<SteamId, PeerState>,
     steam_id: SteamId,
-    port: u16,
-    to_mc_rx: Receiver<Vec<u8>>,
-    from_mc_tx: Sender<(SteamId, Vec<u8>)>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let addr = format!("127.0.0.1:{}", port);
-    info!("🔗 为 {:?} 连接 MC 服务器 {}...", steam_id, addr);
-
-    let mut stream = TcpStream::connect(&addr)?;
-    stream.set_nodelay(true)?;
-    info!("✅ {:?} 已连接到 MC 服务器", steam_id);
-
-    // Create a thread to read from the MC server and send to the main thread
-    let mut stream_clone = stream.try_clone()?;
-    let upstream_thread = thread::spawn(move || {
-        let mut read_buf = [0u8; BUFFER_SIZE];
-        loop {
-            match stream_clone.read(&mut read_buf) {
-                Ok(0) => {
-                    info!("MC 服务器关闭连接 ({:?})", steam_id);
-                    break; // Connection closed
+    reason: RemovalReason,
+    events: Option<&Sender<PeerRemoved>>,
+) {
+    let Some(peer) = peers.remove(&steam_id) else {
+        return;
+    };
+    let streams = peer.streams.len();
+    // Dropping the stream senders makes the bridges close their MC server sockets
+    drop(peer.streams);
+    if let Some((end, message)) = reason.close_reason() {
+        // Close explicitly so the client learns why and can reconnect cleanly
+        peer.connection.into_inner().close(end, Some(message), false);
+    }
+    metrics::clear_peer(steam_id.raw());
+
+    info!("👋 移除玩家 {:?}: {} (MC 连接 {} 条)", steam_id, reason.describe(), streams);
+    structured_log::emit(
+        "peer_removed",
+        &[
+            ("steam_id", json!(steam_id.raw())),
+            ("reason", json!(reason)),
+            ("streams", json!(streams)),
+            ("peers", json!(peers.len())),
+        ],
+    );
+    if let Some(tx) = events {
+        let _ = tx.send(PeerRemoved {
+            steam_id: steam_id.raw().to_string(),
+            reason,
+        });
+    }
+}
+
+/// Close all of a peer's MC server connections while keeping its Steam connection open
+///
+/// With framing the client is told each stream closed, so its MC client drops and can
+/// rejoin over the same link. Without framing the single implicit stream is reopened.
+fn reset_mc_bridge<T: Transport>(steam_id: SteamId, peer: &mut PeerState<T>, bridges: &BridgePool) {
+    // Dropping the senders makes the bridges close their MC server sockets
+    let streams: Vec<u32> = peer.streams.drain().map(|(stream_id, _)| stream_id).collect();
+    peer.handshake_pending.clear();
+    info!("🔁 重置 {:?} 的 MC 连接 ({} 条)", steam_id, streams.len());
+
+    if ENABLE_FRAMING {
+        for stream_id in streams {
+            send_to_peer(steam_id, peer, &Frame::close(stream_id));
+        }
+        peer.status_streams.clear();
+    } else {
+        peer.add_stream(0, bridges.open(steam_id, 0));
+    }
+    structured_log::emit("mc_bridge_reset", &[("steam_id", json!(steam_id.raw()))]);
+}
+
+/// Encode (if framing is enabled) and send a frame to a peer
+fn send_to_peer<T: Transport>(steam_id: SteamId, peer: &mut PeerState<T>, frame: &Frame) {
+    // Status streams are tracked by MC stream id only
+    let status_stream = frame.channel == Channel::Minecraft && peer.status_streams.contains(&frame.stream_id);
+
+    // Oversized payloads go out as ordered chunks instead of being dropped
+    if frame.payload.len() > framing::MAX_CHUNK_PAYLOAD {
+        if status_stream {
+            peer.status_streams.remove(&frame.stream_id);
+        }
+        for chunk in framing::split(frame, framing::MAX_CHUNK_PAYLOAD) {
+            send_to_peer(steam_id, peer, &chunk);
+        }
+        return;
+    }
+    capture::record(steam_id.raw(), Direction::Sent, frame);
+
+    let message = if ENABLE_FRAMING {
+        match framing::encode(frame) {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                error!("✗ 编码帧失败: {}", e);
+                metrics::record_packet_dropped();
+                return;
+            }
+        }
+    } else if frame.kind == FrameKind::Data {
+        frame.payload.clone()
+    } else {
+        // Raw mode has no way to signal a stream close
+        return;
+    };
+
+    // Server list ping replies are transient: losing one only fails that ping.
+    // Anything too big for one packet demotes the stream to reliable for good.
+    let mut unreliable = status_stream;
+    if unreliable && message.len() > MAX_UNRELIABLE_MESSAGE_SIZE {
+        peer.status_streams.remove(&frame.stream_id);
+        unreliable = false;
+    }
+    let payload = frame.payload_len();
+    if unreliable {
+        match peer.connection.send_unreliable(&message) {
+            Ok(()) => record_sent(steam_id, peer, payload, message.len()),
+            Err(err) => record_send_failure(peer, &err),
+        }
+        return;
+    }
+
+    let pending = PendingSend {
+        message,
+        stream_id: frame.stream_id,
+        channel: frame.channel,
+        kind: frame.kind,
+        payload,
+    };
+    if !peer.retry.is_empty() {
+        // Earlier messages are still waiting; sending now would reorder the stream
+        queue_retry(steam_id, peer, pending);
+        return;
+    }
+    match peer.connection.send(&pending.message) {
+        Ok(()) => record_sent(steam_id, peer, pending.payload, pending.message.len()),
+        // A closed connection is not retried
+        Err(TransportError::Closed) => drop_send(steam_id, peer, &pending, &TransportError::Closed),
+        Err(err) => {
+            static RETRY_LOG: LogThrottle = LogThrottle::new();
+            if let Some(suppressed) = RETRY_LOG.allow() {
+                warn!("⚠️ 发送失败，稍后重试: {}{}", err, log_throttle::suppressed_note(suppressed));
+            }
+            peer.retry_attempts = 0;
+            peer.retry_at = Instant::now() + retry_backoff(0);
+            queue_retry(steam_id, peer, pending);
+        }
+    }
+}
+
+/// Queue a message behind earlier failed sends; a full queue drops it for good
+fn queue_retry<T: Transport>(steam_id: SteamId, peer: &mut PeerState<T>, pending: PendingSend) {
+    if peer.retry.len() >= SEND_RETRY_QUEUE_CAPACITY {
+        drop_send(steam_id, peer, &pending, &TransportError::Failed(String::from("重试队列已满")));
+    } else {
+        peer.retry.push_back(pending);
+    }
+}
+
+/// Wait before retry number `attempt + 1`, doubling each time
+fn retry_backoff(attempt: u32) -> Duration {
+    Duration::from_millis(SEND_RETRY_BACKOFF_MS) * (1 << attempt.min(16))
+}
+
+/// Send the messages waiting for a retry, in order, once their backoff has passed
+///
+/// A message still failing after SEND_RETRY_ATTEMPTS retries is dropped and its
+/// stream closed; the peer is removed once too many sends fail in a row.
+fn retry_sends<T: Transport>(steam_id: SteamId, peer: &mut PeerState<T>) {
+    if peer.retry.is_empty() || Instant::now() < peer.retry_at {
+        return;
+    }
+    while let Some(pending) = peer.retry.front() {
+        match peer.connection.send(&pending.message) {
+            Ok(()) => {
+                let (payload, len) = (pending.payload, pending.message.len());
+                peer.retry.pop_front();
+                peer.retry_attempts = 0;
+                record_sent(steam_id, peer, payload, len);
+            }
+            Err(err) if err == TransportError::Closed || peer.retry_attempts >= SEND_RETRY_ATTEMPTS => {
+                let Some(pending) = peer.retry.pop_front() else { break };
+                peer.retry_attempts = 0;
+                drop_send(steam_id, peer, &pending, &err);
+            }
+            Err(err) => {
+                peer.retry_attempts += 1;
+                static RETRY_LOG: LogThrottle = LogThrottle::new();
+                if let Some(suppressed) = RETRY_LOG.allow() {
+                    warn!(
+                        "⚠️ 发送失败，第 {} 次重试: {}{}",
+                        peer.retry_attempts,
+                        err,
+                        log_throttle::suppressed_note(suppressed)
+                    );
                 }
-                Ok(n) => {
-                    if from_mc_tx.send((steam_id, read_buf[..n].to_vec())).is_err() {
-                        break; // Main thread disconnected
-                    }
+                peer.retry_at = Instant::now() + retry_backoff(peer.retry_attempts);
+                return;
+            }
+        }
+    }
+}
+
+/// Give up on a reliable message: count the failed send and close the stream it
+/// belonged to, which cannot carry on with a hole in it
+fn drop_send<T: Transport>(steam_id: SteamId, peer: &mut PeerState<T>, pending: &PendingSend, err: &TransportError) {
+    record_send_failure(peer, err);
+    // A lost open or close leaves no gap in any stream's bytes
+    if pending.kind != FrameKind::Data {
+        return;
+    }
+    match pending.channel {
+        Channel::Minecraft => {
+            if break_stream(steam_id, peer, pending.stream_id).is_err() {
+                // The single raw stream is corrupt, so the peer has nothing left worth keeping
+                peer.send_failures = peer.send_failures.max(MAX_CONSECUTIVE_SEND_FAILURES);
+            }
+        }
+        Channel::Rcon => {
+            // Dropping the sender closes the RCON bridge
+            if peer.rcon_streams.remove(&pending.stream_id).is_some() {
+                info!("丢弃了 {:?} 的 RCON 流 {} 的数据，关闭该连接", steam_id, pending.stream_id);
+                send_to_peer(steam_id, peer, &Frame::close(pending.stream_id).on(Channel::Rcon));
+            }
+        }
+    }
+}
+
+fn record_sent<T>(steam_id: SteamId, peer: &mut PeerState<T>, payload: usize, len: usize) {
+    peer.send_failures = 0;
+    metrics::record_packet_sent(payload as u64, (len - payload) as u64);
+    metrics::record_peer_sent(steam_id.raw(), len as u64);
+}
+
+fn record_send_failure<T>(peer: &mut PeerState<T>, err: &TransportError) {
+    peer.send_failures += 1;
+    static SEND_FAILED_LOG: LogThrottle = LogThrottle::new();
+    if let Some(suppressed) = SEND_FAILED_LOG.allow() {
+        error!(
+            "✗ 发送数据到客户端失败 ({}/{}): {err}{}",
+            peer.send_failures,
+            MAX_CONSECUTIVE_SEND_FAILURES,
+            log_throttle::suppressed_note(suppressed)
+        );
+    }
+    metrics::record_packet_dropped();
+}
+
+/// Receive pending Steam messages from a peer and forward them to its MC bridges
+///
+/// While `paused` the messages are still received, so the Steam connection stays
+/// healthy, but they are held or dropped per the policy instead of forwarded.
+/// Returns false when the peer should be removed (Steam or MC side closed)
+fn receive_from_peer<T: Transport>(
+    steam_id: SteamId,
+    peer: &mut PeerState<T>,
+    bridges: &BridgePool,
+    rcon: Option<&BridgePool>,
+    paused: Option<PausePolicy>,
+) -> Result<(), RemovalReason> {
+    // Frames held during a pause go out before anything newer
+    if paused.is_none() {
+        while let Some(frame) = peer.held.pop_front() {
+            deliver_mc_frame(steam_id, peer, bridges, frame)?;
+        }
+        peer.held_bytes = 0;
+    }
+
+    let messages = peer
+        .connection
+        .receive(64)
+        .map_err(|_| RemovalReason::ConnectionLost)?;
+
+    for data in messages {
+        if data.is_empty() {
+            continue;
+        }
+        let wire_len = data.len();
+        metrics::record_peer_received(steam_id.raw(), wire_len as u64);
+        let frame = if ENABLE_FRAMING {
+            match framing::decode(&data) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    // Skipping the frame would leave a hole in the MC byte stream
+                    error!("✗ 收到来自 {:?} 的无效帧: {}", steam_id, e);
+                    metrics::record_packet_received(0, wire_len as u64);
+                    metrics::record_packet_dropped();
+                    return Err(RemovalReason::ProtocolError);
                 }
-                Err(e) if e.kind() == ErrorKind::WouldBlock => {
-                    // This should not happen with blocking sockets
-                    thread::sleep(Duration::from_millis(10));
+            }
+        } else {
+            Frame::data(0, data)
+        };
+        let payload = frame.payload_len();
+        metrics::record_packet_received(payload as u64, (wire_len - payload) as u64);
+        capture::record(steam_id.raw(), Direction::Received, &frame);
+
+        // MC traffic is channel 0; auxiliary channels get their own handlers here
+        match (frame.channel, paused) {
+            (Channel::Minecraft, None) => deliver_mc_frame(steam_id, peer, bridges, frame)?,
+            (Channel::Minecraft, Some(policy)) => hold_mc_frame(steam_id, peer, bridges, frame, policy)?,
+            // Admin traffic keeps flowing while game forwarding is paused
+            (Channel::Rcon, _) => deliver_rcon_frame(steam_id, peer, rcon, frame),
+        }
+    }
+    Ok(())
+}
+
+/// Deliver a Minecraft channel frame to the peer's MC bridges
+///
+/// Returns the reason when the peer should be removed
+fn deliver_mc_frame<T: Transport>(
+    steam_id: SteamId,
+    peer: &mut PeerState<T>,
+    bridges: &BridgePool,
+    frame: Frame,
+) -> Result<(), RemovalReason> {
+    match frame.kind {
+        FrameKind::Data => {
+            if peer.broken_streams.contains(&frame.stream_id) {
+                return Ok(());
+            }
+            if !peer.streams.contains_key(&frame.stream_id) {
+                if !ENABLE_FRAMING {
+                    // The single raw stream is gone: MC connection closed
+                    return Err(RemovalReason::McClosed);
                 }
-                Err(e) => {
-                    error!("✗ 读取 MC 服务器失败: {:?}", e);
-                    break;
+                let server = peer.stream_servers.get(&frame.stream_id).copied().unwrap_or(0);
+                let Some(tx) = bridges.open_server(steam_id, frame.stream_id, server) else {
+                    // Already refused with a close frame; wait for the client's close
+                    return Ok(());
+                };
+                peer.stream_servers.remove(&frame.stream_id);
+                info!("新的 MC 连接 ({:?}, 流 {}, 服务器 {})", steam_id, frame.stream_id, server);
+                peer.add_stream(frame.stream_id, tx);
+                // An incomplete handshake is left unclassified and stays reliable
+                if UNRELIABLE_STATUS_PINGS && mc_handshake::intent(&frame.payload) == Intent::Status {
+                    peer.status_streams.insert(frame.stream_id);
+                }
+            }
+
+            // Only the first bytes of a stream are checked; anything that clearly
+            // is not Minecraft never reaches the MC server. A dry run accepts any bytes
+            if peer.handshake_pending.remove(&frame.stream_id)
+                && bridges.dry_run().is_none()
+                && mc_handshake::inspect(&frame.payload) == Verdict::Invalid
+            {
+                warn!("🚫 {:?} 的流 {} 不是 Minecraft 握手", steam_id, frame.stream_id);
+                return Err(RemovalReason::InvalidHandshake);
+            }
+
+            let delivered = peer
+                .streams
+                .get(&frame.stream_id)
+                .is_some_and(|tx| tx.send(frame.payload).is_ok());
+            if !delivered {
+                if !ENABLE_FRAMING {
+                    return Err(RemovalReason::McClosed);
                 }
+                // Bridge died before reporting; tell the client this stream is gone
+                peer.streams.remove(&frame.stream_id);
+                send_to_peer(steam_id, peer, &Frame::close(frame.stream_id));
+                peer.status_streams.remove(&frame.stream_id);
             }
         }
-    });
+        FrameKind::Open => {
+            let server = frame.payload.first().copied().unwrap_or(0);
+            if peer.streams.contains_key(&frame.stream_id) {
+                warn!("⚠️ 忽略 {:?} 的流 {} 的打开帧: 连接已建立", steam_id, frame.stream_id);
+            } else {
+                if !bridges.has_server(server) {
+                    warn!("🚫 {:?} 的流 {} 选择了不存在的 MC 服务器 {}", steam_id, frame.stream_id, server);
+                    send_to_peer(steam_id, peer, &Frame::close(frame.stream_id));
+                }
+                peer.stream_servers.insert(frame.stream_id, server);
+            }
+        }
+        FrameKind::Close => {
+            peer.handshake_pending.remove(&frame.stream_id);
+            peer.status_streams.remove(&frame.stream_id);
+            peer.stream_servers.remove(&frame.stream_id);
+            peer.broken_streams.remove(&frame.stream_id);
+            // Dropping the sender closes the bridge and its TCP connection
+            if peer.streams.remove(&frame.stream_id).is_some() {
+                info!("客户端关闭了 MC 连接 ({:?}, 流 {})", steam_id, frame.stream_id);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether a peer may open RCON streams, provided the host offers RCON at all
+fn rcon_permitted(steam_id: SteamId) -> bool {
+    !RCON_REQUIRE_ALLOWLIST || access_list::explicitly_allows(steam_id.raw())
+}
+
+/// Deliver an RCON channel frame, bridging a new stream to the RCON port on its first data
+///
+/// Streams are refused with a close frame when the host does not offer RCON or the
+/// peer is not permitted; the peer's Steam connection and game traffic are unaffected
+fn deliver_rcon_frame<T: Transport>(
+    steam_id: SteamId,
+    peer: &mut PeerState<T>,
+    rcon: Option<&BridgePool>,
+    frame: Frame,
+) {
+    match frame.kind {
+        FrameKind::Data => {
+            if !peer.rcon_streams.contains_key(&frame.stream_id) {
+                let Some(rcon) = rcon.filter(|_| rcon_permitted(steam_id)) else {
+                    warn!("🚫 拒绝 {:?} 的 RCON 连接 (流 {}): 未开放或不在允许列表中", steam_id, frame.stream_id);
+                    send_to_peer(steam_id, peer, &Frame::close(frame.stream_id).on(Channel::Rcon));
+                    return;
+                };
+                info!("🔧 新的 RCON 连接 ({:?}, 流 {})", steam_id, frame.stream_id);
+                structured_log::emit(
+                    "rcon_opened",
+                    &[("steam_id", json!(steam_id.raw())), ("stream_id", json!(frame.stream_id))],
+                );
+                peer.rcon_streams.insert(frame.stream_id, rcon.open(steam_id, frame.stream_id));
+            }
+
+            let delivered = peer
+                .rcon_streams
+                .get(&frame.stream_id)
+                .is_some_and(|tx| tx.send(frame.payload).is_ok());
+            if !delivered {
+                peer.rcon_streams.remove(&frame.stream_id);
+                send_to_peer(steam_id, peer, &Frame::close(frame.stream_id).on(Channel::Rcon));
+            }
+        }
+        // The RCON port is fixed; there is no server to choose
+        FrameKind::Open => {}
+        FrameKind::Close => {
+            if peer.rcon_streams.remove(&frame.stream_id).is_some() {
+                info!("客户端关闭了 RCON 连接 ({:?}, 流 {})", steam_id, frame.stream_id);
+            }
+        }
+    }
+}
+
+/// Close all of a peer's RCON streams, telling the client each one is gone
+fn close_rcon_streams<T: Transport>(steam_id: SteamId, peer: &mut PeerState<T>) {
+    let streams: Vec<u32> = peer.rcon_streams.drain().map(|(stream_id, _)| stream_id).collect();
+    info!("🔒 关闭 {:?} 的 RCON 连接 ({} 条): 不再在允许列表中", steam_id, streams.len());
+    for stream_id in streams {
+        send_to_peer(steam_id, peer, &Frame::close(stream_id).on(Channel::Rcon));
+    }
+}
 
-    // Main bridge loop: receive from Steam and send to MC server
-    for data in to_mc_rx {
-        if stream.write_all(&data).is_err() {
-            break; // MC server connection closed
+/// Hold back a Minecraft channel frame while forwarding is paused
+///
+/// Returns the reason when the peer should be removed
+fn hold_mc_frame<T: Transport>(
+    steam_id: SteamId,
+    peer: &mut PeerState<T>,
+    bridges: &BridgePool,
+    frame: Frame,
+    policy: PausePolicy,
+) -> Result<(), RemovalReason> {
+    match (policy, frame.kind) {
+        // Nothing is held when discarding, so opens and closes can take effect right away
+        (PausePolicy::Discard, FrameKind::Close | FrameKind::Open) => deliver_mc_frame(steam_id, peer, bridges, frame),
+        (PausePolicy::Discard, FrameKind::Data) => {
+            metrics::record_packet_dropped();
+            break_stream(steam_id, peer, frame.stream_id)
+        }
+        (PausePolicy::Buffer, FrameKind::Data) if peer.broken_streams.contains(&frame.stream_id) => {
+            metrics::record_packet_dropped();
+            Ok(())
+        }
+        (PausePolicy::Buffer, _) => {
+            let size = frame.payload_len();
+            if peer.held_bytes + size > MAX_PAUSE_BUFFER_BYTES {
+                static OVERFLOW_LOG: LogThrottle = LogThrottle::new();
+                if let Some(suppressed) = OVERFLOW_LOG.allow() {
+                    warn!(
+                        "⚠️ {:?} 的暂停缓冲已满 ({} 字节)，关闭其流 {}{}",
+                        steam_id,
+                        MAX_PAUSE_BUFFER_BYTES,
+                        frame.stream_id,
+                        log_throttle::suppressed_note(suppressed)
+                    );
+                }
+                metrics::record_packet_dropped();
+                return break_stream(steam_id, peer, frame.stream_id);
+            }
+            peer.held_bytes += size;
+            peer.held.push_back(frame);
+            Ok(())
         }
     }
+}
 
-    // The loop exits when to_mc_rx is disconnected (peer disconnected).
-    // The upstream_thread will exit automatically when from_mc_tx is dropped.
-    let _ = upstream_thread.join();
+/// Drop data for a stream and close it on both ends, since neither the MC server
+/// nor the client's MC connection may see a byte stream with a hole in it
+///
+/// Returns the reason when the peer should be removed
+fn break_stream<T: Transport>(steam_id: SteamId, peer: &mut PeerState<T>, stream_id: u32) -> Result<(), RemovalReason> {
+    if !ENABLE_FRAMING {
+        // The single raw stream cannot be closed on its own
+        return Err(RemovalReason::McClosed);
+    }
+    if !peer.broken_streams.insert(stream_id) {
+        return Ok(());
+    }
+    // Held data for the stream can no longer be delivered; opens and closes still can
+    peer.held
+        .retain(|frame| frame.stream_id != stream_id || frame.kind != FrameKind::Data);
+    peer.held_bytes = peer.held.iter().map(Frame::payload_len).sum();
+    // Dropping the sender closes the bridge and its TCP connection
+    peer.streams.remove(&stream_id);
+    peer.handshake_pending.remove(&stream_id);
+    peer.status_streams.remove(&stream_id);
+    peer.stream_servers.remove(&stream_id);
+    info!("丢弃了 {:?} 的流 {} 的数据，关闭该连接", steam_id, stream_id);
+    send_to_peer(steam_id, peer, &Frame::close(stream_id));
     Ok(())
 }
+
+/// Graceful shutdown: flush both directions, wait for Steam send buffers, then close
+fn shutdown(
+    client: &Client,
+    lobby_id: LobbyId,
+    mut peers: HashMap<SteamId, PeerState>,
+    bridges: &BridgePool,
+    rcon: Option<&BridgePool>,
+    from_mc_rx: &Receiver<McEvent>,
+    grace_period: Duration,
+) {
+    info!("🛑 正在关闭房主会话 (宽限期 {} ms)...", grace_period.as_millis());
+
+    // Flush MC -> Steam data that is already queued
+    while let Ok((steam_id, frame)) = from_mc_rx.try_recv() {
+        if let Some(peer) = peers.get_mut(&steam_id) {
+            handle_mc_frame(steam_id, peer, frame);
+        }
+    }
+
+    // Flush Steam -> MC data that already arrived, including anything held while paused,
+    // and give messages waiting for a retry one last chance
+    for (steam_id, peer) in peers.iter_mut() {
+        let _ = receive_from_peer(*steam_id, peer, bridges, rcon, None);
+        peer.retry_at = Instant::now();
+        retry_sends(*steam_id, peer);
+    }
+
+    // Data sent over app-level reliability is only delivered once acknowledged;
+    // Steam's send buffers know nothing about it
+    let deadline = Instant::now() + grace_period;
+    for peer in peers.values_mut().filter(|peer| peer.connection.is_active()) {
+        peer.connection.settle(deadline);
+    }
+
+    // Dropping the MC senders lets the bridges write out what they have and close
+    let connections: Vec<(SteamId, NetConnection)> = peers
+        .into_iter()
+        .map(|(steam_id, peer)| (steam_id, peer.connection.into_inner()))
+        .collect();
+
+    let pending: Vec<&NetConnection> = connections.iter().map(|(_, conn)| conn).collect();
+    if shutdown::drain_send_buffers(client, &pending, deadline.saturating_duration_since(Instant::now())) {
+        info!("✓ 所有待发送数据已送达");
+    }
+
+    for (steam_id, connection) in connections {
+        connection.close(NetConnectionEnd::AppGeneric, Some("房主已关闭"), true);
+        metrics::clear_peer(steam_id.raw());
+    }
+
+    presence::clear_presence(client);
+    client.matchmaking().leave_lobby(lobby_id);
+    info!("✓ 已离开房间，房主会话结束");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MemoryTransport;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    /// Client frames travel over an in-memory transport into a real TCP "MC server"
+    /// that echoes them back, exercising the host bridge without Steam.
+    #[test]
+    fn test_bridge_roundtrip_over_memory_transport() {
+        let mc_server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = mc_server.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = mc_server.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            while let Ok(n) = stream.read(&mut buf) {
+                if n == 0 || stream.write_all(&buf[..n]).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (mut client_end, host_end) = MemoryTransport::pair();
+        let steam_id = SteamId::from_raw(76561198000000002);
+        let mut peer = PeerState::new(host_end);
+        let (from_mc_tx, from_mc_rx) = send_queue::bounded(MC_READ_QUEUE_CAPACITY);
+        let bridges = BridgePool::new(1, port, from_mc_tx);
+
+        // Legacy server list ping, so the handshake check lets it through
+        let request = Frame::data(3, vec![0xFE, 0x01]);
+        client_end.send(&framing::encode(&request).unwrap()).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let echoed = loop {
+            assert!(Instant::now() < deadline, "no echo from MC bridge");
+            assert!(receive_from_peer(steam_id, &mut peer, &bridges, None, None).is_ok());
+            while let Ok((id, frame)) = from_mc_rx.try_recv() {
+                handle_mc_frame(id, &mut peer, frame);
+            }
+            if let Some(message) = client_end.receive(1).unwrap().pop() {
+                break framing::decode(&message).unwrap();
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(echoed, request);
+        assert!(peer.streams.contains_key(&3));
+
+        // Closing the stream from the client tears down its bridge
+        client_end.send(&framing::encode(&Frame::close(3)).unwrap()).unwrap();
+        assert!(receive_from_peer(steam_id, &mut peer, &bridges, None, None).is_ok());
+        assert!(peer.streams.is_empty());
+
+        // A frame that cannot be decoded is a protocol error, not something to skip
+        client_end.send(&[0xFF, 0x00, 0x00]).unwrap();
+        assert_eq!(
+            receive_from_peer(steam_id, &mut peer, &bridges, None, None),
+            Err(RemovalReason::ProtocolError)
+        );
+
+        metrics::clear_peer(steam_id.raw());
+    }
+
+    #[test]
+    fn test_paused_frames_are_held_until_resume() {
+        let mc_server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = mc_server.local_addr().unwrap().port();
+        let (mut client_end, host_end) = MemoryTransport::pair();
+        let steam_id = SteamId::from_raw(76561198000000004);
+        let mut peer = PeerState::new(host_end);
+        let (from_mc_tx, _from_mc_rx) = send_queue::bounded(MC_READ_QUEUE_CAPACITY);
+        let bridges = BridgePool::new(1, port, from_mc_tx);
+
+        let request = Frame::data(5, vec![0xFE, 0x01]);
+        client_end.send(&framing::encode(&request).unwrap()).unwrap();
+        assert!(receive_from_peer(steam_id, &mut peer, &bridges, None, Some(PausePolicy::Buffer)).is_ok());
+        assert_eq!(peer.held.len(), 1);
+        assert_eq!(peer.held_bytes, 2);
+        assert!(peer.streams.is_empty(), "nothing reaches the MC server while paused");
+
+        // Discarding keeps the peer but closes the stream whose data was dropped,
+        // so the MC server never sees a byte stream with a hole in it
+        client_end.send(&framing::encode(&Frame::data(6, vec![0xFE, 0x01])).unwrap()).unwrap();
+        assert!(receive_from_peer(steam_id, &mut peer, &bridges, None, Some(PausePolicy::Discard)).is_ok());
+        assert_eq!(peer.held.len(), 1);
+        let close = framing::decode(&client_end.receive(1).unwrap().pop().unwrap()).unwrap();
+        assert_eq!(close, Frame::close(6));
+        client_end.send(&framing::encode(&Frame::data(6, vec![0x00])).unwrap()).unwrap();
+        assert!(receive_from_peer(steam_id, &mut peer, &bridges, None, Some(PausePolicy::Discard)).is_ok());
+        assert!(client_end.receive(1).unwrap().is_empty(), "the stream is closed only once");
+        client_end.send(&framing::encode(&Frame::close(6)).unwrap()).unwrap();
+        assert!(receive_from_peer(steam_id, &mut peer, &bridges, None, Some(PausePolicy::Discard)).is_ok());
+        assert!(peer.broken_streams.is_empty());
+        assert_eq!(peer.held.len(), 1);
+
+        // Resuming flushes the held frame, opening its bridge
+        assert!(receive_from_peer(steam_id, &mut peer, &bridges, None, None).is_ok());
+        assert!(peer.held.is_empty());
+        assert_eq!(peer.held_bytes, 0);
+        assert!(peer.streams.contains_key(&5));
+
+        drop(mc_server);
+        metrics::clear_peer(steam_id.raw());
+    }
+
+    #[test]
+    fn test_paused_mc_data_closes_stream() {
+        let (mut client_end, host_end) = MemoryTransport::pair();
+        let steam_id = SteamId::from_raw(76561198000000012);
+        let mut peer = PeerState::new(host_end);
+        let (to_mc, to_mc_rx) = mpsc::channel();
+        peer.add_stream(7, to_mc);
+
+        // Discarding a reply from the MC server closes the stream on both ends
+        let reply = Frame::data(7, b"chunk".to_vec());
+        assert!(forward_mc_frame(steam_id, &mut peer, reply.clone(), Some(PausePolicy::Discard)).is_ok());
+        let close = framing::decode(&client_end.receive(1).unwrap().pop().unwrap()).unwrap();
+        assert_eq!(close, Frame::close(7));
+        assert!(!peer.streams.contains_key(&7));
+        assert!(to_mc_rx.recv().is_err(), "the bridge is closed");
+
+        // The rest of the stream is dropped, even after resuming
+        assert!(forward_mc_frame(steam_id, &mut peer, reply.clone(), Some(PausePolicy::Discard)).is_ok());
+        assert!(forward_mc_frame(steam_id, &mut peer, reply, None).is_ok());
+        assert!(client_end.receive(8).unwrap().is_empty(), "the stream is closed only once");
+
+        // RCON replies are never discarded
+        let rcon = Frame::data(2, b"ok".to_vec()).on(Channel::Rcon);
+        assert!(forward_mc_frame(steam_id, &mut peer, rcon.clone(), Some(PausePolicy::Discard)).is_ok());
+        assert_eq!(framing::decode(&client_end.receive(1).unwrap()[0]).unwrap(), rcon);
+
+        metrics::clear_peer(steam_id.raw());
+    }
+
+    #[test]
+    fn test_open_frame_selects_server() {
+        let main_server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let extra_server = TcpListener::bind("127.0.0.1:0").unwrap();
+        extra_server.set_nonblocking(true).unwrap();
+        let (mut client_end, host_end) = MemoryTransport::pair();
+        let steam_id = SteamId::from_raw(76561198000000010);
+        let mut peer = PeerState::new(host_end);
+        let (from_mc_tx, _from_mc_rx) = send_queue::bounded(MC_READ_QUEUE_CAPACITY);
+        let bridges = BridgePool::new(1, main_server.local_addr().unwrap().port(), from_mc_tx)
+            .with_extra_ports(vec![extra_server.local_addr().unwrap().port()]);
+
+        for frame in [Frame::open(4, 1), Frame::data(4, vec![0xFE, 0x01])] {
+            client_end.send(&framing::encode(&frame).unwrap()).unwrap();
+        }
+        assert!(receive_from_peer(steam_id, &mut peer, &bridges, None, None).is_ok());
+        assert!(peer.streams.contains_key(&4));
+        assert!(peer.stream_servers.is_empty());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while extra_server.accept().is_err() {
+            assert!(Instant::now() < deadline, "bridge did not reach the chosen server");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // An index the host does not offer is refused and its data dropped
+        for frame in [Frame::open(6, 2), Frame::data(6, vec![0xFE, 0x01])] {
+            client_end.send(&framing::encode(&frame).unwrap()).unwrap();
+        }
+        assert!(receive_from_peer(steam_id, &mut peer, &bridges, None, None).is_ok());
+        assert_eq!(framing::decode(&client_end.receive(1).unwrap()[0]).unwrap(), Frame::close(6));
+        assert!(!peer.streams.contains_key(&6));
+        client_end.send(&framing::encode(&Frame::close(6)).unwrap()).unwrap();
+        assert!(receive_from_peer(steam_id, &mut peer, &bridges, None, None).is_ok());
+        assert!(peer.stream_servers.is_empty());
+
+        drop(main_server);
+        metrics::clear_peer(steam_id.raw());
+    }
+
+    #[test]
+    fn test_normalize_servers() {
+        let server = |label: &str, port| McServer {
+            label: label.to_string(),
+            port,
+        };
+        assert_eq!(
+            normalize_servers(25565, vec![server(" 创造服 ", 25566)]),
+            Ok(vec![server("创造服", 25566)])
+        );
+        assert!(normalize_servers(25565, vec![server("生存服", 25565)]).is_err());
+        assert!(normalize_servers(25565, vec![server("  ", 25566)]).is_err());
+        assert!(normalize_servers(25565, vec![server("a", 25566), server("b", 25566)]).is_err());
+    }
+
+    #[test]
+    fn test_removal_reasons_from_receive() {
+        let (client_end, host_end) = MemoryTransport::pair();
+        let steam_id = SteamId::from_raw(76561198000000006);
+        let mut peer = PeerState::new(host_end);
+        let (from_mc_tx, _from_mc_rx) = send_queue::bounded(MC_READ_QUEUE_CAPACITY);
+        let bridges = BridgePool::new(1, 1, from_mc_tx);
+
+        client_end
+            .send(&framing::encode(&Frame::data(1, b"GET / HTTP/1.1\r\n".to_vec())).unwrap())
+            .unwrap();
+        assert_eq!(
+            receive_from_peer(steam_id, &mut peer, &bridges, None, None),
+            Err(RemovalReason::InvalidHandshake)
+        );
+
+        drop(client_end);
+        assert_eq!(
+            receive_from_peer(steam_id, &mut peer, &bridges, None, None),
+            Err(RemovalReason::ConnectionLost)
+        );
+        assert_eq!(json!(RemovalReason::ConnectionLost), json!("connection_lost"));
+
+        metrics::clear_peer(steam_id.raw());
+    }
+
+    #[test]
+    fn test_rcon_streams_require_offer_and_allowlist() {
+        let rcon_server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let rcon_port = rcon_server.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = rcon_server.accept().unwrap();
+            let mut buf = [0u8; 64];
+            let n = stream.read(&mut buf).unwrap();
+            stream.write_all(&buf[..n]).unwrap();
+        });
+
+        let (mut client_end, host_end) = MemoryTransport::pair();
+        let steam_id = SteamId::from_raw(76561198000000008);
+        let mut peer = PeerState::new(host_end);
+        let (from_mc_tx, from_mc_rx) = send_queue::bounded(MC_READ_QUEUE_CAPACITY);
+        let bridges = BridgePool::new(1, 1, from_mc_tx.clone());
+        let rcon = BridgePool::new(1, rcon_port, from_mc_tx).with_channel(Channel::Rcon);
+        let login = Frame::data(2, b"\x0a\x00\x00\x00login".to_vec()).on(Channel::Rcon);
+        let refused = Frame::close(2).on(Channel::Rcon);
+
+        // Not offered, or offered but the peer is not on the allowlist: refused, peer kept
+        for offered in [None, Some(&rcon)] {
+            client_end.send(&framing::encode(&login).unwrap()).unwrap();
+            assert!(receive_from_peer(steam_id, &mut peer, &bridges, offered, None).is_ok());
+            assert_eq!(framing::decode(&client_end.receive(1).unwrap()[0]).unwrap(), refused);
+            assert!(peer.rcon_streams.is_empty());
+        }
+        assert!(peer.streams.is_empty(), "RCON never opens a game stream");
+
+        access_list::update(access_list::ListKind::Allow, steam_id.raw(), true).unwrap();
+        client_end.send(&framing::encode(&login).unwrap()).unwrap();
+        // Paused game forwarding does not hold back RCON
+        assert!(receive_from_peer(steam_id, &mut peer, &bridges, Some(&rcon), Some(PausePolicy::Buffer)).is_ok());
+        assert!(peer.rcon_streams.contains_key(&2));
+        let (id, reply) = from_mc_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((id, reply), (steam_id, login));
+        access_list::update(access_list::ListKind::Allow, steam_id.raw(), false).unwrap();
+
+        metrics::clear_peer(steam_id.raw());
+    }
+
+    /// Reliable sends fail while `failures` is above zero
+    struct FlakyTransport {
+        inner: MemoryTransport,
+        failures: std::cell::Cell<u32>,
+    }
+
+    impl Transport for FlakyTransport {
+        fn send(&self, data: &[u8]) -> Result<(), TransportError> {
+            match self.failures.get() {
+                0 => self.inner.send(data),
+                n => {
+                    self.failures.set(n - 1);
+                    Err(TransportError::Failed(String::from("LimitExceeded")))
+                }
+            }
+        }
+
+        fn send_unreliable(&self, data: &[u8]) -> Result<(), TransportError> {
+            self.inner.send_unreliable(data)
+        }
+
+        fn receive(&mut self, max: usize) -> Result<Vec<Vec<u8>>, TransportError> {
+            self.inner.receive(max)
+        }
+    }
+
+    #[test]
+    fn test_failed_sends_are_retried_later_in_order() {
+        let (mut client_end, host_end) = MemoryTransport::pair();
+        let steam_id = SteamId::from_raw(76561198000000011);
+        let mut peer = PeerState::new(FlakyTransport {
+            inner: host_end,
+            failures: std::cell::Cell::new(2),
+        });
+
+        // The failed send is queued instead of blocking the loop, and later frames wait behind it
+        let frames = [Frame::data(1, b"first".to_vec()), Frame::data(1, b"second".to_vec())];
+        for frame in &frames {
+            send_to_peer(steam_id, &mut peer, frame);
+        }
+        assert_eq!(peer.retry.len(), 2);
+        assert!(client_end.receive(8).unwrap().is_empty());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !peer.retry.is_empty() {
+            assert!(Instant::now() < deadline, "retries never went out");
+            retry_sends(steam_id, &mut peer);
+            thread::sleep(Duration::from_millis(1));
+        }
+        let received: Vec<Frame> = client_end
+            .receive(8)
+            .unwrap()
+            .iter()
+            .map(|message| framing::decode(message).unwrap())
+            .collect();
+        assert_eq!(received, frames);
+        assert_eq!(peer.send_failures, 0);
+
+        metrics::clear_peer(steam_id.raw());
+    }
+
+    #[test]
+    fn test_dropped_send_closes_its_stream() {
+        let (mut client_end, host_end) = MemoryTransport::pair();
+        let steam_id = SteamId::from_raw(76561198000000012);
+        // The first send and every retry fail, after which the link recovers
+        let mut peer = PeerState::new(FlakyTransport {
+            inner: host_end,
+            failures: std::cell::Cell::new(SEND_RETRY_ATTEMPTS + 2),
+        });
+
+        send_to_peer(steam_id, &mut peer, &Frame::data(1, b"lost".to_vec()));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !peer.retry.is_empty() {
+            assert!(Instant::now() < deadline, "the message was never given up on");
+            retry_sends(steam_id, &mut peer);
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // The stream has a hole in it, so it is closed rather than carrying on
+        assert!(peer.broken_streams.contains(&1));
+        let received: Vec<Frame> = client_end
+            .receive(8)
+            .unwrap()
+            .iter()
+            .map(|message| framing::decode(message).unwrap())
+            .collect();
+        assert_eq!(received, vec![Frame::close(1)]);
+
+        metrics::clear_peer(steam_id.raw());
+    }
+
+    #[test]
+    fn test_failed_sends_mark_peer_for_removal() {
+        let (client_end, host_end) = MemoryTransport::pair();
+        let steam_id = SteamId::from_raw(76561198000000003);
+        let mut peer = PeerState::new(host_end);
+
+        // The client going away makes every send fail
+        drop(client_end);
+        for _ in 0..MAX_CONSECUTIVE_SEND_FAILURES {
+            send_to_peer(steam_id, &mut peer, &Frame::data(1, b"lost".to_vec()));
+        }
+        // The stream's close fails to go out as well
+        assert!(peer.send_failures >= MAX_CONSECUTIVE_SEND_FAILURES);
+
+        metrics::clear_peer(steam_id.raw());
+    }
+}
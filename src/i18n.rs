@@ -0,0 +1,340 @@
+use crate::config::DEFAULT_LANGUAGE;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::sync::{LazyLock, Mutex};
+
+/// 面向用户的消息语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Zh,
+    En,
+}
+
+/// 当前语言，未在配置中指定时跟随系统区域设置
+static LANGUAGE: LazyLock<Mutex<Language>> =
+    LazyLock::new(|| Mutex::new(DEFAULT_LANGUAGE.unwrap_or_else(system_language)));
+
+/// 获取当前语言
+pub fn language() -> Language {
+    *LANGUAGE.lock().unwrap()
+}
+
+/// 切换语言，之后产生的消息使用新语言
+pub fn set_language(language: Language) {
+    *LANGUAGE.lock().unwrap() = language;
+}
+
+/// 根据区域设置名称 (如 zh_CN.UTF-8、en-US) 判断语言
+fn language_from_locale(locale: &str) -> Option<Language> {
+    let locale = locale.trim();
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return None;
+    }
+    if locale.to_ascii_lowercase().starts_with("zh") {
+        Some(Language::Zh)
+    } else {
+        Some(Language::En)
+    }
+}
+
+/// 读取系统区域设置，无法判断时使用中文
+fn system_language() -> Language {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .find_map(|locale| language_from_locale(&locale))
+        .or_else(windows_language)
+        .unwrap_or(Language::Zh)
+}
+
+#[cfg(windows)]
+fn windows_language() -> Option<Language> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetUserDefaultLocaleName(locale_name: *mut u16, len: i32) -> i32;
+    }
+
+    // LOCALE_NAME_MAX_LENGTH
+    let mut buf = [0u16; 85];
+    // SAFETY: buf 是有效的可写缓冲区，长度与传入的 len 一致
+    let len = unsafe { GetUserDefaultLocaleName(buf.as_mut_ptr(), buf.len() as i32) };
+    if len <= 1 {
+        return None;
+    }
+    language_from_locale(&String::from_utf16_lossy(&buf[..len as usize - 1]))
+}
+
+#[cfg(not(windows))]
+fn windows_language() -> Option<Language> {
+    None
+}
+
+/// 面向用户的消息，文本按当前语言从目录中取出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    SteamInitFailed,
+    SteamAppIdMissing,
+    NoHostSession,
+    HostSessionEnded,
+    LobbyNotReady,
+    LobbyCreateFailed,
+    LobbyCreateTimeout,
+    HostThreadExited,
+    TaskFailed,
+    InvalidLobbyId,
+    InvalidJoinTarget,
+    FriendNotInLobby,
+    InvalidSteamId,
+    PeerNotConnected,
+    ClientError,
+    ConnectTimeout,
+    NotInLobby,
+    ClientSessionEnded,
+    JoinFailed,
+    JoinTimeout,
+    LobbyInfoUnavailable,
+    ServerPropertiesNotFound,
+    ServerPropertiesReadFailed,
+    WrongPassword,
+    PasswordVerifyTimeout,
+    PasswordRequired,
+    FramingMismatch,
+    VirtualPortMismatch,
+    HostUnknown,
+    CannotConnectSelf,
+    ConnectInitFailed,
+    ClosedByPeer,
+    NotOnAllowList,
+    ProblemDetectedLocally,
+    HostConnectTimeout,
+    BindFailed,
+    ListenPortFallback,
+    InvalidMcPort,
+    InvalidLogLevel,
+    ListenSocketFailed,
+    AppVersionMismatch,
+    MetadataMismatch,
+    LabelMcVersion,
+    LabelModpack,
+    HostAlreadyRunning,
+    ClientAlreadyRunning,
+    HostBlockedByClient,
+    ClientBlockedByHost,
+    SwitchLeaveTimeout,
+    SyncingLobbyInfo,
+    RconNotOffered,
+    HostConnectionLost,
+    ConnectionUnencrypted,
+    LobbyLockFailed,
+    WaitingForMcServer,
+    ThreadPanicked,
+    InvalidMcServers,
+    McServerUnavailable,
+    LocalMcStatusFailed,
+    AlreadyInLobby,
+}
+
+impl Message {
+    /// 当前语言下的文本
+    pub fn text(self) -> &'static str {
+        self.text_in(language())
+    }
+
+    /// 用参数依次替换文本中的 `{}` 占位符
+    pub fn format(self, args: &[&dyn Display]) -> String {
+        let mut result = String::new();
+        let mut parts = self.text().split("{}");
+        if let Some(first) = parts.next() {
+            result.push_str(first);
+        }
+        for (i, part) in parts.enumerate() {
+            if let Some(arg) = args.get(i) {
+                result.push_str(&arg.to_string());
+            }
+            result.push_str(part);
+        }
+        result
+    }
+
+    /// 指定语言下的文本
+    pub fn text_in(self, language: Language) -> &'static str {
+        use Message::*;
+        match language {
+            Language::Zh => match self {
+                SteamInitFailed => "Steam 未运行或初始化失败: {}",
+                SteamAppIdMissing => "未找到 Steam App ID: 请在程序目录下创建 steam_appid.txt 并写入 {}，或通过 Steam 启动本程序",
+                NoHostSession => "没有正在运行的房主会话",
+                HostSessionEnded => "房主会话已结束",
+                LobbyNotReady => "房间尚未创建完成",
+                LobbyCreateFailed => "房间创建失败: {}",
+                LobbyCreateTimeout => "创建房间超时",
+                HostThreadExited => "房主线程意外退出",
+                TaskFailed => "后台任务意外退出",
+                InvalidLobbyId => "无效的房间号",
+                InvalidJoinTarget => "无法识别的房间号、邀请链接或好友代码",
+                FriendNotInLobby => "该好友当前不在房间中，请让好友先创建或加入房间",
+                InvalidSteamId => "无效的 Steam ID",
+                PeerNotConnected => "该玩家 ({}) 当前未连接到房间",
+                ClientError => "客户端错误: {}",
+                ConnectTimeout => "连接超时",
+                NotInLobby => "当前未加入任何房间",
+                ClientSessionEnded => "客户端会话已结束",
+                JoinFailed => "加入房间失败 - 请检查: 1) 房间号是否正确 2) 房主是否仍在运行 3) Steam是否正常连接",
+                JoinTimeout => "加入房间超时 - Steam服务可能暂时不可用，请稍后重试",
+                LobbyInfoUnavailable => "无法获取房间信息 - 房间可能不存在或已关闭",
+                ServerPropertiesNotFound => "未找到 {}，请确认选择的是 Minecraft 服务器目录",
+                ServerPropertiesReadFailed => "无法读取 {}: {}",
+                WrongPassword => "房间密码错误",
+                PasswordVerifyTimeout => "验证密码超时，或房主未设置密码",
+                PasswordRequired => "房间需要密码，但未提供密码",
+                FramingMismatch => "房主的帧协议版本 (v{}) 与本程序 (v{}) 不兼容，请更新到相同版本",
+                VirtualPortMismatch => "房主监听的虚拟端口 ({}) 与本程序使用的端口 ({}) 不一致，请使用相同的配置",
+                HostUnknown => "无法确定房主，房间信息尚未同步，请稍后重试",
+                CannotConnectSelf => "无法连接自己，请使用两个不同的账号测试",
+                ConnectInitFailed => "无法向房主发起连接，Steam NetworkingSockets 初始化失败",
+                ClosedByPeer => "房主拒绝了连接 (ClosedByPeer) - 请确保房主程序正在运行且房间号正确",
+                NotOnAllowList => "你不在允许列表中",
+                ProblemDetectedLocally => "本地检测到连接问题 (ProblemDetectedLocally) - 可能是网络问题或Steam服务不可用",
+                HostConnectTimeout => "连接房主超时 (15秒) - 房主可能不在线或网络问题",
+                BindFailed => "无法绑定端口 {}: {}",
+                ListenPortFallback => "端口 {} 已被占用，改为监听 {}，请在 Minecraft 中连接 127.0.0.1:{}",
+                InvalidMcPort => "无效的 Minecraft 服务器端口: {}",
+                InvalidLogLevel => "无效的日志级别: {}",
+                ListenSocketFailed => "无法创建 Steam 网络监听端口 ({}，中继网络状态: {})，请确认 Steam 已登录且网络未被防火墙或代理拦截，稍后重试",
+                AppVersionMismatch => "版本不匹配，请更新 (房主 {}，本机 {})",
+                MetadataMismatch => "{}不匹配: 房主为 {}，本地为 {}",
+                LabelMcVersion => "Minecraft 版本",
+                LabelModpack => "整合包",
+                HostAlreadyRunning => "房主会话已在运行，请先停止当前会话",
+                ClientAlreadyRunning => "已有客户端会话在运行，请先离开当前房间",
+                HostBlockedByClient => "正在作为客户端连接房间，请先离开房间再创建",
+                ClientBlockedByHost => "正在作为房主运行，请先停止房主再加入房间",
+                SwitchLeaveTimeout => "离开当前房间超时，未能切换到新房间",
+                SyncingLobbyInfo => "正在同步房间信息...",
+                RconNotOffered => "房主未开放 RCON 转发",
+                HostConnectionLost => "与房主的连接已断开 ({})，请重新加入房间",
+                ConnectionUnencrypted => "与房主的 Steam 连接未加密，已断开以保护游戏数据",
+                LobbyLockFailed => "无法更改房间类型",
+                WaitingForMcServer => "等待 Minecraft 服务器启动 ({})",
+                ThreadPanicked => "{} 意外崩溃 ({})，会话已结束",
+                InvalidMcServers => "其他 MC 服务器的名称不能为空，端口不能与主服务器或彼此重复，且最多 {} 个",
+                McServerUnavailable => "房主没有开放序号为 {} 的 MC 服务器",
+                LocalMcStatusFailed => "无法读取本地 MC 服务器 {} 的状态，请确认服务器已启动: {}",
+                AlreadyInLobby => "已在房间中，无需重复加入",
+            },
+            Language::En => match self {
+                SteamInitFailed => "Steam is not running or failed to initialize: {}",
+                SteamAppIdMissing => "No Steam App ID found: create steam_appid.txt next to the program containing {}, or launch it via Steam",
+                NoHostSession => "No host session is running",
+                HostSessionEnded => "The host session has ended",
+                LobbyNotReady => "The lobby has not been created yet",
+                LobbyCreateFailed => "Failed to create lobby: {}",
+                LobbyCreateTimeout => "Timed out creating the lobby",
+                HostThreadExited => "The host thread exited unexpectedly",
+                TaskFailed => "A background task exited unexpectedly",
+                InvalidLobbyId => "Invalid lobby ID",
+                InvalidJoinTarget => "Unrecognized lobby ID, invite link or friend code",
+                FriendNotInLobby => "This friend is not in a lobby; ask them to host or join one first",
+                InvalidSteamId => "Invalid Steam ID",
+                PeerNotConnected => "Player {} is not connected to this lobby",
+                ClientError => "Client error: {}",
+                ConnectTimeout => "Connection timed out",
+                NotInLobby => "Not currently in a lobby",
+                ClientSessionEnded => "The client session has ended",
+                JoinFailed => "Failed to join lobby - check that: 1) the lobby ID is correct 2) the host is still running 3) Steam is connected",
+                JoinTimeout => "Timed out joining lobby - Steam may be temporarily unavailable, please retry later",
+                LobbyInfoUnavailable => "Could not read lobby info - the lobby may not exist or has been closed",
+                ServerPropertiesNotFound => "{} not found; make sure you selected the Minecraft server directory",
+                ServerPropertiesReadFailed => "Failed to read {}: {}",
+                WrongPassword => "Incorrect lobby password",
+                PasswordVerifyTimeout => "Timed out verifying password, or the host has not set one",
+                PasswordRequired => "The lobby requires a password but none was provided",
+                FramingMismatch => "The host's framing protocol (v{}) is incompatible with this version (v{}); please update both to the same version",
+                VirtualPortMismatch => "The host listens on virtual port {} but this client uses port {}; please use matching settings",
+                HostUnknown => "Could not determine the host; lobby data has not synced yet, please try again",
+                CannotConnectSelf => "Cannot connect to yourself; test with two different accounts",
+                ConnectInitFailed => "Could not connect to the host: Steam NetworkingSockets failed to initialize",
+                ClosedByPeer => "The host rejected the connection (ClosedByPeer) - make sure the host is running and the lobby ID is correct",
+                NotOnAllowList => "You are not on this lobby's allowlist",
+                ProblemDetectedLocally => "A local connection problem was detected (ProblemDetectedLocally) - check your network or Steam status",
+                HostConnectTimeout => "Timed out connecting to the host (15s) - the host may be offline or the network is unreachable",
+                BindFailed => "Could not bind port {}: {}",
+                ListenPortFallback => "Port {} is in use; listening on {} instead. Connect to 127.0.0.1:{} in Minecraft",
+                InvalidMcPort => "Invalid Minecraft server port: {}",
+                InvalidLogLevel => "Invalid log level: {}",
+                ListenSocketFailed => "Could not create the Steam networking listen socket ({}, relay network: {}); make sure Steam is signed in and not blocked by a firewall or proxy, then try again",
+                AppVersionMismatch => "Version mismatch, please update (host {}, this device {})",
+                MetadataMismatch => "{} mismatch: host has {}, you have {}",
+                LabelMcVersion => "Minecraft version",
+                LabelModpack => "Modpack",
+                HostAlreadyRunning => "A host session is already running; stop it first",
+                ClientAlreadyRunning => "A client session is already running; leave the current lobby first",
+                HostBlockedByClient => "Currently connected as a client; leave the lobby before hosting",
+                ClientBlockedByHost => "Currently hosting; stop the host before joining a lobby",
+                SwitchLeaveTimeout => "Timed out leaving the current lobby; did not switch to the new one",
+                SyncingLobbyInfo => "Syncing lobby info...",
+                RconNotOffered => "The host does not offer RCON forwarding",
+                HostConnectionLost => "Lost the connection to the host ({}); please rejoin the lobby",
+                ConnectionUnencrypted => "The Steam connection to the host is not encrypted; disconnected to protect game data",
+                LobbyLockFailed => "Could not change the lobby type",
+                WaitingForMcServer => "Waiting for the Minecraft server to start ({})",
+                ThreadPanicked => "{} crashed unexpectedly ({}); the session has ended",
+                InvalidMcServers => "Additional MC servers need a name and a port distinct from the main server and each other, up to {} of them",
+                McServerUnavailable => "The host does not offer an MC server with index {}",
+                LocalMcStatusFailed => "Could not read the status of the local MC server {} - make sure it is running: {}",
+                AlreadyInLobby => "Already in this lobby; no need to join again",
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_from_locale() {
+        assert_eq!(language_from_locale("zh_CN.UTF-8"), Some(Language::Zh));
+        assert_eq!(language_from_locale("zh-Hans-CN"), Some(Language::Zh));
+        assert_eq!(language_from_locale("en-US"), Some(Language::En));
+        assert_eq!(language_from_locale("de_DE"), Some(Language::En));
+        assert_eq!(language_from_locale("C"), None);
+        assert_eq!(language_from_locale(""), None);
+    }
+
+    #[test]
+    fn test_placeholders_match_across_languages() {
+        let messages = [
+            Message::SteamInitFailed,
+            Message::SteamAppIdMissing,
+            Message::ServerPropertiesNotFound,
+            Message::ServerPropertiesReadFailed,
+            Message::LobbyCreateFailed,
+            Message::ClientError,
+            Message::FramingMismatch,
+            Message::VirtualPortMismatch,
+            Message::BindFailed,
+            Message::ListenPortFallback,
+            Message::InvalidMcPort,
+            Message::InvalidLogLevel,
+            Message::PeerNotConnected,
+            Message::ListenSocketFailed,
+            Message::AppVersionMismatch,
+            Message::MetadataMismatch,
+            Message::WaitingForMcServer,
+            Message::ThreadPanicked,
+            Message::InvalidMcServers,
+            Message::McServerUnavailable,
+            Message::LocalMcStatusFailed,
+        ];
+        for message in messages {
+            assert_eq!(
+                message.text_in(Language::Zh).matches("{}").count(),
+                message.text_in(Language::En).matches("{}").count(),
+                "{:?}",
+                message
+            );
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/// 个人账户 SteamID64 的基数，好友代码 (账户 ID) 加上它即为 SteamID64
+const INDIVIDUAL_STEAM_ID_BASE: u64 = 0x0110_0001_0000_0000;
+
+/// SteamID64 中的账户类型 (第 52-55 位)
+const ACCOUNT_TYPE_INDIVIDUAL: u64 = 1;
+const ACCOUNT_TYPE_CHAT: u64 = 8;
+
+/// 用户输入的加入目标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinTarget {
+    /// 房间 ID
+    Lobby(u64),
+    /// 好友的 SteamID64，加入其所在的房间
+    Friend(u64),
+}
+
+/// 宽松地解析房间号: 忽略首尾空白以及复制时夹带的空格和连字符
+pub fn parse_lobby_id(input: &str) -> Option<u64> {
+    let digits: String = input
+        .trim()
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect();
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok().filter(|&id| id != 0)
+}
+
+/// 解析 "+connect_lobby <房间号>" 形式的 connect 字符串
+pub fn parse_connect_lobby(connect: &str) -> Option<u64> {
+    let mut parts = connect.split_whitespace();
+    while let Some(part) = parts.next() {
+        if part == "+connect_lobby" {
+            return parse_lobby_id(parts.next()?);
+        }
+    }
+    None
+}
+
+/// 解析房间号、`steam://joinlobby/<AppID>/<房间号>/<房主>` 链接、connect 字符串或好友代码
+///
+/// 纯数字按 SteamID 的账户类型区分: 房间 (chat) 直接加入，个人账户加入该好友所在的房间；
+/// 不超过 32 位的数字视为好友代码 (Steam "添加好友" 页面显示的账户 ID)
+pub fn parse(input: &str) -> Option<JoinTarget> {
+    let input = input.trim();
+    if let Some(rest) = input.strip_prefix("steam://joinlobby/") {
+        let mut parts = rest.split('/');
+        let _app_id = parts.next()?;
+        return parse_lobby_id(parts.next()?).map(JoinTarget::Lobby);
+    }
+    if input.contains("+connect_lobby") {
+        return parse_connect_lobby(input).map(JoinTarget::Lobby);
+    }
+
+    let id = parse_lobby_id(input)?;
+    if id <= u32::MAX as u64 {
+        return Some(JoinTarget::Friend(INDIVIDUAL_STEAM_ID_BASE + id));
+    }
+    match (id >> 52) & 0xF {
+        ACCOUNT_TYPE_CHAT => Some(JoinTarget::Lobby(id)),
+        ACCOUNT_TYPE_INDIVIDUAL => Some(JoinTarget::Friend(id)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_connect_lobby() {
+        assert_eq!(parse_connect_lobby("+connect_lobby 109775241046654221"), Some(109775241046654221));
+        assert_eq!(parse_connect_lobby("  +connect_lobby   42  "), Some(42));
+        assert_eq!(parse_connect_lobby("+connect_lobby"), None);
+        assert_eq!(parse_connect_lobby("+connect_lobby abc"), None);
+        assert_eq!(parse_connect_lobby("+connect 127.0.0.1"), None);
+    }
+
+    #[test]
+    fn test_parse_join_target() {
+        let lobby = 109775241046654221;
+        assert_eq!(parse(" 109775241046654221 "), Some(JoinTarget::Lobby(lobby)));
+        assert_eq!(parse("1097 7524 1046 654221"), Some(JoinTarget::Lobby(lobby)));
+        assert_eq!(
+            parse("steam://joinlobby/480/109775241046654221/76561198000000002"),
+            Some(JoinTarget::Lobby(lobby))
+        );
+        assert_eq!(parse("+connect_lobby 109775241046654221"), Some(JoinTarget::Lobby(lobby)));
+
+        assert_eq!(parse("76561198000000002"), Some(JoinTarget::Friend(76561198000000002)));
+        // 好友代码即账户 ID
+        assert_eq!(parse("39734274"), Some(JoinTarget::Friend(76561198000000002)));
+
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("0"), None);
+        assert_eq!(parse("steam://joinlobby/480/abc"), None);
+        assert_eq!(parse("hello"), None);
+        // 其他账户类型 (如群组) 无法加入
+        assert_eq!(parse("103582791429521408"), None);
+    }
+}
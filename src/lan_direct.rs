@@ -0,0 +1,315 @@
+use crate::config::LAN_DIRECT_PROBE_TIMEOUT_MS;
+use crate::correlation;
+use crate::mc_socket::configure_mc_stream;
+use crate::mc_status;
+use if_addrs::IfAddr;
+use log::info;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// 房主公布的局域网地址，如 "192.168.1.5/24,10.0.0.2/8"
+pub const LOBBY_KEY_LAN_ADDRS: &str = "lan_addrs";
+
+/// 房主本地 MC 服务器的端口，和局域网地址一起公布
+pub const LOBBY_KEY_LAN_PORT: &str = "lan_port";
+
+/// 房主的局域网验证端口和本房间的随机数，如 "51234:3f9c..."
+pub const LOBBY_KEY_LAN_VERIFY: &str = "lan_verify";
+
+/// 随机数的最大长度，验证时最多读取这么多字节
+const MAX_NONCE_LEN: usize = 64;
+
+/// 本机在某个 IPv4 网段上的地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LanAddr {
+    pub ip: Ipv4Addr,
+    pub prefix_len: u8,
+}
+
+impl LanAddr {
+    /// `ip` 是否与本地址处于同一网段
+    fn contains(&self, ip: Ipv4Addr) -> bool {
+        let mask = u32::MAX
+            .checked_shl(32 - u32::from(self.prefix_len))
+            .unwrap_or(0);
+        u32::from(self.ip) & mask == u32::from(ip) & mask
+    }
+}
+
+/// 本机的私有 IPv4 地址 (10/8、172.16/12、192.168/16)
+///
+/// 只公布私有地址: 公网地址不属于局域网，也不应出现在公开的大厅元数据中
+pub fn local_addrs() -> Vec<LanAddr> {
+    let Ok(interfaces) = if_addrs::get_if_addrs() else {
+        return Vec::new();
+    };
+    interfaces
+        .into_iter()
+        .filter_map(|iface| match iface.addr {
+            IfAddr::V4(v4) if v4.ip.is_private() && v4.prefixlen > 0 => Some(LanAddr {
+                ip: v4.ip,
+                prefix_len: v4.prefixlen,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 编码为大厅元数据的值
+pub fn encode(addrs: &[LanAddr]) -> String {
+    addrs
+        .iter()
+        .map(|addr| format!("{}/{}", addr.ip, addr.prefix_len))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 解析房主公布的地址，忽略无法识别的部分
+pub fn parse(value: &str) -> Vec<LanAddr> {
+    value
+        .split(',')
+        .filter_map(|part| {
+            let (ip, prefix_len) = part.trim().split_once('/')?;
+            let prefix_len: u8 = prefix_len.parse().ok().filter(|len| (1..=32).contains(len))?;
+            Some(LanAddr {
+                ip: ip.parse().ok()?,
+                prefix_len,
+            })
+        })
+        .collect()
+}
+
+/// 房主地址中与本机某个地址处于同一网段的
+///
+/// 双方的网段都须包含对方，避免掩码不同的两个网络被误判为同一局域网
+pub fn same_lan(host: &[LanAddr], local: &[LanAddr]) -> Vec<Ipv4Addr> {
+    host.iter()
+        .filter(|host| local.iter().any(|local| local.contains(host.ip) && host.contains(local.ip)))
+        .map(|host| host.ip)
+        .collect()
+}
+
+/// 生成本房间的随机数 (32 位十六进制)，只用于识别房主的机器，不需要密码学强度
+pub fn new_nonce() -> String {
+    let state = RandomState::new();
+    let mut halves = [0u64; 2];
+    for (index, half) in halves.iter_mut().enumerate() {
+        let mut hasher = state.build_hasher();
+        hasher.write_usize(index);
+        *half = hasher.finish();
+    }
+    format!("{:016x}{:016x}", halves[0], halves[1])
+}
+
+/// 编码为 LOBBY_KEY_LAN_VERIFY 的值
+pub fn encode_verify(port: u16, nonce: &str) -> String {
+    format!("{}:{}", port, nonce)
+}
+
+/// 解析 LOBBY_KEY_LAN_VERIFY 的值
+pub fn parse_verify(value: &str) -> Option<(u16, String)> {
+    let (port, nonce) = value.trim().split_once(':')?;
+    let nonce = nonce.trim();
+    if nonce.is_empty() || nonce.len() > MAX_NONCE_LEN {
+        return None;
+    }
+    Some((port.parse().ok()?, nonce.to_string()))
+}
+
+/// 房主在局域网验证端口上回复本房间随机数
+///
+/// 私有网段在不同家庭网络中经常重复，同网段地址上有 MC 服务器不代表那是房主的服务器；
+/// 客户端只在该地址的验证端口回复了房主公布的随机数时才直连
+pub struct NonceResponder {
+    port: u16,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl NonceResponder {
+    pub fn start(nonce: String) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let handle = correlation::spawn(move || respond(listener, nonce, &thread_stop));
+        Ok(Self {
+            port,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for NonceResponder {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn respond(listener: TcpListener, nonce: String, stop: &AtomicBool) {
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((mut stream, _)) => {
+                let _ = stream.set_nonblocking(false);
+                let _ = stream.set_write_timeout(Some(Duration::from_millis(LAN_DIRECT_PROBE_TIMEOUT_MS)));
+                let _ = stream.write_all(nonce.as_bytes());
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(50)),
+            Err(_) => thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+/// `ip` 上的验证端口是否回复了房主公布的随机数
+fn verify(ip: Ipv4Addr, port: u16, nonce: &str) -> io::Result<bool> {
+    let timeout = Duration::from_millis(LAN_DIRECT_PROBE_TIMEOUT_MS);
+    let stream = TcpStream::connect_timeout(&SocketAddr::from((ip, port)), timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    let mut reply = Vec::new();
+    stream.take(MAX_NONCE_LEN as u64).read_to_end(&mut reply)?;
+    Ok(reply == nonce.as_bytes())
+}
+
+/// 依次确认候选地址是房主的机器，并对其 MC 服务器做服务器列表查询，返回第一个可直连的地址
+///
+/// 私有网段在不同家庭网络中经常重复，网段相同不代表是同一个网络：先要求验证端口回复房主的随机数，
+/// 再确认 MC 服务器确实可以连通
+pub fn probe(candidates: &[Ipv4Addr], port: u16, verify_port: u16, nonce: &str) -> Option<SocketAddr> {
+    let timeout = Duration::from_millis(LAN_DIRECT_PROBE_TIMEOUT_MS);
+    candidates.iter().find_map(|&ip| {
+        match verify(ip, verify_port, nonce) {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("局域网地址 {} 不是房主的机器", ip);
+                return None;
+            }
+            Err(e) => {
+                info!("局域网地址 {} 无法验证: {}", ip, e);
+                return None;
+            }
+        }
+        let addr = SocketAddr::from((ip, port));
+        match mc_status::query(addr, timeout) {
+            Ok(_) => Some(addr),
+            Err(e) => {
+                info!("局域网地址 {} 不可用: {}", addr, e);
+                None
+            }
+        }
+    })
+}
+
+/// 一条直连房主 MC 服务器的连接，两个线程各转发一个方向
+struct DirectLink {
+    streams: [TcpStream; 2],
+    handles: [JoinHandle<()>; 2],
+}
+
+/// 客户端与房主在同一局域网时，绕过 Steam 直接转发到房主 MC 服务器的连接
+pub struct DirectBridges {
+    links: Vec<DirectLink>,
+}
+
+impl DirectBridges {
+    pub fn new() -> Self {
+        Self { links: Vec::new() }
+    }
+
+    /// 连接 `server` 并在本地 MC 客户端连接和它之间双向转发
+    ///
+    /// 连接失败时返回错误，`mc` 保持不变，调用方可改经 Steam 转发
+    pub fn connect(&mut self, mc: &TcpStream, server: SocketAddr) -> io::Result<()> {
+        let upstream = TcpStream::connect_timeout(&server, Duration::from_millis(LAN_DIRECT_PROBE_TIMEOUT_MS))?;
+        configure_mc_stream(&upstream)?;
+
+        let upload = (mc.try_clone()?, upstream.try_clone()?);
+        let download = (upstream.try_clone()?, mc.try_clone()?);
+        // 已结束的连接不再需要等待
+        self.links
+            .retain(|link| !link.handles.iter().all(|handle| handle.is_finished()));
+        self.links.push(DirectLink {
+            streams: [mc.try_clone()?, upstream],
+            handles: [
+                correlation::spawn(move || pipe(upload.0, upload.1)),
+                correlation::spawn(move || pipe(download.0, download.1)),
+            ],
+        });
+        Ok(())
+    }
+
+    /// 断开所有直连并等待转发线程退出
+    pub fn stop(self) {
+        for link in self.links {
+            for stream in &link.streams {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            for handle in link.handles {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+/// 把 `from` 读到的数据写入 `to`，读完后关闭 `to` 的写方向
+fn pipe(mut from: TcpStream, mut to: TcpStream) {
+    let _ = io::copy(&mut from, &mut to);
+    let _ = to.shutdown(Shutdown::Write);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_lan() {
+        let host = parse("192.168.1.5/24, 10.8.0.2/16,bogus,172.16.0.1/0");
+        assert_eq!(host.len(), 2);
+        assert_eq!(encode(&host), "192.168.1.5/24,10.8.0.2/16");
+
+        let local = parse("192.168.1.20/24");
+        assert_eq!(same_lan(&host, &local), [Ipv4Addr::new(192, 168, 1, 5)]);
+
+        // 网段不同，或只有一方的网段包含对方
+        assert!(same_lan(&host, &parse("192.168.2.20/24")).is_empty());
+        assert!(same_lan(&host, &parse("10.0.0.9/8")).is_empty());
+        assert_eq!(same_lan(&host, &parse("10.8.3.9/16")), [Ipv4Addr::new(10, 8, 0, 2)]);
+    }
+
+    #[test]
+    fn test_verify_requires_matching_nonce() {
+        let nonce = new_nonce();
+        assert_eq!(nonce.len(), 32);
+        assert_ne!(nonce, new_nonce());
+
+        let responder = NonceResponder::start(nonce.clone()).unwrap();
+        let value = encode_verify(responder.port(), &nonce);
+        assert_eq!(parse_verify(&value), Some((responder.port(), nonce.clone())));
+        assert_eq!(parse_verify("bogus"), None);
+        assert_eq!(parse_verify("123:"), None);
+
+        assert!(verify(Ipv4Addr::LOCALHOST, responder.port(), &nonce).unwrap());
+        // 同一网段上其他人的机器回复不同的随机数
+        assert!(!verify(Ipv4Addr::LOCALHOST, responder.port(), &new_nonce()).unwrap());
+
+        // 没有回复随机数的地址不会去查询 MC 服务器
+        let port = responder.port();
+        drop(responder);
+        assert_eq!(probe(&[Ipv4Addr::LOCALHOST], port, port, &nonce), None);
+    }
+}
@@ -1,17 +1,35 @@
 use log::{info, warn};
-use std::net::UdpSocket;
+use serde::Deserialize;
+use std::net::{Ipv4Addr, UdpSocket};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
-use crate::config::{LAN_BROADCAST_INTERVAL_MS, LAN_DISCOVERY_PORT, LAN_SERVER_NAME};
+use crate::config::{
+    LAN_BROADCAST_INTERVAL_MS, LAN_DISCOVERY_PORT, LAN_MULTICAST_ADDR, LAN_SERVER_NAME,
+    MAX_LAN_MOTD_LEN,
+};
+use crate::correlation;
+use crate::minecraft_discovery::RESERVED_TAGS;
+
+/// LAN广播范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BroadcastScope {
+    /// 仅发送到本机回环地址，只有同一台电脑上的 Minecraft 能发现
+    #[default]
+    Loopback,
+    /// 同时发送到局域网组播和广播地址，局域网内其他设备上的 Minecraft 也能发现
+    Lan,
+}
 
 /// LAN广播器，用于向本地Minecraft客户端发送局域网服务器发现消息
 pub struct LanBroadcaster {
     socket: UdpSocket,
     server_name: String,
     server_port: u16,
+    scope: BroadcastScope,
     running: Arc<AtomicBool>,
 }
 
@@ -21,50 +39,69 @@ impl LanBroadcaster {
     /// # Arguments
     /// * `server_name` - 服务器名称（显示在MC客户端中）
     /// * `server_port` - 服务器端口（MC客户端连接的端口）
+    /// * `scope` - 广播范围，默认仅本机回环
     pub fn new(
         server_name: Option<String>,
         server_port: u16,
+        scope: BroadcastScope,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         // 创建UDP socket用于发送广播
         // 绑定到任意可用端口
         let socket = UdpSocket::bind("0.0.0.0:0")?;
-        
-        // 设置socket允许广播
-        if let Err(e) = socket.set_broadcast(true) {
-            warn!("⚠ 设置广播模式失败: {:?}", e);
+
+        if scope == BroadcastScope::Lan {
+            // 设置socket允许广播
+            if let Err(e) = socket.set_broadcast(true) {
+                warn!("⚠ 设置广播模式失败: {:?}", e);
+            }
+            // 组播仅限本地网段，并回送给本机的监听者
+            if let Err(e) = socket.set_multicast_ttl_v4(1) {
+                warn!("⚠ 设置组播 TTL 失败: {:?}", e);
+            }
+            if let Err(e) = socket.set_multicast_loop_v4(true) {
+                warn!("⚠ 设置组播回环失败: {:?}", e);
+            }
+        }
+
+        let server_name = server_name.unwrap_or_else(|| LAN_SERVER_NAME.to_string());
+        let sanitized = sanitize_motd(&server_name);
+        if sanitized != server_name {
+            warn!("⚠ 服务器名称包含保留标签或过长，已调整为: {}", sanitized);
         }
 
         Ok(LanBroadcaster {
             socket,
-            server_name: server_name.unwrap_or_else(|| LAN_SERVER_NAME.to_string()),
+            server_name: sanitized,
             server_port,
+            scope,
             running: Arc::new(AtomicBool::new(false)),
         })
     }
 
     /// 发送单次LAN发现广播
     fn broadcast_once(&self) -> Result<(), Box<dyn std::error::Error>> {
-        // Minecraft LAN发现消息格式: [MOTD]服务器名称[/MOTD][AD]端口[/AD]
-        let message = format!(
-            "[MOTD]{}[/MOTD][AD]{}[/AD]",
-            self.server_name, self.server_port
-        );
-
-        // Minecraft使用组播地址 224.0.2.60:4445 进行LAN发现
-        // 同时发送到本地回环地址以确保本地客户端能收到
-        let multicast_target = format!("224.0.2.60:{}", LAN_DISCOVERY_PORT);
-        let localhost_target = format!("127.0.0.1:{}", LAN_DISCOVERY_PORT);
-        
-        // 发送到组播地址
-        if let Err(e) = self.socket.send_to(message.as_bytes(), &multicast_target) {
-            warn!("发送组播LAN广播失败: {:?}", e);
-        }
-        
-        // 同时发送到本地回环地址（某些情况下需要）
-        if let Err(e) = self.socket.send_to(message.as_bytes(), &localhost_target) {
+        let message = lan_message(&self.server_name, self.server_port);
+
+        // 始终发送到本地回环地址，本机的 Minecraft 监听 0.0.0.0:4445 能直接收到
+        let localhost_target = (Ipv4Addr::LOCALHOST, LAN_DISCOVERY_PORT);
+        if let Err(e) = self.socket.send_to(message.as_bytes(), localhost_target) {
             warn!("发送本地LAN广播失败: {:?}", e);
         }
 
+        if self.scope == BroadcastScope::Lan {
+            // Minecraft使用组播地址 224.0.2.60:4445 进行LAN发现
+            let multicast_target = (LAN_MULTICAST_ADDR, LAN_DISCOVERY_PORT);
+            if let Err(e) = self.socket.send_to(message.as_bytes(), multicast_target) {
+                warn!("发送组播LAN广播失败: {:?}", e);
+            }
+
+            // 部分网络不转发组播，再发送一次受限广播作为补充
+            let broadcast_target = (Ipv4Addr::BROADCAST, LAN_DISCOVERY_PORT);
+            if let Err(e) = self.socket.send_to(message.as_bytes(), broadcast_target) {
+                warn!("发送局域网广播失败: {:?}", e);
+            }
+        }
+
         Ok(())
     }
 
@@ -75,10 +112,11 @@ impl LanBroadcaster {
         self.running.store(true, Ordering::Relaxed);
         let running = Arc::clone(&self.running);
 
-        let handle = thread::spawn(move || {
+        let handle = correlation::spawn(move || {
             info!("📡 LAN发现广播已启动");
             info!("   服务器名称: {}", self.server_name);
             info!("   服务器端口: {}", self.server_port);
+            info!("   广播范围: {:?}", self.scope);
 
             let mut broadcast_count = 0u32;
 
@@ -122,3 +160,45 @@ impl Drop for BroadcastHandle {
         }
     }
 }
+
+/// 生成 LAN 发现消息
+///
+/// Minecraft LAN发现消息格式: [MOTD]服务器名称[/MOTD][AD]端口[/AD]
+fn lan_message(server_name: &str, server_port: u16) -> String {
+    format!("[MOTD]{}[/MOTD][AD]{}[/AD]", server_name, server_port)
+}
+
+/// 清理用户提供的服务器名称，使其可以安全地放入 MOTD 标签
+///
+/// 名称中的保留标签会破坏接收端的解析，甚至伪造端口，因此反复移除直到不再出现
+/// (避免 "[/MO[AD]TD]" 这类移除后重新拼出标签的输入)，最后截断到长度上限
+fn sanitize_motd(name: &str) -> String {
+    let mut sanitized = name.to_string();
+    while let Some(tag) = RESERVED_TAGS.iter().find(|tag| sanitized.contains(*tag)) {
+        sanitized = sanitized.replace(tag, "");
+    }
+    sanitized.chars().take(MAX_LAN_MOTD_LEN).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crafted_name_cannot_inject_port() {
+        for name in [
+            "Evil[/MOTD][AD]1337[/AD][MOTD]",
+            "[/MO[AD]TD][A[/AD]D]1337[/AD]",
+        ] {
+            let message = lan_message(&sanitize_motd(name), 25565);
+            for tag in RESERVED_TAGS {
+                assert_eq!(message.matches(tag).count(), 1, "{:?} in {:?}", tag, message);
+            }
+            assert!(message.ends_with("[AD]25565[/AD]"));
+        }
+
+        let long_name = "字".repeat(MAX_LAN_MOTD_LEN + 10);
+        assert_eq!(sanitize_motd(&long_name).chars().count(), MAX_LAN_MOTD_LEN);
+        assert_eq!(sanitize_motd("My [Cool] Server"), "My [Cool] Server");
+    }
+}
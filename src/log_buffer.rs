@@ -0,0 +1,126 @@
+use crate::config::LOG_BUFFER_CAPACITY;
+use log::{Level, LevelFilter, Record};
+use serde::{Serialize, Serializer};
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 内存中保留的一条日志，供界面的事件日志和诊断导出使用
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub timestamp_ms: u64,
+    #[serde(serialize_with = "serialize_level")]
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    /// 所属加入/开服尝试的关联 ID
+    pub correlation_id: Option<String>,
+}
+
+impl LogEntry {
+    /// 单行文本形式: "[LEVEL] [#id] message"
+    pub fn line(&self) -> String {
+        match &self.correlation_id {
+            Some(id) => format!("[{}] [#{}] {}", self.level, id, self.message),
+            None => format!("[{}] {}", self.level, self.message),
+        }
+    }
+}
+
+fn serialize_level<S: Serializer>(level: &Level, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(level.as_str())
+}
+
+/// 最近的日志，超过 LOG_BUFFER_CAPACITY 条时丢弃最旧的
+static BUFFER: LazyLock<Mutex<RingBuffer>> =
+    LazyLock::new(|| Mutex::new(RingBuffer::new(LOG_BUFFER_CAPACITY)));
+
+/// 记录一条日志，由 `correlation::Logger` 对每条启用的日志调用
+pub fn push(record: &Record, correlation_id: Option<&str>) {
+    let entry = LogEntry {
+        timestamp_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        level: record.level(),
+        target: record.target().to_string(),
+        message: record.args().to_string(),
+        correlation_id: correlation_id.map(str::to_string),
+    };
+    BUFFER.lock().unwrap().push(entry);
+}
+
+/// 不低于 `min_level` 的最近 `count` 条日志，按时间从旧到新排列
+pub fn recent(count: usize, min_level: LevelFilter) -> Vec<LogEntry> {
+    BUFFER.lock().unwrap().recent(count, min_level)
+}
+
+struct RingBuffer {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+}
+
+impl RingBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, entry: LogEntry) {
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    fn recent(&self, count: usize, min_level: LevelFilter) -> Vec<LogEntry> {
+        let mut entries: Vec<LogEntry> = self
+            .entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.level <= min_level)
+            .take(count)
+            .cloned()
+            .collect();
+        entries.reverse();
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(level: Level, message: &str) -> LogEntry {
+        LogEntry {
+            timestamp_ms: 0,
+            level,
+            target: "test".to_string(),
+            message: message.to_string(),
+            correlation_id: None,
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer_keeps_latest_and_filters_level() {
+        let mut buffer = RingBuffer::new(3);
+        buffer.push(entry(Level::Info, "a"));
+        buffer.push(entry(Level::Warn, "b"));
+        buffer.push(entry(Level::Debug, "c"));
+        buffer.push(entry(Level::Error, "d"));
+
+        let messages = |entries: Vec<LogEntry>| -> Vec<String> {
+            entries.into_iter().map(|entry| entry.message).collect()
+        };
+        // 最旧的 "a" 已被丢弃
+        assert_eq!(messages(buffer.recent(10, LevelFilter::Trace)), ["b", "c", "d"]);
+        assert_eq!(messages(buffer.recent(2, LevelFilter::Trace)), ["c", "d"]);
+        assert_eq!(messages(buffer.recent(10, LevelFilter::Warn)), ["b", "d"]);
+        assert_eq!(messages(buffer.recent(1, LevelFilter::Warn)), ["d"]);
+
+        let json = serde_json::to_value(entry(Level::Warn, "x")).unwrap();
+        assert_eq!(json["level"], "WARN");
+    }
+}
@@ -0,0 +1,78 @@
+use crate::config::LOG_THROTTLE_INTERVAL_SECS;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 主循环中可能每轮都出现的日志的限频器，每个调用点使用一个静态实例
+///
+/// 持续失败时每轮循环 (约 100µs) 都记录一次会淹没日志文件和界面，
+/// 限频后首次出现立即记录，之后每隔 LOG_THROTTLE_INTERVAL_SECS 秒记录一次并附上期间省略的次数
+#[derive(Debug, Default)]
+pub struct LogThrottle {
+    state: Mutex<ThrottleState>,
+}
+
+#[derive(Debug, Default)]
+struct ThrottleState {
+    last_logged: Option<Instant>,
+    suppressed: u64,
+}
+
+impl LogThrottle {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(ThrottleState {
+                last_logged: None,
+                suppressed: 0,
+            }),
+        }
+    }
+
+    /// 本次是否应当记录；应当记录时返回上次记录以来省略的次数
+    pub fn allow(&self) -> Option<u64> {
+        self.allow_at(Instant::now(), Duration::from_secs(LOG_THROTTLE_INTERVAL_SECS))
+    }
+
+    fn allow_at(&self, now: Instant, interval: Duration) -> Option<u64> {
+        let mut state = self.state.lock().ok()?;
+        if state
+            .last_logged
+            .is_some_and(|at| now.duration_since(at) < interval)
+        {
+            state.suppressed += 1;
+            return None;
+        }
+        state.last_logged = Some(now);
+        Some(std::mem::take(&mut state.suppressed))
+    }
+}
+
+/// 附在日志末尾的省略次数说明，没有省略时为空
+pub fn suppressed_note(suppressed: u64) -> String {
+    if suppressed == 0 {
+        String::new()
+    } else {
+        format!(" (此前另有 {} 次相同错误未记录)", suppressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logs_first_then_summarizes() {
+        let throttle = LogThrottle::new();
+        let interval = Duration::from_secs(5);
+        let start = Instant::now();
+
+        assert_eq!(throttle.allow_at(start, interval), Some(0));
+        for ms in 1..=3 {
+            assert_eq!(throttle.allow_at(start + Duration::from_millis(ms), interval), None);
+        }
+        assert_eq!(throttle.allow_at(start + interval, interval), Some(3));
+        assert_eq!(throttle.allow_at(start + interval * 3, interval), Some(0));
+
+        assert_eq!(suppressed_note(0), "");
+        assert!(suppressed_note(3).contains('3'));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::config::{LOOP_SLEEP_MICROS, RAISE_TIMER_RESOLUTION};
+use log::info;
+use std::thread;
+use std::time::Duration;
+
+/// 主循环每轮结束时的休眠
+///
+/// LOOP_SLEEP_MICROS 为 0 时只让出时间片，延迟最低但会占满一个 CPU 核心
+pub fn pause() {
+    if LOOP_SLEEP_MICROS == 0 {
+        thread::yield_now();
+    } else {
+        thread::sleep(Duration::from_micros(LOOP_SLEEP_MICROS));
+    }
+}
+
+/// 会话期间提高系统计时器精度，释放时恢复
+///
+/// Windows 默认的计时器精度约 15.6 ms，主循环的短休眠会被拉长到这个粒度；
+/// 提高到 1 ms 后休眠更接近设定值。其他系统的休眠精度本就足够，不做任何事
+pub struct TimerResolution {
+    raised: bool,
+}
+
+/// 请求的计时器精度 (毫秒)
+#[cfg(windows)]
+const TIMER_PERIOD_MS: u32 = 1;
+
+impl TimerResolution {
+    /// 按 RAISE_TIMER_RESOLUTION 配置提高计时器精度
+    pub fn raise() -> Self {
+        let raised = RAISE_TIMER_RESOLUTION && begin_period();
+        if raised {
+            info!("⏱ 已提高系统计时器精度");
+        }
+        Self { raised }
+    }
+}
+
+impl Drop for TimerResolution {
+    fn drop(&mut self) {
+        if self.raised {
+            end_period();
+        }
+    }
+}
+
+#[cfg(windows)]
+#[link(name = "winmm")]
+extern "system" {
+    fn timeBeginPeriod(period: u32) -> u32;
+    fn timeEndPeriod(period: u32) -> u32;
+}
+
+/// TIMERR_NOERROR
+#[cfg(windows)]
+const TIMERR_NOERROR: u32 = 0;
+
+#[cfg(windows)]
+fn begin_period() -> bool {
+    // SAFETY: timeBeginPeriod 只读取整数参数；成功的调用由 Drop 中的 timeEndPeriod 配对
+    let result = unsafe { timeBeginPeriod(TIMER_PERIOD_MS) };
+    if result != TIMERR_NOERROR {
+        log::warn!("⚠️ 无法提高系统计时器精度 (错误码 {})", result);
+    }
+    result == TIMERR_NOERROR
+}
+
+#[cfg(windows)]
+fn end_period() {
+    // SAFETY: 与 begin_period 中成功的 timeBeginPeriod 使用相同的参数配对
+    unsafe {
+        timeEndPeriod(TIMER_PERIOD_MS);
+    }
+}
+
+#[cfg(not(windows))]
+fn begin_period() -> bool {
+    false
+}
+
+#[cfg(not(windows))]
+fn end_period() {}
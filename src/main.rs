@@ -3,33 +3,139 @@
     windows_subsystem = "windows"
 )]
 
+mod access_list;
+mod app_reliability;
+mod app_version;
+mod auto_stop;
+mod bridge;
 mod callbacks;
+mod capture;
 mod client_mode;
 mod commands;
 mod config;
+mod correlation;
+mod diagnostics;
+mod encryption;
+mod framing;
+mod friends;
 mod host;
+mod i18n;
+mod join_target;
+mod lan_direct;
 mod lan_discovery;
+mod log_buffer;
+mod log_throttle;
+mod loop_timing;
+mod mc_handshake;
+mod mc_readiness;
+mod mc_socket;
+mod mc_status;
 mod metrics;
+mod metrics_log;
+#[cfg(feature = "prometheus")]
+mod metrics_http;
 mod minecraft_discovery;
+mod panic_guard;
+mod presence;
+mod process_stats;
+mod route_preference;
+mod send_queue;
+mod server_properties;
+mod session;
+mod shutdown;
+mod steam;
+mod structured_log;
+mod transport;
+
+use tauri::Manager;
 
 fn main() {
+    #[cfg(feature = "prometheus")]
+    if config::ENABLE_METRICS_ENDPOINT {
+        metrics_http::spawn();
+    }
+
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
-        .plugin(
-            tauri_plugin_log::Builder::default()
+        .setup(|app| {
+            // The log plugin's backend is wrapped so lines from a join/host attempt
+            // carry that attempt's correlation ID
+            let (log_plugin, max_level, logger) = tauri_plugin_log::Builder::default()
                 .targets([
                     tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::LogDir { file_name: None }).filter(|_| true),
                     tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::Webview),
                 ])
-                .build(),
-        )
+                .split(app.handle())?;
+            app.handle().plugin(log_plugin)?;
+            log::set_logger(Box::leak(Box::new(correlation::Logger::new(logger))))
+                .map_err(|e| e.to_string())?;
+            log::set_max_level(max_level);
+
+            // Host allow/block lists persist in the app data directory
+            match app.path().app_data_dir() {
+                Ok(dir) => access_list::load(&dir.join(access_list::FILE_NAME)),
+                Err(e) => log::warn!("{}", e),
+            }
+
+            // Steam overlay "Join Game" requests are forwarded to the frontend
+            match steam::client() {
+                Ok(client) => callbacks::register_join_requests(&client, app.handle().clone()),
+                Err(e) => log::warn!("{}", e),
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             commands::get_steam_name,
+            commands::get_steam_id,
+            commands::is_steam_available,
             commands::get_lobby_id,
+            commands::get_session_state,
+            commands::get_invite_link,
+            commands::find_friend,
+            commands::invite_friend,
+            commands::get_access_lists,
+            commands::set_access_list_entry,
             commands::get_performance_metrics,
+            commands::get_metrics_delta,
+            commands::get_session_summary,
+            commands::get_process_stats,
+            commands::get_mc_readiness,
+            commands::get_local_mc_status,
+            commands::set_metrics_logging,
+            commands::set_packet_capture,
+            commands::export_diagnostics,
+            commands::get_recent_logs,
+            commands::get_language,
+            commands::set_language,
+            commands::get_rich_presence_enabled,
+            commands::set_rich_presence_enabled,
+            commands::get_route_preference,
+            commands::set_route_preference,
+            commands::list_network_interfaces,
             commands::detect_minecraft_server,
+            commands::read_server_properties,
+            commands::check_port_available,
             commands::start_host,
-            commands::join_lobby
+            commands::rotate_password,
+            commands::get_mc_target_port,
+            commands::set_mc_target_port,
+            commands::reset_mc_bridge,
+            commands::pause_forwarding,
+            commands::resume_forwarding,
+            commands::lock_lobby,
+            commands::unlock_lobby,
+            commands::stop_host,
+            commands::leave_lobby,
+            commands::set_rcon_forwarding,
+            commands::set_mc_server,
+            commands::switch_lobby,
+            commands::lobby_requires_password,
+            commands::estimate_lobby_ping,
+            commands::get_lobby_mc_servers,
+            commands::get_network_location,
+            commands::probe_lobby,
+            commands::join_lobby,
+            commands::join_by_connect_string
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,161 @@
+/// 对端发来的首批数据是否像 Minecraft 握手
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Valid,
+    /// 数据不足以判断，按通过处理
+    Incomplete,
+    /// 明显不是 Minecraft 协议
+    Invalid,
+}
+
+/// 握手声明的连接用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intent {
+    /// 服务器列表查询 (状态 + Ping)，连接很快结束
+    Status,
+    /// 登录或转移，之后是游戏流量
+    Play,
+    /// 数据不完整或无法识别
+    Unknown,
+}
+
+/// 旧版 (1.6 及更早) 客户端的服务器列表查询以 0xFE 开头，没有长度前缀
+const LEGACY_PING: u8 = 0xFE;
+
+/// 握手包的包 ID
+const HANDSHAKE_PACKET_ID: u8 = 0x00;
+
+/// 握手包的下一状态字段: 状态查询 / 登录 / 转移 (1.20.5+)
+const NEXT_STATE_STATUS: i32 = 1;
+const NEXT_STATE_LOGIN: i32 = 2;
+const NEXT_STATE_TRANSFER: i32 = 3;
+
+/// Minecraft 数据包的最大长度 (3 字节 VarInt)
+const MAX_PACKET_LEN: i32 = 2_097_151;
+
+/// 检查一条新 MC 连接的首批数据
+///
+/// 只检查开头的 包长度、包 ID (0x00) 和 协议版本 三个字段，
+/// 不验证地址等后续内容，代理转发等修改过的握手仍然可以通过
+pub fn inspect(data: &[u8]) -> Verdict {
+    if data.first() == Some(&LEGACY_PING) {
+        return Verdict::Valid;
+    }
+
+    let (length, mut offset) = match read_varint(data) {
+        Ok(Some(field)) => field,
+        Ok(None) => return Verdict::Incomplete,
+        Err(()) => return Verdict::Invalid,
+    };
+    // 包 ID 和协议版本至少各占 1 字节
+    if !(2..=MAX_PACKET_LEN).contains(&length) {
+        return Verdict::Invalid;
+    }
+
+    match data.get(offset) {
+        Some(&HANDSHAKE_PACKET_ID) => offset += 1,
+        Some(_) => return Verdict::Invalid,
+        None => return Verdict::Incomplete,
+    }
+
+    // 部分查询工具以 -1 表示未知版本；快照版本设置了第 30 位，仍为正数
+    match read_varint(&data[offset..]) {
+        Ok(Some((version, _))) if version >= -1 => Verdict::Valid,
+        Ok(Some(_)) | Err(()) => Verdict::Invalid,
+        Ok(None) => Verdict::Incomplete,
+    }
+}
+
+/// 从首批数据中识别连接用途
+///
+/// 需要完整的握手包 (包括地址和端口) 才能读到下一状态字段，否则返回 Unknown
+pub fn intent(data: &[u8]) -> Intent {
+    if data.first() == Some(&LEGACY_PING) {
+        return Intent::Status;
+    }
+    match next_state(data) {
+        Some(NEXT_STATE_STATUS) => Intent::Status,
+        Some(NEXT_STATE_LOGIN | NEXT_STATE_TRANSFER) => Intent::Play,
+        _ => Intent::Unknown,
+    }
+}
+
+/// 依次读取 包长度、包 ID、协议版本、地址、端口，返回下一状态字段
+fn next_state(mut data: &[u8]) -> Option<i32> {
+    fn take_varint(data: &mut &[u8]) -> Option<i32> {
+        let (value, len) = read_varint(data).ok()??;
+        *data = &data[len..];
+        Some(value)
+    }
+
+    let _length = take_varint(&mut data)?;
+    if take_varint(&mut data)? != HANDSHAKE_PACKET_ID as i32 {
+        return None;
+    }
+    let _version = take_varint(&mut data)?;
+    // 地址字符串和 2 字节端口
+    let address_len = usize::try_from(take_varint(&mut data)?).ok()?;
+    data = data.get(address_len.checked_add(2)?..)?;
+    take_varint(&mut data)
+}
+
+/// 读取 VarInt，返回 (值, 占用字节数)；数据不足时返回 None，超过 5 字节时返回错误
+pub(crate) fn read_varint(data: &[u8]) -> Result<Option<(i32, usize)>, ()> {
+    let mut value: u32 = 0;
+    for (i, &byte) in data.iter().enumerate() {
+        if i == 5 {
+            return Err(());
+        }
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(Some((value as i32, i + 1)));
+        }
+    }
+    if data.len() >= 5 {
+        Err(())
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_handshake() {
+        // 1.20.1 (协议 763) 连接 localhost:25565，下一状态 2 (登录)
+        let mut handshake = vec![0x10, 0x00, 0xFB, 0x05, 0x09];
+        handshake.extend_from_slice(b"localhost");
+        handshake.extend_from_slice(&[0x63, 0xDD, 0x02]);
+        assert_eq!(inspect(&handshake), Verdict::Valid);
+        assert_eq!(inspect(&[0xFE, 0x01]), Verdict::Valid);
+
+        assert_eq!(inspect(&[]), Verdict::Incomplete);
+        assert_eq!(inspect(&handshake[..2]), Verdict::Incomplete);
+        assert_eq!(inspect(&handshake[..3]), Verdict::Incomplete);
+
+        assert_eq!(inspect(b"GET / HTTP/1.1\r\n"), Verdict::Invalid);
+        assert_eq!(inspect(&[0x10, 0x01, 0x00]), Verdict::Invalid);
+        assert_eq!(inspect(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]), Verdict::Invalid);
+    }
+
+    #[test]
+    fn test_intent() {
+        let handshake = |next_state: u8| {
+            let mut data = vec![0x10, 0x00, 0xFB, 0x05, 0x09];
+            data.extend_from_slice(b"localhost");
+            data.extend_from_slice(&[0x63, 0xDD, next_state]);
+            data
+        };
+        assert_eq!(intent(&handshake(1)), Intent::Status);
+        assert_eq!(intent(&handshake(2)), Intent::Play);
+        assert_eq!(intent(&handshake(3)), Intent::Play);
+        assert_eq!(intent(&[0xFE, 0x01]), Intent::Status);
+
+        // 截断在地址或端口中间时无法判断
+        assert_eq!(intent(&handshake(1)[..10]), Intent::Unknown);
+        assert_eq!(intent(&handshake(1)[..16]), Intent::Unknown);
+        assert_eq!(intent(b"GET / HTTP/1.1\r\n"), Intent::Unknown);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::config::{MC_READINESS_RETRY_MS, MC_READINESS_TIMEOUT_MS};
+use crate::correlation;
+use crate::i18n::Message;
+use log::{info, warn};
+use serde::Serialize;
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// 房主本地 MC 服务器可连接状态变化时发给前端的事件，负载为 `McReadiness`
+pub const MC_READINESS_EVENT: &str = "host-mc-readiness";
+
+/// 房主会话能否把玩家转发到本地 MC 服务器
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct McReadiness {
+    pub ready: bool,
+    pub port: u16,
+    /// 未就绪时提示房主启动 MC 服务器的文本
+    pub message: Option<String>,
+}
+
+/// 当前房主会话的就绪状态，没有会话时为 None
+static READINESS: LazyLock<Mutex<Option<McReadiness>>> = LazyLock::new(|| Mutex::new(None));
+
+/// 当前房主会话的就绪状态
+pub fn current() -> Option<McReadiness> {
+    READINESS.lock().ok()?.clone()
+}
+
+/// 在后台检查 127.0.0.1:port 是否可连接，不可连接时定期重试直到成功
+///
+/// 房间在检查完成前就已创建并公布，MC 服务器晚于房间启动时玩家仍可先加入，
+/// 房主则通过 MC_READINESS_EVENT 知道需要启动服务器
+pub struct ReadinessWatch {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ReadinessWatch {
+    pub fn start(port: u16, readiness_tx: Option<Sender<McReadiness>>) -> Self {
+        Self::start_with_interval(port, Duration::from_millis(MC_READINESS_RETRY_MS), readiness_tx)
+    }
+
+    fn start_with_interval(port: u16, interval: Duration, readiness_tx: Option<Sender<McReadiness>>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let handle = correlation::spawn(move || watch(port, interval, &thread_stop, readiness_tx));
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// 试运行不连接 MC 服务器，直接视为就绪
+    pub fn skip(port: u16, readiness_tx: Option<Sender<McReadiness>>) -> Self {
+        publish(
+            McReadiness {
+                ready: true,
+                port,
+                message: None,
+            },
+            readiness_tx.as_ref(),
+        );
+        Self {
+            stop: Arc::new(AtomicBool::new(true)),
+            handle: None,
+        }
+    }
+}
+
+impl Drop for ReadinessWatch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        if let Ok(mut readiness) = READINESS.lock() {
+            *readiness = None;
+        }
+    }
+}
+
+fn watch(port: u16, interval: Duration, stop: &AtomicBool, readiness_tx: Option<Sender<McReadiness>>) {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let timeout = Duration::from_millis(MC_READINESS_TIMEOUT_MS);
+    let mut waiting = false;
+
+    while !stop.load(Ordering::Relaxed) {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(_) => {
+                info!("✅ 本地 MC 服务器 {} 可连接，房间已就绪", addr);
+                publish(
+                    McReadiness {
+                        ready: true,
+                        port,
+                        message: None,
+                    },
+                    readiness_tx.as_ref(),
+                );
+                return;
+            }
+            Err(e) => {
+                if !waiting {
+                    waiting = true;
+                    warn!("⏳ 本地 MC 服务器 {} 暂不可连接 ({})，房间保留，后台继续重试", addr, e);
+                    publish(
+                        McReadiness {
+                            ready: false,
+                            port,
+                            message: Some(Message::WaitingForMcServer.format(&[&addr])),
+                        },
+                        readiness_tx.as_ref(),
+                    );
+                }
+            }
+        }
+
+        // 分段休眠，会话结束或更换端口时尽快退出
+        let retry_at = Instant::now() + interval;
+        while !stop.load(Ordering::Relaxed) && Instant::now() < retry_at {
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+fn publish(readiness: McReadiness, readiness_tx: Option<&Sender<McReadiness>>) {
+    if let Ok(mut current) = READINESS.lock() {
+        *current = Some(readiness.clone());
+    }
+    if let Some(tx) = readiness_tx {
+        let _ = tx.send(readiness);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_waits_until_server_starts() {
+        // 先取得一个空闲端口再释放，此时没有服务在监听
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel();
+        let watch = ReadinessWatch::start_with_interval(port, Duration::from_millis(50), Some(tx));
+
+        let waiting = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(!waiting.ready);
+        assert_eq!(waiting.port, port);
+        assert!(waiting.message.is_some());
+
+        let _listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+        let ready = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(ready.ready);
+        assert_eq!(ready.message, None);
+
+        drop(watch);
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+}
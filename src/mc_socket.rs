@@ -0,0 +1,73 @@
+use crate::config::{TCP_KEEPALIVE_IDLE_SECS, TCP_KEEPALIVE_INTERVAL_SECS, TCP_NODELAY};
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::time::Duration;
+
+/// 为 MC 连接统一设置 TCP 选项
+///
+/// 客户端接受的 MC 连接和房主连向 MC 服务器的连接都应调用此函数。
+/// 开启 keepalive 后，MC 进程崩溃或断网留下的半开连接会在探测失败后
+/// 以读取错误结束，桥接线程随之退出并向对端发送关闭帧。
+pub fn configure_mc_stream(stream: &TcpStream) -> io::Result<()> {
+    stream.set_nodelay(TCP_NODELAY)?;
+
+    let keepalive = TcpKeepalive::new()
+        .with_time(Duration::from_secs(TCP_KEEPALIVE_IDLE_SECS))
+        .with_interval(Duration::from_secs(TCP_KEEPALIVE_INTERVAL_SECS));
+    SockRef::from(stream).set_tcp_keepalive(&keepalive)?;
+    Ok(())
+}
+
+/// 检查本地 TCP 端口当前是否空闲
+///
+/// 临时绑定后立即释放；结果只代表检查时的状态，真正监听时仍需处理绑定失败
+pub fn is_port_available(port: u16) -> bool {
+    TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).is_ok()
+}
+
+/// 从 `preferred` 开始依次绑定，最多再尝试其后的 `fallbacks` 个端口，返回第一个绑定成功的监听
+///
+/// 直接绑定而不是先检查再绑定: 同一台机器上同时启动的多个客户端不会选中同一个端口。
+/// 全部失败时返回最后一次的错误
+pub fn bind_in_range(ip: Ipv4Addr, preferred: u16, fallbacks: u16) -> io::Result<TcpListener> {
+    let mut last_error = io::Error::from(io::ErrorKind::AddrInUse);
+    for port in preferred..=preferred.saturating_add(fallbacks) {
+        match TcpListener::bind((ip, port)) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configure_mc_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (accepted, _) = listener.accept().unwrap();
+
+        for s in [&stream, &accepted] {
+            configure_mc_stream(s).unwrap();
+            assert_eq!(s.nodelay().unwrap(), TCP_NODELAY);
+            assert!(SockRef::from(s).keepalive().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_port_availability() {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(!is_port_available(port));
+        let next = bind_in_range(Ipv4Addr::UNSPECIFIED, port, 10).unwrap();
+        assert_ne!(next.local_addr().unwrap().port(), port);
+        assert!(bind_in_range(Ipv4Addr::UNSPECIFIED, port, 0).is_err());
+
+        drop(listener);
+        assert!(is_port_available(port));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::mc_handshake::read_varint;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 状态查询使用的协议版本，-1 表示未知，服务器按自身版本回复
+const STATUS_PROTOCOL_VERSION: i32 = -1;
+
+/// 握手后进入的状态: 1 = 状态查询
+const NEXT_STATE_STATUS: i32 = 1;
+
+const STATUS_PACKET_ID: i32 = 0x00;
+const PING_PACKET_ID: i32 = 0x01;
+
+/// 状态回复的最大长度，带图标的回复通常只有几十 KB
+const MAX_STATUS_RESPONSE_LEN: usize = 256 * 1024;
+
+/// 服务器列表查询 (Server List Ping) 得到的服务器状态
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServerStatus {
+    /// 去掉格式代码后的 MOTD 纯文本
+    pub motd: String,
+    /// 版本名，如 "1.20.4"
+    pub version: String,
+    pub protocol: i32,
+    pub players_online: u32,
+    pub players_max: u32,
+    /// Ping/Pong 往返时间
+    pub latency_ms: f32,
+}
+
+/// 对 `addr` 执行一次服务器列表查询，确认对方确实是 Minecraft 服务器
+///
+/// 连接、读写均受 `timeout` 限制；对方不按 Minecraft 协议回复时返回 `InvalidData`
+pub fn query(addr: SocketAddr, timeout: Duration) -> io::Result<ServerStatus> {
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.set_nodelay(true)?;
+
+    // 握手 + 状态请求
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, STATUS_PACKET_ID);
+    write_varint(&mut handshake, STATUS_PROTOCOL_VERSION);
+    write_string(&mut handshake, &addr.ip().to_string());
+    handshake.extend_from_slice(&addr.port().to_be_bytes());
+    write_varint(&mut handshake, NEXT_STATE_STATUS);
+
+    let mut request = Vec::new();
+    write_packet(&mut request, &handshake);
+    write_packet(&mut request, &varint_bytes(STATUS_PACKET_ID));
+    let request_sent = Instant::now();
+    stream.write_all(&request)?;
+
+    let response = read_packet(&mut stream)?;
+    let status_rtt = request_sent.elapsed();
+    let mut body = response.as_slice();
+    if take_varint(&mut body)? != STATUS_PACKET_ID {
+        return Err(invalid_data("状态回复的包 ID 不正确"));
+    }
+    let json_len = usize::try_from(take_varint(&mut body)?)
+        .map_err(|_| invalid_data("状态回复的长度为负"))?;
+    let json = body
+        .get(..json_len)
+        .ok_or_else(|| invalid_data("状态回复被截断"))?;
+    let mut status = parse_status_json(json)?;
+
+    // Ping/Pong 测量往返时间；部分服务器 (如代理) 不回 Pong，此时以状态请求的往返时间代替
+    status.latency_ms = match ping(&mut stream) {
+        Ok(rtt) => rtt,
+        Err(_) => status_rtt,
+    }
+    .as_secs_f32()
+        * 1000.0;
+    Ok(status)
+}
+
+/// 发送 Ping 并等待内容相同的 Pong
+fn ping(stream: &mut TcpStream) -> io::Result<Duration> {
+    let payload = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(1, |d| d.as_millis() as i64);
+    let mut body = varint_bytes(PING_PACKET_ID);
+    body.extend_from_slice(&payload.to_be_bytes());
+    let mut packet = Vec::new();
+    write_packet(&mut packet, &body);
+
+    let sent = Instant::now();
+    stream.write_all(&packet)?;
+    let pong = read_packet(stream)?;
+    let rtt = sent.elapsed();
+
+    let mut pong = pong.as_slice();
+    if take_varint(&mut pong)? != PING_PACKET_ID || pong != payload.to_be_bytes() {
+        return Err(invalid_data("Pong 内容不正确"));
+    }
+    Ok(rtt)
+}
+
+/// 解析状态回复中的 JSON
+fn parse_status_json(json: &[u8]) -> io::Result<ServerStatus> {
+    let value: Value =
+        serde_json::from_slice(json).map_err(|e| invalid_data(&format!("状态回复不是 JSON: {}", e)))?;
+    let version = value
+        .get("version")
+        .ok_or_else(|| invalid_data("状态回复缺少 version 字段"))?;
+
+    let count = |field: &str| {
+        value["players"][field]
+            .as_u64()
+            .map_or(0, |n| n.min(u32::MAX as u64) as u32)
+    };
+    let mut motd = String::new();
+    collect_text(&value["description"], &mut motd);
+
+    Ok(ServerStatus {
+        motd: strip_formatting(&motd).trim().to_string(),
+        version: version["name"].as_str().unwrap_or_default().to_string(),
+        protocol: version["protocol"].as_i64().unwrap_or(-1) as i32,
+        players_online: count("online"),
+        players_max: count("max"),
+        latency_ms: 0.0,
+    })
+}
+
+/// 拼接聊天组件 (字符串、{text, extra} 对象或组件数组) 中的文本
+fn collect_text(component: &Value, out: &mut String) {
+    match component {
+        Value::String(text) => out.push_str(text),
+        Value::Array(parts) => parts.iter().for_each(|part| collect_text(part, out)),
+        Value::Object(fields) => {
+            if let Some(text) = fields.get("text") {
+                collect_text(text, out);
+            }
+            if let Some(extra) = fields.get("extra") {
+                collect_text(extra, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 去掉 "§a" 形式的旧式格式代码
+fn strip_formatting(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// 读取一个带长度前缀的数据包，返回去掉长度后的内容
+fn read_packet(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut prefix = Vec::with_capacity(5);
+    let length = loop {
+        let mut byte = [0u8];
+        stream.read_exact(&mut byte)?;
+        prefix.push(byte[0]);
+        match read_varint(&prefix) {
+            Ok(Some((length, _))) => break length,
+            Ok(None) => continue,
+            Err(()) => return Err(invalid_data("数据包长度不是合法的 VarInt")),
+        }
+    };
+    let length = usize::try_from(length)
+        .ok()
+        .filter(|&len| len > 0 && len <= MAX_STATUS_RESPONSE_LEN)
+        .ok_or_else(|| invalid_data("数据包长度超出范围"))?;
+
+    let mut packet = vec![0u8; length];
+    stream.read_exact(&mut packet)?;
+    Ok(packet)
+}
+
+/// 从切片开头取出一个 VarInt
+fn take_varint(data: &mut &[u8]) -> io::Result<i32> {
+    match read_varint(data) {
+        Ok(Some((value, len))) => {
+            *data = &data[len..];
+            Ok(value)
+        }
+        _ => Err(invalid_data("数据包内容不完整")),
+    }
+}
+
+fn write_packet(out: &mut Vec<u8>, body: &[u8]) {
+    write_varint(out, body.len() as i32);
+    out.extend_from_slice(body);
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    write_varint(out, value.len() as i32);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn varint_bytes(value: i32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(5);
+    write_varint(&mut out, value);
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            out.push(value as u8);
+            return;
+        }
+        out.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_parse_status_json() {
+        let json = r#"{
+            "version": {"name": "1.20.4", "protocol": 765},
+            "players": {"max": 20, "online": 3},
+            "description": {"text": "§aHello ", "extra": [{"text": "World"}, "!"]}
+        }"#;
+        let status = parse_status_json(json.as_bytes()).unwrap();
+        assert_eq!(status.motd, "Hello World!");
+        assert_eq!(status.version, "1.20.4");
+        assert_eq!(status.protocol, 765);
+        assert_eq!((status.players_online, status.players_max), (3, 20));
+
+        assert!(parse_status_json(b"HTTP/1.1 400 Bad Request").is_err());
+        assert!(parse_status_json(br#"{"description": "no version"}"#).is_err());
+    }
+
+    #[test]
+    fn test_query_fake_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // 握手和状态请求
+            read_packet(&mut stream).unwrap();
+            read_packet(&mut stream).unwrap();
+
+            let mut body = varint_bytes(STATUS_PACKET_ID);
+            write_string(
+                &mut body,
+                r#"{"version":{"name":"1.21","protocol":767},"players":{"max":8,"online":1},"description":"LAN"}"#,
+            );
+            let mut response = Vec::new();
+            write_packet(&mut response, &body);
+            stream.write_all(&response).unwrap();
+
+            // 原样回复 Ping
+            let ping = read_packet(&mut stream).unwrap();
+            let mut pong = Vec::new();
+            write_packet(&mut pong, &ping);
+            stream.write_all(&pong).unwrap();
+        });
+
+        let status = query(addr, Duration::from_secs(2)).unwrap();
+        assert_eq!(status.motd, "LAN");
+        assert_eq!(status.version, "1.21");
+        assert_eq!((status.players_online, status.players_max), (1, 8));
+        assert!(status.latency_ms >= 0.0);
+
+        // 非 Minecraft 服务
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.write_all(b"SSH-2.0-OpenSSH_9.6\r\n");
+        });
+        assert!(query(addr, Duration::from_secs(2)).is_err());
+    }
+}
@@ -1,15 +1,24 @@
+use crate::config::{DROP_RATE_WINDOW_SECS, LATENCY_SMOOTHING_FACTOR};
 use log::info;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{LazyLock, Mutex};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// 全局性能指标
+///
+/// bytes_* 是 Steam 上实际收发的字节数，等于 MC 负载字节与协议开销字节
+/// (帧头、关闭帧等控制消息) 之和
 pub struct NetworkMetrics {
     packets_sent: AtomicU64,
     packets_received: AtomicU64,
     bytes_sent: AtomicU64,
     bytes_received: AtomicU64,
+    payload_bytes_sent: AtomicU64,
+    payload_bytes_received: AtomicU64,
+    overhead_bytes_sent: AtomicU64,
+    overhead_bytes_received: AtomicU64,
     packets_dropped: AtomicU64,
 }
 
@@ -18,23 +27,152 @@ static METRICS: NetworkMetrics = NetworkMetrics {
     packets_received: AtomicU64::new(0),
     bytes_sent: AtomicU64::new(0),
     bytes_received: AtomicU64::new(0),
+    payload_bytes_sent: AtomicU64::new(0),
+    payload_bytes_received: AtomicU64::new(0),
+    overhead_bytes_sent: AtomicU64::new(0),
+    overhead_bytes_received: AtomicU64::new(0),
     packets_dropped: AtomicU64::new(0),
 };
 
-/// 延迟信息存储 (SteamId -> ping_ms)
-static LATENCY: LazyLock<Mutex<HashMap<u64, u32>>> =
+/// MC 读取队列已满、读取线程被迫等待的次数
+static BACKPRESSURE_EVENTS: AtomicU64 = AtomicU64::new(0);
+
+/// 到某个对端的延迟
+///
+/// Steam 报告的瞬时 ping 波动较大，界面显示指数移动平均 (EWMA) 值，
+/// 抖动为同一权重下偏差的移动标准差
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LatencyStats {
+    /// 最近一次的瞬时延迟
+    pub raw_ms: u32,
+    pub smoothed_ms: f32,
+    pub jitter_ms: f32,
+    /// 偏差平方的移动平均，jitter_ms 为其平方根
+    #[serde(skip)]
+    variance: f32,
+}
+
+impl LatencyStats {
+    fn new(ping_ms: u32) -> Self {
+        Self {
+            raw_ms: ping_ms,
+            smoothed_ms: ping_ms as f32,
+            jitter_ms: 0.0,
+            variance: 0.0,
+        }
+    }
+
+    /// 以平滑系数 `alpha` (0..=1，越大越跟随最新值) 加入一个新样本
+    fn sample(&mut self, ping_ms: u32, alpha: f32) {
+        let alpha = alpha.clamp(0.0, 1.0);
+        let deviation = ping_ms as f32 - self.smoothed_ms;
+        self.raw_ms = ping_ms;
+        self.smoothed_ms += alpha * deviation;
+        self.variance = (1.0 - alpha) * (self.variance + alpha * deviation * deviation);
+        self.jitter_ms = self.variance.sqrt();
+    }
+}
+
+/// 延迟信息存储 (SteamId -> LatencyStats)
+static LATENCY: LazyLock<Mutex<HashMap<u64, LatencyStats>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 
-/// 记录发送的包
-pub fn record_packet_sent(bytes: u64) {
+/// P2P 连接的路由类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteKind {
+    /// 双方直接连通 (ICE 打洞成功)
+    Direct,
+    /// 经 Steam 中继服务器 (SDR) 转发，延迟通常更高
+    Relayed,
+    /// 无法从连接状态判断
+    Unknown,
+}
+
+/// 连接加密状态存储 (SteamId -> 是否加密)，只记录能从连接状态确认的连接
+static ENCRYPTION: LazyLock<Mutex<HashMap<u64, bool>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 路由信息存储 (SteamId -> RouteKind)
+static ROUTES: LazyLock<Mutex<HashMap<u64, RouteKind>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 单个对端的流量计数
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PeerTraffic {
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl PeerTraffic {
+    /// 计算与更早计数的差值
+    pub fn delta(&self, earlier: &PeerTraffic) -> PeerTraffic {
+        PeerTraffic {
+            packets_sent: self.packets_sent.saturating_sub(earlier.packets_sent),
+            packets_received: self.packets_received.saturating_sub(earlier.packets_received),
+            bytes_sent: self.bytes_sent.saturating_sub(earlier.bytes_sent),
+            bytes_received: self.bytes_received.saturating_sub(earlier.bytes_received),
+        }
+    }
+
+    /// 格式化为单个对端的报告行，流量为 `duration` 内的增量
+    pub fn format_report(&self, steam_id: u64, latency: Option<&LatencyStats>, duration: Duration) -> String {
+        let secs = duration.as_secs_f32().max(0.001);
+        let kbps_sent = self.bytes_sent as f32 / secs / 1024.0;
+        let kbps_recv = self.bytes_received as f32 / secs / 1024.0;
+        let latency = match latency {
+            Some(stats) => format!("{:.0}±{:.0} ms", stats.smoothed_ms, stats.jitter_ms),
+            None => "- ms".to_string(),
+        };
+        format!(
+            "{}: ↑ {:.1} KB/s ↓ {:.1} KB/s | 延迟 {}",
+            steam_id, kbps_sent, kbps_recv, latency
+        )
+    }
+}
+
+/// 对端流量存储 (SteamId -> PeerTraffic)
+static PEER_TRAFFIC: LazyLock<Mutex<HashMap<u64, PeerTraffic>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 丢包率滑动窗口的采样
+static DROP_WINDOW: LazyLock<Mutex<DropWindow>> =
+    LazyLock::new(|| Mutex::new(DropWindow::default()));
+
+/// 当前活动会话的开始时间 (单调时钟, 墙上时钟)，由 SessionMetrics 维护
+static SESSION_START: LazyLock<Mutex<Option<(Instant, SystemTime)>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+/// 最近一个会话的累计值和峰值，会话结束后保留到下一个会话开始
+static SESSION_SUMMARY: LazyLock<Mutex<Option<SummaryTracker>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+/// 记录发送的包，分别给出其中的 MC 负载字节数和协议开销字节数
+pub fn record_packet_sent(payload: u64, overhead: u64) {
     METRICS.packets_sent.fetch_add(1, Ordering::Relaxed);
-    METRICS.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+    METRICS.bytes_sent.fetch_add(payload + overhead, Ordering::Relaxed);
+    METRICS.payload_bytes_sent.fetch_add(payload, Ordering::Relaxed);
+    METRICS.overhead_bytes_sent.fetch_add(overhead, Ordering::Relaxed);
 }
 
-/// 记录接收的包
-pub fn record_packet_received(bytes: u64) {
+/// 记录接收的包，分别给出其中的 MC 负载字节数和协议开销字节数
+pub fn record_packet_received(payload: u64, overhead: u64) {
     METRICS.packets_received.fetch_add(1, Ordering::Relaxed);
-    METRICS.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+    METRICS.bytes_received.fetch_add(payload + overhead, Ordering::Relaxed);
+    METRICS.payload_bytes_received.fetch_add(payload, Ordering::Relaxed);
+    METRICS.overhead_bytes_received.fetch_add(overhead, Ordering::Relaxed);
+}
+
+/// 记录一次背压: Steam 发送跟不上 MC 产生数据的速度
+pub fn record_backpressure() {
+    BACKPRESSURE_EVENTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 获取背压发生的累计次数
+pub fn get_backpressure_events() -> u64 {
+    BACKPRESSURE_EVENTS.load(Ordering::Relaxed)
 }
 
 /// 记录丢弃的包
@@ -42,15 +180,46 @@ pub fn record_packet_dropped() {
     METRICS.packets_dropped.fetch_add(1, Ordering::Relaxed);
 }
 
-/// 更新延迟信息
+/// 记录发往某个对端的包
+pub fn record_peer_sent(steam_id: u64, bytes: u64) {
+    if let Ok(mut traffic) = PEER_TRAFFIC.lock() {
+        let entry = traffic.entry(steam_id).or_default();
+        entry.packets_sent += 1;
+        entry.bytes_sent += bytes;
+    }
+}
+
+/// 记录来自某个对端的包
+pub fn record_peer_received(steam_id: u64, bytes: u64) {
+    if let Ok(mut traffic) = PEER_TRAFFIC.lock() {
+        let entry = traffic.entry(steam_id).or_default();
+        entry.packets_received += 1;
+        entry.bytes_received += bytes;
+    }
+}
+
+/// 获取所有对端的流量信息
+#[allow(dead_code)]
+pub fn get_all_peer_traffic() -> HashMap<u64, PeerTraffic> {
+    if let Ok(traffic) = PEER_TRAFFIC.lock() {
+        traffic.clone()
+    } else {
+        HashMap::new()
+    }
+}
+
+/// 加入一个瞬时延迟样本，更新平滑延迟和抖动
 pub fn update_latency(steam_id: u64, ping_ms: u32) {
     if let Ok(mut latency) = LATENCY.lock() {
-        latency.insert(steam_id, ping_ms);
+        latency
+            .entry(steam_id)
+            .and_modify(|stats| stats.sample(ping_ms, LATENCY_SMOOTHING_FACTOR))
+            .or_insert_with(|| LatencyStats::new(ping_ms));
     }
 }
 
 /// 获取所有延迟信息
-pub fn get_all_latencies() -> std::collections::HashMap<u64, u32> {
+pub fn get_all_latencies() -> std::collections::HashMap<u64, LatencyStats> {
     if let Ok(latency) = LATENCY.lock() {
         latency.clone()
     } else {
@@ -58,11 +227,103 @@ pub fn get_all_latencies() -> std::collections::HashMap<u64, u32> {
     }
 }
 
-/// 清除延迟信息
-pub fn clear_latency(steam_id: u64) {
+/// 延迟最差 (平滑延迟最高) 的连接，多人会话用它代表整体体验
+///
+/// 平滑延迟相同时取 Steam ID 较小的一个，结果不随 HashMap 的遍历顺序变化
+pub fn worst_latency(latencies: &HashMap<u64, LatencyStats>) -> Option<(u64, LatencyStats)> {
+    latencies
+        .iter()
+        .max_by(|(id_a, a), (id_b, b)| {
+            a.smoothed_ms
+                .total_cmp(&b.smoothed_ms)
+                .then_with(|| id_b.cmp(id_a))
+        })
+        .map(|(steam_id, stats)| (*steam_id, *stats))
+}
+
+/// 更新路由信息
+pub fn update_route(steam_id: u64, route: RouteKind) {
+    if let Ok(mut routes) = ROUTES.lock() {
+        routes.insert(steam_id, route);
+    }
+}
+
+/// 获取所有路由信息
+pub fn get_all_routes() -> HashMap<u64, RouteKind> {
+    if let Ok(routes) = ROUTES.lock() {
+        routes.clone()
+    } else {
+        HashMap::new()
+    }
+}
+
+/// 记录连接是否加密
+pub fn update_encryption(steam_id: u64, encrypted: bool) {
+    if let Ok(mut encryption) = ENCRYPTION.lock() {
+        encryption.insert(steam_id, encrypted);
+    }
+}
+
+/// 获取所有已确认的连接加密状态
+pub fn get_all_encryption() -> HashMap<u64, bool> {
+    ENCRYPTION.lock().map(|encryption| encryption.clone()).unwrap_or_default()
+}
+
+/// 从 Steam 的详细连接状态文本判断路由类型
+///
+/// steamworks-rs 没有暴露连接信息中的 Relayed 标志，只能解析
+/// `get_detailed_connection_status` 的文本: 优先看描述传输方式的行，
+/// 其中出现 SDR/relay 视为中继，出现 ICE/direct 视为直连
+pub fn classify_route(detail: &str) -> RouteKind {
+    let classify = |text: &str| {
+        let lower = text.to_lowercase();
+        let words: Vec<&str> = lower
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        if words.iter().any(|w| matches!(*w, "sdr" | "relay" | "relayed")) {
+            RouteKind::Relayed
+        } else if words.iter().any(|w| matches!(*w, "ice" | "direct")) {
+            RouteKind::Direct
+        } else {
+            RouteKind::Unknown
+        }
+    };
+
+    detail
+        .lines()
+        .filter(|line| line.to_lowercase().contains("transport"))
+        .map(classify)
+        .find(|route| *route != RouteKind::Unknown)
+        .unwrap_or_else(|| classify(detail))
+}
+
+/// 清除对端的延迟、路由、加密状态和流量信息
+pub fn clear_peer(steam_id: u64) {
     if let Ok(mut latency) = LATENCY.lock() {
         latency.remove(&steam_id);
     }
+    if let Ok(mut routes) = ROUTES.lock() {
+        routes.remove(&steam_id);
+    }
+    if let Ok(mut encryption) = ENCRYPTION.lock() {
+        encryption.remove(&steam_id);
+    }
+    if let Ok(mut traffic) = PEER_TRAFFIC.lock() {
+        traffic.remove(&steam_id);
+    }
+}
+
+/// 获取活动会话的开始时间 (Unix 毫秒) 和已运行时长，无活动会话时返回 None
+pub fn get_session_uptime() -> Option<(u64, Duration)> {
+    let start = SESSION_START.lock().ok()?;
+    start.map(|(instant, started_at)| {
+        let started_at_ms = started_at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        (started_at_ms, instant.elapsed())
+    })
 }
 
 /// 获取当前指标快照
@@ -72,17 +333,73 @@ pub fn get_snapshot() -> MetricsSnapshot {
         packets_received: METRICS.packets_received.load(Ordering::Relaxed),
         bytes_sent: METRICS.bytes_sent.load(Ordering::Relaxed),
         bytes_received: METRICS.bytes_received.load(Ordering::Relaxed),
+        payload_bytes_sent: METRICS.payload_bytes_sent.load(Ordering::Relaxed),
+        payload_bytes_received: METRICS.payload_bytes_received.load(Ordering::Relaxed),
+        overhead_bytes_sent: METRICS.overhead_bytes_sent.load(Ordering::Relaxed),
+        overhead_bytes_received: METRICS.overhead_bytes_received.load(Ordering::Relaxed),
         packets_dropped: METRICS.packets_dropped.load(Ordering::Relaxed),
     }
 }
 
+/// 最近 DROP_RATE_WINDOW_SECS 秒内的丢包率 (百分比)
+///
+/// 每次调用记录一次累计计数的采样，与窗口起点的采样相减得到窗口内的发送数和丢包数，
+/// 因此反映的是当前网络状况而不是整个进程生命周期的平均值
+pub fn drop_rate() -> f32 {
+    let snapshot = get_snapshot();
+    match DROP_WINDOW.lock() {
+        Ok(mut window) => window.sample(
+            Instant::now(),
+            snapshot.packets_sent,
+            snapshot.packets_dropped,
+            Duration::from_secs(DROP_RATE_WINDOW_SECS),
+        ),
+        Err(_) => 0.0,
+    }
+}
+
+/// 累计发送数和丢包数的时间序列采样
+#[derive(Default)]
+struct DropWindow {
+    samples: VecDeque<(Instant, u64, u64)>,
+}
+
+impl DropWindow {
+    /// 加入一个采样并返回窗口内的丢包率 (百分比)
+    fn sample(&mut self, now: Instant, sent: u64, dropped: u64, window: Duration) -> f32 {
+        self.samples.push_back((now, sent, dropped));
+        // 保留窗口起点之前最近的一个采样作为基准
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|(at, _, _)| now.duration_since(*at) >= window)
+        {
+            self.samples.pop_front();
+        }
+
+        let (_, base_sent, base_dropped) = self.samples[0];
+        let sent = sent.saturating_sub(base_sent);
+        let dropped = dropped.saturating_sub(base_dropped);
+        let total = sent + dropped;
+        if total == 0 {
+            0.0
+        } else {
+            dropped as f32 / total as f32 * 100.0
+        }
+    }
+}
+
 /// 性能指标快照
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MetricsSnapshot {
     pub packets_sent: u64,
     pub packets_received: u64,
     pub bytes_sent: u64,
     pub bytes_received: u64,
+    pub payload_bytes_sent: u64,
+    pub payload_bytes_received: u64,
+    pub overhead_bytes_sent: u64,
+    pub overhead_bytes_received: u64,
     pub packets_dropped: u64,
 }
 
@@ -96,10 +413,40 @@ impl MetricsSnapshot {
                 .saturating_sub(earlier.packets_received),
             bytes_sent: self.bytes_sent.saturating_sub(earlier.bytes_sent),
             bytes_received: self.bytes_received.saturating_sub(earlier.bytes_received),
+            payload_bytes_sent: self
+                .payload_bytes_sent
+                .saturating_sub(earlier.payload_bytes_sent),
+            payload_bytes_received: self
+                .payload_bytes_received
+                .saturating_sub(earlier.payload_bytes_received),
+            overhead_bytes_sent: self
+                .overhead_bytes_sent
+                .saturating_sub(earlier.overhead_bytes_sent),
+            overhead_bytes_received: self
+                .overhead_bytes_received
+                .saturating_sub(earlier.overhead_bytes_received),
             packets_dropped: self.packets_dropped.saturating_sub(earlier.packets_dropped),
         }
     }
 
+    /// 自 `baseline` 以来的增量，以及期间计数是否被重置过
+    ///
+    /// 任一计数小于基线说明新会话已重新计数，此时增量从重置时算起，即当前计数本身
+    pub fn since(&self, baseline: &MetricsSnapshot) -> (MetricsSnapshot, bool) {
+        let pairs = [
+            (self.packets_sent, baseline.packets_sent),
+            (self.packets_received, baseline.packets_received),
+            (self.bytes_sent, baseline.bytes_sent),
+            (self.bytes_received, baseline.bytes_received),
+            (self.packets_dropped, baseline.packets_dropped),
+        ];
+        if pairs.iter().any(|(current, base)| current < base) {
+            (self.clone(), true)
+        } else {
+            (self.delta(baseline), false)
+        }
+    }
+
     /// 格式化输出性能报告
     pub fn format_report(&self, duration: Duration) -> String {
         let secs = duration.as_secs_f32();
@@ -107,29 +454,146 @@ impl MetricsSnapshot {
             return String::from("时间太短，无法计算速率");
         }
 
-        let mbps_sent = (self.bytes_sent as f32 / secs) / 1024.0 / 1024.0;
-        let mbps_recv = (self.bytes_received as f32 / secs) / 1024.0 / 1024.0;
+        // 吞吐量只统计 MC 负载，协议开销单独列出
+        let mbps_sent = (self.payload_bytes_sent as f32 / secs) / 1024.0 / 1024.0;
+        let mbps_recv = (self.payload_bytes_received as f32 / secs) / 1024.0 / 1024.0;
         let pps_sent = self.packets_sent as f32 / secs;
         let pps_recv = self.packets_received as f32 / secs;
+        let overhead_kb = (self.overhead_bytes_sent + self.overhead_bytes_received) as f32 / 1024.0;
 
         format!(
-            "发送: {:.2} MB/s ({:.0} pkt/s) | 接收: {:.2} MB/s ({:.0} pkt/s) | 丢包: {}",
-            mbps_sent, pps_sent, mbps_recv, pps_recv, self.packets_dropped
+            "发送: {:.2} MB/s ({:.0} pkt/s) | 接收: {:.2} MB/s ({:.0} pkt/s) | 协议开销: {:.1} KB | 丢包: {}",
+            mbps_sent, pps_sent, mbps_recv, pps_recv, overhead_kb, self.packets_dropped
         )
     }
 }
 
+/// 会话汇总，供会话结束后的汇总界面使用
+///
+/// 与 get_snapshot 的瞬时计数不同，这里是整个会话的累计值和峰值；
+/// 峰值吞吐量按主循环的定期报告间隔计算
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionSummary {
+    /// 会话是否仍在进行
+    pub active: bool,
+    /// 开始时间 (Unix 毫秒)
+    pub started_at_ms: u64,
+    pub duration_secs: u64,
+    /// 会话期间收发的 MC 负载字节数
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub peak_send_kbps: f32,
+    pub peak_recv_kbps: f32,
+    /// 同时在线玩家数的最大值
+    pub peak_players: usize,
+    /// 会话期间出现过的不同玩家数
+    pub players_seen: usize,
+}
+
+/// SessionSummary 的累计状态
+struct SummaryTracker {
+    start_time: Instant,
+    started_at: SystemTime,
+    /// 会话结束时的时长，进行中为 None
+    ended: Option<Duration>,
+    initial_snapshot: MetricsSnapshot,
+    /// 上次采样吞吐量的时间和当时的计数
+    last_sample: (Instant, MetricsSnapshot),
+    peak_send_kbps: f32,
+    peak_recv_kbps: f32,
+    peak_players: usize,
+    players_seen: HashSet<u64>,
+}
+
+impl SummaryTracker {
+    fn new(start_time: Instant, started_at: SystemTime, snapshot: MetricsSnapshot) -> Self {
+        Self {
+            start_time,
+            started_at,
+            ended: None,
+            initial_snapshot: snapshot.clone(),
+            last_sample: (start_time, snapshot),
+            peak_send_kbps: 0.0,
+            peak_recv_kbps: 0.0,
+            peak_players: 0,
+            players_seen: HashSet::new(),
+        }
+    }
+
+    /// 按自上次采样以来的负载字节数更新峰值吞吐量
+    fn sample_throughput(&mut self, now: Instant, snapshot: MetricsSnapshot) {
+        let secs = now.duration_since(self.last_sample.0).as_secs_f32();
+        if secs > 0.0 {
+            let (delta, _) = snapshot.since(&self.last_sample.1);
+            let kbps = |bytes: u64| bytes as f32 / secs / 1024.0;
+            self.peak_send_kbps = self.peak_send_kbps.max(kbps(delta.payload_bytes_sent));
+            self.peak_recv_kbps = self.peak_recv_kbps.max(kbps(delta.payload_bytes_received));
+        }
+        self.last_sample = (now, snapshot);
+    }
+
+    fn record_players(&mut self, players: impl IntoIterator<Item = u64>) {
+        let mut online = 0;
+        for steam_id in players {
+            self.players_seen.insert(steam_id);
+            online += 1;
+        }
+        self.peak_players = self.peak_players.max(online);
+    }
+
+    fn summary(&self, snapshot: &MetricsSnapshot) -> SessionSummary {
+        let (total, _) = snapshot.since(&self.initial_snapshot);
+        let duration = self.ended.unwrap_or_else(|| self.start_time.elapsed());
+        SessionSummary {
+            active: self.ended.is_none(),
+            started_at_ms: self
+                .started_at
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            duration_secs: duration.as_secs(),
+            bytes_sent: total.payload_bytes_sent,
+            bytes_received: total.payload_bytes_received,
+            peak_send_kbps: self.peak_send_kbps,
+            peak_recv_kbps: self.peak_recv_kbps,
+            peak_players: self.peak_players,
+            players_seen: self.players_seen.len(),
+        }
+    }
+}
+
+/// 获取当前或最近一个会话的汇总，从未开始过会话时返回 None
+pub fn get_session_summary() -> Option<SessionSummary> {
+    let snapshot = get_snapshot();
+    let summary = SESSION_SUMMARY.lock().ok()?;
+    summary.as_ref().map(|tracker| tracker.summary(&snapshot))
+}
+
 /// 会话性能追踪器
 pub struct SessionMetrics {
     start_time: Instant,
     initial_snapshot: MetricsSnapshot,
+    /// 上次打印对端报告的时间和当时各对端的流量计数
+    last_peer_report: Instant,
+    peer_traffic: HashMap<u64, PeerTraffic>,
 }
 
 impl SessionMetrics {
     pub fn new() -> Self {
+        let start_time = Instant::now();
+        let started_at = SystemTime::now();
+        let initial_snapshot = get_snapshot();
+        if let Ok(mut start) = SESSION_START.lock() {
+            *start = Some((start_time, started_at));
+        }
+        if let Ok(mut summary) = SESSION_SUMMARY.lock() {
+            *summary = Some(SummaryTracker::new(start_time, started_at, initial_snapshot.clone()));
+        }
         Self {
-            start_time: Instant::now(),
-            initial_snapshot: get_snapshot(),
+            start_time,
+            initial_snapshot,
+            last_peer_report: start_time,
+            peer_traffic: get_all_peer_traffic(),
         }
     }
 
@@ -146,4 +610,203 @@ impl SessionMetrics {
         let (stats, duration) = self.get_session_stats();
         info!("│ 性能报告: {}", stats.format_report(duration));
     }
+
+    /// 逐个对端打印自上次调用以来的吞吐量及延迟，没有对端流量统计时不输出
+    pub fn print_peer_report(&mut self) {
+        let now = Instant::now();
+        let interval = now.duration_since(self.last_peer_report);
+        let traffic = get_all_peer_traffic();
+        let latencies = get_all_latencies();
+
+        let mut steam_ids: Vec<u64> = traffic.keys().copied().collect();
+        steam_ids.sort_unstable();
+        for steam_id in steam_ids {
+            let earlier = self.peer_traffic.get(&steam_id).copied().unwrap_or_default();
+            let line = traffic[&steam_id]
+                .delta(&earlier)
+                .format_report(steam_id, latencies.get(&steam_id), interval);
+            info!("│   {}", line);
+        }
+
+        self.last_peer_report = now;
+        self.peer_traffic = traffic;
+    }
+
+    /// 更新会话汇总的峰值吞吐量，在定期报告时调用
+    pub fn sample_throughput(&self) {
+        self.with_summary(|tracker| tracker.sample_throughput(Instant::now(), get_snapshot()));
+    }
+
+    /// 以当前在线的玩家更新会话汇总的玩家峰值和累计玩家数
+    pub fn record_players(&self, players: impl IntoIterator<Item = u64>) {
+        self.with_summary(|tracker| tracker.record_players(players));
+    }
+
+    /// 只修改属于本会话的汇总
+    fn with_summary(&self, update: impl FnOnce(&mut SummaryTracker)) {
+        if let Ok(mut summary) = SESSION_SUMMARY.lock() {
+            if let Some(tracker) = summary.as_mut().filter(|t| t.start_time == self.start_time) {
+                update(tracker);
+            }
+        }
+    }
+}
+
+impl Drop for SessionMetrics {
+    fn drop(&mut self) {
+        if let Ok(mut start) = SESSION_START.lock() {
+            // 只清除属于本会话的记录
+            if matches!(*start, Some((instant, _)) if instant == self.start_time) {
+                *start = None;
+            }
+        }
+        // 汇总保留到下一个会话开始，只冻结时长
+        let duration = self.start_time.elapsed();
+        self.with_summary(|tracker| tracker.ended = Some(duration));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_route() {
+        let relayed = "Connection state: connected\nTransport: SDR via sto2\nPing: 85ms";
+        assert_eq!(classify_route(relayed), RouteKind::Relayed);
+
+        let direct = "Connection state: connected\nTransport: ICE (udp)\nCandidates considered: relay, direct";
+        assert_eq!(classify_route(direct), RouteKind::Direct);
+
+        assert_eq!(classify_route("Relayed through Steam"), RouteKind::Relayed);
+        assert_eq!(classify_route("Connection state: connected"), RouteKind::Unknown);
+        assert_eq!(classify_route("device service"), RouteKind::Unknown);
+    }
+
+    #[test]
+    fn test_summary_tracks_peaks_and_totals() {
+        let start = Instant::now();
+        let mut tracker = SummaryTracker::new(start, SystemTime::now(), MetricsSnapshot::default());
+
+        let mut snapshot = MetricsSnapshot {
+            payload_bytes_sent: 10 * 1024,
+            payload_bytes_received: 2 * 1024,
+            ..Default::default()
+        };
+        tracker.sample_throughput(start + Duration::from_secs(1), snapshot.clone());
+        snapshot.payload_bytes_sent += 1024;
+        tracker.sample_throughput(start + Duration::from_secs(2), snapshot.clone());
+
+        tracker.record_players([1, 2, 3]);
+        tracker.record_players([3, 4]);
+
+        let summary = tracker.summary(&snapshot);
+        assert!(summary.active);
+        assert_eq!((summary.bytes_sent, summary.bytes_received), (11 * 1024, 2 * 1024));
+        assert_eq!(summary.peak_send_kbps, 10.0);
+        assert_eq!(summary.peak_recv_kbps, 2.0);
+        assert_eq!((summary.peak_players, summary.players_seen), (3, 4));
+
+        tracker.ended = Some(Duration::from_secs(90));
+        let summary = tracker.summary(&snapshot);
+        assert!(!summary.active);
+        assert_eq!(summary.duration_secs, 90);
+    }
+
+    #[test]
+    fn test_peer_report_uses_interval_delta() {
+        let earlier = PeerTraffic {
+            bytes_sent: 1024,
+            bytes_received: 4096,
+            ..Default::default()
+        };
+        let now = PeerTraffic {
+            bytes_sent: 1024 + 10 * 1024,
+            bytes_received: 4096 + 5 * 1024,
+            ..Default::default()
+        };
+        let latency = LatencyStats::new(35);
+        let line = now
+            .delta(&earlier)
+            .format_report(7, Some(&latency), Duration::from_secs(5));
+        assert_eq!(line, "7: ↑ 2.0 KB/s ↓ 1.0 KB/s | 延迟 35±0 ms");
+
+        let line = PeerTraffic::default().format_report(7, None, Duration::from_secs(5));
+        assert!(line.ends_with("延迟 - ms"), "{}", line);
+    }
+
+    #[test]
+    fn test_latency_smoothing() {
+        let mut stats = LatencyStats::new(50);
+        assert_eq!(stats.smoothed_ms, 50.0);
+        assert_eq!(stats.jitter_ms, 0.0);
+
+        // 单个尖峰只把平滑值拉动一部分，抖动随之上升
+        stats.sample(150, 0.2);
+        assert_eq!(stats.raw_ms, 150);
+        assert!((stats.smoothed_ms - 70.0).abs() < 0.01, "{}", stats.smoothed_ms);
+        assert!(stats.jitter_ms > 0.0);
+
+        // 延迟稳定后平滑值收敛，抖动衰减
+        let spike_jitter = stats.jitter_ms;
+        for _ in 0..50 {
+            stats.sample(50, 0.2);
+        }
+        assert!((stats.smoothed_ms - 50.0).abs() < 0.1, "{}", stats.smoothed_ms);
+        assert!(stats.jitter_ms < spike_jitter / 10.0, "{}", stats.jitter_ms);
+    }
+
+    #[test]
+    fn test_worst_latency() {
+        assert!(worst_latency(&HashMap::new()).is_none());
+
+        let latencies = HashMap::from([
+            (3, LatencyStats::new(40)),
+            (1, LatencyStats::new(120)),
+            (2, LatencyStats::new(120)),
+        ]);
+        let (steam_id, stats) = worst_latency(&latencies).unwrap();
+        assert_eq!(steam_id, 1);
+        assert_eq!(stats.raw_ms, 120);
+    }
+
+    #[test]
+    fn test_snapshot_since_baseline() {
+        let snapshot = |packets: u64, bytes: u64| MetricsSnapshot {
+            packets_sent: packets,
+            packets_received: packets,
+            bytes_sent: bytes,
+            bytes_received: bytes,
+            payload_bytes_sent: bytes,
+            payload_bytes_received: bytes,
+            overhead_bytes_sent: 0,
+            overhead_bytes_received: 0,
+            packets_dropped: 0,
+        };
+        let baseline = snapshot(10, 1000);
+
+        let (delta, reset) = snapshot(15, 1500).since(&baseline);
+        assert!(!reset);
+        assert_eq!(delta, snapshot(5, 500));
+
+        // 计数已重置 (新会话)，增量为重置后的计数
+        let (delta, reset) = snapshot(3, 300).since(&baseline);
+        assert!(reset);
+        assert_eq!(delta, snapshot(3, 300));
+    }
+
+    #[test]
+    fn test_drop_rate_window() {
+        let window = Duration::from_secs(10);
+        let start = Instant::now();
+        let mut drops = DropWindow::default();
+
+        // 没有任何流量时不会除以零
+        assert_eq!(drops.sample(start, 0, 0, window), 0.0);
+        // 早期的大量丢包
+        assert_eq!(drops.sample(start + Duration::from_secs(1), 50, 50, window), 50.0);
+        // 之后 98 个包发送成功、丢了 2 个，旧的丢包移出窗口
+        let rate = drops.sample(start + Duration::from_secs(12), 148, 52, window);
+        assert!((rate - 2.0).abs() < 0.01, "{}", rate);
+    }
 }
@@ -0,0 +1,156 @@
+//! Prometheus 文本格式的本地指标端点，仅在启用 `prometheus` feature 时编译
+
+use crate::config::{METRICS_ENDPOINT_ADDR, METRICS_ENDPOINT_PORT};
+use crate::metrics::{self, LatencyStats};
+use log::{info, warn};
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddrV4, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+/// 在后台线程启动指标端点，默认只监听本机地址
+pub fn spawn() {
+    let addr = SocketAddrV4::new(METRICS_ENDPOINT_ADDR, METRICS_ENDPOINT_PORT);
+    let listener = match TcpListener::bind(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("✗ 无法启动指标端点 {}: {}", addr, e);
+            return;
+        }
+    };
+    info!("📈 指标端点已启动: http://{}/metrics", addr);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = handle_request(stream) {
+                warn!("⚠️ 处理指标请求失败: {}", e);
+            }
+        }
+    });
+}
+
+/// 处理单个 HTTP 请求，只响应 GET /metrics
+fn handle_request(mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+
+    let (status, content_type, body) = if request_line.starts_with("GET ") && path == "/metrics" {
+        ("200 OK", "text/plain; version=0.0.4", render())
+    } else {
+        ("404 Not Found", "text/plain", String::from("not found\n"))
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// 写入一个指标的 HELP 和 TYPE 行
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// 以 Prometheus 文本格式输出当前指标
+pub fn render() -> String {
+    let mut out = String::new();
+    let snapshot = metrics::get_snapshot();
+
+    let totals = [
+        ("mcconnect_packets_sent_total", "Packets sent over Steam", snapshot.packets_sent),
+        ("mcconnect_packets_received_total", "Packets received over Steam", snapshot.packets_received),
+        ("mcconnect_bytes_sent_total", "Bytes sent over Steam", snapshot.bytes_sent),
+        ("mcconnect_bytes_received_total", "Bytes received over Steam", snapshot.bytes_received),
+        ("mcconnect_payload_bytes_sent_total", "Minecraft payload bytes sent", snapshot.payload_bytes_sent),
+        ("mcconnect_payload_bytes_received_total", "Minecraft payload bytes received", snapshot.payload_bytes_received),
+        ("mcconnect_overhead_bytes_sent_total", "Protocol overhead bytes sent", snapshot.overhead_bytes_sent),
+        ("mcconnect_overhead_bytes_received_total", "Protocol overhead bytes received", snapshot.overhead_bytes_received),
+        ("mcconnect_packets_dropped_total", "Packets dropped", snapshot.packets_dropped),
+        (
+            "mcconnect_backpressure_events_total",
+            "Times an MC read thread waited for a full send queue",
+            metrics::get_backpressure_events(),
+        ),
+    ];
+    for (name, help, value) in totals {
+        write_header(&mut out, name, "counter", help);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+
+    let uptime = metrics::get_session_uptime().map(|(_, uptime)| uptime.as_secs());
+    write_header(
+        &mut out,
+        "mcconnect_session_uptime_seconds",
+        "gauge",
+        "Uptime of the active session",
+    );
+    let _ = writeln!(out, "mcconnect_session_uptime_seconds {}", uptime.unwrap_or(0));
+
+    // 按 Steam ID 排序，保证输出稳定
+    let mut traffic: Vec<_> = metrics::get_all_peer_traffic().into_iter().collect();
+    traffic.sort_by_key(|(steam_id, _)| *steam_id);
+    let peer_counters = [
+        ("mcconnect_peer_packets_sent_total", "Packets sent to a peer"),
+        ("mcconnect_peer_packets_received_total", "Packets received from a peer"),
+        ("mcconnect_peer_bytes_sent_total", "Bytes sent to a peer"),
+        ("mcconnect_peer_bytes_received_total", "Bytes received from a peer"),
+    ];
+    for (index, (name, help)) in peer_counters.into_iter().enumerate() {
+        write_header(&mut out, name, "counter", help);
+        for (steam_id, peer) in &traffic {
+            let value = match index {
+                0 => peer.packets_sent,
+                1 => peer.packets_received,
+                2 => peer.bytes_sent,
+                _ => peer.bytes_received,
+            };
+            let _ = writeln!(out, "{}{{steam_id=\"{}\"}} {}", name, steam_id, value);
+        }
+    }
+
+    let mut latencies: Vec<_> = metrics::get_all_latencies().into_iter().collect();
+    latencies.sort_by_key(|(steam_id, _)| *steam_id);
+    type LatencyGauge = (&'static str, &'static str, fn(&LatencyStats) -> f32);
+    let latency_gauges: [LatencyGauge; 3] = [
+        ("mcconnect_peer_latency_ms", "Latest round-trip latency to a peer in milliseconds", |s| s.raw_ms as f32),
+        ("mcconnect_peer_latency_smoothed_ms", "Smoothed (EWMA) round-trip latency to a peer in milliseconds", |s| s.smoothed_ms),
+        ("mcconnect_peer_jitter_ms", "Latency jitter to a peer in milliseconds", |s| s.jitter_ms),
+    ];
+    for (name, help, value_of) in latency_gauges {
+        write_header(&mut out, name, "gauge", help);
+        for (steam_id, stats) in &latencies {
+            let value = value_of(stats);
+            let _ = writeln!(out, "{}{{steam_id=\"{}\"}} {}", name, steam_id, value);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_includes_peer_metrics() {
+        metrics::record_peer_sent(76561198000000001, 42);
+        metrics::update_latency(76561198000000001, 35);
+
+        let text = render();
+        assert!(text.contains("# TYPE mcconnect_bytes_sent_total counter"));
+        assert!(text.contains("mcconnect_peer_bytes_sent_total{steam_id=\"76561198000000001\"} 42"));
+        assert!(text.contains("mcconnect_peer_latency_ms{steam_id=\"76561198000000001\"} 35"));
+
+        metrics::clear_peer(76561198000000001);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::metrics::{self, MetricsSnapshot};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender, TryRecvError};
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 写入文件的一条指标记录 (JSON Lines，每行一条)
+#[derive(Debug, Clone, Serialize)]
+struct MetricsRecord {
+    timestamp_ms: u64,
+    role: &'static str,
+    window_ms: u64,
+    packets_sent: u64,
+    packets_received: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+    payload_bytes_sent: u64,
+    payload_bytes_received: u64,
+    overhead_bytes_sent: u64,
+    overhead_bytes_received: u64,
+    packets_dropped: u64,
+    /// Steam ID (字符串) -> 平滑后的延迟 ms
+    latency_ms: HashMap<String, u32>,
+    /// Steam ID (字符串) -> 抖动 ms
+    jitter_ms: HashMap<String, f32>,
+}
+
+/// 写入线程的发送端，None 表示未启用
+static SINK: LazyLock<Mutex<Option<Sender<MetricsRecord>>>> = LazyLock::new(|| Mutex::new(None));
+
+/// 开始把指标追加写入指定文件，若已在记录则切换到新文件
+///
+/// 文件由独立线程缓冲写入，主循环只做一次非阻塞的通道发送
+pub fn start(path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut writer = BufWriter::new(file);
+
+    let (tx, rx) = mpsc::channel::<MetricsRecord>();
+    let display_path = path.display().to_string();
+    thread::spawn(move || {
+        let mut next = rx.recv().ok();
+        while let Some(record) = next {
+            let written = serde_json::to_writer(&mut writer, &record)
+                .map_err(io::Error::from)
+                .and_then(|_| writer.write_all(b"\n"));
+            if let Err(e) = written {
+                warn!("✗ 写入指标文件失败: {}", e);
+                break;
+            }
+            // 周期性记录间隔较长，没有积压时刷新，避免崩溃丢失数据
+            next = match rx.try_recv() {
+                Ok(record) => Some(record),
+                Err(TryRecvError::Empty) => {
+                    let _ = writer.flush();
+                    rx.recv().ok()
+                }
+                Err(TryRecvError::Disconnected) => None,
+            };
+        }
+        let _ = writer.flush();
+        info!("📁 指标记录已停止: {}", display_path);
+    });
+
+    *SINK.lock().unwrap() = Some(tx);
+    info!("📁 指标记录已启用: {}", path.display());
+    Ok(())
+}
+
+/// 停止记录，写入线程会刷新缓冲区后退出
+pub fn stop() {
+    SINK.lock().unwrap().take();
+}
+
+/// 提交一条周期性指标，未启用时什么也不做
+pub fn record(role: &'static str, stats: &MetricsSnapshot, window_ms: u64) {
+    let sink = SINK.lock().unwrap();
+    let Some(tx) = sink.as_ref() else {
+        return;
+    };
+
+    let latencies = metrics::get_all_latencies();
+    let record = MetricsRecord {
+        timestamp_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        role,
+        window_ms,
+        packets_sent: stats.packets_sent,
+        packets_received: stats.packets_received,
+        bytes_sent: stats.bytes_sent,
+        bytes_received: stats.bytes_received,
+        payload_bytes_sent: stats.payload_bytes_sent,
+        payload_bytes_received: stats.payload_bytes_received,
+        overhead_bytes_sent: stats.overhead_bytes_sent,
+        overhead_bytes_received: stats.overhead_bytes_received,
+        packets_dropped: stats.packets_dropped,
+        latency_ms: latencies
+            .iter()
+            .map(|(steam_id, stats)| (steam_id.to_string(), stats.smoothed_ms.round() as u32))
+            .collect(),
+        jitter_ms: latencies
+            .iter()
+            .map(|(steam_id, stats)| (steam_id.to_string(), stats.jitter_ms))
+            .collect(),
+    };
+    let _ = tx.send(record);
+}
+
+/// 默认的记录文件: <应用数据目录>/metrics/session-<启动时间>.jsonl
+pub fn default_path(data_dir: &Path) -> PathBuf {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    data_dir
+        .join("metrics")
+        .join(format!("session-{}.jsonl", started))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_records_are_appended_as_json_lines() {
+        let path = std::env::temp_dir().join(format!("mcconnect-metrics-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        start(&path).unwrap();
+        let stats = MetricsSnapshot {
+            packets_sent: 3,
+            packets_received: 4,
+            bytes_sent: 300,
+            bytes_received: 400,
+            payload_bytes_sent: 270,
+            payload_bytes_received: 360,
+            overhead_bytes_sent: 30,
+            overhead_bytes_received: 40,
+            packets_dropped: 1,
+        };
+        record("host", &stats, 5000);
+        record("host", &stats, 5000);
+        stop();
+
+        // 写入线程异步退出，等待文件内容落盘
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let content = loop {
+            let content = fs::read_to_string(&path).unwrap_or_default();
+            if content.lines().count() == 2 || Instant::now() > deadline {
+                break content;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["role"], "host");
+        assert_eq!(lines[0]["bytes_sent"], 300);
+        assert_eq!(lines[1]["packets_dropped"], 1);
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,6 +1,11 @@
-use log::{info, warn};
+use crate::config::{LAN_DISCOVERY_PORT, LAN_MULTICAST_ADDR, MAX_LAN_MOTD_LEN, MC_STATUS_TIMEOUT_MS};
+use crate::mc_status;
+use log::info;
 use serde::{Deserialize, Serialize};
-use std::net::{Ipv4Addr, SocketAddr, TcpStream, UdpSocket};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream, UdpSocket};
 use std::time::{Duration, Instant};
 
 /// Minecraft 服务器信息
@@ -10,44 +15,125 @@ pub struct MinecraftServer {
     pub port: u16,
     pub motd: String,
     pub latency_ms: f32,
+    /// 是否通过服务器列表查询确认了对方是 Minecraft 服务器；
+    /// 为 false 时只确认了端口可连接，下面的字段均为 None
+    pub verified: bool,
+    pub version: Option<String>,
+    pub players_online: Option<u32>,
+    pub players_max: Option<u32>,
 }
 
-/// 监听 Minecraft LAN 发现广播，查找本地服务器
+/// 可用于接收组播的本机网络接口
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub ip: String,
+}
+
+/// 列出本机的 IPv4 网络接口 (不含回环接口)
 ///
-/// # Returns
-/// 返回找到的第一个服务器信息，如果超时未找到则返回 None
-pub fn discover_minecraft_server() -> Option<MinecraftServer> {
-    info!("🔍 开始搜索本地 Minecraft 服务器...");
+/// 有 VPN 或虚拟机网卡的机器上，系统默认选择的接口可能收不到局域网广播，
+/// 此时由用户从列表中指定
+pub fn list_interfaces() -> io::Result<Vec<NetworkInterface>> {
+    let mut interfaces: Vec<NetworkInterface> = if_addrs::get_if_addrs()?
+        .into_iter()
+        .filter(|iface| !iface.is_loopback())
+        .filter_map(|iface| match iface.ip() {
+            IpAddr::V4(ip) => Some(NetworkInterface {
+                name: iface.name,
+                ip: ip.to_string(),
+            }),
+            IpAddr::V6(_) => None,
+        })
+        .collect();
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.ip.cmp(&b.ip)));
+    Ok(interfaces)
+}
 
-    // 创建 UDP socket 并绑定到组播端口
-    let socket = match UdpSocket::bind("0.0.0.0:4445") {
-        Ok(s) => s,
-        Err(e) => {
-            warn!("✗ 无法绑定 UDP 端口 4445: {}", e);
-            return None;
+/// 无法完成搜索的原因；搜索正常结束但没有找到服务器不是错误
+///
+/// 序列化为 `{ "kind": "bind", ... }`，前端据此给出针对性的建议
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiscoveryError {
+    /// 指定的接口地址不是合法的 IPv4 地址
+    InvalidInterface { address: String },
+    /// 无法绑定发现端口，通常是权限或防火墙限制
+    Bind { port: u16, reason: String },
+    /// 无法加入组播组，通常是没有可用的网卡或网络禁止组播
+    MulticastJoin { reason: String },
+    /// 设置超时或接收数据失败
+    Socket { reason: String },
+}
+
+impl fmt::Display for DiscoveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiscoveryError::InvalidInterface { address } => {
+                write!(f, "无效的网络接口地址: {}", address)
+            }
+            DiscoveryError::Bind { port, reason } => {
+                write!(f, "无法绑定 UDP 端口 {}: {}", port, reason)
+            }
+            DiscoveryError::MulticastJoin { reason } => write!(f, "无法加入组播组: {}", reason),
+            DiscoveryError::Socket { reason } => write!(f, "接收 LAN 广播失败: {}", reason),
         }
-    };
+    }
+}
 
-    // 加入组播组 224.0.2.60
-    let multicast_addr = Ipv4Addr::new(224, 0, 2, 60);
-    let interface_addr = Ipv4Addr::new(0, 0, 0, 0);
+impl std::error::Error for DiscoveryError {}
 
-    if let Err(e) = socket.join_multicast_v4(&multicast_addr, &interface_addr) {
-        warn!("✗ 无法加入组播组: {}", e);
-        return None;
-    }
+/// 监听 Minecraft LAN 发现广播，查找本地服务器
+///
+/// # Arguments
+/// * `timeout` - 总搜索时长，超过后放弃
+/// * `interface` - 在哪个接口上加入组播组，`0.0.0.0` 表示由系统选择
+/// * `verify` - 是否用服务器列表查询确认服务器并读取真实的 MOTD、版本和人数；
+///   查询失败时退回只测试端口连通性
+///
+/// # Returns
+/// 返回找到的第一个服务器信息，如果超时未找到则返回 `Ok(None)`；
+/// 无法监听广播时返回具体原因
+pub fn discover_minecraft_server(
+    timeout: Duration,
+    interface: Ipv4Addr,
+    verify: bool,
+) -> Result<Option<MinecraftServer>, DiscoveryError> {
+    info!("🔍 开始搜索本地 Minecraft 服务器 (超时 {} 秒)...", timeout.as_secs());
 
-    // 设置 3 秒超时
-    if let Err(e) = socket.set_read_timeout(Some(Duration::from_secs(3))) {
-        warn!("✗ 无法设置超时: {}", e);
-        return None;
-    }
+    // 创建 UDP socket 并绑定到组播端口
+    let socket = bind_discovery_socket().map_err(|e| DiscoveryError::Bind {
+        port: LAN_DISCOVERY_PORT,
+        reason: e.to_string(),
+    })?;
+
+    // 加入组播组
+    socket
+        .join_multicast_v4(&LAN_MULTICAST_ADDR, &interface)
+        .map_err(|e| DiscoveryError::MulticastJoin {
+            reason: e.to_string(),
+        })?;
 
-    info!("📡 监听组播地址 224.0.2.60:4445...");
+    info!(
+        "📡 监听组播地址 {}:{} (接口 {})...",
+        LAN_MULTICAST_ADDR, LAN_DISCOVERY_PORT, interface
+    );
 
-    // 监听广播消息
+    // 监听广播消息，超时针对整个搜索过程而不是单次接收
+    let deadline = Instant::now() + timeout;
     let mut buffer = [0u8; 1024];
     loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            info!("⏱ 搜索超时，未找到 Minecraft 服务器");
+            break;
+        }
+        socket
+            .set_read_timeout(Some(remaining))
+            .map_err(|e| DiscoveryError::Socket {
+                reason: e.to_string(),
+            })?;
+
         match socket.recv_from(&mut buffer) {
             Ok((size, addr)) => {
                 let message = String::from_utf8_lossy(&buffer[..size]);
@@ -56,27 +142,20 @@ pub fn discover_minecraft_server() -> Option<MinecraftServer> {
                 // 解析消息: [MOTD]服务器名称[/MOTD][AD]端口[/AD]
                 if let Some(parsed) = parse_lan_message(&message) {
                     let server_addr = SocketAddr::new(addr.ip(), parsed.port);
-
-                    let now = Instant::now();
-                    let latency =
-                        if let Ok(_stream) = TcpStream::connect_timeout(&server_addr, Duration::from_secs(1)) {
-                            now.elapsed().as_secs_f32() * 1000.0
-                        } else {
-                            -1.0
-                        };
-
-                    let server = MinecraftServer {
-                        ip: server_addr.ip().to_string(),
-                        port: parsed.port,
-                        motd: parsed.motd,
-                        latency_ms: latency,
-                    };
+                    let server = probe_server(server_addr, parsed.motd, verify);
 
                     info!(
-                        "✓ 发现 Minecraft 服务器: {} ({}:{}) - 延迟: {:.2} ms",
-                        server.motd, server.ip, server.port, server.latency_ms
+                        "✓ 发现 Minecraft 服务器: {} ({}:{}) - 延迟: {:.2} ms{}",
+                        server.motd,
+                        server.ip,
+                        server.port,
+                        server.latency_ms,
+                        match &server.version {
+                            Some(version) => format!(" - 版本 {}", version),
+                            None => String::new(),
+                        }
                     );
-                    return Some(server);
+                    return Ok(Some(server));
                 }
             }
             Err(e) => {
@@ -85,15 +164,68 @@ pub fn discover_minecraft_server() -> Option<MinecraftServer> {
                     || e.kind() == std::io::ErrorKind::TimedOut
                 {
                     info!("⏱ 搜索超时，未找到 Minecraft 服务器");
-                } else {
-                    warn!("✗ 接收数据失败: {}", e);
+                    break;
                 }
-                break;
+                return Err(DiscoveryError::Socket {
+                    reason: e.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// 确认广播中的服务器可以连接，`verify` 时优先用服务器列表查询读取真实信息
+fn probe_server(addr: SocketAddr, lan_motd: String, verify: bool) -> MinecraftServer {
+    if verify {
+        match mc_status::query(addr, Duration::from_millis(MC_STATUS_TIMEOUT_MS)) {
+            Ok(status) => {
+                return MinecraftServer {
+                    ip: addr.ip().to_string(),
+                    port: addr.port(),
+                    // 局域网世界的描述可能为空，此时保留广播中的名称
+                    motd: if status.motd.is_empty() { lan_motd } else { status.motd },
+                    latency_ms: status.latency_ms,
+                    verified: true,
+                    version: Some(status.version),
+                    players_online: Some(status.players_online),
+                    players_max: Some(status.players_max),
+                };
             }
+            Err(e) => info!("⚠ {} 的服务器列表查询失败，只检查端口连通性: {}", addr, e),
         }
     }
 
-    None
+    let now = Instant::now();
+    let latency = if let Ok(_stream) = TcpStream::connect_timeout(&addr, Duration::from_secs(1)) {
+        now.elapsed().as_secs_f32() * 1000.0
+    } else {
+        -1.0
+    };
+    MinecraftServer {
+        ip: addr.ip().to_string(),
+        port: addr.port(),
+        motd: lan_motd,
+        latency_ms: latency,
+        verified: false,
+        version: None,
+        players_online: None,
+        players_max: None,
+    }
+}
+
+/// 以地址复用方式绑定发现端口
+///
+/// Minecraft 自身或其他发现工具可能已经监听了该端口，开启 SO_REUSEADDR
+/// (Unix 上同时开启 SO_REUSEPORT) 后多个监听者可以同时收到组播
+fn bind_discovery_socket() -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))))]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, LAN_DISCOVERY_PORT).into())?;
+    Ok(socket.into())
 }
 
 /// 从广播消息中解析出的信息
@@ -102,16 +234,36 @@ struct ParsedInfo {
     motd: String,
 }
 
+/// 广播协议中的保留标签，不允许出现在标签内容中
+pub(crate) const RESERVED_TAGS: [&str; 4] = ["[MOTD]", "[/MOTD]", "[AD]", "[/AD]"];
+
 /// 解析 Minecraft LAN 广播消息
 ///
 /// 消息格式: [MOTD]服务器名称[/MOTD][AD]端口[/AD]
+///
+/// 输入来自网络，可能是畸形或恶意的：标签缺失、重复、嵌套或交叠，MOTD 过长，
+/// 端口不是合法数字等情况一律返回 None
 fn parse_lan_message(message: &str) -> Option<ParsedInfo> {
-    // 提取 MOTD
+    let motd_span = tag_span(message, "MOTD")?;
+    let ad_span = tag_span(message, "AD")?;
+
+    // 两个标签的范围（含标签本身）不能交叠
+    if motd_span.outer_end > ad_span.outer_start && ad_span.outer_end > motd_span.outer_start {
+        return None;
+    }
+
+    // 提取 MOTD 并限制长度
     let motd = extract_tag_value(message, "MOTD")?;
+    if motd.chars().count() > MAX_LAN_MOTD_LEN {
+        return None;
+    }
 
-    // 提取端口
+    // 提取端口，只接受纯数字
     let port_str = extract_tag_value(message, "AD")?;
-    let port = port_str.parse::<u16>().ok()?;
+    if port_str.is_empty() || !port_str.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let port = port_str.parse::<u16>().ok().filter(|&port| port != 0)?;
 
     Some(ParsedInfo {
         port,
@@ -119,27 +271,74 @@ fn parse_lan_message(message: &str) -> Option<ParsedInfo> {
     })
 }
 
+/// 标签在消息中的位置
+struct TagSpan {
+    /// 开始标签的起始位置
+    outer_start: usize,
+    /// 内容起始位置
+    inner_start: usize,
+    /// 内容结束位置（结束标签的起始位置）
+    inner_end: usize,
+    /// 结束标签之后的位置
+    outer_end: usize,
+}
+
+/// 定位标签，要求开始和结束标签各恰好出现一次且顺序正确
+fn tag_span(message: &str, tag: &str) -> Option<TagSpan> {
+    let start_tag = format!("[{}]", tag);
+    let end_tag = format!("[/{}]", tag);
+
+    if message.matches(&start_tag).count() != 1 || message.matches(&end_tag).count() != 1 {
+        return None;
+    }
+
+    let outer_start = message.find(&start_tag)?;
+    let inner_start = outer_start + start_tag.len();
+    let inner_end = message.find(&end_tag)?;
+
+    if inner_start > inner_end {
+        return None;
+    }
+
+    Some(TagSpan {
+        outer_start,
+        inner_start,
+        inner_end,
+        outer_end: inner_end + end_tag.len(),
+    })
+}
+
 /// 从消息中提取标签值
 ///
 /// 例如: extract_tag_value("[MOTD]My Server[/MOTD]", "MOTD") -> Some("My Server")
+///
+/// 标签内容中不允许再出现任何保留标签（嵌套）
 fn extract_tag_value<'a>(message: &'a str, tag: &str) -> Option<&'a str> {
-    let start_tag = format!("[{}]", tag);
-    let end_tag = format!("[/{}]", tag);
-    
-    let start = message.find(&start_tag)? + start_tag.len();
-    let end = message.find(&end_tag)?;
-    
-    if start < end {
-        Some(&message[start..end])
-    } else {
-        None
+    let span = tag_span(message, tag)?;
+    let value = &message[span.inner_start..span.inner_end];
+
+    if RESERVED_TAGS.iter().any(|reserved| value.contains(reserved)) {
+        return None;
     }
+    Some(value)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_discovery_socket_coexists_with_other_listeners() {
+        let first = bind_discovery_socket().unwrap();
+        let second = bind_discovery_socket().unwrap();
+        // 复用地址后两个监听者都能加入组播组 (沙箱环境可能没有组播路由)
+        for socket in [&first, &second] {
+            if let Err(e) = socket.join_multicast_v4(&LAN_MULTICAST_ADDR, &Ipv4Addr::UNSPECIFIED) {
+                eprintln!("跳过组播加入检查: {}", e);
+            }
+        }
+    }
+
     #[test]
     fn test_parse_lan_message() {
         let message = "[MOTD]My Test Server[/MOTD][AD]25565[/AD]";
@@ -154,4 +353,56 @@ mod tests {
         assert_eq!(extract_tag_value("[AD]12345[/AD]", "AD"), Some("12345"));
         assert_eq!(extract_tag_value("Invalid", "MOTD"), None);
     }
+
+    #[test]
+    fn test_parse_truncated_messages() {
+        assert!(parse_lan_message("").is_none());
+        assert!(parse_lan_message("[MOTD]Server[/MOTD]").is_none());
+        assert!(parse_lan_message("[MOTD]Server[/MOTD][AD]25565").is_none());
+        assert!(parse_lan_message("[MOTD]Server[AD]25565[/AD]").is_none());
+        assert!(parse_lan_message("[MOTD]Server[/MOTD][AD]25565[/A").is_none());
+    }
+
+    #[test]
+    fn test_parse_repeated_tags() {
+        assert!(parse_lan_message("[MOTD]A[/MOTD][MOTD]B[/MOTD][AD]25565[/AD]").is_none());
+        assert!(parse_lan_message("[MOTD]A[/MOTD][AD]1[/AD][AD]25565[/AD]").is_none());
+        assert!(parse_lan_message("[MOTD]A[/MOTD][/MOTD][AD]25565[/AD]").is_none());
+    }
+
+    #[test]
+    fn test_parse_nested_and_overlapping_tags() {
+        // AD 嵌套在 MOTD 中
+        assert!(parse_lan_message("[MOTD]x[AD]1[/AD]y[/MOTD]").is_none());
+        // MOTD 与 AD 交叠
+        assert!(parse_lan_message("[MOTD]x[AD]y[/MOTD]25565[/AD]").is_none());
+        // 结束标签出现在开始标签之前
+        assert!(parse_lan_message("[/MOTD]x[MOTD][AD]25565[/AD]").is_none());
+    }
+
+    #[test]
+    fn test_parse_invalid_ports() {
+        for ad in ["", "abc", "25565abc", " 25565", "+25565", "-1", "0", "65536", "99999999999999999999"] {
+            let message = format!("[MOTD]Server[/MOTD][AD]{}[/AD]", ad);
+            assert!(parse_lan_message(&message).is_none(), "port {:?} should be rejected", ad);
+        }
+    }
+
+    #[test]
+    fn test_parse_motd_length_limit() {
+        let long_motd = "字".repeat(MAX_LAN_MOTD_LEN + 1);
+        let message = format!("[MOTD]{}[/MOTD][AD]25565[/AD]", long_motd);
+        assert!(parse_lan_message(&message).is_none());
+
+        let max_motd = "字".repeat(MAX_LAN_MOTD_LEN);
+        let message = format!("[MOTD]{}[/MOTD][AD]25565[/AD]", max_motd);
+        assert_eq!(parse_lan_message(&message).unwrap().motd, max_motd);
+    }
+
+    #[test]
+    fn test_parse_tolerates_order_and_empty_motd() {
+        let parsed = parse_lan_message("[AD]25565[/AD][MOTD][/MOTD]").unwrap();
+        assert_eq!(parsed.port, 25565);
+        assert_eq!(parsed.motd, "");
+    }
 }
@@ -0,0 +1,95 @@
+use log::error;
+use serde::Serialize;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+/// 会话的工作线程 panic 时发给前端的事件，负载为 `ThreadPanic`
+pub const THREAD_PANIC_EVENT: &str = "session-thread-panicked";
+
+/// 一次被捕获的线程 panic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThreadPanic {
+    /// 线程的用途，如 "桥接线程 0"
+    pub thread: String,
+    pub message: String,
+}
+
+/// 尚未被会话主循环取走的 panic
+static PANICS: LazyLock<Mutex<Vec<ThreadPanic>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// PANICS 非空，主循环每轮只读这个标志，不必加锁
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// 运行线程主体，捕获其中的 panic 并记录，返回 None
+///
+/// 线程 panic 后不会通知任何人，会话会在缺少这个线程的情况下继续运行；
+/// 记录下来的 panic 由主循环通过 `take` 取走，再决定移除连接还是结束会话
+pub fn run<T>(thread: impl Into<String>, body: impl FnOnce() -> T) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(value) => Some(value),
+        Err(payload) => {
+            let report = ThreadPanic {
+                thread: thread.into(),
+                message: panic_message(payload.as_ref()),
+            };
+            error!("💥 {} 发生 panic: {}", report.thread, report.message);
+            if let Ok(mut panics) = PANICS.lock() {
+                panics.push(report);
+                PENDING.store(true, Ordering::Relaxed);
+            }
+            None
+        }
+    }
+}
+
+/// 是否有尚未处理的 panic
+pub fn pending() -> bool {
+    PENDING.load(Ordering::Relaxed)
+}
+
+/// 取走所有尚未处理的 panic
+pub fn take() -> Vec<ThreadPanic> {
+    PANICS
+        .lock()
+        .map(|mut panics| {
+            PENDING.store(false, Ordering::Relaxed);
+            std::mem::take(&mut *panics)
+        })
+        .unwrap_or_default()
+}
+
+/// panic! 的参数通常是 &str 或 String
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("未知错误"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_panic_is_reported() {
+        let handle = thread::spawn(|| run("测试线程", || -> u32 { panic!("注入的错误 {}", 42) }));
+        // panic 被捕获，线程正常结束
+        assert_eq!(handle.join().unwrap(), None);
+        assert!(pending());
+
+        let panics: Vec<ThreadPanic> = take().into_iter().filter(|p| p.thread == "测试线程").collect();
+        assert_eq!(
+            panics,
+            [ThreadPanic {
+                thread: String::from("测试线程"),
+                message: String::from("注入的错误 42"),
+            }]
+        );
+
+        assert_eq!(run("测试线程", || 7), Some(7));
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use steamworks::{Client, LobbyId};
+
+/// 是否向好友展示会话状态，部分用户出于隐私考虑会关闭
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// 查询富状态是否启用
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// 启用或关闭富状态，关闭时立即清除已设置的内容
+pub fn set_enabled(client: &Client, enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        clear_presence(client);
+    }
+}
+
+/// 设置好友列表中显示的状态文本
+pub fn set_status(client: &Client, status: &str) {
+    if is_enabled() {
+        client.friends().set_rich_presence("status", Some(status));
+    }
+}
+
+/// 设置 connect 键，好友可通过 Steam 覆盖层的"加入游戏"直接进入房间
+pub fn set_connect(client: &Client, lobby_id: LobbyId) {
+    if is_enabled() {
+        client
+            .friends()
+            .set_rich_presence("connect", Some(&format!("+connect_lobby {}", lobby_id.raw())));
+    }
+}
+
+/// 房主会话状态
+pub fn set_hosting(client: &Client, players: usize) {
+    set_status(client, &format!("正在开服 — {} 名玩家", players));
+}
+
+/// 客户端会话状态
+pub fn set_connected(client: &Client, host_name: &str) {
+    set_status(client, &format!("已连接到 {} 的世界", host_name));
+}
+
+/// 会话结束时清除所有富状态
+pub fn clear_presence(client: &Client) {
+    client.friends().clear_rich_presence();
+}
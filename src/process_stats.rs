@@ -0,0 +1,182 @@
+use serde::Serialize;
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 本进程的资源占用
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ProcessStats {
+    /// 自上次查询以来的 CPU 占用，100 表示占满一个核心 (与 top 相同)；
+    /// 首次查询或当前系统不支持时为 None
+    pub cpu_percent: Option<f32>,
+    /// 常驻内存 (RSS / 工作集) 字节数，当前系统不支持时为 None
+    pub rss_bytes: Option<u64>,
+    /// 逻辑 CPU 数，用于换算整机占用
+    pub cpu_cores: usize,
+}
+
+/// 上次查询的时间和当时进程累计使用的 CPU 时间
+static LAST_SAMPLE: LazyLock<Mutex<Option<(Instant, Duration)>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+/// 查询本进程当前的 CPU 和内存占用
+///
+/// CPU 占用按两次查询之间的 CPU 时间计算，前端定期查询即可看到主循环的实际开销
+pub fn sample() -> ProcessStats {
+    let now = Instant::now();
+    let cpu_time = cpu_time();
+    let cpu_percent = match (LAST_SAMPLE.lock(), cpu_time) {
+        (Ok(mut last), Some(cpu_time)) => last
+            .replace((now, cpu_time))
+            .and_then(|(at, previous)| cpu_percent(now.duration_since(at), cpu_time.saturating_sub(previous))),
+        _ => None,
+    };
+    ProcessStats {
+        cpu_percent,
+        rss_bytes: rss_bytes(),
+        cpu_cores: thread::available_parallelism().map_or(1, |n| n.get()),
+    }
+}
+
+/// `wall` 时间内使用了 `cpu` 的 CPU 时间对应的占用百分比
+fn cpu_percent(wall: Duration, cpu: Duration) -> Option<f32> {
+    (!wall.is_zero()).then(|| (cpu.as_secs_f64() / wall.as_secs_f64() * 100.0) as f32)
+}
+
+/// /proc/self/stat 中时间的单位 (USER_HZ)，Linux 对用户态固定为 100
+#[cfg(target_os = "linux")]
+const CLOCK_TICKS_PER_SEC: u64 = 100;
+
+/// 进程累计使用的用户态和内核态 CPU 时间
+#[cfg(target_os = "linux")]
+fn cpu_time() -> Option<Duration> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    let ticks = parse_stat_ticks(&stat)?;
+    Some(Duration::from_millis(ticks * 1000 / CLOCK_TICKS_PER_SEC))
+}
+
+#[cfg(target_os = "linux")]
+fn rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    parse_status_rss(&status)
+}
+
+/// /proc/self/stat 中 utime 与 stime (第 14、15 项) 之和
+///
+/// 第 2 项是带括号的进程名，可能包含空格，因此从最后一个右括号之后开始数
+#[cfg(any(target_os = "linux", test))]
+fn parse_stat_ticks(stat: &str) -> Option<u64> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // rest 从第 3 项 (状态) 开始
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// /proc/self/status 中的 VmRSS 行，如 "VmRSS:     12345 kB"
+#[cfg(any(target_os = "linux", test))]
+fn parse_status_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[derive(Default)]
+struct FileTime {
+    low: u32,
+    high: u32,
+}
+
+#[cfg(windows)]
+impl FileTime {
+    /// FILETIME 以 100 纳秒为单位
+    fn duration(&self) -> Duration {
+        Duration::from_nanos(((u64::from(self.high) << 32) | u64::from(self.low)) * 100)
+    }
+}
+
+/// PROCESS_MEMORY_COUNTERS，由系统填写，只读取其中的工作集大小
+#[cfg(windows)]
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+struct ProcessMemoryCounters {
+    cb: u32,
+    page_fault_count: u32,
+    peak_working_set_size: usize,
+    working_set_size: usize,
+    quota_peak_paged_pool_usage: usize,
+    quota_paged_pool_usage: usize,
+    quota_peak_non_paged_pool_usage: usize,
+    quota_non_paged_pool_usage: usize,
+    pagefile_usage: usize,
+    peak_pagefile_usage: usize,
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetCurrentProcess() -> isize;
+    fn GetProcessTimes(
+        process: isize,
+        creation: *mut FileTime,
+        exit: *mut FileTime,
+        kernel: *mut FileTime,
+        user: *mut FileTime,
+    ) -> i32;
+    fn K32GetProcessMemoryInfo(process: isize, counters: *mut ProcessMemoryCounters, cb: u32) -> i32;
+}
+
+#[cfg(windows)]
+fn cpu_time() -> Option<Duration> {
+    let (mut creation, mut exit, mut kernel, mut user) =
+        (FileTime::default(), FileTime::default(), FileTime::default(), FileTime::default());
+    // SAFETY: GetCurrentProcess 返回无需关闭的伪句柄；四个输出参数都指向有效的 FILETIME
+    let ok = unsafe {
+        GetProcessTimes(GetCurrentProcess(), &mut creation, &mut exit, &mut kernel, &mut user)
+    };
+    (ok != 0).then(|| kernel.duration() + user.duration())
+}
+
+#[cfg(windows)]
+fn rss_bytes() -> Option<u64> {
+    let mut counters = ProcessMemoryCounters {
+        cb: std::mem::size_of::<ProcessMemoryCounters>() as u32,
+        ..Default::default()
+    };
+    // SAFETY: counters 的大小与传入的 cb 一致
+    let ok = unsafe { K32GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, counters.cb) };
+    (ok != 0).then_some(counters.working_set_size as u64)
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn rss_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_files() {
+        let stat = "4242 (mc connect (dev)) S 1 4242 4242 0 -1 4194560 812 0 0 0 150 25 0 0 20 0 9 0 1234 0 0";
+        assert_eq!(parse_stat_ticks(stat), Some(175));
+        assert_eq!(parse_stat_ticks("4242 (truncated) S 1"), None);
+
+        let status = "Name:\tmcconnect\nVmPeak:\t  90000 kB\nVmRSS:\t   12345 kB\nThreads:\t9\n";
+        assert_eq!(parse_status_rss(status), Some(12345 * 1024));
+        assert_eq!(parse_status_rss("Name:\tmcconnect\n"), None);
+
+        assert_eq!(cpu_percent(Duration::from_secs(2), Duration::from_secs(1)), Some(50.0));
+        assert_eq!(cpu_percent(Duration::ZERO, Duration::from_secs(1)), None);
+    }
+}
@@ -0,0 +1,64 @@
+use crate::config::ROUTE_PREFERENCE;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use steamworks::networking_types::{NetworkingConfigEntry, NetworkingConfigValue};
+
+/// P2P_Transport_ICE_Enable 的取值: 不尝试直连 / 尝试所有直连候选地址
+const ICE_DISABLE: i32 = 0;
+const ICE_ENABLE_ALL: i32 = 0x7fff_ffff;
+
+/// 直连优先时给中继路径增加的代价 (毫秒)，远大于实际延迟差，只在直连完全不通时才会选中继
+const DIRECT_ONLY_SDR_PENALTY_MS: i32 = 10_000;
+
+/// 建立 P2P 连接时的路径偏好
+///
+/// 只影响之后创建的监听端口和发起的连接，运行中的会话不受影响
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutePreference {
+    /// 由 Steam 自动选择
+    #[default]
+    Automatic,
+    /// 只经 Steam 中继 (SDR)，适合严格 NAT，延迟略高但最稳定
+    RelayOnly,
+    /// 只尝试直连 (ICE)，延迟最低
+    ///
+    /// Steam 无法完全关闭中继，这里给中继路径加上很大的代价；对称 NAT 等网络下
+    /// 直连无法建立，连接会明显变慢甚至失败
+    DirectOnly,
+}
+
+static PREFERENCE: Mutex<RoutePreference> = Mutex::new(ROUTE_PREFERENCE);
+
+/// 当前的路径偏好
+pub fn get() -> RoutePreference {
+    *PREFERENCE.lock().unwrap()
+}
+
+/// 修改路径偏好，下次开服或加入房间时生效
+pub fn set(preference: RoutePreference) {
+    *PREFERENCE.lock().unwrap() = preference;
+}
+
+impl RoutePreference {
+    /// 创建监听端口或发起连接时附带的配置项
+    pub fn config_entries(self) -> Vec<NetworkingConfigEntry> {
+        match self {
+            RoutePreference::Automatic => Vec::new(),
+            RoutePreference::RelayOnly => vec![NetworkingConfigEntry::new_int32(
+                NetworkingConfigValue::P2PTransportICEEnable,
+                ICE_DISABLE,
+            )],
+            RoutePreference::DirectOnly => vec![
+                NetworkingConfigEntry::new_int32(
+                    NetworkingConfigValue::P2PTransportICEEnable,
+                    ICE_ENABLE_ALL,
+                ),
+                NetworkingConfigEntry::new_int32(
+                    NetworkingConfigValue::P2PTransportSDRPenalty,
+                    DIRECT_ONLY_SDR_PENALTY_MS,
+                ),
+            ],
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::config::SEND_QUEUE_FULL_POLICY;
+use crate::metrics;
+use std::sync::mpsc::{self, Receiver, SendError, SyncSender, TrySendError};
+
+/// 队列满时如何处理新数据
+///
+/// MC 连接是 TCP 字节流，经 Steam 可靠传输后仍要求一个字节不丢地按序送达。
+/// 丢弃其中任意一段都会让后续的包长度错位，MC 客户端或服务器随即报解码错误断开，
+/// 甚至把错位的数据当作别的包处理，因此桥接 MC 流量时必须使用 `Block`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueFullPolicy {
+    /// 等待空位，由 TCP 流控把压力传回数据来源
+    Block,
+    /// 丢弃新数据并计入丢包，只适用于允许丢失的流量
+    Drop,
+}
+
+/// MC 读取线程到主循环的有界队列
+///
+/// 默认策略下队列满时 `send` 阻塞，读取线程随之停止读取 TCP，由 TCP 流控把压力
+/// 传回 Minecraft，而不是在内存中无限缓冲 Steam 来不及发送的数据
+pub struct SendQueue<T> {
+    tx: SyncSender<T>,
+    policy: QueueFullPolicy,
+}
+
+impl<T> Clone for SendQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            policy: self.policy,
+        }
+    }
+}
+
+/// 创建容量为 `capacity`、使用 SEND_QUEUE_FULL_POLICY 的队列，返回发送端和主循环使用的接收端
+pub fn bounded<T>(capacity: usize) -> (SendQueue<T>, Receiver<T>) {
+    bounded_with_policy(capacity, SEND_QUEUE_FULL_POLICY)
+}
+
+/// 创建容量为 `capacity`、队列满时按 `policy` 处理的队列
+pub fn bounded_with_policy<T>(capacity: usize, policy: QueueFullPolicy) -> (SendQueue<T>, Receiver<T>) {
+    let (tx, rx) = mpsc::sync_channel(capacity);
+    (SendQueue { tx, policy }, rx)
+}
+
+impl<T> SendQueue<T> {
+    /// 队列满时的处理策略
+    pub fn policy(&self) -> QueueFullPolicy {
+        self.policy
+    }
+
+    /// 放入队列，队列满时按策略等待空位 (记录一次背压) 或丢弃 (记录一次丢包)；
+    /// 接收端已关闭时返回错误
+    pub fn send(&self, item: T) -> Result<(), SendError<T>> {
+        match self.tx.try_send(item) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(item)) => match self.policy {
+                QueueFullPolicy::Block => {
+                    metrics::record_backpressure();
+                    self.tx.send(item)
+                }
+                QueueFullPolicy::Drop => {
+                    metrics::record_packet_dropped();
+                    Ok(())
+                }
+            },
+            Err(TrySendError::Disconnected(item)) => Err(SendError(item)),
+        }
+    }
+
+    /// 不论策略如何都等待空位，用于连接关闭等不能丢失的控制消息
+    pub fn send_blocking(&self, item: T) -> Result<(), SendError<T>> {
+        self.tx.send(item)
+    }
+
+    /// 不等待地放入队列，队列满时把条目交还调用方，是否计为背压由调用方决定
+    pub fn try_send(&self, item: T) -> Result<(), TrySendError<T>> {
+        self.tx.try_send(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_full_queue_blocks_until_drained() {
+        let before = metrics::get_backpressure_events();
+        let (queue, rx) = bounded(1);
+        queue.send(1).unwrap();
+
+        let sender = thread::spawn(move || queue.send(2));
+        thread::sleep(Duration::from_millis(50));
+        assert!(!sender.is_finished(), "send should wait for space");
+
+        assert_eq!(rx.recv().unwrap(), 1);
+        sender.join().unwrap().unwrap();
+        assert_eq!(rx.recv().unwrap(), 2);
+        assert!(metrics::get_backpressure_events() > before);
+
+        // 接收端关闭后不再阻塞
+        let (queue, rx) = bounded::<u8>(1);
+        drop(rx);
+        assert!(queue.send(3).is_err());
+    }
+
+    #[test]
+    fn test_drop_policy_discards_when_full() {
+        let before = metrics::get_snapshot().packets_dropped;
+        let (queue, rx) = bounded_with_policy(1, QueueFullPolicy::Drop);
+        queue.send(1).unwrap();
+        queue.send(2).unwrap();
+        assert!(metrics::get_snapshot().packets_dropped > before);
+
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert!(rx.try_recv().is_err(), "the second item was dropped");
+    }
+}
@@ -0,0 +1,142 @@
+use crate::config::{MC_RCON_PORT, MC_SERVER_PORT};
+use crate::i18n::Message;
+use log::{info, warn};
+use serde::Serialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// server.properties 中未设置 motd 时 Minecraft 使用的默认值
+const DEFAULT_MOTD: &str = "A Minecraft Server";
+
+/// 从 server.properties 读取的开服参数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServerProperties {
+    pub port: u16,
+    pub motd: String,
+    /// 启用了 RCON (enable-rcon=true) 时的 RCON 端口
+    pub rcon_port: Option<u16>,
+}
+
+/// 读取服务器目录下的 server.properties
+///
+/// 缺少的键使用 Minecraft 的默认值；文件不存在或无法读取时返回错误
+pub fn read(dir: &Path) -> Result<ServerProperties, String> {
+    let path = dir.join("server.properties");
+    let contents = fs::read_to_string(&path).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => Message::ServerPropertiesNotFound.format(&[&path.display()]),
+        _ => Message::ServerPropertiesReadFailed.format(&[&path.display(), &e]),
+    })?;
+
+    let properties = parse(&contents);
+    info!(
+        "📄 读取 {}: 端口 {}, MOTD \"{}\"",
+        path.display(),
+        properties.port,
+        properties.motd
+    );
+    Ok(properties)
+}
+
+/// 解析 server.properties 内容
+///
+/// 文件是 Java properties 格式: 每行 key=value，# 或 ! 开头为注释
+fn parse(contents: &str) -> ServerProperties {
+    let mut port = MC_SERVER_PORT;
+    let mut motd = DEFAULT_MOTD.to_string();
+    let mut rcon_enabled = false;
+    let mut rcon_port = MC_RCON_PORT;
+
+    for line in contents.lines() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        match key.trim() {
+            "server-port" => match value.trim().parse::<u16>() {
+                Ok(value) if value != 0 => port = value,
+                _ => warn!("⚠️ server.properties 中的端口无效: {}", value.trim()),
+            },
+            "motd" => motd = unescape(value.trim_start()),
+            "enable-rcon" => rcon_enabled = value.trim() == "true",
+            "rcon.port" => match value.trim().parse::<u16>() {
+                Ok(value) if value != 0 => rcon_port = value,
+                _ => warn!("⚠️ server.properties 中的 RCON 端口无效: {}", value.trim()),
+            },
+            _ => {}
+        }
+    }
+
+    ServerProperties {
+        port,
+        motd,
+        rcon_port: rcon_enabled.then_some(rcon_port),
+    }
+}
+
+/// 还原 properties 转义: Minecraft 写入时会转义 `:`、`=` 以及非 ASCII 字符 (\uXXXX)
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('f') => result.push('\u{c}'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    Some(decoded) => result.push(decoded),
+                    None => {
+                        result.push_str("\\u");
+                        result.push_str(&hex);
+                    }
+                }
+            }
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_server_properties() {
+        let contents = "#Minecraft server properties\n\
+                        #Mon Jan 01 00:00:00 UTC 2024\n\
+                        enable-command-block=false\n\
+                        motd=\\u00A7a\\u6211\\u7684\\u4E16\\u754C\\: survival\n\
+                        server-port=25570\n\
+                        rcon.port=25580\n";
+        let properties = parse(contents);
+        assert_eq!(properties.port, 25570);
+        assert_eq!(properties.motd, "§a我的世界: survival");
+        assert_eq!(properties.rcon_port, None);
+        assert_eq!(parse("enable-rcon=true\nrcon.port=25580\n").rcon_port, Some(25580));
+        assert_eq!(parse("enable-rcon=true\n").rcon_port, Some(MC_RCON_PORT));
+
+        // 缺少的键或无效的端口使用默认值
+        let properties = parse("server-port=abc\n");
+        assert_eq!(properties.port, MC_SERVER_PORT);
+        assert_eq!(properties.motd, DEFAULT_MOTD);
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let dir = std::env::temp_dir().join("mcconnect-no-such-server-dir");
+        assert!(read(&dir).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::config::ALLOW_MIXED_MODE;
+use crate::i18n::Message;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 会话角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionRole {
+    Host,
+    Client,
+}
+
+/// 当前会话状态，由房主/客户端循环发布，前端通过 get_session_state 读取
+///
+/// 房间和 Steam ID 以字符串返回，避免超出 JavaScript 安全整数范围
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum SessionState {
+    Idle,
+    Hosting { lobby_id: String, peers: usize },
+    Connecting,
+    Connected { lobby_id: String, ping_ms: Option<u32> },
+    /// Steam 连接已断开，会话暂停等待恢复；hosting 区分房主和客户端会话
+    SteamOffline { lobby_id: String, hosting: bool },
+    Error { message: String },
+}
+
+/// 整个状态在一把锁内替换，读取方不会看到半更新的状态
+static STATE: LazyLock<Mutex<SessionState>> = LazyLock::new(|| Mutex::new(SessionState::Idle));
+
+/// 读取当前会话状态
+pub fn state() -> SessionState {
+    STATE.lock().unwrap().clone()
+}
+
+/// 发布新的会话状态
+pub fn set_state(state: SessionState) {
+    *STATE.lock().unwrap() = state;
+}
+
+/// 当前会话所在的房间及所属角色，会话结束时随守卫清除
+static LOBBY: LazyLock<Mutex<Option<(SessionRole, u64)>>> = LazyLock::new(|| Mutex::new(None));
+
+/// 记录会话所在的房间；该角色的会话已结束时忽略，不留下已失效的房间号
+pub fn set_lobby_id(role: SessionRole, lobby_id: u64) {
+    let mut lobby = LOBBY.lock().unwrap();
+    if is_active(role) {
+        *lobby = Some((role, lobby_id));
+    }
+}
+
+/// 当前会话所在的房间，没有活动会话时为 None
+pub fn lobby_id() -> Option<u64> {
+    LOBBY
+        .lock()
+        .unwrap()
+        .filter(|(role, _)| is_active(*role))
+        .map(|(_, lobby_id)| lobby_id)
+}
+
+/// 清除该角色记录的房间，停止房间或离开时立即调用，不必等会话线程退出
+pub fn clear_lobby_id(role: SessionRole) {
+    let mut lobby = LOBBY.lock().unwrap();
+    if lobby.is_some_and(|(owner, _)| owner == role) {
+        *lobby = None;
+    }
+}
+
+/// 客户端会话正在加入或已加入的房间，从开始加入时记录，用于识别重复的加入请求
+static CLIENT_TARGET: LazyLock<Mutex<Option<u64>>> = LazyLock::new(|| Mutex::new(None));
+
+/// 客户端会话正在加入或已加入的房间，没有客户端会话时为 None
+pub fn client_target() -> Option<u64> {
+    CLIENT_TARGET.lock().unwrap().filter(|_| is_active(SessionRole::Client))
+}
+
+impl SessionState {
+    /// 该状态是否属于指定角色的活动会话
+    fn belongs_to(&self, role: SessionRole) -> bool {
+        match self {
+            SessionState::Hosting { .. } => role == SessionRole::Host,
+            SessionState::Connecting | SessionState::Connected { .. } => role == SessionRole::Client,
+            SessionState::SteamOffline { hosting, .. } => {
+                role == if *hosting { SessionRole::Host } else { SessionRole::Client }
+            }
+            SessionState::Idle | SessionState::Error { .. } => false,
+        }
+    }
+}
+
+static HOST_ACTIVE: AtomicBool = AtomicBool::new(false);
+static CLIENT_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+impl SessionRole {
+    fn flag(self) -> &'static AtomicBool {
+        match self {
+            SessionRole::Host => &HOST_ACTIVE,
+            SessionRole::Client => &CLIENT_ACTIVE,
+        }
+    }
+
+    fn other(self) -> SessionRole {
+        match self {
+            SessionRole::Host => SessionRole::Client,
+            SessionRole::Client => SessionRole::Host,
+        }
+    }
+}
+
+/// 会话占用守卫，随会话线程结束而释放
+pub struct SessionGuard {
+    role: SessionRole,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        // 会话结束后回到空闲，错误状态保留给前端展示
+        {
+            let mut state = STATE.lock().unwrap();
+            if state.belongs_to(self.role) {
+                *state = SessionState::Idle;
+            }
+        }
+        // 包括连接失效等致命错误结束的会话，错误状态保留但房间号已无效
+        clear_lobby_id(self.role);
+        if self.role == SessionRole::Client {
+            *CLIENT_TARGET.lock().unwrap() = None;
+        }
+        self.role.flag().store(false, Ordering::SeqCst);
+    }
+}
+
+/// 尝试开始一个会话，同一角色同时只允许一个会话
+///
+/// 未启用混合模式时，房主和客户端会话也互斥
+pub fn try_begin(role: SessionRole) -> Result<SessionGuard, String> {
+    if !ALLOW_MIXED_MODE && role.other().flag().load(Ordering::SeqCst) {
+        return Err(match role {
+            SessionRole::Host => Message::HostBlockedByClient.text().to_string(),
+            SessionRole::Client => Message::ClientBlockedByHost.text().to_string(),
+        });
+    }
+
+    if role
+        .flag()
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Err(match role {
+            SessionRole::Host => Message::HostAlreadyRunning.text().to_string(),
+            SessionRole::Client => Message::ClientAlreadyRunning.text().to_string(),
+        });
+    }
+
+    // 新会话开始时清除上一次的错误
+    {
+        let mut state = STATE.lock().unwrap();
+        if matches!(*state, SessionState::Error { .. }) {
+            *state = SessionState::Idle;
+        }
+    }
+    Ok(SessionGuard { role })
+}
+
+/// 尝试开始加入 `lobby_id` 的客户端会话
+///
+/// 已有客户端会话正在加入或已加入同一房间时 (如重复点击加入) 返回 AlreadyInLobby，
+/// 现有会话不受影响，也不会再绑定一个监听端口或建立第二条连接
+pub fn try_begin_join(lobby_id: u64) -> Result<SessionGuard, String> {
+    let mut target = CLIENT_TARGET.lock().unwrap();
+    if *target == Some(lobby_id) && is_active(SessionRole::Client) {
+        return Err(Message::AlreadyInLobby.text().to_string());
+    }
+    let guard = try_begin(SessionRole::Client)?;
+    *target = Some(lobby_id);
+    Ok(guard)
+}
+
+/// 指定角色当前是否有会话在运行
+pub fn is_active(role: SessionRole) -> bool {
+    role.flag().load(Ordering::SeqCst)
+}
+
+/// 等待指定角色的会话线程结束并释放占用，超时返回 false
+pub fn wait_until_ended(role: SessionRole, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while is_active(role) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_resets_state_and_keeps_errors() {
+        let guard = try_begin(SessionRole::Client).unwrap();
+        assert!(try_begin(SessionRole::Client).is_err());
+
+        set_state(SessionState::Connected {
+            lobby_id: "1".to_string(),
+            ping_ms: Some(20),
+        });
+        drop(guard);
+        assert_eq!(state(), SessionState::Idle);
+
+        let guard = try_begin(SessionRole::Client).unwrap();
+        set_state(SessionState::Error {
+            message: "failed".to_string(),
+        });
+        drop(guard);
+        assert!(matches!(state(), SessionState::Error { .. }));
+
+        let guard = try_begin(SessionRole::Client).unwrap();
+        assert_eq!(state(), SessionState::Idle);
+
+        // 房间号随会话结束清除，结束后才送达的房间号不会被记录
+        set_lobby_id(SessionRole::Client, 42);
+        assert_eq!(lobby_id(), Some(42));
+        drop(guard);
+        assert_eq!(lobby_id(), None);
+        set_lobby_id(SessionRole::Client, 43);
+        assert_eq!(lobby_id(), None);
+
+        // 重复加入同一房间得到明确的提示，加入其他房间仍提示先离开
+        let guard = try_begin_join(42).unwrap();
+        assert_eq!(client_target(), Some(42));
+        assert_eq!(try_begin_join(42).err().as_deref(), Some(Message::AlreadyInLobby.text()));
+        assert_eq!(try_begin_join(43).err().as_deref(), Some(Message::ClientAlreadyRunning.text()));
+        drop(guard);
+        assert_eq!(client_target(), None);
+        drop(try_begin_join(42).unwrap());
+    }
+}
@@ -0,0 +1,44 @@
+use log::{info, warn};
+use std::thread;
+use std::time::{Duration, Instant};
+use steamworks::networking_sockets::NetConnection;
+use steamworks::Client;
+
+/// 等待连接的 Steam 发送缓冲区清空，超过宽限期后放弃
+///
+/// 返回 true 表示所有可靠消息均已被对端确认
+pub fn drain_send_buffers(
+    client: &Client,
+    connections: &[&NetConnection],
+    grace_period: Duration,
+) -> bool {
+    if connections.is_empty() {
+        return true;
+    }
+
+    let sockets = client.networking_sockets();
+    for connection in connections {
+        let _ = connection.flush_messages();
+    }
+
+    let deadline = Instant::now() + grace_period;
+    loop {
+        // 尚未发出以及已发出但未确认的可靠消息字节数
+        let pending: i64 = connections
+            .iter()
+            .filter_map(|conn| sockets.get_realtime_connection_status(conn, 0).ok())
+            .map(|(status, _)| status.pending_reliable() as i64 + status.sent_unacked_reliable() as i64)
+            .sum();
+
+        if pending == 0 {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            warn!("⚠️ 宽限期已到，仍有 {} 字节未送达，强制关闭", pending);
+            return false;
+        }
+
+        info!("⏳ 等待发送缓冲区清空... (剩余 {} 字节)", pending);
+        thread::sleep(Duration::from_millis(100));
+    }
+}
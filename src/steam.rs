@@ -0,0 +1,249 @@
+use crate::config::{CALLBACK_PUMP_INTERVAL_MS, FALLBACK_APP_ID, STEAM_APP_ID};
+use crate::i18n::Message;
+use crate::structured_log;
+use log::{info, warn};
+use serde_json::json;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use steamworks::networking_types::NetworkingAvailability;
+use steamworks::{CallbackHandle, Client, SteamServersConnected, SteamServersDisconnected};
+
+/// 进程内共享的 Steam 客户端，首次使用时初始化
+static CLIENT: LazyLock<Mutex<Option<Client>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Steam 客户端与 Steam 服务器之间的连接状态，由连接回调维护
+static ONLINE: AtomicBool = AtomicBool::new(true);
+
+/// 连接状态回调句柄，在进程生命周期内保留
+static CONNECTION_HANDLES: Mutex<Vec<CallbackHandle>> = Mutex::new(Vec::new());
+
+/// 获取共享的 Steam 客户端
+///
+/// 首次成功初始化时启动专用的回调线程，之后所有命令和会话线程共用同一个客户端。
+/// 房主和客户端循环不再自行调用 `run_callbacks`，只依赖已注册的回调和事件队列，
+/// 避免回调被某个循环抢先消费或因循环阻塞而积压。初始化失败时不缓存，下次调用重试。
+pub fn client() -> Result<Client, String> {
+    let mut slot = CLIENT.lock().unwrap();
+    if let Some(client) = slot.as_ref() {
+        return Ok(client.clone());
+    }
+
+    let client = init_client()?;
+    watch_connection(&client);
+    spawn_callback_pump(client.clone());
+    *slot = Some(client.clone());
+    Ok(client)
+}
+
+/// Steam 初始化时使用的 App ID 来源
+enum AppIdSource {
+    /// 显式指定的 App ID (配置或程序目录下的 steam_appid.txt)
+    Explicit(u32),
+    /// 由 Steam 启动环境或工作目录下的 steam_appid.txt 提供，SDK 自行读取
+    Ambient,
+}
+
+/// 按 配置 -> 启动环境/工作目录 -> 程序目录 的顺序确定 App ID 后初始化
+///
+/// 找不到任何 App ID 时直接返回可操作的错误，而不是 SDK 含糊的初始化失败
+fn init_client() -> Result<Client, String> {
+    let client = match find_app_id() {
+        Some(AppIdSource::Explicit(app_id)) => {
+            info!("🎮 使用 App ID {} 初始化 Steam", app_id);
+            Client::init_app(app_id)
+        }
+        Some(AppIdSource::Ambient) => Client::init(),
+        None => return Err(Message::SteamAppIdMissing.format(&[&FALLBACK_APP_ID])),
+    };
+    client.map_err(|e| Message::SteamInitFailed.format(&[&e]))
+}
+
+fn find_app_id() -> Option<AppIdSource> {
+    if let Some(app_id) = STEAM_APP_ID {
+        return Some(AppIdSource::Explicit(app_id));
+    }
+
+    // 通过 Steam 启动时由客户端设置环境变量；SDK 也会读取工作目录下的 steam_appid.txt
+    if env::var_os("SteamAppId").is_some() || Path::new("steam_appid.txt").is_file() {
+        return Some(AppIdSource::Ambient);
+    }
+
+    // 从快捷方式等其他工作目录启动时，SDK 找不到程序目录下的文件，这里读取后显式传入
+    let exe = env::current_exe().ok()?;
+    let contents = fs::read_to_string(exe.parent()?.join("steam_appid.txt")).ok()?;
+    match parse_app_id(&contents) {
+        Some(app_id) => Some(AppIdSource::Explicit(app_id)),
+        None => {
+            warn!("⚠️ steam_appid.txt 内容无效: {:?}", contents.trim());
+            None
+        }
+    }
+}
+
+/// 解析 steam_appid.txt 的内容
+fn parse_app_id(contents: &str) -> Option<u32> {
+    contents.trim().parse().ok().filter(|&app_id| app_id != 0)
+}
+
+/// 以固定间隔调用 `run_callbacks`，在进程生命周期内一直运行
+fn spawn_callback_pump(client: Client) {
+    let result = thread::Builder::new()
+        .name("steam-callbacks".to_string())
+        .spawn(move || loop {
+            client.run_callbacks();
+            thread::sleep(Duration::from_millis(CALLBACK_PUMP_INTERVAL_MS));
+        });
+
+    match result {
+        Ok(_) => info!("🔁 Steam 回调线程已启动 (间隔 {} ms)", CALLBACK_PUMP_INTERVAL_MS),
+        Err(e) => warn!("✗ 无法启动 Steam 回调线程: {}", e),
+    }
+}
+
+/// 注册 Steam 服务器连接回调
+///
+/// Steam 注销或与服务器断开时，NetworkingSockets 的发送和接收会陆续失败，
+/// 表现与玩家掉线相同。会话循环据此区分两者，离线期间暂停转发而不是断开玩家。
+fn watch_connection(client: &Client) {
+    let disconnected = client.register_callback(|val: SteamServersDisconnected| {
+        warn!("📴 Steam 连接已断开: {:?}", val.reason);
+        ONLINE.store(false, Ordering::SeqCst);
+    });
+    let connected = client.register_callback(|_: SteamServersConnected| {
+        info!("📶 Steam 连接已恢复");
+        ONLINE.store(true, Ordering::SeqCst);
+    });
+    CONNECTION_HANDLES
+        .lock()
+        .unwrap()
+        .extend([disconnected, connected]);
+}
+
+/// 初始化 Steam 中继网络访问并等待其就绪
+///
+/// 中继网络配置在首次使用时才下载，此前创建监听端口或发起连接可能失败。
+/// 中继不可用或超时仍未就绪时返回当前状态的描述
+pub fn wait_for_relay(client: &Client, timeout: Duration) -> Result<(), String> {
+    let utils = client.networking_utils();
+    utils.init_relay_network_access();
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        let status = utils.relay_network_status();
+        let availability = status.availability();
+        let describe = || format!("{:?} {}", availability, status.debugging_message().trim());
+        match availability {
+            Ok(NetworkingAvailability::Current) => return Ok(()),
+            // 已放弃尝试，继续等待没有意义
+            Ok(NetworkingAvailability::CannotTry | NetworkingAvailability::Failed) => {
+                return Err(describe())
+            }
+            _ if Instant::now() >= deadline => return Err(describe()),
+            _ => thread::sleep(Duration::from_millis(100)),
+        }
+    }
+}
+
+/// 本机在 Steam 中继网络中的位置 (到各中继节点的延迟)，以字符串形式返回
+///
+/// 中继网络初始化后需要几秒完成测量，此前返回 None
+pub fn local_ping_location(client: &Client) -> Option<String> {
+    let utils = client.networking_utils();
+    utils
+        .local_ping_location()
+        .map(|location| utils.ping_location_to_string(&location))
+}
+
+/// 根据对方公布的位置字符串估算本机与其之间的延迟 (毫秒)
+///
+/// 只是经由中继的估计值，直连时的实际延迟通常更低；无法估算时返回 None
+pub fn estimate_ping(client: &Client, location: &str) -> Option<u32> {
+    let utils = client.networking_utils();
+    let location = utils.parse_ping_location_string(location)?;
+    utils.estimate_ping_from_local_host(&location)
+}
+
+/// Steam 当前是否在线
+pub fn is_online() -> bool {
+    ONLINE.load(Ordering::SeqCst)
+}
+
+/// Steam 在线状态的变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnlineChange {
+    WentOffline,
+    CameBack,
+}
+
+/// 会话循环使用的离线检测，记录上次观察到的状态以便只在变化时通知
+pub struct OfflineWatch {
+    role: &'static str,
+    paused: bool,
+}
+
+impl OfflineWatch {
+    pub fn new(role: &'static str) -> Self {
+        Self {
+            role,
+            paused: false,
+        }
+    }
+
+    /// 检查在线状态，状态变化时返回变化类型
+    pub fn poll(&mut self) -> Option<OnlineChange> {
+        let offline = !is_online();
+        if offline == self.paused {
+            return None;
+        }
+        self.paused = offline;
+
+        let (event, change) = if offline {
+            warn!("⏸️ Steam 离线，暂停转发并等待 Steam 恢复...");
+            ("steam_offline", OnlineChange::WentOffline)
+        } else {
+            info!("▶️ Steam 已恢复，继续转发");
+            ("steam_online", OnlineChange::CameBack)
+        };
+        structured_log::emit(event, &[("role", json!(self.role))]);
+        Some(change)
+    }
+
+    /// 当前是否因 Steam 离线而暂停
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_app_id() {
+        assert_eq!(parse_app_id("480"), Some(480));
+        assert_eq!(parse_app_id("480\r\n"), Some(480));
+        assert_eq!(parse_app_id(""), None);
+        assert_eq!(parse_app_id("0"), None);
+        assert_eq!(parse_app_id("spacewar"), None);
+    }
+
+    #[test]
+    fn test_offline_watch_reports_changes_once() {
+        let mut watch = OfflineWatch::new("test");
+        assert_eq!(watch.poll(), None);
+
+        ONLINE.store(false, Ordering::SeqCst);
+        assert_eq!(watch.poll(), Some(OnlineChange::WentOffline));
+        assert_eq!(watch.poll(), None);
+        assert!(watch.paused());
+
+        ONLINE.store(true, Ordering::SeqCst);
+        assert_eq!(watch.poll(), Some(OnlineChange::CameBack));
+        assert!(!watch.paused());
+    }
+}
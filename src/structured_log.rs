@@ -0,0 +1,95 @@
+use crate::config::STRUCTURED_LOG_FORMAT;
+use crate::metrics::MetricsSnapshot;
+use log::info;
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+/// 结构化日志的输出格式
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// 不输出结构化日志，只保留默认的友好日志
+    Off,
+    /// event=peer_added steam_id=123 ...
+    KeyValue,
+    /// {"event":"peer_added","steam_id":123,...}
+    Json,
+}
+
+/// 结构化日志使用的 target，便于在日志中筛选
+pub const LOG_TARGET: &str = "mcconnect::event";
+
+/// 输出一条结构化事件日志，格式为 Off 时不做任何事
+///
+/// 与面向用户的友好日志并存，用于排查吞吐和连接问题时 grep/解析
+pub fn emit(event: &str, fields: &[(&str, Value)]) {
+    if let Some(line) = format_line(STRUCTURED_LOG_FORMAT, event, fields) {
+        info!(target: LOG_TARGET, "{}", line);
+    }
+}
+
+/// 输出周期性指标事件
+pub fn emit_metrics(role: &str, stats: &MetricsSnapshot, duration: Duration) {
+    emit(
+        "metrics",
+        &[
+            ("role", json!(role)),
+            ("window_ms", json!(duration.as_millis() as u64)),
+            ("packets_sent", json!(stats.packets_sent)),
+            ("packets_received", json!(stats.packets_received)),
+            ("bytes_sent", json!(stats.bytes_sent)),
+            ("bytes_received", json!(stats.bytes_received)),
+            ("payload_bytes_sent", json!(stats.payload_bytes_sent)),
+            ("payload_bytes_received", json!(stats.payload_bytes_received)),
+            ("overhead_bytes_sent", json!(stats.overhead_bytes_sent)),
+            ("overhead_bytes_received", json!(stats.overhead_bytes_received)),
+            ("packets_dropped", json!(stats.packets_dropped)),
+        ],
+    );
+}
+
+/// 按指定格式生成一行日志
+fn format_line(format: LogFormat, event: &str, fields: &[(&str, Value)]) -> Option<String> {
+    match format {
+        LogFormat::Off => None,
+        LogFormat::KeyValue => {
+            let mut line = format!("event={}", event);
+            for (key, value) in fields {
+                // 字符串值按 JSON 规则加引号并转义，数字和布尔值原样输出
+                line.push_str(&format!(" {}={}", key, value));
+            }
+            Some(line)
+        }
+        LogFormat::Json => {
+            let mut object = Map::new();
+            object.insert("event".to_string(), json!(event));
+            for (key, value) in fields {
+                object.insert(key.to_string(), value.clone());
+            }
+            Some(Value::Object(object).to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_line() {
+        let fields = [("steam_id", json!(42u64)), ("reason", json!("peer closed"))];
+
+        assert_eq!(format_line(LogFormat::Off, "peer_removed", &fields), None);
+        assert_eq!(
+            format_line(LogFormat::KeyValue, "peer_removed", &fields).unwrap(),
+            "event=peer_removed steam_id=42 reason=\"peer closed\""
+        );
+
+        let parsed: Value =
+            serde_json::from_str(&format_line(LogFormat::Json, "peer_removed", &fields).unwrap())
+                .unwrap();
+        assert_eq!(parsed["event"], "peer_removed");
+        assert_eq!(parsed["steam_id"], 42);
+        assert_eq!(parsed["reason"], "peer closed");
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use steamworks::networking_sockets::NetConnection;
+use steamworks::networking_types::SendFlags;
+
+/// 传输层错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportError {
+    /// 对端已关闭连接
+    Closed,
+    /// 底层发送或接收失败
+    Failed(String),
+    /// 对端发来的数据无法解析，连接上的字节流已不可信
+    Protocol(String),
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportError::Closed => write!(f, "连接已关闭"),
+            TransportError::Failed(reason) => write!(f, "传输失败: {}", reason),
+            TransportError::Protocol(reason) => write!(f, "协议错误: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+/// 房主和客户端数据通路使用的消息传输接口
+///
+/// 生产环境由 Steam `NetConnection` 实现；测试中用 `MemoryTransport`
+/// 在同一进程内连接房主和客户端，无需 Steam 即可覆盖桥接逻辑。
+pub trait Transport {
+    /// 可靠、有序地发送一条消息
+    fn send(&self, data: &[u8]) -> Result<(), TransportError>;
+
+    /// 不可靠地发送一条消息：可能丢失，也不保证与其他消息的顺序；
+    /// 无法立即发送时直接丢弃而不是排队。默认回退为可靠发送
+    fn send_unreliable(&self, data: &[u8]) -> Result<(), TransportError> {
+        self.send(data)
+    }
+
+    /// 取出最多 `max` 条已到达的消息，暂无消息时返回空列表
+    fn receive(&mut self, max: usize) -> Result<Vec<Vec<u8>>, TransportError>;
+}
+
+impl Transport for NetConnection {
+    fn send(&self, data: &[u8]) -> Result<(), TransportError> {
+        self.send_message(data, SendFlags::RELIABLE_NO_NAGLE)
+            .map(|_| ())
+            .map_err(|e| TransportError::Failed(format!("{:?}", e)))
+    }
+
+    fn send_unreliable(&self, data: &[u8]) -> Result<(), TransportError> {
+        self.send_message(data, SendFlags::UNRELIABLE_NO_DELAY)
+            .map(|_| ())
+            .map_err(|e| TransportError::Failed(format!("{:?}", e)))
+    }
+
+    fn receive(&mut self, max: usize) -> Result<Vec<Vec<u8>>, TransportError> {
+        self.receive_messages(max)
+            .map(|messages| messages.iter().map(|m| m.data().to_vec()).collect())
+            .map_err(|e| TransportError::Failed(format!("{:?}", e)))
+    }
+}
+
+/// 进程内回环传输，`pair` 返回的两端互相收发
+#[allow(dead_code)]
+pub struct MemoryTransport {
+    tx: Sender<Vec<u8>>,
+    rx: Receiver<Vec<u8>>,
+}
+
+#[allow(dead_code)]
+impl MemoryTransport {
+    /// 创建一对相连的传输端点
+    pub fn pair() -> (Self, Self) {
+        let (a_tx, b_rx) = mpsc::channel();
+        let (b_tx, a_rx) = mpsc::channel();
+        (
+            Self { tx: a_tx, rx: a_rx },
+            Self { tx: b_tx, rx: b_rx },
+        )
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn send(&self, data: &[u8]) -> Result<(), TransportError> {
+        self.tx
+            .send(data.to_vec())
+            .map_err(|_| TransportError::Closed)
+    }
+
+    fn receive(&mut self, max: usize) -> Result<Vec<Vec<u8>>, TransportError> {
+        let mut messages = Vec::new();
+        while messages.len() < max {
+            match self.rx.try_recv() {
+                Ok(message) => messages.push(message),
+                Err(TryRecvError::Empty) => break,
+                // 先交付已到达的消息，之后再报告关闭
+                Err(TryRecvError::Disconnected) if messages.is_empty() => {
+                    return Err(TransportError::Closed)
+                }
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_transport_pair() {
+        let (a, mut b) = MemoryTransport::pair();
+        a.send(b"one").unwrap();
+        a.send(b"two").unwrap();
+        a.send(b"three").unwrap();
+
+        assert_eq!(b.receive(2).unwrap(), vec![b"one".to_vec(), b"two".to_vec()]);
+        assert_eq!(b.receive(64).unwrap(), vec![b"three".to_vec()]);
+        assert!(b.receive(64).unwrap().is_empty());
+
+        drop(a);
+        assert_eq!(b.receive(64), Err(TransportError::Closed));
+        assert_eq!(b.send(b"x"), Err(TransportError::Closed));
+    }
+}
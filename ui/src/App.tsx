@@ -4,9 +4,19 @@ import { Host } from "./views/Host";
 import { Client } from "./views/Client";
 import { LogPanel, LogButton } from "./components/LogPanel";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 
 type View = "home" | "host" | "client";
 
+// 收到加入请求时与当前会话的关系
+type JoinConflict = "none" | "already_joined" | "in_other_lobby" | "hosting";
+
+interface JoinRequest {
+  lobby_id: string;
+  conflict: JoinConflict;
+  current_lobby_id: string | null;
+}
+
 // 全局连接状态
 interface ConnectionState {
   type: "host" | "client" | null;
@@ -18,6 +28,9 @@ function App() {
   const [view, setView] = useState<View>("home");
   const [steamName, setSteamName] = useState("");
   const [showLogs, setShowLogs] = useState(false);
+  const [invitedLobbyId, setInvitedLobbyId] = useState<string | null>(null);
+  // 切换房间后重新挂载加入页面，使其重新读取连接状态
+  const [clientKey, setClientKey] = useState(0);
   const [connectionState, setConnectionState] = useState<ConnectionState>({
     type: null,
     lobbyId: null,
@@ -66,6 +79,36 @@ function App() {
     return () => clearInterval(interval);
   }, []);
 
+  // 监听 Steam 覆盖层"加入游戏"请求，跳转到加入页面
+  useEffect(() => {
+    const unlistenPromise = listen<JoinRequest>(
+      "lobby-join-requested",
+      async (event) => {
+        const { lobby_id, conflict } = event.payload;
+        if (conflict === "hosting") {
+          window.alert(`收到加入房间 ${lobby_id} 的邀请，但当前正在开服，请先停止房主`);
+          return;
+        }
+        if (conflict === "in_other_lobby") {
+          if (!window.confirm(`收到加入房间 ${lobby_id} 的邀请，是否离开当前房间并切换？`)) {
+            return;
+          }
+          // 需要密码等加入失败的情况，由加入页面预填房间号后让用户重试
+          await invoke("switch_lobby", { lobbyIdStr: lobby_id }).catch((e) =>
+            console.error("Failed to switch lobby:", e)
+          );
+          setClientKey((key) => key + 1);
+        }
+        setInvitedLobbyId(lobby_id);
+        setView("client");
+      }
+    );
+
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
   return (
     <>
       <div className="min-h-screen w-screen bg-transparent flex flex-col">
@@ -110,12 +153,14 @@ function App() {
               )}
               {view === "client" && (
                 <Client
+                  key={clientKey}
                   onBack={() => {
                     // 返回主页时保持连接状态
                     checkActiveConnection();
                     setView("home");
                   }}
                   connectionState={connectionState}
+                  invitedLobbyId={invitedLobbyId}
                   onConnectionChange={(lobbyId) => {
                     setConnectionState({
                       type: "client",
@@ -0,0 +1,127 @@
+import React, { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { Button } from "./Button";
+import { X } from "lucide-react";
+
+interface AccessLists {
+  allowlist: string[];
+  blocklist: string[];
+}
+
+type ListKind = "allow" | "block";
+
+// 房主的允许/阻止名单，修改会保存并立即对运行中的房间生效
+export const AccessListPanel: React.FC = () => {
+  const [lists, setLists] = useState<AccessLists>({
+    allowlist: [],
+    blocklist: [],
+  });
+  const [steamId, setSteamId] = useState("");
+  const [kind, setKind] = useState<ListKind>("block");
+  const [error, setError] = useState("");
+
+  useEffect(() => {
+    invoke<AccessLists>("get_access_lists")
+      .then(setLists)
+      .catch((e) => console.error("Failed to load access lists:", e));
+  }, []);
+
+  const update = async (list: ListKind, id: string, present: boolean) => {
+    try {
+      const updated = await invoke<AccessLists>("set_access_list_entry", {
+        list,
+        steamId: id,
+        present,
+      });
+      setLists(updated);
+      setError("");
+      return true;
+    } catch (e) {
+      setError(`${e}`);
+      return false;
+    }
+  };
+
+  const handleAdd = async () => {
+    if (await update(kind, steamId, true)) {
+      setSteamId("");
+    }
+  };
+
+  const renderList = (title: string, list: ListKind, ids: string[]) => (
+    <div>
+      <p className="text-xs text-white/50 mb-1">{title}</p>
+      {ids.length === 0 ? (
+        <p className="text-xs text-white/30">
+          {list === "allow" ? "空 (不限制)" : "空"}
+        </p>
+      ) : (
+        <div className="flex flex-wrap gap-2">
+          {ids.map((id) => (
+            <span
+              key={id}
+              className="flex items-center space-x-1 px-2 py-1 rounded-lg bg-white/10 text-xs font-mono text-white/80"
+            >
+              <span>{id}</span>
+              <button
+                onClick={() => update(list, id, false)}
+                className="text-white/40 hover:text-red-300"
+              >
+                <X size={12} />
+              </button>
+            </span>
+          ))}
+        </div>
+      )}
+    </div>
+  );
+
+  return (
+    <div className="space-y-3">
+      <label className="block text-sm font-semibold text-white/70 tracking-wide">
+        玩家名单{" "}
+        <span className="text-xs text-white/40 font-normal">
+          (阻止列表优先；允许列表非空时只接受其中的玩家)
+        </span>
+      </label>
+      <div className="flex space-x-3">
+        <select
+          value={kind}
+          onChange={(e) => setKind(e.target.value as ListKind)}
+          className="bg-white/5 border border-white/20 rounded-2xl px-3 text-white text-sm focus:outline-none"
+        >
+          <option value="block">阻止</option>
+          <option value="allow">允许</option>
+        </select>
+        <input
+          type="text"
+          value={steamId}
+          onChange={(e) => setSteamId(e.target.value)}
+          className="
+            flex-1
+            bg-white/5 backdrop-blur-xl
+            border border-white/20
+            rounded-2xl px-5 py-3
+            text-white font-medium
+            placeholder:text-white/30
+            focus:outline-none
+            focus:ring-2 focus:ring-blue-500/50
+            focus:border-blue-500/50
+            transition-all duration-200
+          "
+          placeholder="Steam ID (17 位数字)"
+        />
+        <Button
+          variant="secondary"
+          onClick={handleAdd}
+          disabled={!steamId.trim()}
+        >
+          添加
+        </Button>
+      </div>
+      {error && <p className="text-xs text-red-300">{error}</p>}
+      {renderList("允许列表", "allow", lists.allowlist)}
+      {renderList("阻止列表", "block", lists.blocklist)}
+    </div>
+  );
+};
@@ -0,0 +1,45 @@
+import React, { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+interface NetworkInterface {
+  name: string;
+  ip: string;
+}
+
+interface InterfaceSelectProps {
+  // 选中接口的 IPv4 地址，空字符串表示由系统选择
+  value: string;
+  onChange: (ip: string) => void;
+  disabled?: boolean;
+}
+
+// 搜索局域网服务器使用的网卡；有 VPN 或虚拟网卡时系统默认的选择可能收不到广播
+export const InterfaceSelect: React.FC<InterfaceSelectProps> = ({
+  value,
+  onChange,
+  disabled,
+}) => {
+  const [interfaces, setInterfaces] = useState<NetworkInterface[]>([]);
+
+  useEffect(() => {
+    invoke<NetworkInterface[]>("list_network_interfaces")
+      .then(setInterfaces)
+      .catch((e) => console.error("Failed to list network interfaces:", e));
+  }, []);
+
+  return (
+    <select
+      value={value}
+      onChange={(e) => onChange(e.target.value)}
+      disabled={disabled}
+      className="w-full bg-white/5 border border-white/20 rounded-2xl px-3 py-2 text-white/80 text-sm focus:outline-none disabled:opacity-50"
+    >
+      <option value="">网卡: 自动选择</option>
+      {interfaces.map((iface) => (
+        <option key={`${iface.name}-${iface.ip}`} value={iface.ip}>
+          {iface.name} ({iface.ip})
+        </option>
+      ))}
+    </select>
+  );
+};
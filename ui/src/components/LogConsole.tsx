@@ -1,5 +1,6 @@
 import React, { useState, useEffect, useRef } from 'react';
 import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/core';
 import { AnsiUp } from 'ansi_up';
 
 const ansi_up = new AnsiUp();
@@ -13,6 +14,17 @@ export const LogConsole: React.FC = () => {
   const [logs, setLogs] = useState<LogEntry[]>([]);
   const scrollRef = useRef<HTMLDivElement>(null);
   const [isPaused, setIsPaused] = useState(false);
+  const [exportStatus, setExportStatus] = useState('');
+
+  // 导出日志、指标和状态，用户反馈问题时附上该文件
+  const handleExport = async () => {
+    try {
+      const path = await invoke<string>('export_diagnostics', { path: null });
+      setExportStatus(`诊断信息已导出: ${path}`);
+    } catch (e) {
+      setExportStatus(`导出失败: ${e}`);
+    }
+  };
 
   useEffect(() => {
     const setupListener = async () => {
@@ -70,6 +82,12 @@ export const LogConsole: React.FC = () => {
             >
                 {isPaused ? '暂停滚动' : '自动滚动'}
             </button>
+            <button
+                onClick={handleExport}
+                className="px-3 py-1 text-xs rounded-lg bg-white/10 text-white/70 hover:bg-white/20 transition-all"
+            >
+                导出诊断
+            </button>
             <button
                 onClick={() => setLogs([])}
                 className="px-3 py-1 text-xs rounded-lg bg-red-500/20 text-red-300 hover:bg-red-500/30 transition-all"
@@ -78,6 +96,11 @@ export const LogConsole: React.FC = () => {
             </button>
         </div>
       </div>
+      {exportStatus && (
+        <div className="flex-shrink-0 px-4 py-1 bg-black/20 text-xs text-white/60 break-all">
+          {exportStatus}
+        </div>
+      )}
       <div
         ref={scrollRef}
         className="flex-grow p-4 bg-black/30 backdrop-blur-sm rounded-b-2xl overflow-y-auto font-mono text-xs leading-relaxed"
@@ -1,17 +1,38 @@
 import React from "react";
 import { Activity, ArrowUp, ArrowDown, AlertTriangle } from "lucide-react";
 
+interface PeerLatency {
+  steam_id: string;
+  name: string;
+  latency_ms: number;
+  latency_raw_ms: number;
+  jitter_ms: number;
+  route: "direct" | "relayed" | "unknown" | null;
+  encrypted?: boolean | null;
+}
+
 interface PerformanceMetrics {
   packets_sent: number;
   packets_received: number;
   bytes_sent: number;
   bytes_received: number;
   packets_dropped: number;
+  drop_rate?: number;
   send_rate_mbps: number;
   recv_rate_mbps: number;
   send_rate_pps: number;
   recv_rate_pps: number;
+  /** 各连接的延迟 */
+  peers?: PeerLatency[];
+  /** 多个连接时为延迟最差的一个 */
   latency_ms?: number | null;
+  latency_raw_ms?: number | null;
+  jitter_ms?: number | null;
+  route?: "direct" | "relayed" | "unknown" | null;
+  /** 所有已确认的连接都加密时为 true，无法确认时为 null */
+  is_connection_encrypted?: boolean | null;
+  session_started_at_ms?: number | null;
+  session_uptime_secs?: number | null;
 }
 
 interface PerformancePanelProps {
@@ -67,16 +88,54 @@ export const PerformancePanel: React.FC<PerformancePanelProps> = ({
         <div className="flex items-center space-x-2">
           {metrics.latency_ms !== null && metrics.latency_ms !== undefined && (
             <div className="flex items-center space-x-1.5 px-3 py-1.5 rounded-xl bg-purple-500/20 border border-purple-500/30">
-              <span className="text-xs font-medium text-purple-300">
+              <span
+                className="text-xs font-medium text-purple-300"
+                title={
+                  (metrics.peers?.length ?? 0) > 1
+                    ? "多名玩家时显示延迟最高的一个"
+                    : undefined
+                }
+              >
                 延迟: {metrics.latency_ms}ms
+                {metrics.jitter_ms !== null && metrics.jitter_ms !== undefined && (
+                  <span
+                    className="text-purple-300/60"
+                    title={`瞬时 ${metrics.latency_raw_ms ?? "-"}ms`}
+                  >
+                    {" "}± {metrics.jitter_ms.toFixed(0)}ms
+                  </span>
+                )}
               </span>
             </div>
           )}
+          {metrics.route === "direct" && (
+            <div className="flex items-center space-x-1.5 px-3 py-1.5 rounded-xl bg-green-500/20 border border-green-500/30">
+              <span className="text-xs font-medium text-green-300">直连</span>
+            </div>
+          )}
+          {metrics.route === "relayed" && (
+            <div
+              className="flex items-center space-x-1.5 px-3 py-1.5 rounded-xl bg-orange-500/20 border border-orange-500/30"
+              title="未能直连，数据经 Steam 中继服务器转发，延迟可能较高"
+            >
+              <span className="text-xs font-medium text-orange-300">中继</span>
+            </div>
+          )}
+          {metrics.is_connection_encrypted === false && (
+            <div
+              className="flex items-center space-x-1.5 px-3 py-1.5 rounded-xl bg-red-500/20 border border-red-500/30"
+              title="Steam 连接未加密，游戏数据可能被窃听"
+            >
+              <span className="text-xs font-medium text-red-300">未加密</span>
+            </div>
+          )}
           {metrics.packets_dropped > 0 && (
             <div className="flex items-center space-x-2 px-3 py-1.5 rounded-xl bg-yellow-500/20 border border-yellow-500/30">
               <AlertTriangle size={14} className="text-yellow-400" />
               <span className="text-xs font-medium text-yellow-300">
                 丢包: {metrics.packets_dropped}
+                {metrics.drop_rate !== undefined &&
+                  ` (${metrics.drop_rate.toFixed(1)}%)`}
               </span>
             </div>
           )}
@@ -147,6 +206,31 @@ export const PerformancePanel: React.FC<PerformancePanelProps> = ({
         </div>
       </div>
 
+      {/* Per-peer latency, only useful with more than one connection */}
+      {metrics.peers && metrics.peers.length > 1 && (
+        <div className="space-y-1.5 pt-3 border-t border-white/10">
+          {metrics.peers.map((peer) => (
+            <div
+              key={peer.steam_id}
+              className="flex justify-between text-xs text-white/70"
+            >
+              <span className="truncate" title={peer.steam_id}>
+                {peer.name}
+              </span>
+              <span className="font-mono">
+                {peer.latency_ms}ms
+                <span className="text-white/40">
+                  {" "}± {peer.jitter_ms.toFixed(0)}ms
+                  {peer.route === "direct" && " · 直连"}
+                  {peer.route === "relayed" && " · 中继"}
+                  {peer.encrypted === false && " · 未加密"}
+                </span>
+              </span>
+            </div>
+          ))}
+        </div>
+      )}
+
       {/* Progress bars */}
       <div className="space-y-3 pt-3 border-t border-white/10">
         <div>
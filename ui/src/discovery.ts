@@ -0,0 +1,24 @@
+// detect_minecraft_server 无法监听 LAN 广播时返回的原因
+export type DiscoveryError =
+  | { kind: "invalid_interface"; address: string }
+  | { kind: "bind"; port: number; reason: string }
+  | { kind: "multicast_join"; reason: string }
+  | { kind: "socket"; reason: string };
+
+// 针对失败原因给出建议；不是 DiscoveryError 时返回 null
+export const describeDiscoveryError = (e: unknown): string | null => {
+  if (typeof e !== "object" || e === null || !("kind" in e)) {
+    return null;
+  }
+  const error = e as DiscoveryError;
+  switch (error.kind) {
+    case "invalid_interface":
+      return `无效的网卡地址: ${error.address}`;
+    case "bind":
+      return `无法监听 UDP 端口 ${error.port}，请检查防火墙是否允许本程序访问局域网`;
+    case "multicast_join":
+      return "无法加入局域网组播，请确认已连接网络，或关闭 VPN / 虚拟网卡后重试";
+    case "socket":
+      return `接收局域网广播失败: ${error.reason}`;
+  }
+};
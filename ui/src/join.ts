@@ -0,0 +1,27 @@
+// join_lobby 失败时返回的原因
+export type JoinError =
+  | {
+      kind: "version_mismatch";
+      message: string;
+      host_version: string | null;
+      local_version: string;
+      update_url: string;
+    }
+  | {
+      kind: "unresolved_target";
+      message: string;
+      input: string;
+      friend_steam_id: string | null;
+    }
+  | { kind: "failed"; message: string };
+
+const isJoinError = (e: unknown): e is JoinError =>
+  typeof e === "object" && e !== null && "kind" in e && "message" in e;
+
+// 给用户的失败提示
+export const describeJoinError = (e: unknown): string =>
+  isJoinError(e) ? e.message : `${e}`;
+
+// 版本不兼容时返回下载新版本的页面，其他失败返回 null
+export const joinErrorUpdateUrl = (e: unknown): string | null =>
+  isJoinError(e) && e.kind === "version_mismatch" ? e.update_url : null;
@@ -2,6 +2,7 @@ import React, { useState, useEffect } from "react";
 import { Card } from "../components/Card";
 import { Button } from "../components/Button";
 import { PerformancePanel } from "../components/PerformancePanel";
+import { InterfaceSelect } from "../components/InterfaceSelect";
 import {
   ArrowLeft,
   LogIn,
@@ -14,6 +15,9 @@ import {
   XCircle,
 } from "lucide-react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { describeDiscoveryError } from "../discovery";
+import { describeJoinError, joinErrorUpdateUrl } from "../join";
 
 interface ConnectionState {
   type: "host" | "client" | null;
@@ -25,6 +29,7 @@ interface ClientProps {
   onBack: () => void;
   connectionState?: ConnectionState;
   onConnectionChange?: (lobbyId: string | null) => void;
+  invitedLobbyId?: string | null;
 }
 
 interface PerformanceMetrics {
@@ -33,13 +38,22 @@ interface PerformanceMetrics {
   bytes_sent: number;
   bytes_received: number;
   packets_dropped: number;
+  drop_rate?: number;
   send_rate_mbps: number;
   recv_rate_mbps: number;
   send_rate_pps: number;
   recv_rate_pps: number;
   latency_ms?: number | null;
+  latency_raw_ms?: number | null;
+  jitter_ms?: number | null;
+  route?: "direct" | "relayed" | "unknown" | null;
+  session_started_at_ms?: number | null;
+  session_uptime_secs?: number | null;
 }
 
+/** 估算延迟达到该值时提示可能卡顿 */
+const HIGH_PING_WARN_MS = 150;
+
 interface LanServer {
   ip: string;
   port: number;
@@ -51,18 +65,128 @@ export const Client: React.FC<ClientProps> = ({
   onBack,
   connectionState,
   onConnectionChange,
+  invitedLobbyId,
 }) => {
   const [lobbyId, setLobbyId] = useState("");
   const [password, setPassword] = useState("");
+  // 关闭后 Minecraft 中不会出现 "LAN world"，只能直接连接
+  const [lanBroadcast, setLanBroadcast] = useState(true);
   const [status, setStatus] = useState<
     "idle" | "connecting" | "connected" | "error"
   >("idle");
   const [message, setMessage] = useState("");
+  // 与房主版本不兼容时的更新页面
+  const [updateUrl, setUpdateUrl] = useState<string | null>(null);
+  // 本地监听端口，默认端口被占用时后端会改用其他端口
+  const [listenPort, setListenPort] = useState(55555);
+  // 与房主在同一局域网时直连的房主 MC 服务器地址
+  const [lanDirect, setLanDirect] = useState<string | null>(null);
+  // 开启 RCON 转发后的本机端口，未开启时为 null
+  const [rconPort, setRconPort] = useState<number | null>(null);
+  // 房主开放的主服务器以外的 MC 服务器，以及新连接转发到的服务器序号 (0 为主服务器)
+  const [mcServers, setMcServers] = useState<string[]>([]);
+  const [mcServer, setMcServer] = useState(0);
   const [metrics, setMetrics] = useState<PerformanceMetrics | null>(null);
+  // Steam 掉线时后端暂停转发，恢复后自动继续
+  const [steamOffline, setSteamOffline] = useState(false);
   const [lanServer, setLanServer] = useState<LanServer | null>(null);
   const [discoveryStatus, setDiscoveryStatus] = useState<
-    "idle" | "scanning" | "found" | "not_found"
+    "idle" | "scanning" | "found" | "not_found" | "failed"
   >("idle");
+  const [discoveryError, setDiscoveryError] = useState("");
+  const [discoveryInterface, setDiscoveryInterface] = useState("");
+
+  // 加入前根据房主公布的中继网络位置估算的延迟，无法估算时为 null
+  const [estimatedPing, setEstimatedPing] = useState<number | null>(null);
+
+  useEffect(() => {
+    setEstimatedPing(null);
+    if (!lobbyId.trim() || status !== "idle") return;
+    // 输入停顿后再查询，避免每次按键都请求房间数据
+    let cancelled = false;
+    const timer = window.setTimeout(async () => {
+      const ping = await invoke<number | null>("estimate_lobby_ping", {
+        lobbyIdStr: lobbyId,
+      }).catch(() => null);
+      if (!cancelled) {
+        setEstimatedPing(ping);
+      }
+    }, 800);
+    return () => {
+      cancelled = true;
+      window.clearTimeout(timer);
+    };
+  }, [lobbyId, status]);
+
+  // 设置了最长运行时间时，离开房间前一分钟收到预警
+  useEffect(() => {
+    const unlistenPromise = listen<{ role: string; remaining_secs: number }>(
+      "session-auto-stop-warning",
+      (event) => {
+        if (event.payload.role === "client") {
+          setMessage(`已接近最长运行时间，将在 ${event.payload.remaining_secs} 秒后自动离开房间`);
+        }
+      }
+    );
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // 房间元数据同步较慢时显示进度，而不是看起来卡住
+  useEffect(() => {
+    const unlistenPromise = listen<{
+      message: string;
+      attempt: number;
+      max_attempts: number;
+    }>("client-join-progress", (event) => {
+      const { message, attempt, max_attempts } = event.payload;
+      setMessage(`${message} (${attempt}/${max_attempts})`);
+    });
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // 读取线程崩溃时后端已关闭对应的 MC 连接，房间连接不受影响
+  useEffect(() => {
+    const unlistenPromise = listen<{ thread: string; message: string }>(
+      "session-thread-panicked",
+      (event) => {
+        setMessage(
+          `${event.payload.thread} 意外崩溃 (${event.payload.message})，对应的 MC 连接已关闭，请重新连接`
+        );
+      }
+    );
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // 会话中与房主的连接失效，后端已清理会话，需要重新加入
+  useEffect(() => {
+    const unlistenPromise = listen<{ message: string }>(
+      "client-connection-lost",
+      (event) => {
+        setStatus("error");
+        setMessage(event.payload.message);
+        setRconPort(null);
+        if (onConnectionChange) {
+          onConnectionChange(null);
+        }
+      }
+    );
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, [onConnectionChange]);
+
+  // 通过 Steam 覆盖层"加入游戏"进入时，预填房间号
+  useEffect(() => {
+    if (invitedLobbyId && status === "idle") {
+      setLobbyId(invitedLobbyId);
+    }
+  }, [invitedLobbyId]);
 
   // 检查是否有活跃的连接，恢复状态
   useEffect(() => {
@@ -97,33 +221,90 @@ export const Client: React.FC<ClientProps> = ({
 
   const handleJoin = async () => {
     if (!lobbyId) return;
+
+    // 未填写密码时先查询房间是否需要密码，避免加入到一半才失败
+    if (!password.trim()) {
+      setMessage("正在获取房间信息...");
+      const required = await invoke<boolean>("lobby_requires_password", {
+        lobbyIdStr: lobbyId,
+      }).catch(() => false);
+      if (required) {
+        setStatus("error");
+        setMessage("该房间需要密码，请输入密码后再加入");
+        return;
+      }
+    }
+
     setStatus("connecting");
     setMessage("正在加入房间...");
+    setUpdateUrl(null);
     try {
-      await invoke("join_lobby", {
-        lobbyIdStr: lobbyId,
+      // 除房间号外也接受 steam://joinlobby 链接和好友代码
+      const report = await invoke<{
+        warnings: string[];
+        listen_port: number;
+        lobby_id: string;
+        lan_direct: string | null;
+        mc_servers: string[];
+        mc_server: number;
+      }>("join_by_connect_string", {
+        connect: lobbyId,
         password: password.trim() || null,
+        lanBroadcast,
       });
+      setListenPort(report.listen_port);
+      setLanDirect(report.lan_direct);
+      setMcServers(report.mc_servers);
+      setMcServer(report.mc_server);
       setStatus("connected");
-      setMessage(""); // 清空消息，使用专门的连接成功提示
+      // 清空消息，使用专门的连接成功提示；版本不匹配等非致命警告仍需展示
+      setMessage(
+        report.warnings.length > 0 ? `警告: ${report.warnings.join("；")}` : ""
+      );
       // 通知父组件连接状态变化
       if (onConnectionChange) {
-        onConnectionChange(lobbyId);
+        onConnectionChange(report.lobby_id);
       }
     } catch (e) {
       setStatus("error");
-      setMessage(`连接失败: ${e}`);
+      setMessage(`连接失败: ${describeJoinError(e)}`);
+      setUpdateUrl(joinErrorUpdateUrl(e));
       if (onConnectionChange) {
         onConnectionChange(null);
       }
     }
   };
 
+  // 房主开放且自己在允许列表中时，可把 RCON 客户端连到本机端口管理房主的服务器
+  const handleToggleRcon = async () => {
+    try {
+      const port = await invoke<number | null>("set_rcon_forwarding", {
+        enabled: rconPort === null,
+      });
+      setRconPort(port);
+    } catch (e) {
+      setMessage(`RCON 转发失败: ${e}`);
+    }
+  };
+
+  // 只影响之后的连接，已在游戏中的玩家需在 Minecraft 中重新连接
+  const handleSelectMcServer = async (server: number) => {
+    try {
+      await invoke("set_mc_server", { server });
+      setMcServer(server);
+      setMessage("已切换服务器，请在 Minecraft 中重新连接");
+    } catch (e) {
+      setMessage(`切换服务器失败: ${e}`);
+    }
+  };
+
   const handleDiscover = async () => {
     setDiscoveryStatus("scanning");
     setLanServer(null);
     try {
-      const server = await invoke<LanServer | null>("detect_minecraft_server");
+      const server = await invoke<LanServer | null>("detect_minecraft_server", {
+        interface: discoveryInterface || null,
+      });
       if (server) {
         setLanServer(server);
         setDiscoveryStatus("found");
@@ -132,7 +313,8 @@ export const Client: React.FC<ClientProps> = ({
       }
     } catch (e) {
       console.error("Failed to discover LAN server:", e);
-      setDiscoveryStatus("not_found");
+      setDiscoveryError(describeDiscoveryError(e) ?? `${e}`);
+      setDiscoveryStatus("failed");
     }
   };
 
@@ -147,6 +329,8 @@ export const Client: React.FC<ClientProps> = ({
           "get_performance_metrics"
         );
         setMetrics(data);
+        const session = await invoke<{ state: string }>("get_session_state");
+        setSteamOffline(session.state === "steam_offline");
       } catch (e) {
         console.error("Failed to get metrics:", e);
       }
@@ -188,6 +372,11 @@ export const Client: React.FC<ClientProps> = ({
             <>
               {/* LAN Discovery Section */}
               <div className="space-y-4">
+                <InterfaceSelect
+                  value={discoveryInterface}
+                  onChange={setDiscoveryInterface}
+                  disabled={discoveryStatus === "scanning"}
+                />
                 <Button
                   onClick={handleDiscover}
                   disabled={discoveryStatus === "scanning"}
@@ -239,6 +428,13 @@ export const Client: React.FC<ClientProps> = ({
                     <p className="text-sm font-medium">未找到局域网服务器</p>
                   </div>
                 )}
+
+                {discoveryStatus === "failed" && (
+                  <div className="bg-red-500/10 p-4 rounded-2xl flex items-center space-x-3 text-red-300 animate-fade-in border border-red-500/20">
+                    <XCircle size={20} />
+                    <p className="text-sm font-medium">{discoveryError}</p>
+                  </div>
+                )}
               </div>
 
               <div className="relative flex items-center justify-center">
@@ -277,9 +473,21 @@ export const Client: React.FC<ClientProps> = ({
                     disabled:opacity-50 disabled:cursor-not-allowed
                     shadow-lg shadow-black/10
                   "
-                  placeholder="输入好友分享的房间号"
+                  placeholder="房间号、邀请链接或好友代码"
                   disabled={status === "connecting"}
                 />
+                {estimatedPing !== null && (
+                  <p
+                    className={`text-xs mt-2 ${
+                      estimatedPing >= HIGH_PING_WARN_MS
+                        ? "text-yellow-300"
+                        : "text-white/50"
+                    }`}
+                  >
+                    {estimatedPing >= HIGH_PING_WARN_MS ? "⚠ " : ""}
+                    房主预计延迟约 {estimatedPing}ms (经 Steam 中继估算)
+                  </p>
+                )}
               </div>
 
               <div>
@@ -311,6 +519,22 @@ export const Client: React.FC<ClientProps> = ({
                   disabled={status === "connecting"}
                 />
               </div>
+
+              <label className="flex items-center space-x-3 text-sm text-white/70 cursor-pointer select-none">
+                <input
+                  type="checkbox"
+                  checked={lanBroadcast}
+                  onChange={(e) => setLanBroadcast(e.target.checked)}
+                  disabled={status === "connecting"}
+                  className="w-4 h-4 accent-purple-500"
+                />
+                <span>
+                  LAN 自动发现{" "}
+                  <span className="text-xs text-white/40">
+                    (关闭后请在 Minecraft 中直接连接加入后显示的地址)
+                  </span>
+                </span>
+              </label>
             </>
           )}
 
@@ -328,6 +552,16 @@ export const Client: React.FC<ClientProps> = ({
             `}
             >
               <p className="text-sm font-medium leading-relaxed">{message}</p>
+              {status === "error" && updateUrl && (
+                <a
+                  href={updateUrl}
+                  target="_blank"
+                  rel="noreferrer"
+                  className="inline-block mt-2 text-sm font-semibold underline text-red-200 hover:text-white"
+                >
+                  下载最新版本
+                </a>
+              )}
             </div>
           )}
 
@@ -359,23 +593,28 @@ export const Client: React.FC<ClientProps> = ({
                     space-y-4
                   "
                   >
-                    {/* 方式1: LAN 自动发现 */}
-                    <div className="flex items-center space-x-3">
-                      <div className="p-2 rounded-lg bg-purple-500/20 border border-purple-500/30">
-                        <Globe size={20} className="text-purple-300" />
-                      </div>
-                      <div className="flex-1">
-                        <p className="text-sm font-medium text-white/70 mb-1">
-                          方式一：自动发现
-                        </p>
-                        <p className="text-lg font-bold text-white/95 tracking-wide">
-                          多人游戏 → 查找 "LAN world"
-                        </p>
-                      </div>
-                    </div>
+                    {lanBroadcast && (
+                      <>
+                        {/* 方式1: LAN 自动发现 */}
+                        <div className="flex items-center space-x-3">
+                          <div className="p-2 rounded-lg bg-purple-500/20 border border-purple-500/30">
+                            <Globe size={20} className="text-purple-300" />
+                          </div>
+                          <div className="flex-1">
+                            <p className="text-sm font-medium text-white/70 mb-1">
+                              方式一：自动发现
+                            </p>
+                            <p className="text-lg font-bold text-white/95 tracking-wide">
+                              多人游戏 → 查找 "LAN world
+                              {listenPort !== 55555 ? ` (${listenPort})` : ""}"
+                            </p>
+                          </div>
+                        </div>
 
-                    {/* 分隔线 */}
-                    <div className="border-t border-white/10"></div>
+                        {/* 分隔线 */}
+                        <div className="border-t border-white/10"></div>
+                      </>
+                    )}
 
                     {/* 方式2: 手动连接 */}
                     <div className="flex items-center space-x-3">
@@ -387,11 +626,16 @@ export const Client: React.FC<ClientProps> = ({
                           方式二：手动连接（如自动发现失败）
                         </p>
                         <p className="text-lg font-bold text-white/95 tracking-wide font-mono">
-                          127.0.0.1:55555
+                          127.0.0.1:{listenPort}
                         </p>
                         <p className="text-xs text-white/50 mt-1">
                           多人游戏 → 直接连接 → 输入上方地址
                         </p>
+                        {lanDirect && (
+                          <p className="text-xs text-emerald-300/80 mt-1">
+                            房主在同一局域网，游戏数据将直连 {lanDirect}，不经 Steam 中转
+                          </p>
+                        )}
                       </div>
                     </div>
                   </div>
@@ -406,7 +650,42 @@ export const Client: React.FC<ClientProps> = ({
           )}
 
           {/* Performance Panel */}
+          {status === "connected" && steamOffline && (
+            <div className="p-3 rounded-xl bg-yellow-500/20 border border-yellow-500/30 text-sm text-yellow-200">
+              Steam 连接已断开，转发已暂停，Steam 恢复后将自动继续
+            </div>
+          )}
           {status === "connected" && <PerformancePanel metrics={metrics} />}
+          {status === "connected" && (
+            <div className="flex items-center justify-between p-3 rounded-xl bg-white/5 border border-white/10 text-sm text-white/70">
+              <span>
+                {rconPort === null
+                  ? "RCON 转发 (需房主开放)"
+                  : `RCON 客户端请连接 127.0.0.1:${rconPort}`}
+              </span>
+              <Button variant="secondary" onClick={handleToggleRcon}>
+                {rconPort === null ? "开启" : "关闭"}
+              </Button>
+            </div>
+          )}
+
+          {status === "connected" && mcServers.length > 0 && (
+            <div className="flex items-center justify-between p-3 rounded-xl bg-white/5 border border-white/10 text-sm text-white/70">
+              <span>房主的 MC 服务器</span>
+              <select
+                value={mcServer}
+                onChange={(e) => handleSelectMcServer(parseInt(e.target.value))}
+                className="bg-white/5 border border-white/20 rounded-xl px-3 py-1 text-white"
+              >
+                <option value={0}>主服务器</option>
+                {mcServers.map((label, index) => (
+                  <option key={index} value={index + 1}>
+                    {label}
+                  </option>
+                ))}
+              </select>
+            </div>
+          )}
 
           {/* Join Button */}
           {status !== "connected" && (
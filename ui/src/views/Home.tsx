@@ -1,7 +1,8 @@
-import React from "react";
+import React, { useState, useEffect } from "react";
 import { Card } from "../components/Card";
 import { Button } from "../components/Button";
 import { Server, Users, Wifi, WifiOff } from "lucide-react";
+import { invoke } from "@tauri-apps/api/core";
 
 interface ConnectionState {
   type: "host" | "client" | null;
@@ -21,6 +22,14 @@ export const Home: React.FC<HomeProps> = ({
   connectionState,
 }) => {
   const isConnected = connectionState.lobbyId !== null;
+  // 好友代码，方便对方在 Steam 中添加好友
+  const [friendCode, setFriendCode] = useState<number | null>(null);
+
+  useEffect(() => {
+    invoke<{ friend_code: number }>("get_steam_id")
+      .then((info) => setFriendCode(info.friend_code))
+      .catch(() => setFriendCode(null));
+  }, [steamName]);
 
   return (
     <div className="flex flex-col items-center justify-center w-full py-8 space-y-8 animate-fade-in">
@@ -37,6 +46,11 @@ export const Home: React.FC<HomeProps> = ({
           <p className="text-xl font-medium text-white">
             {steamName || "加载中..."}
           </p>
+          {friendCode !== null && (
+            <p className="text-xs text-white/40 font-mono">
+              好友代码: {friendCode}
+            </p>
+          )}
         </div>
 
         {/* 连接状态指示 */}
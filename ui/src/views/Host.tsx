@@ -2,6 +2,8 @@ import React, { useState, useEffect, useRef } from "react";
 import { Card } from "../components/Card";
 import { Button } from "../components/Button";
 import { PerformancePanel } from "../components/PerformancePanel";
+import { AccessListPanel } from "../components/AccessListPanel";
+import { InterfaceSelect } from "../components/InterfaceSelect";
 import {
   ArrowLeft,
   Play,
@@ -12,6 +14,8 @@ import {
   Sparkles,
 } from "lucide-react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { describeDiscoveryError } from "../discovery";
 
 interface ConnectionState {
   type: "host" | "client" | null;
@@ -31,16 +35,88 @@ interface PerformanceMetrics {
   bytes_sent: number;
   bytes_received: number;
   packets_dropped: number;
+  drop_rate?: number;
   send_rate_mbps: number;
   recv_rate_mbps: number;
   send_rate_pps: number;
   recv_rate_pps: number;
   latency_ms?: number | null;
+  latency_raw_ms?: number | null;
+  jitter_ms?: number | null;
+  route?: "direct" | "relayed" | "unknown" | null;
+  session_started_at_ms?: number | null;
+  session_uptime_secs?: number | null;
+}
+
+type PausePolicy = "buffer" | "discard";
+
+interface ForwardingState {
+  paused: boolean;
+  policy: PausePolicy | null;
+}
+
+type RemovalReason =
+  | "disconnected"
+  | "connection_lost"
+  | "send_failures"
+  | "mc_closed"
+  | "invalid_handshake"
+  | "not_allowed"
+  | "password_changed"
+  | "protocol_error";
+
+const REMOVAL_REASON_LABELS: Record<RemovalReason, string> = {
+  disconnected: "玩家已断开",
+  connection_lost: "连接丢失",
+  send_failures: "连续发送失败",
+  mc_closed: "MC 连接已关闭",
+  invalid_handshake: "不是 Minecraft 连接",
+  not_allowed: "不在允许列表中",
+  password_changed: "房间密码已更换",
+  protocol_error: "协议错误",
+};
+
+interface PeerRemoved {
+  steam_id: string;
+  reason: RemovalReason;
+}
+
+type LobbyLock = "friends_only" | "private";
+
+interface LobbyLockState {
+  locked: LobbyLock | null;
+  automatic: boolean;
+}
+
+interface McReadiness {
+  ready: boolean;
+  port: number;
+  message: string | null;
+}
+
+/** get_local_mc_status 的结果，即服务器列表查询读到的状态 */
+interface LocalMcStatus {
+  motd: string;
+  version: string;
+  protocol: number;
+  players_online: number;
+  players_max: number;
+  latency_ms: number;
+}
+
+interface AutoStopWarning {
+  role: "host" | "client";
+  remaining_secs: number;
 }
 
 interface MinecraftServerInfo {
   port: number;
   motd: string;
+  /** 是否通过服务器列表查询确认是 Minecraft 服务器，否则只确认了端口可连接 */
+  verified: boolean;
+  version: string | null;
+  players_online: number | null;
+  players_max: number | null;
 }
 
 export const Host: React.FC<HostProps> = ({
@@ -50,6 +126,14 @@ export const Host: React.FC<HostProps> = ({
 }) => {
   const [port, setPort] = useState("25565");
   const [password, setPassword] = useState("");
+  // 开放 RCON 转发时 MC 服务器的 RCON 端口，只有允许列表中的玩家可以使用
+  const [allowRcon, setAllowRcon] = useState(false);
+  const [rconPort, setRconPort] = useState("25575");
+  // 在线人数达到该值时自动锁定房间，留空不自动锁定
+  const [lockAtPlayers, setLockAtPlayers] = useState("");
+  // 主服务器以外同时开放的 MC 服务器，每行 "名称=端口"，玩家加入时选择
+  const [extraServers, setExtraServers] = useState("");
+  const [serverDir, setServerDir] = useState("");
   const [status, setStatus] = useState<"idle" | "running" | "error">("idle");
   const [message, setMessage] = useState("正在自动检测 Minecraft 服务器...");
   const [lobbyId, setLobbyId] = useState<string | null>(null);
@@ -95,11 +179,190 @@ export const Host: React.FC<HostProps> = ({
     restoreState();
   }, [connectionState, onConnectionChange]);
   const [copied, setCopied] = useState(false);
+
+  // 运行中更换转发到的 MC 服务器端口
+  const handleSetTargetPort = async (reconnect: boolean) => {
+    try {
+      await invoke("set_mc_target_port", { port: parseInt(port), reconnect });
+      setMessage(
+        reconnect
+          ? `✓ 已切换到端口 ${port}，现有玩家正在重连`
+          : `✓ 新连接将使用端口 ${port}`
+      );
+      if (onConnectionChange && lobbyId) {
+        onConnectionChange(lobbyId, parseInt(port));
+      }
+    } catch (e) {
+      setMessage(`更换端口失败: ${e}`);
+    }
+  };
+
+  // 暂停/恢复转发，玩家的 Steam 连接保持不变
+  const handlePause = async (policy: PausePolicy) => {
+    try {
+      await invoke("pause_forwarding", { policy });
+    } catch (e) {
+      setMessage(`暂停转发失败: ${e}`);
+    }
+  };
+
+  const handleResume = async () => {
+    try {
+      await invoke("resume_forwarding");
+    } catch (e) {
+      setMessage(`恢复转发失败: ${e}`);
+    }
+  };
+
+  // 锁定后只有好友 (或受邀者) 能加入，已连接的玩家不受影响
+  const handleLock = async (lock: LobbyLock) => {
+    try {
+      await invoke("lock_lobby", { lock });
+    } catch (e) {
+      setMessage(`锁定房间失败: ${e}`);
+    }
+  };
+
+  const handleUnlock = async () => {
+    try {
+      await invoke("unlock_lobby");
+    } catch (e) {
+      setMessage(`解锁房间失败: ${e}`);
+    }
+  };
+
+  // 从服务器目录的 server.properties 读取端口
+  const handleReadProperties = async () => {
+    if (!serverDir.trim()) return;
+    try {
+      const properties = await invoke<{
+        port: number;
+        motd: string;
+        rcon_port: number | null;
+      }>("read_server_properties", { dir: serverDir.trim() });
+      setPort(properties.port.toString());
+      if (properties.rcon_port !== null) {
+        setRconPort(properties.rcon_port.toString());
+      }
+      setMessage(`✓ 已从 server.properties 读取: ${properties.motd}`);
+    } catch (e) {
+      setMessage(`读取失败: ${e}`);
+    }
+  };
+  // 用服务器列表查询确认本地 MC 服务器运行正常
+  const handleCheckStatus = async () => {
+    try {
+      const server = await invoke<LocalMcStatus>("get_local_mc_status", {
+        port: parseInt(port) || null,
+      });
+      setMessage(
+        `✓ ${server.motd || "MC 服务器"} · 版本 ${server.version} · 在线 ${server.players_online}/${server.players_max} · ${server.latency_ms.toFixed(1)}ms`
+      );
+    } catch (e) {
+      setMessage(`${e}`);
+    }
+  };
   const [metrics, setMetrics] = useState<PerformanceMetrics | null>(null);
+  // Steam 掉线时后端暂停转发，恢复后自动继续
+  const [steamOffline, setSteamOffline] = useState(false);
+  // 手动暂停转发时使用的策略，未暂停时为 null
+  const [pausePolicy, setPausePolicy] = useState<PausePolicy | null>(null);
+  // 本地 MC 服务器尚不可连接时的提示，可连接后为 null
+  const [mcWaiting, setMcWaiting] = useState<string | null>(null);
+
+  useEffect(() => {
+    const unlistenPromise = listen<McReadiness>("host-mc-readiness", (event) => {
+      setMcWaiting(event.payload.ready ? null : event.payload.message);
+    });
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // 房间当前的锁定类型，公开时为 null
+  const [lobbyLock, setLobbyLock] = useState<LobbyLock | null>(null);
+
+  useEffect(() => {
+    const unlistenPromise = listen<LobbyLockState>(
+      "host-lobby-lock-changed",
+      (event) => {
+        setLobbyLock(event.payload.locked);
+        if (event.payload.automatic) {
+          setMessage("已达到设定人数，房间已自动锁定");
+        }
+      }
+    );
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlistenPromise = listen<ForwardingState>(
+      "host-forwarding-changed",
+      (event) => setPausePolicy(event.payload.policy)
+    );
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // 桥接线程崩溃时后端会关闭房间
+  useEffect(() => {
+    const unlistenPromise = listen<{ thread: string; message: string }>(
+      "session-thread-panicked",
+      (event) => {
+        setStatus("error");
+        setMessage(
+          `${event.payload.thread} 意外崩溃 (${event.payload.message})，房间已关闭`
+        );
+      }
+    );
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // 玩家被移除时显示原因
+  useEffect(() => {
+    const unlistenPromise = listen<PeerRemoved>("host-peer-removed", (event) => {
+      const { steam_id, reason } = event.payload;
+      setMessage(`玩家 ${steam_id} 已离开: ${REMOVAL_REASON_LABELS[reason] ?? reason}`);
+    });
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // 设置了最长运行时间时，房间关闭前一分钟收到预警
+  useEffect(() => {
+    const unlistenPromise = listen<AutoStopWarning>(
+      "session-auto-stop-warning",
+      (event) => {
+        if (event.payload.role === "host") {
+          setMessage(`已接近最长运行时间，房间将在 ${event.payload.remaining_secs} 秒后自动关闭`);
+        }
+      }
+    );
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // 会话结束后暂停状态随之失效
+  useEffect(() => {
+    if (status !== "running") {
+      setPausePolicy(null);
+      setLobbyLock(null);
+      setMcWaiting(null);
+    }
+  }, [status]);
   const [detecting, setDetecting] = useState(true);
   const [detectedServer, setDetectedServer] =
     useState<MinecraftServerInfo | null>(null);
   const [detectionAttempts, setDetectionAttempts] = useState(0);
+  // 搜索服务器使用的网卡，空字符串表示由系统选择
+  const [discoveryInterface, setDiscoveryInterface] = useState("");
   const detectionIntervalRef = useRef<number | null>(null);
   const attemptsRef = useRef(0);
   const isDetectingRef = useRef(false); // 防止重复请求
@@ -156,7 +419,8 @@ export const Host: React.FC<HostProps> = ({
 
       try {
         const server = await invoke<MinecraftServerInfo | null>(
-          "detect_minecraft_server"
+          "detect_minecraft_server",
+          { interface: discoveryInterface || null }
         );
 
         if (server) {
@@ -174,7 +438,17 @@ export const Host: React.FC<HostProps> = ({
         }
       } catch (e) {
         console.error("检测失败:", e);
-        // 不显示每次失败的错误，只在达到最大次数时提示
+        // 无法监听广播时重试也不会成功，停止轮询并提示原因
+        const advice = describeDiscoveryError(e);
+        if (advice) {
+          setDetecting(false);
+          setMessage(`${advice}；也可以手动输入端口号`);
+          if (detectionIntervalRef.current) {
+            clearInterval(detectionIntervalRef.current);
+            detectionIntervalRef.current = null;
+          }
+        }
+        // 其他失败不逐次提示，只在达到最大次数时提示
       } finally {
         isDetectingRef.current = false;
       }
@@ -193,7 +467,7 @@ export const Host: React.FC<HostProps> = ({
         detectionIntervalRef.current = null;
       }
     };
-  }, [detectedServer, status]); // 移除 detectionAttempts 避免死循环
+  }, [detectedServer, status, discoveryInterface]); // 移除 detectionAttempts 避免死循环
 
   const handleStart = async () => {
     // 停止检测
@@ -206,29 +480,29 @@ export const Host: React.FC<HostProps> = ({
     setStatus("running");
     setMessage("正在启动主机...");
     try {
-      await invoke("start_host", {
+      // start_host 在房间创建完成后才返回房间号
+      const idStr = await invoke<string>("start_host", {
         port: parseInt(port),
         password: password.trim() || null,
+        rconPort: allowRcon ? parseInt(rconPort) : null,
+        lockAtPlayers: lockAtPlayers.trim() ? parseInt(lockAtPlayers) : null,
+        extraServers: extraServers
+          .split("\n")
+          .filter((line) => line.trim())
+          .map((line) => {
+            const at = line.lastIndexOf("=");
+            return {
+              label: line.slice(0, at).trim(),
+              port: parseInt(line.slice(at + 1)) || 0,
+            };
+          }),
       });
-      setMessage("主机运行中... 正在获取房间号...");
-
-      // Wait a bit for lobby creation, then fetch lobby ID once
-      setTimeout(async () => {
-        try {
-          const id = await invoke<number | null>("get_lobby_id");
-          if (id) {
-            const idStr = id.toString();
-            setLobbyId(idStr);
-            setMessage("主机运行中... 请将房间号分享给好友");
-            // 通知父组件连接状态变化
-            if (onConnectionChange) {
-              onConnectionChange(idStr, parseInt(port));
-            }
-          }
-        } catch (e) {
-          console.error("Failed to get lobby ID:", e);
-        }
-      }, 1000);
+      setLobbyId(idStr);
+      setMessage("主机运行中... 请将房间号分享给好友");
+      // 通知父组件连接状态变化
+      if (onConnectionChange) {
+        onConnectionChange(idStr, parseInt(port));
+      }
     } catch (e) {
       setStatus("error");
       setMessage(`启动失败: ${e}`);
@@ -254,6 +528,8 @@ export const Host: React.FC<HostProps> = ({
           "get_performance_metrics"
         );
         setMetrics(data);
+        const session = await invoke<{ state: string }>("get_session_state");
+        setSteamOffline(session.state === "steam_offline");
       } catch (e) {
         console.error("Failed to get metrics:", e);
       }
@@ -339,8 +615,25 @@ export const Host: React.FC<HostProps> = ({
                     <span className="font-mono font-semibold text-white/70">
                       {detectedServer.port}
                     </span>
+                    {detectedServer.verified ? (
+                      <>
+                        {" · "}版本 {detectedServer.version}
+                        {" · "}在线 {detectedServer.players_online}/
+                        {detectedServer.players_max}
+                      </>
+                    ) : (
+                      " · 未能确认是 Minecraft 服务器"
+                    )}
                   </p>
                 )}
+                {!detectedServer && status !== "running" && (
+                  <div className="mt-3">
+                    <InterfaceSelect
+                      value={discoveryInterface}
+                      onChange={setDiscoveryInterface}
+                    />
+                  </div>
+                )}
               </div>
             </div>
 
@@ -377,7 +670,6 @@ export const Host: React.FC<HostProps> = ({
                   shadow-lg shadow-black/10
                 "
                 placeholder="25565"
-                disabled={status === "running"}
               />
               {detectedServer && (
                 <div className="absolute right-4 top-1/2 -translate-y-1/2">
@@ -390,6 +682,74 @@ export const Host: React.FC<HostProps> = ({
                 </div>
               )}
             </div>
+            {status === "running" && (
+              <div className="flex items-center justify-between mt-2 space-x-3">
+                <p className="text-xs text-white/40">
+                  已连接的玩家保持原端口，除非选择重连
+                </p>
+                <div className="flex space-x-2 shrink-0">
+                  <Button
+                    variant="secondary"
+                    onClick={() => handleSetTargetPort(false)}
+                    disabled={!port}
+                  >
+                    应用
+                  </Button>
+                  <Button
+                    variant="secondary"
+                    onClick={() => handleSetTargetPort(true)}
+                    disabled={!port}
+                  >
+                    应用并重连
+                  </Button>
+                </div>
+              </div>
+            )}
+            <div className="flex justify-end mt-2">
+              <Button variant="secondary" onClick={handleCheckStatus}>
+                检查服务器状态
+              </Button>
+            </div>
+          </div>
+
+          {/* server.properties */}
+          <div>
+            <label className="block text-sm font-semibold text-white/70 mb-3 tracking-wide">
+              服务器目录{" "}
+              <span className="text-xs text-white/40 font-normal">
+                (可选，从 server.properties 读取端口)
+              </span>
+            </label>
+            <div className="flex space-x-3">
+              <input
+                type="text"
+                value={serverDir}
+                onChange={(e) => setServerDir(e.target.value)}
+                className="
+                  flex-1
+                  bg-white/5 backdrop-blur-xl
+                  border border-white/20
+                  rounded-2xl px-5 py-4
+                  text-white font-medium
+                  placeholder:text-white/30
+                  focus:outline-none
+                  focus:ring-2 focus:ring-blue-500/50
+                  focus:border-blue-500/50
+                  transition-all duration-200
+                  disabled:opacity-50 disabled:cursor-not-allowed
+                  shadow-lg shadow-black/10
+                "
+                placeholder="例如 D:\MinecraftServer"
+                disabled={status === "running"}
+              />
+              <Button
+                variant="secondary"
+                onClick={handleReadProperties}
+                disabled={status === "running" || !serverDir.trim()}
+              >
+                读取
+              </Button>
+            </div>
           </div>
 
           {/* Password Input */}
@@ -421,6 +781,63 @@ export const Host: React.FC<HostProps> = ({
             />
           </div>
 
+          {/* RCON forwarding */}
+          <div>
+            <label className="flex items-center space-x-3 text-sm text-white/70 cursor-pointer select-none">
+              <input
+                type="checkbox"
+                checked={allowRcon}
+                onChange={(e) => setAllowRcon(e.target.checked)}
+                disabled={status === "running"}
+                className="w-4 h-4 accent-blue-500"
+              />
+              <span>
+                开放 RCON 转发{" "}
+                <span className="text-xs text-white/40">
+                  (仅允许列表中的玩家可用)
+                </span>
+              </span>
+            </label>
+            {allowRcon && (
+              <input
+                type="number"
+                value={rconPort}
+                onChange={(e) => setRconPort(e.target.value)}
+                className="mt-3 w-full bg-white/5 border border-white/20 rounded-2xl px-5 py-3 text-white focus:outline-none focus:ring-2 focus:ring-blue-500/50 disabled:opacity-50"
+                placeholder="RCON 端口 (server.properties 中的 rcon.port)"
+                disabled={status === "running"}
+              />
+            )}
+          </div>
+
+          {/* Automatic lobby lock */}
+          <div>
+            <input
+              type="number"
+              min="1"
+              value={lockAtPlayers}
+              onChange={(e) => setLockAtPlayers(e.target.value)}
+              className="w-full bg-white/5 border border-white/20 rounded-2xl px-5 py-3 text-white focus:outline-none focus:ring-2 focus:ring-blue-500/50 disabled:opacity-50"
+              placeholder="在线人数达到多少时自动锁定为仅好友 (留空不锁定)"
+              disabled={status === "running"}
+            />
+          </div>
+
+          {/* Additional MC servers */}
+          <div>
+            <textarea
+              value={extraServers}
+              onChange={(e) => setExtraServers(e.target.value)}
+              rows={2}
+              className="w-full bg-white/5 border border-white/20 rounded-2xl px-5 py-3 text-white focus:outline-none focus:ring-2 focus:ring-blue-500/50 disabled:opacity-50"
+              placeholder="同时开放的其他 MC 服务器，每行 名称=端口 (如 创造服=25566)，玩家加入时选择"
+              disabled={status === "running"}
+            />
+          </div>
+
+          {/* Allow / block lists */}
+          <AccessListPanel />
+
           {/* Lobby ID Display */}
           {lobbyId && (
             <div
@@ -492,6 +909,78 @@ export const Host: React.FC<HostProps> = ({
           </div>
 
           {/* Performance Panel */}
+          {status === "running" && steamOffline && (
+            <div className="p-3 rounded-xl bg-yellow-500/20 border border-yellow-500/30 text-sm text-yellow-200">
+              Steam 连接已断开，转发已暂停，Steam 恢复后将自动继续
+            </div>
+          )}
+          {status === "running" && mcWaiting && (
+            <div className="p-3 rounded-xl bg-yellow-500/20 border border-yellow-500/30 text-sm text-yellow-200">
+              {mcWaiting}，房间已创建，服务器启动后玩家即可进入
+            </div>
+          )}
+          {status === "running" && pausePolicy && (
+            <div className="flex items-center justify-between p-3 rounded-xl bg-yellow-500/20 border border-yellow-500/30 text-sm text-yellow-200">
+              <span>
+                转发已暂停，
+                {pausePolicy === "buffer"
+                  ? "数据将在恢复后送达"
+                  : "期间的数据被丢弃"}
+                ，玩家的 Steam 连接保持不变
+              </span>
+              <Button onClick={handleResume} className="px-3 py-1 text-xs">
+                恢复转发
+              </Button>
+            </div>
+          )}
+          {status === "running" && !pausePolicy && (
+            <div className="flex space-x-2">
+              <Button
+                onClick={() => handlePause("buffer")}
+                variant="secondary"
+                className="flex-1 py-2 text-xs"
+              >
+                暂停转发 (缓冲)
+              </Button>
+              <Button
+                onClick={() => handlePause("discard")}
+                variant="secondary"
+                className="flex-1 py-2 text-xs"
+              >
+                暂停转发 (丢弃)
+              </Button>
+            </div>
+          )}
+          {status === "running" && lobbyLock && (
+            <div className="flex items-center justify-between p-3 rounded-xl bg-blue-500/20 border border-blue-500/30 text-sm text-blue-200">
+              <span>
+                房间已锁定为
+                {lobbyLock === "friends_only" ? "仅好友" : "私密 (仅限邀请)"}
+                ，现有玩家不受影响
+              </span>
+              <Button onClick={handleUnlock} className="px-3 py-1 text-xs">
+                解锁
+              </Button>
+            </div>
+          )}
+          {status === "running" && !lobbyLock && (
+            <div className="flex space-x-2">
+              <Button
+                onClick={() => handleLock("friends_only")}
+                variant="secondary"
+                className="flex-1 py-2 text-xs"
+              >
+                锁定为仅好友
+              </Button>
+              <Button
+                onClick={() => handleLock("private")}
+                variant="secondary"
+                className="flex-1 py-2 text-xs"
+              >
+                锁定为私密
+              </Button>
+            </div>
+          )}
           {status === "running" && <PerformancePanel metrics={metrics} />}
 
           {/* Start Button */}